@@ -0,0 +1,173 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for complete backups of a profile. A `Backup` contains the items and the config, and
+//! the lists of a server's users, and is sealed into a single password-encrypted file, which can be
+//! restored on another device without setting up synchronization.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{Backup, Config, TdList, Todo};
+//!
+//! let mut list = TdList::new_client();
+//! list.add_todo(Todo::new_undated("Pack the boxes".to_string()).unwrap());
+//! let conf = Config::new_default(b"sync password".to_vec(), "localhost:55995".to_string(), None);
+//!
+//! let sealed = Backup::new(&conf, &list, false).seal(b"backup password").unwrap();
+//! let backup = Backup::open(&sealed, b"backup password").unwrap();
+//!
+//! assert_eq!(backup.items().todos()[0].body(), "Pack the boxes");
+//! assert!(!backup.config().has_secrets());
+//! ```
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, current_time, Error, Result, TdList};
+use crate::network::crypt;
+
+/// The start of sealed backups. It tells backups apart from other files and allows changing the
+/// format later.
+const MAGIC: &[u8] = b"mtdbackup1\n";
+
+/// The items and the config of a profile at a point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    created_at: NaiveDateTime,
+    config: Config,
+    items: TdList,
+    // Backups made before users had lists of their own have none.
+    #[serde(default)]
+    user_lists: BTreeMap<String, TdList>,
+}
+
+impl Backup {
+    /// Creates a new `Backup` of the config and the items. The encryption password and the Tor
+    /// control password are left out of the config unless `include_secrets` is `true`.
+    pub fn new(config: &Config, items: &TdList, include_secrets: bool) -> Backup {
+        let config = if include_secrets { config.clone() } else { config.without_secrets() };
+        Backup { created_at: current_time(), config, items: items.clone(), user_lists: BTreeMap::new() }
+    }
+
+    /// Sets the lists of the server's users by user name.
+    pub fn set_user_lists(&mut self, user_lists: BTreeMap<String, TdList>) {
+        self.user_lists = user_lists;
+    }
+
+    /// Returns when the `Backup` was created.
+    pub fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+
+    /// Returns the backed up config.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the backed up items.
+    pub fn items(&self) -> &TdList {
+        &self.items
+    }
+
+    /// Returns the backed up lists of the server's users by user name.
+    pub fn user_lists(&self) -> &BTreeMap<String, TdList> {
+        &self.user_lists
+    }
+
+    /// Returns the config, the items and the lists of the users, consuming the `Backup`.
+    pub fn into_parts(self) -> (Config, TdList, BTreeMap<String, TdList>) {
+        (self.config, self.items, self.user_lists)
+    }
+
+    /// Encrypts the `Backup` with the password.
+    pub fn seal(&self, password: &[u8]) -> Result<Vec<u8>> {
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend(crypt::encrypt(serde_json::to_string(self)?.as_bytes(), password)?);
+        Ok(sealed)
+    }
+
+    /// Decrypts a `Backup` sealed with `Backup::seal`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the data isn't a sealed backup and
+    /// `Error::DecryptingFailed` if the password is incorrect.
+    pub fn open(sealed: &[u8], password: &[u8]) -> Result<Backup> {
+        let encrypted = sealed.strip_prefix(MAGIC)
+            .ok_or_else(|| Error::InvalidArgument("The file isn't an mtd backup.".to_string()))?;
+        let json = crypt::decrypt(encrypted, password)?;
+
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{Backup, Config, Error, TdList, Todo};
+
+    #[test]
+    fn backup_opens_with_the_same_password() {
+        let mut list = TdList::new_server();
+        list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        let mut conf = Config::new_default(b"secret".to_vec(), "127.0.0.1:55980".to_string(), None);
+        conf.set_tor_control_password(Some("tor".to_string()));
+
+        let sealed = Backup::new(&conf, &list, true).seal(b"password").unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains("secret"));
+
+        let backup = Backup::open(&sealed, b"password").unwrap();
+        assert_eq!(backup.items().todos(), list.todos());
+        assert!(backup.items().is_server());
+        assert_eq!(backup.config().encryption_password(), &b"secret".to_vec());
+        assert_eq!(backup.config().tor_control_password(), Some("tor"));
+        assert!(matches!(Backup::open(&sealed, b"wrong"), Err(Error::DecryptingFailed)));
+        assert!(matches!(Backup::open(b"{}", b"password"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn backup_keeps_the_lists_of_users() {
+        let mut alices = TdList::new_server();
+        alices.add_todo(Todo::new_undated("Alice's todo".to_string()).unwrap());
+        let conf = Config::new_default(b"secret".to_vec(), "127.0.0.1:55980".to_string(), None);
+
+        let mut backup = Backup::new(&conf, &TdList::new_server(), false);
+        backup.set_user_lists(BTreeMap::from([("alice".to_string(), alices)]));
+        let backup = Backup::open(&backup.seal(b"password").unwrap(), b"password").unwrap();
+        assert_eq!(backup.user_lists()["alice"].todos()[0].body(), "Alice's todo");
+
+        // Older backups have no user lists.
+        let mut json = serde_json::to_value(&backup).unwrap();
+        json.as_object_mut().unwrap().remove("user_lists");
+        let old: Backup = serde_json::from_value(json).unwrap();
+        assert!(old.user_lists().is_empty());
+    }
+
+    #[test]
+    fn secrets_are_left_out_unless_asked_for() {
+        let mut conf = Config::new_default(b"secret".to_vec(), "127.0.0.1:55980".to_string(), None);
+        conf.set_tor_control_password(Some("tor".to_string()));
+
+        let backup = Backup::new(&conf, &TdList::new_client(), false);
+        assert!(!backup.config().has_secrets());
+        assert_eq!(backup.config().socket_addr(), "127.0.0.1:55980");
+        assert!(conf.has_secrets());
+    }
+}
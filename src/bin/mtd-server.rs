@@ -0,0 +1,270 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A minimal mtd server for containers and init systems. Unlike `mtd server` it never prompts:
+//! everything is configured with flags or environment variables, and messages are logged to stderr
+//! as JSON lines.
+
+use std::{fs, process};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+#[cfg(not(feature = "utc"))]
+use chrono::Local;
+use clap::Parser;
+use serde_json::json;
+
+use mtd::{Config, DataEncryption, Error, LogLevel, MtdNetMgr, PasswordEncryption, read_data_file, Result, ServerUser, StorageEncryption, TdList};
+
+#[derive(Parser)]
+#[clap(author, version, about = "Runs an mtd server configured with flags or environment variables.", long_about = None)]
+struct ServerArgs {
+    /// Socket address to listen on, such as 0.0.0.0:55995 or unix:/run/mtd.sock
+    #[clap(value_parser, long, env = "MTD_ADDR", default_value = "0.0.0.0:55995")]
+    addr: String,
+    /// Address clients should connect to if it differs from the listening address
+    #[clap(value_parser, long, env = "MTD_ADVERTISED_ADDR")]
+    advertised_addr: Option<String>,
+    /// Encryption password shared with the clients
+    #[clap(value_parser, long, env = "MTD_PASSWORD", hide_env_values = true, required_unless_present_any = &["password-file", "users-file"])]
+    password: Option<String>,
+    /// File to read the encryption password from, such as a container secret
+    #[clap(value_parser, long, env = "MTD_PASSWORD_FILE", conflicts_with = "password")]
+    password_file: Option<PathBuf>,
+    /// File the items are saved to. Without it the items are lost when the server stops
+    #[clap(value_parser, long, env = "MTD_SAVE_FILE")]
+    save_file: Option<PathBuf>,
+    /// Seconds to wait for a client before giving up on the connection
+    #[clap(value_parser = clap::value_parser!(u64).range(1..), long, env = "MTD_TIMEOUT", default_value = "30")]
+    timeout: u64,
+    /// Seconds between maintenance runs, which remove old items and write backups
+    #[clap(value_parser = clap::value_parser!(u64).range(1..), long, env = "MTD_MAINTENANCE_INTERVAL", default_value = "3600")]
+    maintenance_interval: u64,
+    /// Number of backups of the save file to keep. All backups are kept by default
+    #[clap(value_parser, long, env = "MTD_MAX_BACKUPS")]
+    max_backups: Option<usize>,
+    /// Largest number of items a client may sync
+    #[clap(value_parser, long, env = "MTD_MAX_ITEMS")]
+    max_items: Option<usize>,
+    /// TCP socket address to answer the /healthz and /readyz health probes on, such as 0.0.0.0:8080
+    #[clap(value_parser, long, env = "MTD_HEALTH_ADDR")]
+    health_addr: Option<String>,
+    /// File to read the password the save file is encrypted with from. The save file isn't encrypted
+    /// without it
+    #[clap(value_parser, long, env = "MTD_DATA_PASSWORD_FILE")]
+    data_password_file: Option<PathBuf>,
+    /// Largest number of clients served at the same time
+    #[clap(value_parser, long, env = "MTD_MAX_WORKERS", default_value = "32")]
+    max_workers: usize,
+    /// JSON file listing the users with a list of their own, such as
+    /// [{"name": "alice", "password": "..."}]
+    #[clap(value_parser, long, env = "MTD_USERS_FILE")]
+    users_file: Option<PathBuf>,
+}
+
+impl ServerArgs {
+    /// Creates the server config from the arguments.
+    fn config(&self) -> Result<Config> {
+        let password = match (&self.password, &self.password_file) {
+            (Some(password), _) => password.clone(),
+            (None, Some(path)) => read_secret(path)?,
+            (None, None) => String::new(),
+        };
+
+        let mut conf = Config::new(self.addr.clone(), password.into_bytes(), Duration::from_secs(self.timeout), self.save_file.clone(), false);
+        conf.set_advertised_addr(self.advertised_addr.clone());
+        conf.set_maintenance_interval(Duration::from_secs(self.maintenance_interval));
+        conf.set_max_backups(self.max_backups);
+        conf.set_max_items(self.max_items);
+        conf.set_health_addr(self.health_addr.clone());
+        conf.set_max_workers(self.max_workers);
+        if let Some(path) = &self.users_file {
+            conf.set_users(read_users(path)?);
+        }
+        if self.data_password_file.is_some() {
+            conf.set_data_encryption(DataEncryption::Password);
+        }
+        conf.validate()?;
+
+        Ok(conf)
+    }
+
+    /// Returns the encryption of the save file if it is encrypted.
+    fn data_encryption(&self) -> Result<Option<PasswordEncryption>> {
+        match &self.data_password_file {
+            Some(path) => Ok(Some(PasswordEncryption::new(read_secret(path)?.into_bytes()))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reads a password from a file.
+fn read_secret(path: &Path) -> Result<String> {
+    // Files written by editors and secret managers often end with a newline.
+    Ok(fs::read_to_string(path)?.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads the users of the server from a JSON file.
+fn read_users(path: &Path) -> Result<Vec<ServerUser>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Formats a log message as a single JSON line.
+fn log_line(level: LogLevel, msg: &str) -> String {
+    let level = match level {
+        LogLevel::Debug => "debug",
+        LogLevel::Info => "info",
+        LogLevel::Error => "error",
+    };
+    #[cfg(not(feature = "utc"))]
+    let time = Local::now().to_rfc3339();
+    #[cfg(feature = "utc")]
+    let time = chrono::Utc::now().to_rfc3339();
+    json!({ "time": time, "level": level, "msg": msg }).to_string()
+}
+
+fn log(level: LogLevel, msg: &str) {
+    // Every connection is logged at the debug level, which would drown out the rest.
+    if level != LogLevel::Debug {
+        eprintln!("{}", log_line(level, msg));
+    }
+}
+
+/// Reads the saved items or creates a new server list if nothing is saved yet.
+fn read_list(save_file: Option<&PathBuf>, encryption: Option<&dyn StorageEncryption>) -> Result<TdList> {
+    let list = match save_file.filter(|path| path.exists()) {
+        Some(path) => TdList::new_from_json(&read_data_file(path, encryption)?)?,
+        None => TdList::new_server(),
+    };
+
+    if !list.is_server() {
+        return Err(Error::InvalidArgument("The save file contains a client's items.".to_string()));
+    }
+    Ok(list)
+}
+
+fn run() -> Result<()> {
+    let args = ServerArgs::parse();
+    let conf = args.config()?;
+    let encryption = args.data_encryption()?;
+    let mut list = read_list(conf.save_location(), encryption.as_ref().map(|e| e as &dyn StorageEncryption))?;
+
+    log(LogLevel::Info, &format!("Listening on {}. Clients should connect to {}.", conf.socket_addr(), conf.advertised_addr()));
+
+    // The server finishes the syncs in progress and saves the items when interrupted or terminated.
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone())?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone())?;
+
+    let mut net_mgr = MtdNetMgr::new(&mut list, &conf);
+    net_mgr.set_log_callback(log);
+    net_mgr.set_stop_flag(stop);
+    if let Some(encryption) = &encryption {
+        net_mgr.set_storage_encryption(encryption);
+    }
+    net_mgr.server_listening_loop()
+}
+
+fn main() {
+    if let Err(e) = run() {
+        log(LogLevel::Error, &e.to_string());
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use serde_json::Value;
+
+    use mtd::{LogLevel, TdList};
+
+    use crate::{log_line, read_list, ServerArgs};
+
+    #[test]
+    fn config_is_read_from_flags() {
+        let args = ServerArgs::try_parse_from(["mtd-server", "--addr", "127.0.0.1:4000", "--password", "pw", "--max-backups", "3"]).unwrap();
+        let conf = args.config().unwrap();
+
+        assert_eq!(conf.socket_addr(), "127.0.0.1:4000");
+        assert_eq!(conf.encryption_password(), &b"pw".to_vec());
+        assert_eq!(conf.max_backups(), Some(3));
+        assert_eq!(conf.health_addr(), None);
+        assert_eq!(conf.max_workers(), 32);
+        assert!(!conf.local_only());
+    }
+
+    #[test]
+    fn password_is_read_from_a_file() {
+        let path = std::env::temp_dir().join("mtd-server-password-test");
+        std::fs::write(&path, "secret\n").unwrap();
+
+        let args = ServerArgs::try_parse_from(["mtd-server", "--password-file", path.to_str().unwrap()]).unwrap();
+        assert_eq!(args.config().unwrap().encryption_password(), &b"secret".to_vec());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn users_are_read_from_a_file() {
+        let path = std::env::temp_dir().join("mtd-server-users-test.json");
+        std::fs::write(&path, r#"[{"name": "alice", "password": "alice's"}]"#).unwrap();
+
+        let args = ServerArgs::try_parse_from(["mtd-server", "--users-file", path.to_str().unwrap()]).unwrap();
+        let conf = args.config().unwrap();
+        assert_eq!(conf.users()[0].name, "alice");
+        assert!(conf.encryption_password().is_empty());
+
+        std::fs::write(&path, r#"[{"name": "../alice", "password": "alice's"}]"#).unwrap();
+        assert!(args.config().is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let args = ServerArgs::try_parse_from(["mtd-server", "--addr", "nowhere", "--password", "pw"]).unwrap();
+        assert!(args.config().is_err());
+        let args = ServerArgs::try_parse_from(["mtd-server", "--password", "pw", "--health-addr", "8080"]).unwrap();
+        assert!(args.config().is_err());
+        let args = ServerArgs::try_parse_from(["mtd-server", "--password", ""]).unwrap();
+        assert!(args.config().is_err());
+        let args = ServerArgs::try_parse_from(["mtd-server", "--password", "pw", "--max-workers", "0"]).unwrap();
+        assert!(args.config().is_err());
+    }
+
+    #[test]
+    fn client_save_file_is_rejected() {
+        let path = std::env::temp_dir().join("mtd-server-client-list-test");
+        std::fs::write(&path, TdList::new_client().to_json().unwrap()).unwrap();
+
+        assert!(read_list(Some(&path), None).is_err());
+        assert!(read_list(None, None).unwrap().is_server());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn log_lines_are_json() {
+        let line: Value = serde_json::from_str(&log_line(LogLevel::Error, "Error occurred: \"x\"")).unwrap();
+
+        assert_eq!(line["level"], "error");
+        assert_eq!(line["msg"], "Error occurred: \"x\"");
+        assert!(line["time"].is_string());
+    }
+}
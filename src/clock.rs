@@ -0,0 +1,103 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+
+//! A module defining where mtd gets the current date from. Views that depend on the current date,
+//! such as `TdList::today`, take a `Clock` so that frontends and tests can decide what "today" is.
+//!
+//! Everything else that needs the current date or time uses `current_date` and `current_time`.
+//! They use the local time zone unless mtd is built with the `utc` feature, which makes them use
+//! UTC. Servers in containers without time zone data then behave the same everywhere, and the dates
+//! of the items are still interpreted by each client in its own time zone when it syncs.
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::NaiveDate;
+//! use mtd::{Clock, FixedClock, TdList, Todo};
+//!
+//! let clock = FixedClock(NaiveDate::from_ymd(2024, 8, 15));
+//! let mut list = TdList::new_client();
+//! list.add_todo(Todo::new_specific_date("Dentist".to_string(), clock.today()).unwrap());
+//!
+//! assert_eq!(list.today(&clock).undone_todos.len(), 1);
+//! ```
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+#[cfg(not(feature = "utc"))]
+use chrono::Local;
+
+/// Returns the current date in the local time zone, or in UTC with the `utc` feature.
+pub fn current_date() -> NaiveDate {
+    current_time().date()
+}
+
+/// Returns the current time in the local time zone, or in UTC with the `utc` feature.
+pub fn current_time() -> NaiveDateTime {
+    #[cfg(not(feature = "utc"))]
+    return Local::now().naive_local();
+    #[cfg(feature = "utc")]
+    return Utc::now().naive_utc();
+}
+
+/// Converts a time in UTC to the time zone `current_time` uses.
+pub(crate) fn local_time(time: DateTime<Utc>) -> NaiveDateTime {
+    #[cfg(not(feature = "utc"))]
+    return time.with_timezone(&Local).naive_local();
+    #[cfg(feature = "utc")]
+    return time.naive_utc();
+}
+
+/// A source of the current date.
+pub trait Clock {
+    /// Returns the current date.
+    fn today(&self) -> NaiveDate;
+}
+
+/// A `Clock` returning the date of the system given by `current_date`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn today(&self) -> NaiveDate {
+        current_date()
+    }
+}
+
+/// A `Clock` that is always at the same date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub NaiveDate);
+
+impl Clock for FixedClock {
+    fn today(&self) -> NaiveDate {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::current_time;
+
+    #[test]
+    fn current_time_uses_the_time_zone_of_the_build() {
+        #[cfg(not(feature = "utc"))]
+        let expected = chrono::Local::now().naive_local();
+        #[cfg(feature = "utc")]
+        let expected = chrono::Utc::now().naive_utc();
+
+        assert!((current_time() - expected).num_seconds().abs() < 5);
+    }
+}
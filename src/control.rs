@@ -0,0 +1,287 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! Control sockets for telling long-running mtd processes, such as servers and sync watchers, to
+//! stop or to reload their config. Every such process binds a `ControlSocket` in a shared
+//! directory, and `send` delivers a request to all of them. Control sockets are Unix domain sockets
+//! and are only supported on Unix.
+//!
+//! A process checks the flag returned by `ControlSocket::stop_flag` in its loop. When it is set, the
+//! process saves its state and leaves the loop, and then checks `ControlSocket::take_request` to see
+//! whether it should start again with a reloaded config.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Error, Result};
+
+/// A request sent to running mtd processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// Save the state and exit.
+    Stop,
+    /// Save the state and start again with a reloaded config.
+    Reload,
+}
+
+impl ControlRequest {
+    fn parse(line: &str) -> Option<ControlRequest> {
+        match line.trim() {
+            "stop" => Some(ControlRequest::Stop),
+            "reload" => Some(ControlRequest::Reload),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ControlRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlRequest::Stop => write!(f, "stop"),
+            ControlRequest::Reload => write!(f, "reload"),
+        }
+    }
+}
+
+/// A socket a running mtd process receives `ControlRequest`s on. The socket is a file named
+/// `<pid>.sock` in the control directory and it is removed when the `ControlSocket` is dropped.
+/// Requests are received on a background thread.
+pub struct ControlSocket {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    request: Arc<Mutex<Option<ControlRequest>>>,
+}
+
+impl ControlSocket {
+    /// Binds a control socket in the given directory, creating the directory if needed. The
+    /// description, such as `server` or `sync --watch`, is told to processes that send requests.
+    ///
+    /// The directory must be accessible only by the user running the process, since anyone who can
+    /// write to it could stop the process or pose as one. Returns an `Error::InvalidArgument`
+    /// otherwise.
+    pub fn bind(dir: &Path, description: &str) -> Result<ControlSocket> {
+        let path = dir.join(format!("{}.sock", std::process::id()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let request = Arc::new(Mutex::new(None));
+
+        imp::listen(&path, description.to_string(), stop.clone(), request.clone())?;
+
+        Ok(ControlSocket { path, stop, request })
+    }
+
+    /// Returns the flag that is set when a request arrives. Loops that should stop on a request
+    /// check it regularly.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Returns the latest request that arrived and clears it and the stop flag.
+    pub fn take_request(&self) -> Option<ControlRequest> {
+        self.stop.store(false, Ordering::SeqCst);
+        self.request.lock().map_or(None, |mut request| request.take())
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Sends a request to every process with a control socket in the given directory. Returns the
+/// descriptions of the processes that received it. Sockets left behind by processes that didn't exit
+/// cleanly are removed. Like `ControlSocket::bind`, refuses to use a directory that other users can
+/// access.
+pub fn send(dir: &Path, request: ControlRequest) -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    imp::check_private(dir)?;
+
+    let mut received = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sock") {
+            match imp::send(&path, request) {
+                Ok(description) => received.push(description),
+                Err(Error::IOErr(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    let _ = std::fs::remove_file(&path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(received)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use nix::unistd::geteuid;
+
+    use crate::control::ControlRequest;
+    use crate::{Error, Result};
+
+    /// How long a request or its reply may take.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub(super) fn listen(path: &Path, description: String, stop: Arc<AtomicBool>, request: Arc<Mutex<Option<ControlRequest>>>) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+            check_private(dir)?;
+        }
+        // A socket with the same pid can only be left behind by an earlier process.
+        let _ = fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = handle(stream, &description, &stop, &request);
+            }
+        });
+        Ok(())
+    }
+
+    /// Returns an error unless the directory is owned by the user of this process and no one else
+    /// can access it.
+    pub(super) fn check_private(dir: &Path) -> Result<()> {
+        let meta = fs::symlink_metadata(dir)?;
+        if !meta.is_dir() || meta.uid() != geteuid().as_raw() || meta.permissions().mode() & 0o077 != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "The control directory '{}' must be a directory that only its owner can access.",
+                dir.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn handle(stream: UnixStream, description: &str, stop: &AtomicBool, request: &Mutex<Option<ControlRequest>>) -> Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+
+        match ControlRequest::parse(&line) {
+            Some(received) => {
+                if let Ok(mut request) = request.lock() {
+                    *request = Some(received);
+                }
+                stop.store(true, Ordering::SeqCst);
+                writeln!(&stream, "ok {}", description)?;
+            }
+            None => writeln!(&stream, "error unknown request")?,
+        }
+        Ok(())
+    }
+
+    pub(super) fn send(path: &Path, request: ControlRequest) -> Result<String> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        writeln!(&stream, "{}", request)?;
+
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply)?;
+        reply.trim().strip_prefix("ok ")
+            .map(str::to_string)
+            .ok_or_else(|| Error::InvalidArgument(format!("Unexpected reply from {}: {}", path.display(), reply.trim())))
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::AtomicBool;
+
+    use crate::control::ControlRequest;
+    use crate::{Error, Result};
+
+    fn unsupported() -> Error {
+        Error::InvalidArgument("Control sockets are only supported on Unix.".to_string())
+    }
+
+    pub(super) fn listen(_: &Path, _: String, _: Arc<AtomicBool>, _: Arc<Mutex<Option<ControlRequest>>>) -> Result<()> {
+        Err(unsupported())
+    }
+
+    pub(super) fn send(_: &Path, _: ControlRequest) -> Result<String> {
+        Err(unsupported())
+    }
+
+    pub(super) fn check_private(_: &Path) -> Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::sync::atomic::Ordering;
+
+    use crate::control::{ControlRequest, ControlSocket, send};
+
+    #[test]
+    fn requests_reach_every_socket_and_set_the_stop_flag() {
+        let dir = env::temp_dir().join("mtd-control-test");
+        let _ = fs::remove_dir_all(&dir);
+
+        let socket = ControlSocket::bind(&dir, "server").unwrap();
+        // A socket of a process that is gone.
+        fs::write(dir.join("1.sock"), "").unwrap();
+
+        assert_eq!(send(&dir, ControlRequest::Reload).unwrap(), vec!["server".to_string()]);
+        assert!(socket.stop_flag().load(Ordering::SeqCst));
+        assert!(!dir.join("1.sock").exists());
+
+        assert_eq!(socket.take_request(), Some(ControlRequest::Reload));
+        assert!(!socket.stop_flag().load(Ordering::SeqCst));
+        assert_eq!(socket.take_request(), None);
+
+        drop(socket);
+        assert!(send(&dir, ControlRequest::Stop).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directories_others_can_access_are_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join("mtd-control-shared-test");
+        let _ = fs::remove_dir_all(&dir);
+
+        let socket = ControlSocket::bind(&dir, "server").unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        drop(socket);
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(ControlSocket::bind(&dir, "server").is_err());
+        assert!(send(&dir, ControlRequest::Stop).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! Parsing of dates written the way people usually write them, such as "tomorrow", "next fri",
+//! "in 3 days" or "aug 20".
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::NaiveDate;
+//! use mtd::dateparse::parse_date_from;
+//!
+//! // 2022-6-10 is a Friday.
+//! let today = NaiveDate::from_ymd(2022, 6, 10);
+//!
+//! assert_eq!(parse_date_from("tomorrow", today).unwrap(), NaiveDate::from_ymd(2022, 6, 11));
+//! assert_eq!(parse_date_from("fri", today).unwrap(), today);
+//! assert_eq!(parse_date_from("next fri", today).unwrap(), NaiveDate::from_ymd(2022, 6, 17));
+//! assert_eq!(parse_date_from("in 3 days", today).unwrap(), NaiveDate::from_ymd(2022, 6, 13));
+//! assert_eq!(parse_date_from("aug 20", today).unwrap(), NaiveDate::from_ymd(2022, 8, 20));
+//! ```
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::{current_date, Error, Result, weekday_to_date_wtd};
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// Parses a date relative to the current date. See `parse_date_from` for the accepted formats.
+pub fn parse_date(input: &str) -> Result<NaiveDate> {
+    parse_date_from(input, current_date())
+}
+
+/// Parses a date relative to `today`. Returns an `Error::InvalidArgument` if the input isn't
+/// recognized. Case is ignored. The accepted formats are:
+///
+/// * `today`, `tomorrow` and `yesterday`
+/// * a weekday such as `fri` or `friday`, meaning the next such day from today on
+/// * `next` and a weekday, meaning the next such day after today
+/// * `in N days` or `in N weeks`
+/// * a month and a day such as `aug 20` or `20 august`, meaning the next such date from today on
+/// * an exact date such as `2024-08-20`
+pub fn parse_date_from(input: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let lowercase = input.trim().to_lowercase();
+    let words: Vec<&str> = lowercase.split_whitespace().collect();
+
+    let date = match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.succ_opt(),
+        ["yesterday"] => today.pred_opt(),
+        [word] => match word.parse::<Weekday>() {
+            Ok(weekday) => Some(weekday_to_date_wtd(weekday, today)),
+            Err(_) => NaiveDate::parse_from_str(word, "%Y-%m-%d").ok(),
+        },
+        ["next", word] => word.parse::<Weekday>().ok().and_then(|weekday| {
+            today.succ_opt().map(|tomorrow| weekday_to_date_wtd(weekday, tomorrow))
+        }),
+        ["in", count, unit] => parse_offset(count, unit).and_then(|offset| today.checked_add_signed(offset)),
+        [first, second] => month_day(first, second, today).or_else(|| month_day(second, first, today)),
+        _ => None,
+    };
+
+    date.ok_or_else(|| Error::InvalidArgument(format!("Unrecognized date '{}'.", input.trim())))
+}
+
+fn parse_offset(count: &str, unit: &str) -> Option<Duration> {
+    let count: u32 = count.parse().ok()?;
+    match unit {
+        "day" | "days" => Some(Duration::days(count as i64)),
+        "week" | "weeks" => Some(Duration::weeks(count as i64)),
+        _ => None,
+    }
+}
+
+/// Returns the next date from today on with the given month and day.
+fn month_day(month: &str, day: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if month.len() < 3 {
+        return None;
+    }
+    let month = MONTHS.iter().position(|name| name.starts_with(month))? as u32 + 1;
+    let day: u32 = day.parse().ok()?;
+
+    match NaiveDate::from_ymd_opt(today.year(), month, day) {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::dateparse::parse_date_from;
+
+    #[test]
+    fn parse_date_from_wraps_month_days_to_next_year() {
+        let today = NaiveDate::from_ymd(2022, 12, 30);
+
+        assert_eq!(parse_date_from("Jan 2", today).unwrap(), NaiveDate::from_ymd(2023, 1, 2));
+        assert_eq!(parse_date_from("30 december", today).unwrap(), today);
+        assert_eq!(parse_date_from("in 2 weeks", today).unwrap(), NaiveDate::from_ymd(2023, 1, 13));
+    }
+
+    #[test]
+    fn parse_date_from_rejects_unknown_input() {
+        let today = NaiveDate::from_ymd(2022, 6, 10);
+
+        assert!(parse_date_from("someday", today).is_err());
+        assert!(parse_date_from("in -1 days", today).is_err());
+        assert!(parse_date_from("feb 30", today).is_err());
+        assert!(parse_date_from("ma 3", today).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for the devices paired with a server. Instead of knowing the server's encryption
+//! password, a device is paired once with a one-time code and gets a token of its own, which it
+//! uses as its encryption password. A revoked token stops working without the other devices having
+//! to change anything.
+//!
+//! A server keeps its devices in `<save file>.devices.json` and reads the file again for every new
+//! connection, so pairing codes can be added and tokens revoked while the server runs.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{current_time, DeviceRegistry, parse_pairing_code};
+//!
+//! let mut registry = DeviceRegistry::default();
+//! let code = registry.add_pairing("laptop", current_time()).unwrap();
+//!
+//! let (name, _secret) = parse_pairing_code(&code).unwrap();
+//! assert_eq!(name, "laptop");
+//! assert!(registry.devices().is_empty());
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{Duration, NaiveDateTime};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+use crate::network::is_valid_name;
+use crate::storage::write_private_file;
+
+/// How many minutes a pairing code can be used after it was created.
+pub const PAIRING_CODE_MINUTES: i64 = 10;
+/// The characters of pairing secrets. Characters that are easy to mistake for each other are left
+/// out.
+const SECRET_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// The number of characters in a pairing secret, which is printed in groups of four.
+const SECRET_LEN: usize = 12;
+
+/// A device paired with a server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Device {
+    /// The name the device logs in with.
+    pub name: String,
+    /// The token of the device, which it uses as its encryption password.
+    pub token: String,
+    /// When the device was paired.
+    pub paired_at: NaiveDateTime,
+}
+
+/// A device waiting to be paired.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Pairing {
+    name: String,
+    secret: String,
+    expires: NaiveDateTime,
+}
+
+/// The devices paired with a server and the pairing codes that haven't been used yet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRegistry {
+    #[serde(default)]
+    devices: Vec<Device>,
+    #[serde(default)]
+    pairings: Vec<Pairing>,
+}
+
+impl DeviceRegistry {
+    /// Reads the registry from a file. A missing file is an empty registry.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(DeviceRegistry::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the registry to a file. Only the owner can read the file, because the tokens and
+    /// pairing codes are passwords.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_private_file(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Returns the paired devices.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Returns the names of the devices waiting to be paired and when their codes expire. Expired
+    /// codes are included.
+    pub fn pending(&self) -> impl Iterator<Item = (&str, NaiveDateTime)> {
+        self.pairings.iter().map(|pairing| (pairing.name.as_str(), pairing.expires))
+    }
+
+    /// Creates a one-time pairing code for a new device, which is valid for
+    /// `PAIRING_CODE_MINUTES`. An earlier code of the same device stops working.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the name isn't valid or a device with the name is
+    /// already paired.
+    pub fn add_pairing(&mut self, name: &str, now: NaiveDateTime) -> Result<String> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidArgument(format!("Invalid device name \"{}\". Use letters, numbers, '-', '_' and '.'.", name)));
+        }
+        if self.devices.iter().any(|device| device.name == name) {
+            return Err(Error::InvalidArgument(format!("The device {} is already paired. Revoke it first to pair it again.", name)));
+        }
+
+        let mut rng = rand::thread_rng();
+        let secret: String = (0..SECRET_LEN).map(|_| SECRET_ALPHABET[rng.gen_range(0..SECRET_ALPHABET.len())] as char).collect();
+        self.pairings.retain(|pairing| pairing.name != name);
+        self.pairings.push(Pairing { name: name.to_string(), secret: secret.clone(), expires: now + Duration::minutes(PAIRING_CODE_MINUTES) });
+
+        let groups: Vec<&str> = (0..SECRET_LEN).step_by(4).map(|i| &secret[i..i + 4]).collect();
+        Ok(format!("{}:{}", name, groups.join("-")))
+    }
+
+    /// Revokes the token of a device, or the pairing code of a device that isn't paired yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if there's no such device.
+    pub fn revoke(&mut self, name: &str) -> Result<()> {
+        let count = self.devices.len() + self.pairings.len();
+        self.devices.retain(|device| device.name != name);
+        self.pairings.retain(|pairing| pairing.name != name);
+        if self.devices.len() + self.pairings.len() == count {
+            return Err(Error::InvalidArgument(format!("There's no device named {}.", name)));
+        }
+        Ok(())
+    }
+
+    /// Returns the tokens of the paired devices by device name.
+    pub(crate) fn tokens(&self) -> HashMap<String, Vec<u8>> {
+        self.devices.iter().map(|device| (device.name.clone(), device.token.clone().into_bytes())).collect()
+    }
+
+    /// Returns the secrets of the pairing codes that haven't expired by device name.
+    pub(crate) fn secrets(&self, now: NaiveDateTime) -> HashMap<String, Vec<u8>> {
+        self.pairings.iter()
+            .filter(|pairing| pairing.expires > now)
+            .map(|pairing| (pairing.name.clone(), pairing.secret.clone().into_bytes()))
+            .collect()
+    }
+
+    /// Replaces the pairing code of a device with the token it was given.
+    pub(crate) fn complete_pairing(&mut self, name: &str, token: String, now: NaiveDateTime) {
+        self.pairings.retain(|pairing| pairing.name != name);
+        self.devices.retain(|device| device.name != name);
+        self.devices.push(Device { name: name.to_string(), token, paired_at: now });
+    }
+}
+
+/// Adds the address clients should connect to, `Config::advertised_addr` of the server, to a
+/// pairing code, such as `laptop:ABCD-EFGH-JKLM@mtd.example.com:4000`. The device then knows where
+/// to sync without being configured first.
+pub fn pairing_payload(code: &str, server_addr: &str) -> String {
+    format!("{}@{}", code, server_addr)
+}
+
+/// Splits a pairing payload made with `pairing_payload` into the pairing code and the address of the
+/// server. A plain pairing code has no address.
+pub fn split_pairing_payload(payload: &str) -> (&str, Option<&str>) {
+    match payload.trim().split_once('@') {
+        Some((code, addr)) => (code, Some(addr.trim()).filter(|addr| !addr.is_empty())),
+        None => (payload.trim(), None),
+    }
+}
+
+/// Splits a pairing code printed by `DeviceRegistry::add_pairing` into the device name and the
+/// secret. Case, spaces and dashes in the secret don't matter. The address of a pairing payload is
+/// left out.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidArgument` if the text isn't a pairing code.
+pub fn parse_pairing_code(code: &str) -> Result<(String, String)> {
+    let invalid = || Error::InvalidArgument(format!("\"{}\" isn't a pairing code. It looks like laptop:ABCD-EFGH-JKLM.", code));
+    let (name, secret) = split_pairing_payload(code).0.split_once(':').ok_or_else(invalid)?;
+    let secret: String = secret.chars().filter(|c| !matches!(c, '-' | ' ')).map(|c| c.to_ascii_uppercase()).collect();
+    if !is_valid_name(name) || secret.len() != SECRET_LEN || !secret.bytes().all(|b| SECRET_ALPHABET.contains(&b)) {
+        return Err(invalid());
+    }
+    Ok((name.to_string(), secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+    use std::path::PathBuf;
+
+    use chrono::{Duration, NaiveDate};
+
+    use crate::devices::{DeviceRegistry, PAIRING_CODE_MINUTES, pairing_payload, parse_pairing_code, split_pairing_payload};
+
+    /// Returns a path in the temporary directory that no other test or test run uses at the same time.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("mtd-{}-{}.json", name, process::id()))
+    }
+
+    #[test]
+    fn pairing_codes_are_replaced_with_tokens_once_used() {
+        let now = NaiveDate::from_ymd(2024, 5, 1).and_hms(12, 0, 0);
+        let mut registry = DeviceRegistry::default();
+        let code = registry.add_pairing("laptop", now).unwrap();
+        let (name, secret) = parse_pairing_code(&code.to_lowercase()).unwrap();
+
+        assert_eq!(name, "laptop");
+        assert_eq!(registry.secrets(now).get("laptop"), Some(&secret.clone().into_bytes()));
+        assert!(registry.secrets(now + Duration::minutes(PAIRING_CODE_MINUTES)).is_empty());
+        assert!(registry.add_pairing("bad:name", now).is_err());
+
+        registry.complete_pairing("laptop", "token".to_string(), now + Duration::minutes(1));
+        assert!(registry.secrets(now).is_empty());
+        assert_eq!(registry.tokens().get("laptop"), Some(&b"token".to_vec()));
+        assert!(registry.add_pairing("laptop", now).is_err());
+
+        let path = temp_path("device-registry-test");
+        registry.write(&path).unwrap();
+        let mut read = DeviceRegistry::read(&path).unwrap();
+        assert_eq!(read, registry);
+
+        read.revoke("laptop").unwrap();
+        assert!(read.tokens().is_empty());
+        assert!(read.revoke("laptop").is_err());
+        assert!(DeviceRegistry::read(&temp_path("nonexistent-devices")).unwrap().devices().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pairing_payloads_carry_the_server_address() {
+        let payload = pairing_payload("phone:ABCD-EFGH-JKL2", "[::1]:4000");
+
+        assert_eq!(payload, "phone:ABCD-EFGH-JKL2@[::1]:4000");
+        assert_eq!(split_pairing_payload(&payload), ("phone:ABCD-EFGH-JKL2", Some("[::1]:4000")));
+        assert_eq!(parse_pairing_code(&payload).unwrap(), ("phone".to_string(), "ABCDEFGHJKL2".to_string()));
+        assert_eq!(split_pairing_payload(" phone:ABCD-EFGH-JKL2 "), ("phone:ABCD-EFGH-JKL2", None));
+        assert_eq!(split_pairing_payload("phone:ABCD-EFGH-JKL2@"), ("phone:ABCD-EFGH-JKL2", None));
+    }
+
+    #[test]
+    fn pairing_codes_are_parsed_leniently() {
+        assert_eq!(parse_pairing_code(" phone:abcd efgh-jkl2 ").unwrap(), ("phone".to_string(), "ABCDEFGHJKL2".to_string()));
+        assert!(parse_pairing_code("phone").is_err());
+        assert!(parse_pairing_code("phone:ABCD-EFGH").is_err());
+        assert!(parse_pairing_code("phone:ABCD-EFGH-JKL1").is_err());
+        assert!(parse_pairing_code(":ABCD-EFGH-JKLM").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn registry_is_readable_only_by_the_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("device-registry-mode-test");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut registry = DeviceRegistry::default();
+        registry.add_pairing("laptop", NaiveDate::from_ymd(2024, 5, 1).and_hms(12, 0, 0)).unwrap();
+        registry.write(&path).unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(DeviceRegistry::read(&path).unwrap(), registry);
+        fs::remove_file(path).unwrap();
+    }
+}
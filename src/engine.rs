@@ -0,0 +1,1166 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! The sync engine: the handshake and sync protocol of mtd without any networking. A `ClientEngine`
+//! and a `ServerEngine` exchange `ClientMessage`s and `ServerMessage`s, which the engines encode into
+//! encrypted frames. Frontends move the frames over any channel they like. `MtdNetMgr` drives the
+//! engines over its `Transport`s.
+//!
+//! A frame is sent as is, so a channel that doesn't keep message boundaries has to add its own
+//! framing. `MtdNetMgr` prefixes each frame with its length as a little-endian `u32`.
+//!
+//! A server can host a separate list for each of its users. A client that logs in as a user sends
+//! the user name in front of its first frame, and the server decrypts the rest with that user's
+//! password. The handshake only succeeds if the client knows the password, after which
+//! `ServerEngine::user` tells whose list the client syncs. The user name itself isn't encrypted.
+//!
+//! Devices paired with a server log in the same way with `device:<name>` and their token as the
+//! password, and sync the shared list. A device being paired logs in with `pair:<name>` and the
+//! secret of its pairing code, and may only ask for its token. User names can't contain `:`, so
+//! these never clash with users.
+//!
+//! Servers that support delta syncs tell it in their welcome. A client syncing with such a server
+//! sends the `SyncToken` of its last sync and gets only the items changed after it together with
+//! the sync ids of all items, and it commits only the items it changed. Clients rebuild the
+//! server's list from their own items, so a client missing some of them reads the whole list
+//! instead. Older clients and servers keep sending whole lists.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{TdList, Todo};
+//! use mtd::engine::{ClientAction, ClientEngine, ClientOutcome, ClientRequest, ServerEngine};
+//!
+//! let mut client_list = TdList::new_client();
+//! client_list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+//! let mut server_list = TdList::new_server();
+//!
+//! let mut client = ClientEngine::new(ClientRequest::Sync, client_list, b"secret");
+//! let mut server = ServerEngine::new(b"secret");
+//!
+//! let hello = client.start();
+//! let mut frame = client.encode(&hello).unwrap();
+//! let outcome = loop {
+//!     // The frame travels to the server and the reply back to the client.
+//!     let action = server.handle(server.decode(&frame).unwrap(), &mut server_list).unwrap();
+//!     let reply = server.encode(&action.reply.unwrap()).unwrap();
+//!
+//!     let reply = client.decode(&reply).unwrap();
+//!     match client.handle(reply).unwrap() {
+//!         ClientAction::Send(msg) => frame = client.encode(&msg).unwrap(),
+//!         ClientAction::Finish { outcome, .. } => break outcome,
+//!     }
+//! };
+//!
+//! match outcome {
+//!     ClientOutcome::Synced(list) => assert_eq!(list.todos().len(), 1),
+//!     _ => unreachable!(),
+//! }
+//! assert_eq!(server_list.todos().len(), 1);
+//! ```
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rand::random;
+
+use crate::{Conflict, ConflictPolicy, Error, ListDelta, Result, SyncToken, TdList};
+use crate::network::crypt::{decrypt, encrypt};
+use crate::proxy::base64;
+
+/// The start of a first frame that logs in as a user. The user name and a newline follow it.
+const LOGIN_PREFIX: &[u8] = b"mtd-login\n";
+/// The starts of the login names of paired devices and of devices being paired.
+const DEVICE_LOGIN: &str = "device:";
+const PAIR_LOGIN: &str = "pair:";
+/// The version of the sync protocol that this crate speaks. Version 1 syncs whole lists, version 2
+/// adds delta syncs and version 3 pairing devices. Servers tell their version in the welcome;
+/// servers that don't are version 1.
+pub const PROTOCOL_VERSION: u32 = 3;
+/// The oldest version of the sync protocol that this crate still speaks.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// The first version of the sync protocol with delta syncs.
+const DELTA_VERSION: u32 = 2;
+/// The first version of the sync protocol with pairing devices.
+const PAIRING_VERSION: u32 = 3;
+/// The starts of messages containing a `ListDelta`, a request for one and a `SyncToken`.
+const DELTA_PREFIX: &[u8] = b"delta\n";
+const READ_DELTA_PREFIX: &[u8] = b"read-delta\n";
+const SAVED_PREFIX: &[u8] = b"saved\n";
+/// The start of a message containing the token of a paired device.
+const PAIRED_PREFIX: &[u8] = b"paired\n";
+
+/// What a client wants from the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientRequest {
+    /// Synchronize the client's items with the server.
+    Sync,
+    /// Get the server's items without synchronizing.
+    Fetch,
+    /// Only check that the server is reachable and uses the same password.
+    Ping,
+    /// Get a token for a device with a pairing code. Engines making this request are created with
+    /// `ClientEngine::pair`.
+    Pair,
+}
+
+/// Messages sent by a client.
+#[derive(Debug, Clone)]
+pub enum ClientMessage {
+    /// Starts the handshake. The server proves that it knows the password by echoing the random
+    /// `auth_data`. Clients that send their time get the server's time back.
+    Hello {
+        /// Random data the server has to echo.
+        auth_data: [u8; 8],
+        /// The client's time in milliseconds since the Unix epoch.
+        sent_at: Option<i64>,
+        /// The user the client logs in as. `None` for the server's shared list.
+        user: Option<String>,
+    },
+    /// Asks for the server's items for synchronizing.
+    Read,
+    /// Asks for the server's items changed after the client's last delta sync. `None` asks for all
+    /// items.
+    ReadDelta(Option<SyncToken>),
+    /// Asks for the server's items without synchronizing.
+    Peek,
+    /// Checks that the connection works.
+    Ping,
+    /// Asks for the token of a device being paired.
+    Pair,
+    /// The synchronized items that the server should save.
+    Commit(TdList),
+    /// The synchronized items that changed, which the server should save.
+    CommitDelta(ListDelta),
+    /// Acknowledges that the client got the server's confirmation of a commit.
+    Ack {
+        /// Asks the server to keep the connection open for the next request.
+        keep: bool,
+    },
+}
+
+/// Messages sent by a server.
+#[derive(Debug, Clone)]
+pub enum ServerMessage {
+    /// Answers the handshake with the session id used by the rest of the messages.
+    Welcome {
+        /// The session id.
+        sid: [u8; 8],
+        /// The echoed random data of the client.
+        auth_data: [u8; 8],
+        /// The echoed time of the client in milliseconds since the Unix epoch, if the client sent
+        /// its time.
+        sent_at: Option<i64>,
+        /// The server's own time in milliseconds since the Unix epoch. Only told to clients that
+        /// sent their time. Servers older than clock skew checks echo the time without their own.
+        server_time: Option<i64>,
+        /// The protocol version of the server and the oldest version it still speaks. Only told
+        /// along with the server's time, and `(1, 1)` for servers that don't tell it.
+        versions: (u32, u32),
+    },
+    /// The server's items.
+    Items(TdList),
+    /// The server's items changed after the client's last delta sync.
+    Delta(ListDelta),
+    /// Confirms a commit or a ping.
+    Ok,
+    /// Confirms a delta commit with the token of the saved items.
+    Saved(SyncToken),
+    /// The token of a paired device.
+    Paired(String),
+    /// Refuses a commit that exceeds the server's limits. Contains a description of each offending
+    /// item.
+    Rejected(Vec<String>),
+}
+
+impl ClientMessage {
+    fn encode(&self, sid: &[u8; 8]) -> Result<Vec<u8>> {
+        let body = match self {
+            ClientMessage::Hello { auth_data, sent_at, .. } => {
+                let mut hello = auth_data.to_vec();
+                if let Some(sent_at) = sent_at {
+                    hello.extend_from_slice(&sent_at.to_le_bytes());
+                }
+                return Ok(hello);
+            }
+            ClientMessage::Read => b"read".to_vec(),
+            ClientMessage::ReadDelta(token) => [READ_DELTA_PREFIX, &serde_json::to_vec(token)?].concat(),
+            ClientMessage::Peek => b"peek".to_vec(),
+            ClientMessage::Ping => b"ping".to_vec(),
+            ClientMessage::Pair => b"pair".to_vec(),
+            ClientMessage::Commit(list) => list.to_json()?.into_bytes(),
+            ClientMessage::CommitDelta(delta) => [DELTA_PREFIX, &serde_json::to_vec(delta)?].concat(),
+            ClientMessage::Ack { keep: false } => b"ack".to_vec(),
+            ClientMessage::Ack { keep: true } => b"ack-keep".to_vec(),
+        };
+        Ok([sid.as_slice(), &body].concat())
+    }
+
+    fn decode(msg: &[u8], sid: Option<&[u8; 8]>, commit: bool) -> Result<Self> {
+        let sid = match sid {
+            Some(sid) => sid,
+            None => {
+                return match msg.len() {
+                    8 => Ok(ClientMessage::Hello { auth_data: msg.try_into().unwrap(), sent_at: None, user: None }),
+                    16 => Ok(ClientMessage::Hello {
+                        auth_data: msg[..8].try_into().unwrap(),
+                        sent_at: Some(i64::from_le_bytes(msg[8..].try_into().unwrap())),
+                        user: None,
+                    }),
+                    _ => Err(Error::AuthFailed),
+                };
+            }
+        };
+
+        let body = check_sid(sid, msg)?;
+        if let Some(json) = body.strip_prefix(READ_DELTA_PREFIX) {
+            return Ok(ClientMessage::ReadDelta(serde_json::from_slice(json)?));
+        }
+        Ok(match body {
+            // A client that can't rebuild the server's items from a delta reads them all instead.
+            b"read" => ClientMessage::Read,
+            json if commit => match json.strip_prefix(DELTA_PREFIX) {
+                Some(json) => ClientMessage::CommitDelta(serde_json::from_slice(json)?),
+                None => ClientMessage::Commit(TdList::new_from_json(&String::from_utf8_lossy(json))?),
+            },
+            b"peek" => ClientMessage::Peek,
+            b"ping" => ClientMessage::Ping,
+            b"pair" => ClientMessage::Pair,
+            b"ack" => ClientMessage::Ack { keep: false },
+            b"ack-keep" => ClientMessage::Ack { keep: true },
+            _ => return Err(Error::Unknown),
+        })
+    }
+}
+
+impl ServerMessage {
+    fn encode(&self, sid: &[u8; 8]) -> Result<Vec<u8>> {
+        let body = match self {
+            ServerMessage::Welcome { sid, auth_data, sent_at, server_time, versions: (version, min_version) } => {
+                let mut welcome = [sid.as_slice(), auth_data].concat();
+                if let Some(sent_at) = sent_at {
+                    welcome.extend_from_slice(&sent_at.to_le_bytes());
+                }
+                if let (Some(_), Some(server_time)) = (sent_at, server_time) {
+                    welcome.extend_from_slice(&server_time.to_le_bytes());
+                    // Older clients ignore anything after the times.
+                    welcome.extend_from_slice(&version.to_le_bytes());
+                    welcome.extend_from_slice(&min_version.to_le_bytes());
+                }
+                return Ok(welcome);
+            }
+            ServerMessage::Items(list) => list.to_json()?.into_bytes(),
+            ServerMessage::Delta(delta) => [DELTA_PREFIX, &serde_json::to_vec(delta)?].concat(),
+            ServerMessage::Ok => b"ok".to_vec(),
+            ServerMessage::Saved(token) => [SAVED_PREFIX, &serde_json::to_vec(token)?].concat(),
+            ServerMessage::Paired(token) => [PAIRED_PREFIX, token.as_bytes()].concat(),
+            ServerMessage::Rejected(problems) => {
+                [b"rejected".as_slice(), problems.iter().map(|p| format!("\n{}", p)).collect::<String>().as_bytes()].concat()
+            }
+        };
+        Ok([sid.as_slice(), &body].concat())
+    }
+
+    fn decode(msg: &[u8], sid: Option<&[u8; 8]>) -> Result<Self> {
+        let sid = match sid {
+            Some(sid) => sid,
+            None => {
+                if msg.len() < 16 {
+                    return Err(Error::AuthFailed);
+                }
+                // Servers older than clock skew checks echo the whole hello, so the client's time
+                // may come without the server's.
+                let sent_at = msg.get(16..24).map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()));
+                let server_time = msg.get(24..32).map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()));
+                let versions = match (msg.get(32..36), msg.get(36..40)) {
+                    (Some(version), Some(min_version)) => (
+                        u32::from_le_bytes(version.try_into().unwrap()),
+                        u32::from_le_bytes(min_version.try_into().unwrap()),
+                    ),
+                    _ => (1, 1),
+                };
+                return Ok(ServerMessage::Welcome {
+                    sid: msg[..8].try_into().unwrap(),
+                    auth_data: msg[8..16].try_into().unwrap(),
+                    sent_at,
+                    server_time,
+                    versions,
+                });
+            }
+        };
+
+        Ok(match check_sid(sid, msg)? {
+            b"ok" => ServerMessage::Ok,
+            rejected if rejected.starts_with(b"rejected") => {
+                ServerMessage::Rejected(String::from_utf8_lossy(rejected).lines().skip(1).map(str::to_string).collect())
+            }
+            delta if delta.starts_with(DELTA_PREFIX) => ServerMessage::Delta(serde_json::from_slice(&delta[DELTA_PREFIX.len()..])?),
+            saved if saved.starts_with(SAVED_PREFIX) => ServerMessage::Saved(serde_json::from_slice(&saved[SAVED_PREFIX.len()..])?),
+            paired if paired.starts_with(PAIRED_PREFIX) => ServerMessage::Paired(String::from_utf8_lossy(&paired[PAIRED_PREFIX.len()..]).into_owned()),
+            json => ServerMessage::Items(TdList::new_from_json(&String::from_utf8_lossy(json))?),
+        })
+    }
+}
+
+/// Checks if a message contains a valid session id. Returns the message without the session id if
+/// the session id is correct. Otherwise returns an `Error::AuthFailed`.
+fn check_sid<'b>(correct_sid: &[u8; 8], msg_with_sid: &'b [u8]) -> Result<&'b [u8]> {
+    if msg_with_sid.len() >= 8 && &msg_with_sid[..8] == correct_sid {
+        Ok(&msg_with_sid[8..])
+    } else {
+        Err(Error::AuthFailed)
+    }
+}
+
+/// Splits the user name from the first frame of a client that logs in as a user. Returns `None` and
+/// the frame as is if the client doesn't log in.
+fn split_login(frame: &[u8]) -> Result<(Option<String>, &[u8])> {
+    let login = match frame.strip_prefix(LOGIN_PREFIX) {
+        Some(login) => login,
+        None => return Ok((None, frame)),
+    };
+    let end = login.iter().position(|b| *b == b'\n').ok_or(Error::AuthFailed)?;
+    let user = String::from_utf8(login[..end].to_vec()).map_err(|_| Error::AuthFailed)?;
+    Ok((Some(user), &login[end + 1..]))
+}
+
+/// The phase of a client's request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPhase {
+    /// Connecting to the server.
+    #[default]
+    Connecting,
+    /// Authenticating the server and agreeing on a session id.
+    Handshake,
+    /// Receiving the server's items.
+    Receiving,
+    /// Merging the server's items with the client's items.
+    Merging,
+    /// Sending the merged items and waiting for the server to save them.
+    Sending,
+    /// The request is done.
+    Done,
+}
+
+/// How far a client's request has progressed. Byte counts are the sizes of the encrypted frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyncProgress {
+    /// The current phase.
+    pub phase: SyncPhase,
+    /// The number of items sent to the server.
+    pub items_sent: usize,
+    /// The number of items received from the server.
+    pub items_received: usize,
+    /// The number of bytes sent to the server.
+    pub bytes_sent: u64,
+    /// The number of bytes received from the server.
+    pub bytes_received: u64,
+    /// The number of bytes received once the frame being received is complete, or 0 if no frame is
+    /// being received. Frontends that read frames in parts set it to show how far the frame is.
+    pub bytes_expected: u64,
+}
+
+/// What a client should do after handling a server's message.
+#[derive(Debug)]
+pub enum ClientAction {
+    /// Send a message and wait for the server's reply.
+    Send(ClientMessage),
+    /// The request is done. The reply should still be sent to the server, but the outcome doesn't
+    /// depend on the server getting it.
+    Finish {
+        /// A last message to the server.
+        reply: Option<ClientMessage>,
+        /// The result of the request.
+        outcome: ClientOutcome,
+    },
+}
+
+/// The result of a client's request.
+#[derive(Debug)]
+pub enum ClientOutcome {
+    /// The client's items after synchronizing. The server has already saved its items.
+    Synced(TdList),
+    /// The server's items.
+    Fetched(TdList),
+    /// The server is reachable and uses the same password.
+    Connected,
+    /// The token of the paired device.
+    Paired(String),
+}
+
+#[derive(Debug)]
+enum ClientState {
+    Start,
+    Resumed,
+    AwaitWelcome { auth_data: [u8; 8], sent_at: i64 },
+    AwaitItems,
+    AwaitToken,
+    AwaitOk { synced: TdList },
+    Done,
+}
+
+/// The client side of the sync protocol. A `ClientEngine` makes one request. A client that keeps
+/// its connection open makes the next request with an engine created with `ClientEngine::resume`.
+#[derive(Debug)]
+pub struct ClientEngine {
+    request: ClientRequest,
+    list: TdList,
+    password: Vec<u8>,
+    user: Option<String>,
+    keep_alive: bool,
+    server_version: u32,
+    conflict_policy: ConflictPolicy,
+    conflicts: Vec<Conflict>,
+    sid: Option<[u8; 8]>,
+    clock_skew: Option<chrono::Duration>,
+    progress: Cell<SyncProgress>,
+    state: ClientState,
+}
+
+impl ClientEngine {
+    /// Creates an engine that makes a request starting with a handshake. `list` is the client's
+    /// `TdList` and `password` the encryption password shared with the server.
+    pub fn new(request: ClientRequest, list: TdList, password: &[u8]) -> Self {
+        Self {
+            request,
+            list,
+            password: password.to_vec(),
+            user: None,
+            keep_alive: false,
+            server_version: 1,
+            conflict_policy: ConflictPolicy::default(),
+            conflicts: Vec::new(),
+            sid: None,
+            clock_skew: None,
+            progress: Cell::new(SyncProgress::default()),
+            state: ClientState::Start,
+        }
+    }
+
+    /// Creates an engine that makes a request over a connection that has already done a handshake
+    /// with the given session id.
+    pub fn resume(request: ClientRequest, list: TdList, password: &[u8], sid: [u8; 8]) -> Self {
+        let mut engine = ClientEngine::new(request, list, password);
+        engine.sid = Some(sid);
+        engine.state = ClientState::Resumed;
+        engine
+    }
+
+    /// Creates an engine that gets a token for the device `name` from the server with the secret of
+    /// a pairing code. The request finishes with `ClientOutcome::Paired`.
+    pub fn pair(name: &str, secret: &[u8]) -> Self {
+        let mut engine = ClientEngine::new(ClientRequest::Pair, TdList::new_client(), secret);
+        engine.user = Some(format!("{}{}", PAIR_LOGIN, name));
+        engine
+    }
+
+    /// Sets the user the client logs in as during the handshake. The password of the engine is then
+    /// the user's password. `None` uses the server's shared list.
+    pub fn set_user(&mut self, user: Option<String>) {
+        self.user = user;
+    }
+
+    /// Sets the paired device the client logs in as during the handshake. The password of the
+    /// engine is then the device's token. Devices sync the server's shared list.
+    pub fn set_device(&mut self, device: Option<&str>) {
+        self.user = device.map(|device| format!("{}{}", DEVICE_LOGIN, device));
+    }
+
+    /// Sets whether the client asks the server to keep the connection open after a sync.
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    /// Returns the sync protocol version used with the server. Known once the handshake is done.
+    pub fn server_version(&self) -> u32 {
+        self.server_version
+    }
+
+    /// Sets the sync protocol version used with the server. Engines find it out during the
+    /// handshake, so only engines created with `ClientEngine::resume` need this.
+    pub fn set_server_version(&mut self, version: u32) {
+        self.server_version = version;
+    }
+
+    /// Sets how items changed both on the client and on the server are resolved.
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// Returns the items that were changed both on the client and on the server, once the items are
+    /// synced.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Returns the session id once the handshake is done.
+    pub fn sid(&self) -> Option<[u8; 8]> {
+        self.sid
+    }
+
+    /// Returns how much the server's clock is ahead of the local clock, measured during the
+    /// handshake. `None` before the handshake or if the server is too old to tell its time.
+    pub fn clock_skew(&self) -> Option<chrono::Duration> {
+        self.clock_skew
+    }
+
+    /// Returns how far the request has progressed.
+    pub fn progress(&self) -> SyncProgress {
+        self.progress.get()
+    }
+
+    /// Changes the progress with the given function.
+    fn update_progress(&self, update: impl FnOnce(&mut SyncProgress)) {
+        let mut progress = self.progress.get();
+        update(&mut progress);
+        self.progress.set(progress);
+    }
+
+    /// Returns the first message to send to the server.
+    pub fn start(&mut self) -> ClientMessage {
+        match self.state {
+            ClientState::Start => {
+                let auth_data: [u8; 8] = random();
+                let sent_at = Utc::now().timestamp_millis();
+                self.update_progress(|progress| progress.phase = SyncPhase::Handshake);
+                self.state = ClientState::AwaitWelcome { auth_data, sent_at };
+                ClientMessage::Hello { auth_data, sent_at: Some(sent_at), user: self.user.clone() }
+            }
+            // A resumed engine skips the handshake.
+            _ => self.request_message(),
+        }
+    }
+
+    fn request_message(&mut self) -> ClientMessage {
+        let phase;
+        (self.state, phase) = match self.request {
+            ClientRequest::Ping => (ClientState::AwaitOk { synced: TdList::new_client() }, SyncPhase::Sending),
+            ClientRequest::Pair => (ClientState::AwaitToken, SyncPhase::Receiving),
+            _ => (ClientState::AwaitItems, SyncPhase::Receiving),
+        };
+        self.update_progress(|progress| progress.phase = phase);
+        match self.request {
+            ClientRequest::Sync if self.server_version >= DELTA_VERSION => ClientMessage::ReadDelta(self.list.sync_token()),
+            ClientRequest::Sync => ClientMessage::Read,
+            ClientRequest::Fetch => ClientMessage::Peek,
+            ClientRequest::Ping => ClientMessage::Ping,
+            ClientRequest::Pair => ClientMessage::Pair,
+        }
+    }
+
+    /// Handles a message from the server. Returns an `Error::AuthFailed` if the server doesn't know
+    /// the password and an `Error::Unknown` if the message isn't what the protocol expects.
+    pub fn handle(&mut self, msg: ServerMessage) -> Result<ClientAction> {
+        match (std::mem::replace(&mut self.state, ClientState::Done), msg) {
+            (ClientState::AwaitWelcome { auth_data, sent_at }, ServerMessage::Welcome { sid, auth_data: echoed, sent_at: echoed_sent_at, server_time, versions: (version, min_version) }) => {
+                let received_at = Utc::now().timestamp_millis();
+                if echoed != auth_data || echoed_sent_at != Some(sent_at) {
+                    return Err(Error::AuthFailed);
+                }
+                if version < MIN_PROTOCOL_VERSION || min_version > PROTOCOL_VERSION || (self.request == ClientRequest::Pair && version < PAIRING_VERSION) {
+                    return Err(Error::IncompatibleProtocol { client: PROTOCOL_VERSION, server: version });
+                }
+                if let Some(server_time) = server_time {
+                    // Assume that the server's time was taken halfway through the round trip.
+                    let local_time = sent_at + (received_at - sent_at) / 2;
+                    self.clock_skew = Some(chrono::Duration::milliseconds(server_time - local_time));
+                }
+                self.sid = Some(sid);
+                // Speak the newest version that both know.
+                self.server_version = version.min(PROTOCOL_VERSION);
+                Ok(ClientAction::Send(self.request_message()))
+            }
+            (ClientState::AwaitItems, ServerMessage::Items(mut server)) => {
+                self.update_progress(|progress| progress.items_received = server.item_count());
+                if self.request == ClientRequest::Fetch {
+                    self.update_progress(|progress| progress.phase = SyncPhase::Done);
+                    return Ok(ClientAction::Finish { reply: None, outcome: ClientOutcome::Fetched(server) });
+                }
+                // Sync a copy so that the client's list isn't left half synced if the commit fails.
+                let mut synced = self.list.clone();
+                self.conflicts = synced.sync_with_policy(&mut server, self.conflict_policy);
+                synced.set_sync_token(None);
+                self.update_progress(|progress| {
+                    progress.phase = SyncPhase::Sending;
+                    progress.items_sent = server.item_count();
+                });
+                self.state = ClientState::AwaitOk { synced };
+                Ok(ClientAction::Send(ClientMessage::Commit(server)))
+            }
+            (ClientState::AwaitItems, ServerMessage::Delta(delta)) => {
+                self.update_progress(|progress| progress.items_received = delta.item_count());
+                let mut server = match self.list.apply_delta(delta) {
+                    Some(server) => server,
+                    None => {
+                        // The server has items this client doesn't, such as ones removed locally
+                        // without syncing. Only the whole list can tell what they are.
+                        self.update_progress(|progress| progress.phase = SyncPhase::Receiving);
+                        self.state = ClientState::AwaitItems;
+                        return Ok(ClientAction::Send(ClientMessage::Read));
+                    }
+                };
+                let before = server.clone();
+                let mut synced = self.list.clone();
+                self.conflicts = synced.sync_with_policy(&mut server, self.conflict_policy);
+                let delta = server.delta_from(&before);
+                self.update_progress(|progress| {
+                    progress.phase = SyncPhase::Sending;
+                    progress.items_sent = delta.item_count();
+                });
+                self.state = ClientState::AwaitOk { synced };
+                Ok(ClientAction::Send(ClientMessage::CommitDelta(delta)))
+            }
+            (ClientState::AwaitOk { synced }, ServerMessage::Ok) => {
+                self.update_progress(|progress| progress.phase = SyncPhase::Done);
+                if self.request == ClientRequest::Ping {
+                    return Ok(ClientAction::Finish { reply: None, outcome: ClientOutcome::Connected });
+                }
+                Ok(ClientAction::Finish {
+                    reply: Some(ClientMessage::Ack { keep: self.keep_alive }),
+                    outcome: ClientOutcome::Synced(synced),
+                })
+            }
+            (ClientState::AwaitOk { mut synced }, ServerMessage::Saved(token)) => {
+                self.update_progress(|progress| progress.phase = SyncPhase::Done);
+                synced.set_sync_token(Some(token));
+                Ok(ClientAction::Finish {
+                    reply: Some(ClientMessage::Ack { keep: self.keep_alive }),
+                    outcome: ClientOutcome::Synced(synced),
+                })
+            }
+            (ClientState::AwaitToken, ServerMessage::Paired(token)) => {
+                self.update_progress(|progress| progress.phase = SyncPhase::Done);
+                Ok(ClientAction::Finish { reply: None, outcome: ClientOutcome::Paired(token) })
+            }
+            (ClientState::AwaitOk { .. }, ServerMessage::Rejected(problems)) => Err(Error::LimitsExceeded(problems)),
+            (ClientState::AwaitWelcome { .. }, _) => Err(Error::AuthFailed),
+            _ => Err(Error::Unknown),
+        }
+    }
+
+    /// Encodes and encrypts a message into a frame. The frame counts as sent in the progress.
+    pub fn encode(&self, msg: &ClientMessage) -> Result<Vec<u8>> {
+        let mut frame = encrypt(&msg.encode(&self.sid.unwrap_or_default())?, &self.password)?;
+        if let ClientMessage::Hello { user: Some(user), .. } = msg {
+            frame = [LOGIN_PREFIX, user.as_bytes(), b"\n", &frame].concat();
+        }
+        self.update_progress(|progress| progress.bytes_sent += frame.len() as u64);
+        Ok(frame)
+    }
+
+    /// Decrypts and decodes a frame sent by the server. The frame counts as received in the
+    /// progress.
+    pub fn decode(&self, frame: &[u8]) -> Result<ServerMessage> {
+        self.update_progress(|progress| {
+            progress.bytes_received += frame.len() as u64;
+            progress.bytes_expected = 0;
+        });
+        let msg = ServerMessage::decode(&decrypt(frame, &self.password)?, self.sid.as_ref())?;
+        if matches!(msg, ServerMessage::Items(_) | ServerMessage::Delta(_)) && self.request == ClientRequest::Sync {
+            // Merging a large list takes a while, so frontends can report it before calling handle.
+            self.update_progress(|progress| progress.phase = SyncPhase::Merging);
+        }
+        Ok(msg)
+    }
+}
+
+/// The message a `ServerEngine` waits for next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    /// The handshake.
+    AwaitHello,
+    /// A new request.
+    AwaitCommand,
+    /// The synchronized items of a sync.
+    AwaitCommit,
+    /// The acknowledgement of a saved sync.
+    AwaitAck,
+}
+
+/// What a server should do after handling a client's message.
+#[derive(Debug)]
+pub struct ServerAction {
+    /// A message to send to the client.
+    pub reply: Option<ServerMessage>,
+    /// `true` if the server's items changed and should be saved before the reply is sent.
+    pub save: bool,
+    /// `Some` when a request is done. `Some(true)` if the connection should be kept open for the
+    /// client's next request.
+    pub finished: Option<bool>,
+    /// The name and the new token of a device that was paired. The server should save the token
+    /// in place of the pairing code before the reply is sent.
+    pub paired: Option<(String, String)>,
+}
+
+impl ServerAction {
+    fn reply(msg: ServerMessage) -> Self {
+        Self { reply: Some(msg), save: false, finished: None, paired: None }
+    }
+}
+
+/// The server side of the sync protocol. A `ServerEngine` handles the requests of one connection.
+#[derive(Debug)]
+pub struct ServerEngine {
+    password: Vec<u8>,
+    users: HashMap<String, Vec<u8>>,
+    user: Option<String>,
+    devices: HashMap<String, Vec<u8>>,
+    device: Option<String>,
+    pairings: HashMap<String, Vec<u8>>,
+    pairing: Option<String>,
+    sid: [u8; 8],
+    kept: bool,
+    state: ServerState,
+    max_items: Option<usize>,
+    max_body_len: Option<usize>,
+}
+
+impl ServerEngine {
+    /// Creates an engine for a new connection with a random session id. `password` is the
+    /// encryption password shared with the clients. An empty password only lets users set with
+    /// `ServerEngine::set_users` in.
+    pub fn new(password: &[u8]) -> Self {
+        Self {
+            password: password.to_vec(),
+            users: HashMap::new(),
+            user: None,
+            devices: HashMap::new(),
+            device: None,
+            pairings: HashMap::new(),
+            pairing: None,
+            sid: random(),
+            kept: false,
+            state: ServerState::AwaitHello,
+            max_items: None,
+            max_body_len: None,
+        }
+    }
+
+    /// Creates an engine for a connection that is kept open between requests and has already done
+    /// a handshake with the given session id.
+    pub fn resume(password: &[u8], sid: [u8; 8]) -> Self {
+        Self { sid, kept: true, state: ServerState::AwaitCommand, ..ServerEngine::new(password) }
+    }
+
+    /// Sets how many items and how long bodies the engine accepts in a commit. A commit exceeding
+    /// the limits is answered with `ServerMessage::Rejected` and the server's items are left as they
+    /// were. `None` means no limit.
+    pub fn set_limits(&mut self, max_items: Option<usize>, max_body_len: Option<usize>) {
+        self.max_items = max_items;
+        self.max_body_len = max_body_len;
+    }
+
+    /// Sets the users clients can log in as and their passwords. Users without a password, such as
+    /// the users of a config restored from a backup without secrets, can't log in.
+    pub fn set_users(&mut self, users: HashMap<String, Vec<u8>>) {
+        self.users = users.into_iter().filter(|(_, password)| !password.is_empty()).collect();
+    }
+
+    /// Sets the paired devices that can log in and their tokens.
+    pub fn set_devices(&mut self, devices: HashMap<String, Vec<u8>>) {
+        self.devices = devices;
+    }
+
+    /// Sets the devices waiting to be paired and the secrets of their pairing codes.
+    pub fn set_pairings(&mut self, pairings: HashMap<String, Vec<u8>>) {
+        self.pairings = pairings;
+    }
+
+    /// Returns the user the client logged in as during the handshake. `None` if the client uses the
+    /// shared list.
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// Returns the paired device the client logged in as during the handshake.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Returns the password of a login name: the token of a device, the secret of a pairing code or
+    /// the password of a user. An empty password is never accepted.
+    fn login_password(&self, login: &str) -> Option<&Vec<u8>> {
+        let password = if let Some(device) = login.strip_prefix(DEVICE_LOGIN) {
+            self.devices.get(device)
+        } else if let Some(device) = login.strip_prefix(PAIR_LOGIN) {
+            self.pairings.get(device)
+        } else {
+            self.users.get(login)
+        };
+        password.filter(|password| !password.is_empty())
+    }
+
+    /// Returns the session id of the connection.
+    pub fn sid(&self) -> [u8; 8] {
+        self.sid
+    }
+
+    /// Returns the message the engine waits for next.
+    pub fn state(&self) -> ServerState {
+        self.state
+    }
+
+    /// Handles a message from the client. `list` is the server's `TdList`, which is replaced by the
+    /// client's commit. Returns an `Error::Unknown` if the message isn't what the protocol expects.
+    pub fn handle(&mut self, msg: ClientMessage, list: &mut TdList) -> Result<ServerAction> {
+        match (self.state, msg) {
+            (ServerState::AwaitHello, ClientMessage::Hello { auth_data, sent_at, user }) => {
+                if let Some(login) = user {
+                    self.password = self.login_password(&login).cloned().ok_or(Error::AuthFailed)?;
+                    if let Some(device) = login.strip_prefix(DEVICE_LOGIN) {
+                        self.device = Some(device.to_string());
+                    } else if let Some(device) = login.strip_prefix(PAIR_LOGIN) {
+                        self.pairing = Some(device.to_string());
+                    } else {
+                        self.user = Some(login);
+                    }
+                }
+                self.state = ServerState::AwaitCommand;
+                // Older clients only send the random data and expect it back as is.
+                let server_time = sent_at.map(|_| Utc::now().timestamp_millis());
+                Ok(ServerAction::reply(ServerMessage::Welcome { sid: self.sid, auth_data, sent_at, server_time, versions: (PROTOCOL_VERSION, MIN_PROTOCOL_VERSION) }))
+            }
+            (ServerState::AwaitCommand, ClientMessage::Pair) if self.pairing.is_some() => {
+                // The pairing code can't be used again, so the connection ends here.
+                let device = self.pairing.take().unwrap_or_default();
+                let token = base64(&random::<[u8; 32]>());
+                Ok(ServerAction {
+                    finished: Some(false),
+                    paired: Some((device, token.clone())),
+                    ..ServerAction::reply(ServerMessage::Paired(token))
+                })
+            }
+            // A device being paired only gets its token.
+            _ if self.pairing.is_some() => Err(Error::AuthFailed),
+            (ServerState::AwaitCommand | ServerState::AwaitCommit, ClientMessage::Read) => {
+                self.state = ServerState::AwaitCommit;
+                Ok(ServerAction::reply(ServerMessage::Items(list.clone())))
+            }
+            (ServerState::AwaitCommand, ClientMessage::ReadDelta(token)) => {
+                self.state = ServerState::AwaitCommit;
+                Ok(ServerAction::reply(ServerMessage::Delta(list.delta_since(token))))
+            }
+            (ServerState::AwaitCommand, ClientMessage::Peek) => {
+                Ok(ServerAction { finished: Some(self.kept), ..ServerAction::reply(ServerMessage::Items(list.clone())) })
+            }
+            (ServerState::AwaitCommand, ClientMessage::Ping) => {
+                Ok(ServerAction { finished: Some(self.kept), ..ServerAction::reply(ServerMessage::Ok) })
+            }
+            (ServerState::AwaitCommit, ClientMessage::Commit(committed)) => {
+                if let Err(Error::LimitsExceeded(problems)) = committed.check_limits(self.max_items, self.max_body_len) {
+                    self.state = ServerState::AwaitCommand;
+                    return Ok(ServerAction { finished: Some(false), ..ServerAction::reply(ServerMessage::Rejected(problems)) });
+                }
+                self.state = ServerState::AwaitAck;
+                list.commit(committed);
+                Ok(ServerAction { save: true, ..ServerAction::reply(ServerMessage::Ok) })
+            }
+            (ServerState::AwaitCommit, ClientMessage::CommitDelta(delta)) => {
+                let committed = list.apply_delta(delta).ok_or(Error::Unknown)?;
+                if let Err(Error::LimitsExceeded(problems)) = committed.check_limits(self.max_items, self.max_body_len) {
+                    self.state = ServerState::AwaitCommand;
+                    return Ok(ServerAction { finished: Some(false), ..ServerAction::reply(ServerMessage::Rejected(problems)) });
+                }
+                self.state = ServerState::AwaitAck;
+                list.commit(committed);
+                let token = list.sync_token().ok_or(Error::Unknown)?;
+                Ok(ServerAction { save: true, ..ServerAction::reply(ServerMessage::Saved(token)) })
+            }
+            (ServerState::AwaitAck, ClientMessage::Ack { keep }) => {
+                self.state = ServerState::AwaitCommand;
+                self.kept = keep;
+                Ok(ServerAction { reply: None, save: false, finished: Some(keep), paired: None })
+            }
+            _ => Err(Error::Unknown),
+        }
+    }
+
+    /// Encodes and encrypts a message into a frame.
+    pub fn encode(&self, msg: &ServerMessage) -> Result<Vec<u8>> {
+        encrypt(&msg.encode(&self.sid)?, &self.password)
+    }
+
+    /// Decrypts and decodes a frame sent by the client. Returns an `Error::AuthFailed` if the client
+    /// logs in as an unknown user and an `Error::Unknown` if the frame isn't a command the engine
+    /// knows.
+    pub fn decode(&self, frame: &[u8]) -> Result<ClientMessage> {
+        if self.state != ServerState::AwaitHello {
+            return ClientMessage::decode(&decrypt(frame, &self.password)?, Some(&self.sid), self.state == ServerState::AwaitCommit);
+        }
+
+        let (user, frame) = split_login(frame)?;
+        let password = match &user {
+            Some(login) => self.login_password(login).ok_or(Error::AuthFailed)?,
+            None if self.password.is_empty() => return Err(Error::AuthFailed),
+            None => &self.password,
+        };
+        match ClientMessage::decode(&decrypt(frame, password)?, None, false)? {
+            ClientMessage::Hello { auth_data, sent_at, .. } => Ok(ClientMessage::Hello { auth_data, sent_at, user }),
+            msg => Ok(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Error, TdList, Todo};
+    use crate::engine::{ClientAction, ClientEngine, ClientMessage, ClientOutcome, ClientRequest, ServerEngine, ServerMessage, SyncPhase, SyncProgress, PROTOCOL_VERSION};
+
+    #[test]
+    fn engines_fetch_and_refuse_wrong_password_and_order() {
+        let mut server_list = TdList::new_server();
+        server_list.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+
+        let mut client = ClientEngine::new(ClientRequest::Fetch, TdList::new_client(), b"secret");
+        let mut server = ServerEngine::new(b"secret");
+
+        let hello = client.start();
+        let hello = server.decode(&client.encode(&hello).unwrap()).unwrap();
+        let welcome = server.handle(hello, &mut server_list).unwrap().reply.unwrap();
+        let peek = match client.handle(client.decode(&server.encode(&welcome).unwrap()).unwrap()).unwrap() {
+            ClientAction::Send(msg) => msg,
+            action => panic!("unexpected {:?}", action),
+        };
+        assert!(matches!(peek, ClientMessage::Peek));
+        assert!(client.clock_skew().is_some());
+
+        // A commit before reading isn't allowed.
+        assert!(server.handle(ClientMessage::Commit(TdList::new_server()), &mut server_list).is_err());
+
+        let action = server.handle(server.decode(&client.encode(&peek).unwrap()).unwrap(), &mut server_list).unwrap();
+        assert_eq!(action.finished, Some(false));
+        match client.handle(action.reply.unwrap()).unwrap() {
+            ClientAction::Finish { outcome: ClientOutcome::Fetched(list), .. } => assert_eq!(list.todos().len(), 1),
+            action => panic!("unexpected {:?}", action),
+        }
+
+        let wrong = ClientEngine::new(ClientRequest::Ping, TdList::new_client(), b"wrong");
+        assert!(ServerEngine::new(b"secret").decode(&wrong.encode(&ClientMessage::Ping).unwrap()).is_err());
+    }
+
+    #[test]
+    fn client_engine_reports_the_progress_of_a_fetch() {
+        let mut server_list = TdList::new_server();
+        server_list.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+
+        let mut client = ClientEngine::new(ClientRequest::Fetch, TdList::new_client(), b"secret");
+        let mut server = ServerEngine::new(b"secret");
+        assert_eq!(client.progress().phase, SyncPhase::Connecting);
+
+        let mut msg = client.start();
+        assert_eq!(client.progress().phase, SyncPhase::Handshake);
+        loop {
+            let action = server.handle(server.decode(&client.encode(&msg).unwrap()).unwrap(), &mut server_list).unwrap();
+            match client.handle(client.decode(&server.encode(&action.reply.unwrap()).unwrap()).unwrap()).unwrap() {
+                ClientAction::Send(next) => msg = next,
+                ClientAction::Finish { .. } => break,
+            }
+        }
+
+        assert_eq!(client.progress().phase, SyncPhase::Done);
+        assert_eq!(client.progress().items_received, 1);
+        assert!(client.progress().bytes_sent > 0);
+        assert!(client.progress().bytes_received > client.progress().bytes_sent);
+    }
+
+    #[test]
+    fn users_log_in_with_their_own_passwords() {
+        let users = HashMap::from([("alice".to_string(), b"alice's".to_vec()), ("bob".to_string(), b"bob's".to_vec())]);
+        let server = |password: &[u8]| {
+            let mut server = ServerEngine::new(password);
+            server.set_users(users.clone());
+            server
+        };
+        let client = |user: Option<&str>, password: &[u8]| {
+            let mut client = ClientEngine::new(ClientRequest::Ping, TdList::new_client(), password);
+            client.set_user(user.map(str::to_string));
+            let hello = client.start();
+            (client.encode(&hello).unwrap(), client)
+        };
+
+        let (hello, mut alice) = client(Some("alice"), b"alice's");
+        let mut alices_server = server(b"");
+        let hello = alices_server.decode(&hello).unwrap();
+        let welcome = alices_server.handle(hello, &mut TdList::new_server()).unwrap().reply.unwrap();
+        assert_eq!(alices_server.user(), Some("alice"));
+        let welcome = alice.decode(&alices_server.encode(&welcome).unwrap()).unwrap();
+        assert!(matches!(alice.handle(welcome).unwrap(), ClientAction::Send(ClientMessage::Ping)));
+
+        // A user can't log in with another user's password or as an unknown user.
+        assert!(server(b"").decode(&client(Some("bob"), b"alice's").0).is_err());
+        assert!(matches!(server(b"").decode(&client(Some("carol"), b"alice's").0), Err(Error::AuthFailed)));
+        // Without a shared password only users can log in.
+        assert!(matches!(server(b"").decode(&client(None, b"").0), Err(Error::AuthFailed)));
+        // Users without a password can't log in at all.
+        let mut no_password = server(b"");
+        no_password.set_users(HashMap::from([("carol".to_string(), Vec::new())]));
+        assert!(matches!(no_password.decode(&client(Some("carol"), b"").0), Err(Error::AuthFailed)));
+        assert!(server(b"shared").decode(&client(None, b"shared").0).is_ok());
+    }
+
+    #[test]
+    fn devices_get_a_token_with_a_pairing_code_and_log_in_with_it() {
+        let mut server = ServerEngine::new(b"");
+        server.set_pairings(HashMap::from([("laptop".to_string(), b"CODE".to_vec())]));
+        let mut pairing = ClientEngine::pair("laptop", b"CODE");
+        let mut list = TdList::new_server();
+
+        let hello = pairing.start();
+        let welcome = server.handle(server.decode(&pairing.encode(&hello).unwrap()).unwrap(), &mut list).unwrap().reply.unwrap();
+        let welcome = pairing.decode(&server.encode(&welcome).unwrap()).unwrap();
+        let request = match pairing.handle(welcome).unwrap() {
+            ClientAction::Send(request) => request,
+            action => panic!("unexpected {:?}", action),
+        };
+        let action = server.handle(server.decode(&pairing.encode(&request).unwrap()).unwrap(), &mut list).unwrap();
+        let (device, token) = action.paired.unwrap();
+        assert_eq!(device, "laptop");
+        assert_eq!(action.finished, Some(false));
+        let reply = pairing.decode(&server.encode(&action.reply.unwrap()).unwrap()).unwrap();
+        assert!(matches!(pairing.handle(reply).unwrap(), ClientAction::Finish { outcome: ClientOutcome::Paired(paired), .. } if paired == token));
+
+        let login = |device: &str, password: &[u8]| {
+            let mut server = ServerEngine::new(b"");
+            server.set_devices(HashMap::from([("laptop".to_string(), token.clone().into_bytes())]));
+            let mut client = ClientEngine::new(ClientRequest::Ping, TdList::new_client(), password);
+            client.set_device(Some(device));
+            let hello = client.start();
+            server.decode(&client.encode(&hello).unwrap()).and_then(|hello| server.handle(hello, &mut TdList::new_server())).map(|_| server)
+        };
+        assert_eq!(login("laptop", token.as_bytes()).unwrap().device(), Some("laptop"));
+        assert!(login("laptop", b"CODE").is_err());
+        assert!(matches!(login("phone", token.as_bytes()), Err(Error::AuthFailed)));
+
+        // A device being paired can't read the items.
+        let mut server = ServerEngine::new(b"");
+        server.set_pairings(HashMap::from([("laptop".to_string(), b"CODE".to_vec())]));
+        let mut client = ClientEngine::pair("laptop", b"CODE");
+        let hello = client.start();
+        server.handle(server.decode(&client.encode(&hello).unwrap()).unwrap(), &mut list).unwrap();
+        assert!(matches!(server.handle(ClientMessage::Peek, &mut list), Err(Error::AuthFailed)));
+    }
+
+    /// Syncs a client's list with a server's list and returns the synced list and the progress.
+    fn sync(client_list: TdList, server_list: &mut TdList) -> (TdList, SyncProgress) {
+        let mut client = ClientEngine::new(ClientRequest::Sync, client_list, b"secret");
+        let mut server = ServerEngine::new(b"secret");
+        let mut msg = client.start();
+        loop {
+            let action = server.handle(server.decode(&client.encode(&msg).unwrap()).unwrap(), server_list).unwrap();
+            let reply = client.decode(&server.encode(&action.reply.unwrap()).unwrap()).unwrap();
+            match client.handle(reply).unwrap() {
+                ClientAction::Send(next) => msg = next,
+                ClientAction::Finish { outcome: ClientOutcome::Synced(list), .. } => return (list, client.progress()),
+                action => panic!("unexpected {:?}", action),
+            }
+        }
+    }
+
+    #[test]
+    fn delta_syncs_send_only_changed_items() {
+        let mut server_list = TdList::new_server();
+        for i in 0..3 {
+            server_list.add_todo(Todo::new_undated(format!("Todo {}", i)).unwrap());
+        }
+
+        let (mut client_list, progress) = sync(TdList::new_client(), &mut server_list);
+        assert_eq!(progress.items_received, 3);
+        assert_eq!(client_list.sync_token(), server_list.sync_token());
+
+        client_list.get_todo_mut(1).unwrap().set_body("Changed".to_string()).unwrap();
+        let (client_list, progress) = sync(client_list, &mut server_list);
+        assert_eq!((progress.items_received, progress.items_sent), (0, 1));
+        assert_eq!(server_list.todos().iter().map(|todo| todo.body()).collect::<Vec<_>>(), vec!["Todo 0", "Changed", "Todo 2"]);
+
+        // The client only gets what changed after its last sync, such as a commit of another client.
+        let mut changed = server_list.clone();
+        changed.get_todo_mut(2).unwrap().set_body("Changed on the server".to_string()).unwrap();
+        server_list.commit(changed);
+        let (client_list, progress) = sync(client_list, &mut server_list);
+        assert_eq!(progress.items_received, 1);
+        assert_eq!(client_list.get_todo(2).unwrap().body(), "Changed on the server");
+
+        // A client that lost items it had synced reads the whole list.
+        let mut lost = TdList::new_client();
+        lost.set_sync_token(client_list.sync_token());
+        let (lost, progress) = sync(lost, &mut server_list);
+        assert_eq!(progress.items_received, 3);
+        assert_eq!(lost.todos().len(), 3);
+        assert_eq!(lost.sync_token(), None);
+    }
+
+    #[test]
+    fn client_engine_reads_whole_lists_from_servers_without_deltas() {
+        // Servers older than clock skew checks echo the hello as is.
+        let mut client = ClientEngine::new(ClientRequest::Sync, TdList::new_client(), b"secret");
+        let (auth_data, sent_at) = match client.start() {
+            ClientMessage::Hello { auth_data, sent_at, .. } => (auth_data, sent_at.unwrap()),
+            msg => panic!("unexpected {:?}", msg),
+        };
+        let welcome = ServerMessage::decode(&[[1; 8].as_slice(), &auth_data, &sent_at.to_le_bytes()].concat(), None).unwrap();
+        assert!(matches!(client.handle(welcome).unwrap(), ClientAction::Send(ClientMessage::Read)));
+        assert_eq!(client.server_version(), 1);
+        assert_eq!(client.clock_skew(), None);
+
+        let mut client = ClientEngine::new(ClientRequest::Sync, TdList::new_client(), b"secret");
+        let hello = client.start();
+        let mut server = ServerEngine::new(b"secret");
+        let welcome = server.handle(server.decode(&client.encode(&hello).unwrap()).unwrap(), &mut TdList::new_server()).unwrap().reply.unwrap();
+        let welcome = match welcome {
+            ServerMessage::Welcome { sid, auth_data, sent_at, server_time, .. } => ServerMessage::Welcome { sid, auth_data, sent_at, server_time, versions: (1, 1) },
+            msg => panic!("unexpected {:?}", msg),
+        };
+        assert!(matches!(client.handle(welcome).unwrap(), ClientAction::Send(ClientMessage::Read)));
+    }
+
+    #[test]
+    fn client_engine_refuses_servers_with_incompatible_protocols() {
+        for (versions, server) in [((PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 1), PROTOCOL_VERSION + 1), ((0, 0), 0)] {
+            let mut client = ClientEngine::new(ClientRequest::Sync, TdList::new_client(), b"secret");
+            let (auth_data, sent_at) = match client.start() {
+                ClientMessage::Hello { auth_data, sent_at, .. } => (auth_data, sent_at.unwrap()),
+                msg => panic!("unexpected {:?}", msg),
+            };
+            let welcome = ServerMessage::Welcome { sid: [1; 8], auth_data, sent_at: Some(sent_at), server_time: Some(sent_at), versions };
+            let welcome = ServerMessage::decode(&welcome.encode(&[1; 8]).unwrap(), None).unwrap();
+            match client.handle(welcome) {
+                Err(Error::IncompatibleProtocol { client, server: s }) => assert_eq!((client, s), (PROTOCOL_VERSION, server)),
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        }
+
+        // A newer server that still speaks this version is fine.
+        let mut client = ClientEngine::new(ClientRequest::Sync, TdList::new_client(), b"secret");
+        let (auth_data, sent_at) = match client.start() {
+            ClientMessage::Hello { auth_data, sent_at, .. } => (auth_data, sent_at.unwrap()),
+            msg => panic!("unexpected {:?}", msg),
+        };
+        let welcome = ServerMessage::Welcome { sid: [1; 8], auth_data, sent_at: Some(sent_at), server_time: Some(sent_at), versions: (PROTOCOL_VERSION + 1, 1) };
+        assert!(matches!(client.handle(welcome).unwrap(), ClientAction::Send(ClientMessage::ReadDelta(_))));
+        assert_eq!(client.server_version(), PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn client_engine_refuses_server_that_doesnt_echo_auth_data() {
+        let mut client = ClientEngine::new(ClientRequest::Ping, TdList::new_client(), b"secret");
+        client.start();
+        let welcome = ServerMessage::Welcome { sid: [1; 8], auth_data: [0; 8], sent_at: None, server_time: None, versions: (1, 1) };
+        assert!(client.handle(welcome).is_err());
+    }
+}
@@ -0,0 +1,642 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! Exporting the items of a `TdList` to formats other programs understand. Each format has an
+//! `Exporter` that frontends can use as is.
+//!
+//! Parts of items that the standard fields of a format can't hold, such as the done dates of
+//! tasks, are kept in annotations like `mtd:postponed=2 mtd:after=<uuid>` where the format has room
+//! for them. `Exporter::losses` tells what an export leaves out anyway.
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::{NaiveDate, TimeZone, Utc};
+//! use mtd::{TdList, Todo};
+//! use mtd::export::{Exporter, IcalExporter};
+//!
+//! let mut list = TdList::new_client();
+//! list.add_todo(Todo::new_specific_date("Pay rent".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap());
+//!
+//! let exporter = IcalExporter::new(NaiveDate::from_ymd(2024, 8, 15), Utc.ymd(2024, 8, 15).and_hms(12, 0, 0));
+//! let ical = exporter.export(&list);
+//!
+//! assert!(ical.contains("BEGIN:VTODO\r\n"));
+//! assert!(ical.contains("SUMMARY:Pay rent\r\n"));
+//! assert!(ical.contains("DUE;VALUE=DATE:20240815\r\n"));
+//! ```
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+
+use crate::{DayView, FixedClock, SortOrder, Task, TdList, Todo, Uuid};
+
+/// A format the items of a `TdList` can be exported to.
+pub trait Exporter {
+    /// Exports the items of the list as text in the format of the `Exporter`.
+    fn export(&self, list: &TdList) -> String;
+
+    /// Returns what exporting the list leaves out, such as items or fields the format can't hold.
+    /// An empty list means that the export keeps everything about the items.
+    fn losses(&self, list: &TdList) -> Vec<Loss>;
+}
+
+/// A part of an item that an export leaves out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loss {
+    /// The `uuid` of the item.
+    pub uuid: Uuid,
+    /// The body of the item.
+    pub body: String,
+    /// What is left out: `item` if the whole item is, otherwise the name of a field such as `notes`.
+    pub field: &'static str,
+}
+
+impl Loss {
+    fn todo(todo: &Todo, field: &'static str) -> Loss {
+        Loss { uuid: todo.uuid(), body: todo.body().to_string(), field }
+    }
+
+    fn task(task: &Task, field: &'static str) -> Loss {
+        Loss { uuid: task.uuid(), body: task.body().to_string(), field }
+    }
+}
+
+/// Exports items as an iCalendar (RFC 5545) calendar for showing them in calendar apps. `Todo`s
+/// become `VTODO` entries due on their date. `Task`s become `VEVENT`s that recur on their weekdays
+/// with an `RRULE`. Done dates of tasks and times that differ by weekday can't be represented and are
+/// left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcalExporter {
+    today: NaiveDate,
+    stamp: DateTime<Utc>,
+}
+
+impl IcalExporter {
+    /// Creates a new `IcalExporter`. Recurring tasks start from `today` or the end of their pause.
+    /// `stamp` is the time the calendar is created at, which calendar apps use to tell newer
+    /// versions of the same entries apart.
+    pub fn new(today: NaiveDate, stamp: DateTime<Utc>) -> Self {
+        IcalExporter { today, stamp }
+    }
+
+    fn todo_lines(&self, todo: &Todo) -> Vec<String> {
+        let mut lines = vec![
+            "BEGIN:VTODO".to_string(),
+            format!("UID:{}@mtd", todo.uuid()),
+            format!("DTSTAMP:{}", self.stamp.format("%Y%m%dT%H%M%SZ")),
+            format!("SUMMARY:{}", escape(todo.body())),
+            match todo.time() {
+                Some(time) => format!("DUE:{}", date_time(todo.date(), time)),
+                None => format!("DUE;VALUE=DATE:{}", todo.date().format("%Y%m%d")),
+            },
+            format!("STATUS:{}", if todo.done() { "COMPLETED" } else { "NEEDS-ACTION" }),
+        ];
+        // In iCalendar 1 is the most important and 9 the least important, 0 means no priority.
+        if todo.priority() > 0 {
+            lines.push(format!("PRIORITY:{}", 10 - todo.priority().min(9)));
+        }
+        if let Some(notes) = todo.notes() {
+            lines.push(format!("DESCRIPTION:{}", escape(notes)));
+        }
+        lines.push(format!("X-MTD-ANNOTATIONS:{}", escape(&todo_annotations(todo))));
+        lines.push("END:VTODO".to_string());
+        lines
+    }
+
+    /// Returns the lines of a recurring event for the task or `None` if the task doesn't recur
+    /// anymore.
+    fn task_lines(&self, task: &Task) -> Option<Vec<String>> {
+        let from = match task.paused_until() {
+            Some(until) if task.paused(self.today) => until,
+            None if task.paused(self.today) => return None,
+            _ => self.today,
+        };
+        let start = task.next_date(from)?;
+
+        let days = task.weekdays().iter().map(|wd| ical_weekday(*wd)).collect::<Vec<&str>>().join(",");
+        let mut rule = format!("RRULE:FREQ=WEEKLY;INTERVAL={};WKST=MO;BYDAY={}", task.every_weeks(), days);
+        if let Some(until) = task.until() {
+            // UNTIL has to be of the same type as DTSTART, so a timed task repeats until the end of
+            // its last day.
+            match task.time() {
+                Some(_) => rule.push_str(&format!(";UNTIL={}", date_time(until, NaiveTime::from_hms(23, 59, 59)))),
+                None => rule.push_str(&format!(";UNTIL={}", until.format("%Y%m%d"))),
+            }
+        }
+
+        let mut lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@mtd", task.uuid()),
+            format!("DTSTAMP:{}", self.stamp.format("%Y%m%dT%H%M%SZ")),
+            format!("SUMMARY:{}", escape(task.body())),
+            match task.time() {
+                Some(time) => format!("DTSTART:{}", date_time(start, time)),
+                None => format!("DTSTART;VALUE=DATE:{}", start.format("%Y%m%d")),
+            },
+            rule,
+        ];
+        if let Some(notes) = task.notes() {
+            lines.push(format!("DESCRIPTION:{}", escape(notes)));
+        }
+        lines.push(format!("X-MTD-ANNOTATIONS:{}", escape(&task_annotations(task, self.today))));
+        lines.push("END:VEVENT".to_string());
+        Some(lines)
+    }
+}
+
+impl Exporter for IcalExporter {
+    fn export(&self, list: &TdList) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//mtd//mtd//EN".to_string(),
+        ];
+        for todo in list.todos() {
+            lines.extend(self.todo_lines(todo));
+        }
+        for task in list.tasks() {
+            lines.extend(self.task_lines(task).into_iter().flatten());
+        }
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+
+    fn losses(&self, list: &TdList) -> Vec<Loss> {
+        list.tasks().into_iter()
+            .filter(|task| self.task_lines(task).is_none())
+            .map(|task| Loss::task(task, "item"))
+            .collect()
+    }
+}
+
+/// Exports items as comma-separated values (RFC 4180) for spreadsheets, one item per row after a
+/// header row. Columns that don't apply to the type of the item are left empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvExporter {
+    today: NaiveDate,
+}
+
+impl CsvExporter {
+    /// The columns of the exported rows in order.
+    pub const COLUMNS: [&'static str; 14] = ["type", "id", "body", "date", "weekdays", "every_weeks", "anchor", "until", "time", "priority", "done", "notes", "uuid", "annotations"];
+
+    /// Creates a new `CsvExporter`. The `done` column of `Task`s tells whether they are done on
+    /// `today`.
+    pub fn new(today: NaiveDate) -> Self {
+        CsvExporter { today }
+    }
+
+    fn todo_row(&self, todo: &Todo) -> Vec<String> {
+        vec![
+            "todo".to_string(),
+            todo.id().to_string(),
+            todo.body().to_string(),
+            todo.date().to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            todo.time().map(|time| time.format("%H:%M").to_string()).unwrap_or_default(),
+            todo.priority().to_string(),
+            todo.done().to_string(),
+            todo.notes().unwrap_or_default().to_string(),
+            todo.uuid().to_string(),
+            todo_annotations(todo),
+        ]
+    }
+
+    fn task_row(&self, task: &Task) -> Vec<String> {
+        vec![
+            "task".to_string(),
+            task.id().to_string(),
+            task.body().to_string(),
+            String::new(),
+            task.weekdays().iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(" "),
+            task.every_weeks().to_string(),
+            task.anchor().map(|anchor| anchor.to_string()).unwrap_or_default(),
+            task.until().map(|until| until.to_string()).unwrap_or_default(),
+            task.time().map(|time| time.format("%H:%M").to_string()).unwrap_or_default(),
+            String::new(),
+            task.done(self.today).to_string(),
+            task.notes().unwrap_or_default().to_string(),
+            task.uuid().to_string(),
+            task_annotations(task, self.today),
+        ]
+    }
+}
+
+impl Exporter for CsvExporter {
+    fn export(&self, list: &TdList) -> String {
+        let mut rows = vec![CsvExporter::COLUMNS.iter().map(|column| column.to_string()).collect()];
+        rows.extend(list.todos().into_iter().map(|todo| self.todo_row(todo)));
+        rows.extend(list.tasks().into_iter().map(|task| self.task_row(task)));
+
+        rows.iter()
+            .map(|row: &Vec<String>| format!("{}\r\n", row.iter().map(|value| csv_field(value)).collect::<Vec<String>>().join(",")))
+            .collect()
+    }
+
+    fn losses(&self, _list: &TdList) -> Vec<Loss> {
+        Vec::new()
+    }
+}
+
+/// Exports the items of a week as a Markdown checklist with a section for each day, for printing
+/// or pasting into notes. Days are shown like `TdList::week_view` groups them, so undone todos from
+/// earlier days are listed under today.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownExporter {
+    start: NaiveDate,
+    today: NaiveDate,
+}
+
+impl MarkdownExporter {
+    /// Creates a new `MarkdownExporter` for the 7 days starting from `start`.
+    pub fn new(start: NaiveDate, today: NaiveDate) -> Self {
+        MarkdownExporter { start, today }
+    }
+
+    fn day_lines(&self, day: &DayView) -> Vec<String> {
+        let mut lines = vec![format!("## {} {}", day.date.weekday(), day.date), String::new()];
+
+        let todos = day.undone_todos.iter().map(|todo| (false, *todo)).chain(day.done_todos.iter().map(|todo| (true, *todo)));
+        for (done, todo) in todos {
+            let mut line = checklist_item(done, todo.time(), todo.body());
+            let overdue = todo.days_overdue(self.today);
+            if overdue > 0 {
+                line.push_str(&format!(" _(overdue {} {})_", overdue, if overdue == 1 { "day" } else { "days" }));
+            }
+            lines.push(line);
+        }
+
+        let tasks = day.undone_tasks.iter().map(|task| (false, *task)).chain(day.done_tasks.iter().map(|task| (true, *task)));
+        for (done, task) in tasks {
+            lines.push(checklist_item(done, task.time_for(day.date.weekday()), task.body()));
+        }
+
+        if day.is_empty() {
+            lines.push("_Nothing to do._".to_string());
+        }
+        lines.push(String::new());
+        lines
+    }
+}
+
+impl Exporter for MarkdownExporter {
+    fn export(&self, list: &TdList) -> String {
+        let week = list.week_view(self.start, &FixedClock(self.today), SortOrder::Manual);
+
+        let mut lines = vec![format!("# Week of {}", self.start), String::new()];
+        for day in &week.days {
+            lines.extend(self.day_lines(day));
+        }
+
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    fn losses(&self, list: &TdList) -> Vec<Loss> {
+        let week = list.week_view(self.start, &FixedClock(self.today), SortOrder::Manual);
+        let shown = |uuid: Uuid| week.days.iter().any(|day| {
+            day.undone_todos.iter().chain(&day.done_todos).any(|todo| todo.uuid() == uuid)
+                || day.undone_tasks.iter().chain(&day.done_tasks).any(|task| task.uuid() == uuid)
+        });
+
+        // Only the body, the time and whether the item is done are shown.
+        let mut losses = Vec::new();
+        for todo in list.todos() {
+            if !shown(todo.uuid()) {
+                losses.push(Loss::todo(todo, "item"));
+                continue;
+            }
+            if todo.notes().is_some() {
+                losses.push(Loss::todo(todo, "notes"));
+            }
+            if todo.priority() > 0 {
+                losses.push(Loss::todo(todo, "priority"));
+            }
+        }
+        for task in list.tasks() {
+            if !shown(task.uuid()) {
+                losses.push(Loss::task(task, "item"));
+            } else if task.notes().is_some() {
+                losses.push(Loss::task(task, "notes"));
+            }
+        }
+        losses
+    }
+}
+
+/// Returns the annotations of the parts of a `Todo` that the standard fields of the formats don't
+/// hold, such as `mtd:id=3 mtd:postponed=2`.
+fn todo_annotations(todo: &Todo) -> String {
+    let mut annotations = vec![format!("mtd:id={}", todo.id())];
+    if todo.priority() > 0 {
+        annotations.push(format!("mtd:priority={}", todo.priority()));
+    }
+    if let Some(date) = todo.done_date() {
+        annotations.push(format!("mtd:done={}", date));
+    }
+    if todo.postponed() > 0 {
+        annotations.push(format!("mtd:postponed={}", todo.postponed()));
+    }
+    if !todo.blocked_by().is_empty() {
+        annotations.push(format!("mtd:after={}", join(todo.blocked_by())));
+    }
+    if let Some(created) = todo.created() {
+        annotations.push(format!("mtd:created={}", created.format("%Y-%m-%dT%H:%M:%S")));
+    }
+    annotations.join(" ")
+}
+
+/// Returns the annotations of the parts of a `Task` that the standard fields of the formats don't
+/// hold, such as `mtd:times=Mon@18:00 mtd:completions=2024-08-12`.
+fn task_annotations(task: &Task, today: NaiveDate) -> String {
+    let mut annotations = vec![format!("mtd:id={}", task.id())];
+    if !task.weekday_times().is_empty() {
+        let mut times: Vec<(&Weekday, &NaiveTime)> = task.weekday_times().iter().collect();
+        times.sort_by_key(|(wd, _)| wd.num_days_from_monday());
+        let times: Vec<String> = times.iter().map(|(wd, time)| format!("{}@{}", wd, time.format("%H:%M"))).collect();
+        annotations.push(format!("mtd:times={}", times.join(",")));
+    }
+    if !task.completions().is_empty() {
+        annotations.push(format!("mtd:completions={}", join(task.completions())));
+    }
+    match task.paused_until() {
+        Some(until) => annotations.push(format!("mtd:paused={}", until)),
+        None if task.paused(today) => annotations.push("mtd:paused=indefinitely".to_string()),
+        None => {}
+    }
+    if let Some(anchor) = task.anchor() {
+        annotations.push(format!("mtd:anchor={}", anchor));
+    }
+    if let Some(created) = task.created() {
+        annotations.push(format!("mtd:created={}", created));
+    }
+    annotations.join(" ")
+}
+
+/// Joins values with commas.
+fn join<T: ToString>(values: impl IntoIterator<Item = T>) -> String {
+    values.into_iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// Formats an item of a Markdown checklist, such as `- [ ] 09:30 Call the bank`.
+fn checklist_item(done: bool, time: Option<NaiveTime>, body: &str) -> String {
+    let check = if done { "x" } else { " " };
+    let body = body.replace(['\r', '\n'], " ");
+    match time {
+        Some(time) => format!("- [{}] {} {}", check, time.format("%H:%M"), body),
+        None => format!("- [{}] {}", check, body),
+    }
+}
+
+/// Quotes a CSV field if it contains a comma, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Formats a local date and time without a time zone, which calendar apps show in their own time
+/// zone.
+fn date_time(date: NaiveDate, time: NaiveTime) -> String {
+    date.and_time(time).format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn ical_weekday(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// Escapes text for an iCalendar property value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Ends a content line with CRLF and folds it so that no line is longer than 75 bytes. Continuation
+/// lines start with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+    use crate::{current_date, Task, TdList, Todo};
+    use crate::export::{CsvExporter, csv_field, escape, Exporter, fold, IcalExporter, Loss, MarkdownExporter, task_annotations, todo_annotations};
+
+    fn exporter() -> IcalExporter {
+        // 2024-8-15 is a Thursday.
+        IcalExporter::new(NaiveDate::from_ymd(2024, 8, 15), Utc.ymd(2024, 8, 15).and_hms(12, 0, 0))
+    }
+
+    #[test]
+    fn todos_are_exported_as_vtodos() {
+        let mut list = TdList::new_client();
+        let mut todo = Todo::new_specific_date("Call, then email".to_string(), NaiveDate::from_ymd(2024, 8, 16)).unwrap();
+        todo.set_time(Some(NaiveTime::from_hms(9, 30, 0)));
+        todo.set_priority(2);
+        todo.set_notes(Some("Line 1\nLine 2".to_string()));
+        todo.set_done(true);
+        let uuid = todo.uuid();
+        list.add_todo(todo);
+
+        let ical = exporter().export(&list);
+        let expected = format!(
+            "BEGIN:VTODO\r\nUID:{}@mtd\r\nDTSTAMP:20240815T120000Z\r\nSUMMARY:Call\\, then email\r\nDUE:20240816T093000\r\n\
+             STATUS:COMPLETED\r\nPRIORITY:8\r\nDESCRIPTION:Line 1\\nLine 2\r\nX-MTD-ANNOTATIONS:mtd:id=0 mtd:priority=2 mtd:done=",
+            uuid
+        );
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//mtd//mtd//EN\r\n"));
+        assert!(ical.contains(&expected));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn tasks_are_exported_as_recurring_events() {
+        let mut list = TdList::new_client();
+        let mut task = Task::new("Gym".to_string(), vec![Weekday::Mon, Weekday::Wed]).unwrap();
+        task.set_time(Some(NaiveTime::from_hms(18, 0, 0)));
+        task.set_until(Some(NaiveDate::from_ymd(2024, 12, 31)));
+        list.add_task(task);
+
+        let ical = exporter().export(&list);
+        assert!(ical.contains("SUMMARY:Gym\r\nDTSTART:20240819T180000\r\nRRULE:FREQ=WEEKLY;INTERVAL=1;WKST=MO;BYDAY=MO,WE;UNTIL=20241231T235959\r\nX-MTD-ANNOTATIONS:mtd:id=0"));
+    }
+
+    #[test]
+    fn untimed_tasks_end_on_a_date() {
+        let mut list = TdList::new_client();
+        let mut task = Task::new("Course".to_string(), vec![Weekday::Mon]).unwrap();
+        task.set_until(Some(NaiveDate::from_ymd(2024, 12, 31)));
+        list.add_task(task);
+
+        let ical = exporter().export(&list);
+        assert!(ical.contains("DTSTART;VALUE=DATE:20240819\r\nRRULE:FREQ=WEEKLY;INTERVAL=1;WKST=MO;BYDAY=MO;UNTIL=20241231\r\n"));
+    }
+
+    #[test]
+    fn paused_tasks_start_after_the_pause() {
+        let mut list = TdList::new_client();
+        let mut task = Task::new("Paused".to_string(), vec![Weekday::Thu]).unwrap();
+        task.pause_until(NaiveDate::from_ymd(2024, 8, 20));
+        list.add_task(task);
+        let mut task = Task::new("Stopped".to_string(), vec![Weekday::Thu]).unwrap();
+        task.set_paused(true);
+        list.add_task(task);
+
+        let ical = exporter().export(&list);
+        assert!(ical.contains("SUMMARY:Paused\r\nDTSTART;VALUE=DATE:20240822\r\n"));
+        assert!(ical.contains("mtd:paused=2024-08-20"));
+        assert!(!ical.contains("Stopped"));
+
+        let losses = exporter().losses(&list);
+        assert_eq!(losses.len(), 1);
+        assert_eq!((losses[0].body.as_str(), losses[0].field), ("Stopped", "item"));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let line = format!("SUMMARY:{}", "ä".repeat(40));
+        let folded = fold(&line);
+
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+        assert_eq!(escape("a;b\\c"), "a\\;b\\\\c");
+    }
+
+    #[test]
+    fn csv_has_a_row_per_item() {
+        let mut list = TdList::new_client();
+        let mut todo = Todo::new_specific_date("Buy milk, eggs".to_string(), NaiveDate::from_ymd(2024, 8, 16)).unwrap();
+        todo.set_priority(1);
+        let todo_uuid = todo.uuid();
+        list.add_todo(todo);
+        let mut task = Task::new("Gym".to_string(), vec![Weekday::Mon, Weekday::Thu]).unwrap();
+        task.set_time(Some(NaiveTime::from_hms(18, 0, 0)));
+        task.set_done(true, NaiveDate::from_ymd(2024, 8, 15));
+        let task_uuid = task.uuid();
+        list.add_task(task);
+
+        let csv = CsvExporter::new(NaiveDate::from_ymd(2024, 8, 15)).export(&list);
+        let rows: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], "type,id,body,date,weekdays,every_weeks,anchor,until,time,priority,done,notes,uuid,annotations");
+        assert!(rows[1].starts_with(&format!("todo,0,\"Buy milk, eggs\",2024-08-16,,,,,,1,false,,{},mtd:id=0 mtd:priority=1 mtd:created=", todo_uuid)));
+        assert!(rows[2].starts_with(&format!("task,0,Gym,,Mon Thu,1,,,18:00,,true,,{},mtd:id=0 mtd:completions=2024-08-15 mtd:created=", task_uuid)));
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn markdown_has_a_checklist_per_day() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_specific_date("Overdue".to_string(), NaiveDate::from_ymd(2024, 8, 13)).unwrap());
+        let mut todo = Todo::new_specific_date("Call".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap();
+        todo.set_time(Some(NaiveTime::from_hms(9, 30, 0)));
+        todo.set_done(true);
+        list.add_todo(todo);
+        list.add_task(Task::new("Gym".to_string(), vec![Weekday::Fri]).unwrap());
+
+        let md = MarkdownExporter::new(NaiveDate::from_ymd(2024, 8, 15), NaiveDate::from_ymd(2024, 8, 15)).export(&list);
+        assert!(md.starts_with("# Week of 2024-08-15\n\n## Thu 2024-08-15\n\n- [ ] Overdue _(overdue 2 days)_\n- [x] 09:30 Call\n\n"));
+        assert!(md.contains("## Fri 2024-08-16\n\n- [ ] Gym\n\n"));
+        assert!(md.contains("## Sat 2024-08-17\n\n_Nothing to do._\n\n"));
+        assert_eq!(md.matches("## ").count(), 7);
+    }
+
+    /// Reads the `mtd:key=value` annotations of an export back.
+    fn annotations(text: &str) -> HashMap<&str, &str> {
+        text.split(' ').filter_map(|annotation| annotation.strip_prefix("mtd:")?.split_once('=')).collect()
+    }
+
+    #[test]
+    fn annotations_keep_what_standard_fields_cannot() {
+        let mut list = TdList::new_client();
+        let mut blocker = Todo::new_undated("Blocker".to_string()).unwrap();
+        blocker.set_notes(Some("Ask for access".to_string()));
+        list.add_todo(blocker);
+        let mut todo = Todo::new_specific_date("Deploy".to_string(), NaiveDate::from_ymd(2024, 8, 16)).unwrap();
+        todo.set_priority(12);
+        todo.postpone(2).unwrap();
+        list.add_todo(todo);
+        list.add_todo_dependency(1, 0).unwrap();
+        let mut task = Task::new("Gym".to_string(), vec![Weekday::Mon, Weekday::Wed]).unwrap();
+        task.set_weekday_time(Weekday::Wed, Some(NaiveTime::from_hms(7, 0, 0)));
+        task.set_weekday_time(Weekday::Mon, Some(NaiveTime::from_hms(18, 0, 0)));
+        task.set_done(true, NaiveDate::from_ymd(2024, 8, 12));
+        task.set_done(true, NaiveDate::from_ymd(2024, 8, 14));
+        task.set_paused(true);
+        list.add_task(task);
+
+        let todo = list.get_todo(1).unwrap();
+        let todo_annotations = todo_annotations(todo);
+        let read = annotations(&todo_annotations);
+        assert_eq!(read["id"], "1");
+        assert_eq!(read["priority"], "12");
+        assert_eq!(read["postponed"], "1");
+        assert_eq!(read["after"], list.get_todo(0).unwrap().uuid().to_string());
+        assert_eq!(read["created"], todo.created().unwrap().format("%Y-%m-%dT%H:%M:%S").to_string());
+
+        let task_annotations = task_annotations(list.get_task(0).unwrap(), NaiveDate::from_ymd(2024, 8, 15));
+        let read = annotations(&task_annotations);
+        assert_eq!(read["times"], "Mon@18:00,Wed@07:00");
+        assert_eq!(read["completions"], "2024-08-12,2024-08-14");
+        assert_eq!(read["paused"], "indefinitely");
+
+        // Nothing is lost in CSV, but Markdown only shows the bodies of the unblocked items of the week.
+        let today = current_date();
+        assert!(CsvExporter::new(today).losses(&list).is_empty());
+        let losses = MarkdownExporter::new(today, today).losses(&list);
+        let blocker = list.get_todo(0).unwrap();
+        assert!(losses.contains(&Loss { uuid: blocker.uuid(), body: "Blocker".to_string(), field: "notes" }));
+        assert!(losses.contains(&Loss { uuid: todo.uuid(), body: "Deploy".to_string(), field: "item" }));
+        assert!(losses.iter().any(|loss| loss.body == "Gym" && loss.field == "item"));
+    }
+}
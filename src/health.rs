@@ -0,0 +1,281 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for the HTTP health endpoints of a server, which container orchestrators and init
+//! systems use to supervise it. `/healthz` tells that the server is running and `/readyz` whether it
+//! can serve clients. `/metrics` reports counters of the server in the Prometheus text format.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::Result;
+
+/// How long a probe may take to send its request before the connection is dropped. Other probes
+/// wait meanwhile, so this is kept short.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often the probe thread checks for new probes and whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The state of a running server as reported by the health endpoints.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct HealthStatus {
+    /// `true` once the items have been read and the server listens for clients.
+    pub(crate) list_loaded: bool,
+    /// `true` if the server holds the lock of its save file or doesn't have one.
+    pub(crate) lock_held: bool,
+    /// When the items were last written to the save file.
+    pub(crate) last_persist: Option<NaiveDateTime>,
+    /// The counters reported by `/metrics`.
+    #[serde(skip)]
+    pub(crate) metrics: ServerMetrics,
+}
+
+impl HealthStatus {
+    /// Returns `true` if the server can serve clients.
+    fn is_ready(&self) -> bool {
+        self.list_loaded && self.lock_held
+    }
+}
+
+/// Counters of a running server.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ServerMetrics {
+    /// The number of connections accepted from clients.
+    pub(crate) connections: u64,
+    /// The number of clients connected right now.
+    pub(crate) clients: usize,
+    /// The number of syncs saved and the seconds they took in total.
+    pub(crate) syncs: u64,
+    pub(crate) sync_seconds: f64,
+    /// The number of syncs rejected for exceeding the limits of the server.
+    pub(crate) rejected_syncs: u64,
+    /// The number of clients that used a wrong password or an unknown user.
+    pub(crate) auth_failures: u64,
+    /// The number of connections that ended in any other error.
+    pub(crate) errors: u64,
+    /// The number of items on the server including the lists of its users.
+    pub(crate) items: usize,
+}
+
+impl ServerMetrics {
+    /// Formats the metrics in the Prometheus text format.
+    fn to_prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, String); 9] = [
+            ("mtd_connections_total", "counter", "Connections accepted from clients.", self.connections.to_string()),
+            ("mtd_clients", "gauge", "Clients connected right now.", self.clients.to_string()),
+            ("mtd_syncs_total", "counter", "Syncs saved.", self.syncs.to_string()),
+            ("mtd_sync_duration_seconds_total", "counter", "Seconds the saved syncs took in total.", self.sync_seconds.to_string()),
+            ("mtd_rejected_syncs_total", "counter", "Syncs rejected for exceeding the limits.", self.rejected_syncs.to_string()),
+            ("mtd_auth_failures_total", "counter", "Clients that used a wrong password or an unknown user.", self.auth_failures.to_string()),
+            ("mtd_errors_total", "counter", "Connections that ended in any other error.", self.errors.to_string()),
+            ("mtd_items", "gauge", "Items on the server.", self.items.to_string()),
+            ("mtd_up", "gauge", "Always 1 while the server runs.", "1".to_string()),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            // Writing to a string never fails.
+            let _ = write!(text, "# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value);
+        }
+        text
+    }
+}
+
+/// Answers health probes on a background thread, so that a probe slow to send its request doesn't
+/// hold up the clients of the server. The server shares its status with the thread by calling
+/// `update`. Until the first update, the status tells that the items aren't loaded yet. The thread
+/// stops when the `HealthServer` is dropped.
+pub(crate) struct HealthServer {
+    status: Arc<Mutex<HealthStatus>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HealthServer {
+    /// Starts answering health probes on a TCP socket address such as `0.0.0.0:8080`. `lock_held`
+    /// is called for each probe to check the lock of the save file, so the check is up to date.
+    pub(crate) fn start(addr: &str, lock_held: impl Fn() -> bool + Send + 'static) -> Result<HealthServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let status = Arc::new(Mutex::new(HealthStatus::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (status, stop) = (status.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let mut current = match status.lock() {
+                                Ok(status) => status.clone(),
+                                Err(_) => return,
+                            };
+                            current.lock_held = lock_held();
+                            // A misbehaving probe shouldn't stop the other probes.
+                            let _ = respond(stream, &current);
+                        }
+                        // Either no probe is waiting or accepting failed for a reason, such as running
+                        // out of file descriptors, that is likely to pass.
+                        Err(_) => thread::sleep(POLL_INTERVAL),
+                    }
+                }
+            })
+        };
+
+        Ok(HealthServer { status, stop, thread: Some(thread) })
+    }
+
+    /// Replaces the status the probes are answered with. The `lock_held` field is ignored, since the
+    /// lock is checked for each probe.
+    pub(crate) fn update(&self, status: HealthStatus) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status;
+        }
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads a request from a probe and writes the response.
+fn respond(stream: TcpStream, status: &HealthStatus) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+
+    let (code, body) = response(path, status)?;
+    let reason = match code {
+        200 => "OK",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    let content_type = if path == "/metrics" { "text/plain; version=0.0.4" } else { "application/json" };
+    write!(
+        &stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+/// Returns the status code and body of the response to a request for a path. Bodies are JSON except
+/// for `/metrics`.
+fn response(path: &str, status: &HealthStatus) -> Result<(u16, String)> {
+    match path {
+        "/healthz" => Ok((200, serde_json::to_string(status)?)),
+        "/metrics" => Ok((200, status.metrics.to_prometheus())),
+        "/readyz" if status.is_ready() => Ok((200, serde_json::to_string(status)?)),
+        "/readyz" => Ok((503, serde_json::to_string(status)?)),
+        _ => Ok((404, "{}".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use chrono::NaiveDate;
+
+    use crate::health::{HealthServer, HealthStatus, response, ServerMetrics};
+
+    fn status(lock_held: bool) -> HealthStatus {
+        HealthStatus {
+            list_loaded: true,
+            lock_held,
+            last_persist: Some(NaiveDate::from_ymd(2024, 8, 15).and_hms(12, 0, 0)),
+            metrics: ServerMetrics::default(),
+        }
+    }
+
+    #[test]
+    fn readyz_fails_without_the_lock() {
+        assert_eq!(response("/readyz", &status(true)).unwrap().0, 200);
+        assert_eq!(response("/readyz", &status(false)).unwrap().0, 503);
+        assert_eq!(response("/healthz", &status(false)).unwrap().0, 200);
+        assert_eq!(response("/other", &status(true)).unwrap().0, 404);
+    }
+
+    #[test]
+    fn response_contains_the_status() {
+        let (_, body) = response("/readyz", &status(true)).unwrap();
+        assert_eq!(body, r#"{"list_loaded":true,"lock_held":true,"last_persist":"2024-08-15T12:00:00"}"#);
+    }
+
+    #[test]
+    fn metrics_are_in_the_prometheus_format() {
+        let mut status = status(true);
+        status.metrics.syncs = 2;
+        status.metrics.sync_seconds = 0.5;
+        status.metrics.auth_failures = 1;
+
+        let (code, body) = response("/metrics", &status).unwrap();
+        assert_eq!(code, 200);
+        assert!(body.contains("# TYPE mtd_syncs_total counter\nmtd_syncs_total 2\n"));
+        assert!(body.contains("\nmtd_sync_duration_seconds_total 0.5\n"));
+        assert!(body.contains("\nmtd_auth_failures_total 1\n"));
+        assert!(body.lines().all(|line| line.starts_with('#') || line.split(' ').count() == 2));
+    }
+
+    fn probe(path: &str) -> String {
+        let mut stream = TcpStream::connect("127.0.0.1:55981").unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn server_answers_probes_with_the_latest_status() {
+        let server = HealthServer::start("127.0.0.1:55981", || true).unwrap();
+
+        // The items haven't been loaded before the first update.
+        let response = probe("/readyz");
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.ends_with(r#"{"list_loaded":false,"lock_held":true,"last_persist":null}"#));
+
+        // A probe that doesn't send its request doesn't hold up the server.
+        let _silent = TcpStream::connect("127.0.0.1:55981").unwrap();
+        server.update(status(false));
+
+        let response = probe("/readyz");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#""lock_held":true,"last_persist":"2024-08-15T12:00:00"}"#));
+    }
+}
@@ -0,0 +1,1004 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+
+//! Importing items from files other programs write. Each format has an `Importer` that reads the
+//! text of a file into `Entry`s, and `import` adds them to a `TdList`. Entries that came from mtd,
+//! such as the rows of an edited CSV export, update the items they came from instead.
+//!
+//! Parts of an entry that mtd can't hold, such as the tags of a Taskwarrior task, are listed in
+//! `Entry::losses`. Importing into a copy of a list tells what an import would do without changing
+//! anything.
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::NaiveDate;
+//! use mtd::TdList;
+//! use mtd::import::{Action, import, Importer, TodoTxtImporter};
+//!
+//! let mut list = TdList::new_client();
+//! let importer = TodoTxtImporter::new(NaiveDate::from_ymd(2024, 8, 15));
+//! let entries = importer.read("(A) Call the bank due:2024-08-16\nx 2024-08-14 Pay rent\n");
+//!
+//! let outcomes = import(&mut list, entries, &[]);
+//!
+//! assert!(outcomes.iter().all(|outcome| outcome.action == Action::Create));
+//! assert_eq!(list.todos().len(), 2);
+//! ```
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{AutoRule, current_date, Task, TdList, Todo, Uuid};
+use crate::clock::local_time;
+use crate::rules;
+use crate::export::CsvExporter;
+
+/// A format items can be imported from.
+pub trait Importer {
+    /// Reads the entries of a file in the format of the `Importer`. Entries that can't become items
+    /// are returned with the reason.
+    fn read(&self, text: &str) -> Vec<Entry>;
+}
+
+/// An item read from a file.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Where the entry is in the file: the number of its first line, or the number of the object
+    /// for Taskwarrior.
+    pub source: usize,
+    /// The `uuid` of the item the entry came from, if it came from mtd.
+    pub uuid: Option<Uuid>,
+    /// The body of the item, or as much of it as could be read.
+    pub body: String,
+    /// The item, or why the entry can't become one.
+    pub item: std::result::Result<ImportedItem, String>,
+    /// The parts of the entry that the item leaves out, such as `tags`.
+    pub losses: Vec<String>,
+}
+
+/// An item an `Entry` becomes.
+#[derive(Debug, Clone)]
+pub enum ImportedItem {
+    /// A one-off item.
+    Todo(Todo),
+    /// A recurring item.
+    Task(Task),
+}
+
+/// What importing an `Entry` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// A new item was added.
+    Create,
+    /// The item the entry came from was changed.
+    Update,
+    /// The item the entry came from was already the same.
+    Unchanged,
+    /// The entry can't become an item.
+    Skip,
+}
+
+/// The result of importing an `Entry`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Outcome {
+    /// The `source` of the entry.
+    pub source: usize,
+    /// What was done with the entry.
+    pub action: Action,
+    /// `todo` or `task`, or `None` for skipped entries.
+    #[serde(rename = "type")]
+    pub item_type: Option<&'static str>,
+    /// The `body` of the entry.
+    pub body: String,
+    /// The id of the created or updated item.
+    pub id: Option<u64>,
+    /// The parts of the entry that the item leaves out.
+    pub losses: Vec<String>,
+    /// Why the entry was skipped.
+    pub reason: Option<String>,
+}
+
+/// Adds the items of the entries to the list. An entry with the `uuid` of an item of the same type
+/// in the list updates that item instead. Entries that can't become items are skipped. The rules
+/// are applied to the created `Todo`s like to the ones added with `Config::apply_rules`.
+pub fn import(list: &mut TdList, entries: Vec<Entry>, rules: &[AutoRule]) -> Vec<Outcome> {
+    entries.into_iter()
+        .map(|entry| {
+            let mut outcome = Outcome {
+                source: entry.source,
+                action: Action::Skip,
+                item_type: None,
+                body: entry.body,
+                id: None,
+                losses: entry.losses,
+                reason: None,
+            };
+            match entry.item {
+                Err(reason) => outcome.reason = Some(reason),
+                Ok(ImportedItem::Todo(mut todo)) => {
+                    outcome.item_type = Some("todo");
+                    match entry.uuid.and_then(|uuid| list.get_todo_by_uuid_mut(uuid).ok()) {
+                        Some(existing) => {
+                            outcome.id = Some(existing.id());
+                            outcome.action = if update_todo(existing, &todo) { Action::Update } else { Action::Unchanged };
+                        }
+                        None => {
+                            rules::apply_rules(rules, &mut todo);
+                            outcome.id = Some(list.add_todo(todo));
+                            outcome.action = Action::Create;
+                        }
+                    }
+                }
+                Ok(ImportedItem::Task(task)) => {
+                    outcome.item_type = Some("task");
+                    match entry.uuid.and_then(|uuid| list.get_task_by_uuid_mut(uuid).ok()) {
+                        Some(existing) => {
+                            outcome.id = Some(existing.id());
+                            outcome.action = if update_task(existing, &task) { Action::Update } else { Action::Unchanged };
+                        }
+                        None => {
+                            outcome.id = Some(list.add_task(task));
+                            outcome.action = Action::Create;
+                        }
+                    }
+                }
+            }
+            outcome
+        })
+        .collect()
+}
+
+/// Copies the fields an import sets from `new` to `todo`. Returns `true` if any of them differed.
+fn update_todo(todo: &mut Todo, new: &Todo) -> bool {
+    let mut changed = false;
+    if todo.body() != new.body() && todo.set_body(new.body().to_string()).is_ok() {
+        changed = true;
+    }
+    if todo.date() != new.date() {
+        todo.set_date(new.date());
+        changed = true;
+    }
+    if todo.time() != new.time() {
+        todo.set_time(new.time());
+        changed = true;
+    }
+    if todo.notes() != new.notes() {
+        todo.set_notes(new.notes().map(str::to_string));
+        changed = true;
+    }
+    if todo.priority() != new.priority() {
+        todo.set_priority(new.priority());
+        changed = true;
+    }
+    if todo.done() != new.done() {
+        todo.set_done(new.done());
+        changed = true;
+    }
+    changed
+}
+
+/// Copies the fields an import sets from `new` to `task`. Returns `true` if any of them differed.
+fn update_task(task: &mut Task, new: &Task) -> bool {
+    let mut changed = false;
+    if task.body() != new.body() && task.set_body(new.body().to_string()).is_ok() {
+        changed = true;
+    }
+    if task.weekdays() != new.weekdays() && task.set_weekdays(new.weekdays().clone()).is_ok() {
+        changed = true;
+    }
+    if task.time() != new.time() {
+        task.set_time(new.time());
+        changed = true;
+    }
+    if task.notes() != new.notes() {
+        task.set_notes(new.notes().map(str::to_string));
+        changed = true;
+    }
+    if task.until() != new.until() {
+        task.set_until(new.until());
+        changed = true;
+    }
+    if task.every_weeks() != new.every_weeks() && task.set_every_weeks(new.every_weeks(), new.anchor().unwrap_or_else(current_date)).is_ok() {
+        changed = true;
+    }
+    changed
+}
+
+/// Imports a todo.txt file, where each line is a todo like `x (A) 2024-08-01 Call the bank
+/// +errands due:2024-08-16`. Priorities A to Z become priorities 26 to 1, so that A stays the most
+/// important. Todos without a `due:` date are for today. Projects, contexts and other tags stay in
+/// the body. Creation and completion dates can't be kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoTxtImporter {
+    today: NaiveDate,
+}
+
+impl TodoTxtImporter {
+    /// Creates a new `TodoTxtImporter`. Todos without a date are for `today`.
+    pub fn new(today: NaiveDate) -> Self {
+        TodoTxtImporter { today }
+    }
+
+    fn entry(&self, source: usize, line: &str) -> Entry {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let mut losses = Vec::new();
+
+        let done = words.first() == Some(&"x");
+        if done {
+            words.remove(0);
+            if words.first().is_some_and(|word| iso_date(word).is_some()) {
+                words.remove(0);
+                losses.push("completion date".to_string());
+            }
+        }
+        let mut priority = words.first().and_then(|word| todo_txt_priority(word));
+        if priority.is_some() {
+            words.remove(0);
+        }
+        if words.first().is_some_and(|word| iso_date(word).is_some()) {
+            words.remove(0);
+            losses.push("creation date".to_string());
+        }
+
+        let mut date = None;
+        let mut body = Vec::new();
+        for word in words {
+            match word.split_once(':') {
+                Some(("due", value)) if iso_date(value).is_some() => date = iso_date(value),
+                // Done todos keep their priority in a tag.
+                Some(("pri", value)) if todo_txt_priority(&format!("({})", value)).is_some() => {
+                    priority = todo_txt_priority(&format!("({})", value));
+                }
+                Some(("rec", _)) => {
+                    losses.push("recurrence".to_string());
+                    body.push(word);
+                }
+                _ => body.push(word),
+            }
+        }
+        let body = body.join(" ");
+
+        let item = Todo::new_specific_date(body.clone(), date.unwrap_or(self.today))
+            .map(|mut todo| {
+                if let Some(priority) = priority {
+                    todo.set_priority(priority);
+                }
+                if done {
+                    todo.set_done(true);
+                }
+                ImportedItem::Todo(todo)
+            })
+            .map_err(|e| e.to_string());
+
+        Entry { source, uuid: None, body, item, losses }
+    }
+}
+
+impl Importer for TodoTxtImporter {
+    fn read(&self, text: &str) -> Vec<Entry> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| self.entry(i + 1, line))
+            .collect()
+    }
+}
+
+/// Imports an iCalendar (RFC 5545) file, such as one exported from a calendar app. `VTODO`s become
+/// `Todo`s due on their date. `VEVENT`s that repeat daily or weekly become `Task`s, other `VEVENT`s
+/// become `Todo`s. Entries exported by mtd update the items they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcalImporter {
+    today: NaiveDate,
+}
+
+/// A property of an iCalendar component, such as `DUE;VALUE=DATE:20240815`.
+struct Property {
+    name: String,
+    value: String,
+}
+
+impl IcalImporter {
+    /// The properties that are read or that don't matter to mtd.
+    const KNOWN: [&'static str; 16] = [
+        "UID", "SUMMARY", "DESCRIPTION", "DUE", "DTSTART", "STATUS", "COMPLETED", "PRIORITY", "RRULE", "DTSTAMP",
+        "CREATED", "LAST-MODIFIED", "SEQUENCE", "CLASS", "TRANSP", "PERCENT-COMPLETE",
+    ];
+
+    /// Creates a new `IcalImporter`. To-dos without a date are for `today`.
+    pub fn new(today: NaiveDate) -> Self {
+        IcalImporter { today }
+    }
+
+    fn entry(&self, source: usize, kind: &str, properties: &[Property], mut losses: Vec<String>) -> Entry {
+        let get = |name: &str| properties.iter().find(|property| property.name == name).map(|property| property.value.as_str());
+        for property in properties {
+            if !IcalImporter::KNOWN.contains(&property.name.as_str()) && !property.name.starts_with("X-") {
+                losses.push(property.name.to_lowercase());
+            }
+        }
+
+        let body = get("SUMMARY").map(unescape).unwrap_or_default();
+        let uuid = get("UID").and_then(|uid| uid.strip_suffix("@mtd")).and_then(|uuid| Uuid::parse_str(uuid).ok());
+        let notes = get("DESCRIPTION").map(unescape);
+
+        // The annotations of an mtd export keep the exact priority.
+        let annotations = get("X-MTD-ANNOTATIONS").map(unescape).unwrap_or_default();
+        let mut priority = get("PRIORITY").and_then(|priority| priority.parse::<u8>().ok()).filter(|priority| (1..=9).contains(priority)).map(|priority| 10 - priority);
+        for annotation in annotations.split_whitespace() {
+            match annotation.split_once('=') {
+                Some(("mtd:priority", value)) => priority = value.parse().ok().or(priority),
+                Some(("mtd:id", _)) => {}
+                _ => losses.push(format!("annotation {}", annotation)),
+            }
+        }
+
+        let when = match get(if kind == "VTODO" { "DUE" } else { "DTSTART" }).or_else(|| get("DTSTART")) {
+            Some(value) => match ical_date_time(value) {
+                Some(when) => Some(when),
+                None => return Entry { source, uuid, body, item: Err(format!("Invalid date \"{}\".", value)), losses },
+            },
+            None => None,
+        };
+        let (date, time) = when.unwrap_or((self.today, None));
+
+        let item = match (kind, get("RRULE")) {
+            ("VEVENT", Some(rule)) => self.task(&body, rule, date, time, &mut losses).map(|mut task| {
+                task.set_notes(notes);
+                ImportedItem::Task(task)
+            }),
+            _ => Todo::new_specific_date(body.clone(), date).map_err(|e| e.to_string()).map(|mut todo| {
+                todo.set_time(time);
+                todo.set_notes(notes);
+                if let Some(priority) = priority {
+                    todo.set_priority(priority);
+                }
+                if get("STATUS") == Some("COMPLETED") || get("COMPLETED").is_some() {
+                    todo.set_done(true);
+                }
+                ImportedItem::Todo(todo)
+            }),
+        };
+        Entry { source, uuid, body, item, losses }
+    }
+
+    /// Returns a `Task` for an event that repeats by the rule.
+    fn task(&self, body: &str, rule: &str, start: NaiveDate, time: Option<NaiveTime>, losses: &mut Vec<String>) -> std::result::Result<Task, String> {
+        let parts: Vec<(&str, &str)> = rule.split(';').filter_map(|part| part.split_once('=')).collect();
+        let get = |name: &str| parts.iter().find(|(key, _)| *key == name).map(|(_, value)| *value);
+        let interval: u32 = get("INTERVAL").map_or(Ok(1), str::parse).map_err(|_| format!("Invalid repetition \"{}\".", rule))?;
+
+        let weekdays = match (get("FREQ"), interval) {
+            (Some("DAILY"), 1) => vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun],
+            (Some("WEEKLY"), _) => match get("BYDAY") {
+                Some(days) => days.split(',').map(ical_weekday).collect::<Option<Vec<Weekday>>>()
+                    .ok_or_else(|| format!("Invalid repetition \"{}\".", rule))?,
+                None => vec![start.weekday()],
+            },
+            _ => return Err("Only events that repeat every day or on weekdays can be imported.".to_string()),
+        };
+        if get("COUNT").is_some() {
+            losses.push("count".to_string());
+        }
+
+        let mut task = Task::new(body.to_string(), weekdays).map_err(|e| e.to_string())?;
+        task.set_time(time);
+        if let Some(until) = get("UNTIL") {
+            task.set_until(Some(ical_date_time(until).ok_or_else(|| format!("Invalid date \"{}\".", until))?.0));
+        }
+        if get("FREQ") == Some("WEEKLY") && interval > 1 {
+            task.set_every_weeks(interval, start).map_err(|e| e.to_string())?;
+        }
+        Ok(task)
+    }
+}
+
+impl Importer for IcalImporter {
+    fn read(&self, text: &str) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        // The kind, first line and properties of the component being read, and the losses of the
+        // components nested in it.
+        let mut component: Option<(String, usize, Vec<Property>, Vec<String>)> = None;
+        let mut depth = 0;
+
+        for (line_number, line) in unfold(text) {
+            let (name, value) = match property(&line) {
+                Some(property) => property,
+                None => continue,
+            };
+            match (name.as_str(), &mut component) {
+                ("BEGIN", None) if value == "VTODO" || value == "VEVENT" => {
+                    component = Some((value, line_number, Vec::new(), Vec::new()));
+                    depth = 0;
+                }
+                ("BEGIN", None) if value != "VCALENDAR" => {
+                    entries.push(Entry {
+                        source: line_number,
+                        uuid: None,
+                        body: String::new(),
+                        item: Err(format!("{} entries can't be imported.", value)),
+                        losses: Vec::new(),
+                    });
+                }
+                ("BEGIN", Some((_, _, _, losses))) => {
+                    // Components in entries, such as alarms, can't be kept.
+                    depth += 1;
+                    losses.push(value.trim_start_matches('V').to_lowercase());
+                }
+                ("END", Some((kind, source, properties, losses))) if depth == 0 && value == *kind => {
+                    entries.push(self.entry(*source, kind, properties, losses.clone()));
+                    component = None;
+                }
+                ("END", Some(_)) => depth -= 1,
+                (_, Some((_, _, properties, _))) if depth == 0 => properties.push(Property { name, value }),
+                _ => {}
+            }
+        }
+        entries
+    }
+}
+
+/// Imports a CSV (RFC 4180) file with a header row, such as one exported by mtd or a spreadsheet.
+/// The columns are named like those of `CsvExporter`, and only `body` is required. A row is a
+/// `Task` if its `type` is `task` or if it has no type but has `weekdays`, and a `Todo` otherwise.
+/// Dates are like `2024-08-15` and times like `18:00`. Rows with the `uuid` of an item update it.
+/// Tasks that repeat every few weeks keep the cadence of their `anchor` date, or start it today.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvImporter {
+    today: NaiveDate,
+}
+
+impl CsvImporter {
+    /// Creates a new `CsvImporter`. Todos without a date are for `today`.
+    pub fn new(today: NaiveDate) -> Self {
+        CsvImporter { today }
+    }
+
+    fn entry(&self, source: usize, columns: &[String], row: &[String]) -> Entry {
+        let get = |name: &str| columns.iter().position(|column| column == name)
+            .and_then(|i| row.get(i))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty());
+        let mut losses: Vec<String> = columns.iter().zip(row)
+            .filter(|(column, value)| !CsvExporter::COLUMNS.contains(&column.as_str()) && !value.trim().is_empty())
+            .map(|(column, _)| column.clone())
+            .collect();
+        for annotation in get("annotations").unwrap_or_default().split_whitespace() {
+            // The anchor has its own column.
+            if !["mtd:id=", "mtd:priority=", "mtd:anchor="].iter().any(|known| annotation.starts_with(known)) {
+                losses.push(format!("annotation {}", annotation));
+            }
+        }
+
+        let body = get("body").unwrap_or_default().to_string();
+        let uuid = get("uuid").and_then(|uuid| Uuid::parse_str(uuid).ok());
+        let item = match get("type") {
+            Some("task") => self.task(&body, get, &mut losses),
+            None if get("weekdays").is_some() => self.task(&body, get, &mut losses),
+            Some("todo") | None => self.todo(&body, get),
+            Some(other) => Err(format!("Unknown type \"{}\".", other)),
+        };
+        Entry { source, uuid, body, item, losses }
+    }
+
+    fn todo<'a>(&self, body: &str, get: impl Fn(&str) -> Option<&'a str>) -> std::result::Result<ImportedItem, String> {
+        let date = get("date").map_or(Ok(self.today), |date| iso_date(date).ok_or_else(|| format!("Invalid date \"{}\".", date)))?;
+        let time = get("time").map(|time| clock_time(time).ok_or_else(|| format!("Invalid time \"{}\".", time))).transpose()?;
+        let priority = get("priority").map(|priority| priority.parse::<u8>().map_err(|_| format!("Invalid priority \"{}\".", priority))).transpose()?;
+
+        let mut todo = Todo::new_specific_date(body.to_string(), date).map_err(|e| e.to_string())?;
+        todo.set_time(time);
+        todo.set_notes(get("notes").map(str::to_string));
+        todo.set_priority(priority.unwrap_or(0));
+        todo.set_done(get("done") == Some("true"));
+        Ok(ImportedItem::Todo(todo))
+    }
+
+    fn task<'a>(&self, body: &str, get: impl Fn(&str) -> Option<&'a str>, losses: &mut Vec<String>) -> std::result::Result<ImportedItem, String> {
+        let weekdays = get("weekdays").ok_or_else(|| "A task needs weekdays.".to_string())?;
+        let weekdays = weekdays.split([' ', ','])
+            .filter(|day| !day.is_empty())
+            .map(|day| day.parse::<Weekday>().map_err(|_| format!("Invalid weekday \"{}\".", day)))
+            .collect::<std::result::Result<Vec<Weekday>, String>>()?;
+        let every_weeks = get("every_weeks").map(|weeks| weeks.parse::<u32>().ok().filter(|weeks| *weeks > 0).ok_or_else(|| format!("Invalid number of weeks \"{}\".", weeks))).transpose()?;
+        let anchor = get("anchor").map(|anchor| iso_date(anchor).ok_or_else(|| format!("Invalid date \"{}\".", anchor))).transpose()?;
+        let until = get("until").map(|until| iso_date(until).ok_or_else(|| format!("Invalid date \"{}\".", until))).transpose()?;
+        let time = get("time").map(|time| clock_time(time).ok_or_else(|| format!("Invalid time \"{}\".", time))).transpose()?;
+        if get("done") == Some("true") {
+            losses.push("done".to_string());
+        }
+
+        let mut task = Task::new(body.to_string(), weekdays).map_err(|e| e.to_string())?;
+        task.set_time(time);
+        task.set_notes(get("notes").map(str::to_string));
+        task.set_until(until);
+        if let Some(weeks) = every_weeks.filter(|weeks| *weeks > 1) {
+            task.set_every_weeks(weeks, anchor.unwrap_or(self.today)).map_err(|e| e.to_string())?;
+        }
+        Ok(ImportedItem::Task(task))
+    }
+}
+
+impl Importer for CsvImporter {
+    fn read(&self, text: &str) -> Vec<Entry> {
+        let mut rows = csv_rows(text).into_iter();
+        let columns: Vec<String> = match rows.next() {
+            Some((_, header)) => header.iter().map(|column| column.trim().to_lowercase()).collect(),
+            None => return Vec::new(),
+        };
+        if !columns.iter().any(|column| column == "body") {
+            return vec![Entry {
+                source: 1,
+                uuid: None,
+                body: String::new(),
+                item: Err("The header row has no body column.".to_string()),
+                losses: Vec::new(),
+            }];
+        }
+
+        rows.filter(|(_, row)| row.iter().any(|value| !value.trim().is_empty()))
+            .map(|(line, row)| self.entry(line, &columns, &row))
+            .collect()
+    }
+}
+
+/// Imports the JSON that Taskwarrior's `task export` prints. Pending and completed tasks become
+/// `Todo`s due on their due or scheduled date. Recurring tasks that repeat daily, on weekdays or
+/// weekly become `Task`s, and their occurrences are skipped. Priorities H, M and L become 3, 2 and
+/// 1, and annotations become notes. Deleted tasks are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskwarriorImporter {
+    today: NaiveDate,
+}
+
+impl TaskwarriorImporter {
+    /// The attributes that are read or that don't matter to mtd.
+    const KNOWN: [&'static str; 16] = [
+        "id", "uuid", "description", "status", "entry", "modified", "end", "due", "scheduled", "priority",
+        "annotations", "recur", "until", "urgency", "mask", "imask",
+    ];
+
+    /// Creates a new `TaskwarriorImporter`. Tasks without a date are for `today`.
+    pub fn new(today: NaiveDate) -> Self {
+        TaskwarriorImporter { today }
+    }
+
+    fn entry(&self, source: usize, object: &Value) -> Entry {
+        let get = |name: &str| object.get(name).and_then(Value::as_str);
+        let body = get("description").unwrap_or_default().to_string();
+        let mut losses: Vec<String> = object.as_object().into_iter().flatten()
+            .map(|(key, _)| key)
+            .filter(|key| !TaskwarriorImporter::KNOWN.contains(&key.as_str()) && key.as_str() != "parent")
+            .cloned()
+            .collect();
+        let entry = |item, losses| Entry { source, uuid: None, body: body.clone(), item, losses };
+
+        if !object.is_object() {
+            return entry(Err("Not a Taskwarrior task.".to_string()), Vec::new());
+        }
+        let date = match get("due").or_else(|| get("scheduled")).map(|date| taskwarrior_date(date).ok_or(date)).transpose() {
+            Ok(date) => date,
+            Err(date) => return entry(Err(format!("Invalid date \"{}\".", date)), losses),
+        };
+        if get("due").is_some() && get("scheduled").is_some() {
+            losses.push("scheduled".to_string());
+        }
+        let notes: Vec<&str> = object.get("annotations").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|annotation| annotation.get("description").and_then(Value::as_str))
+            .collect();
+        let notes = if notes.is_empty() { None } else { Some(notes.join("\n")) };
+
+        let item = match get("status") {
+            Some("deleted") => Err("The task was deleted.".to_string()),
+            _ if object.get("parent").is_some() => Err("An occurrence of a recurring task, which is imported as a whole.".to_string()),
+            Some("recurring") => self.task(&body, get("recur").unwrap_or_default(), date, get("until"), &mut losses).map(|mut task| {
+                task.set_notes(notes);
+                if get("priority").is_some() {
+                    losses.push("priority".to_string());
+                }
+                ImportedItem::Task(task)
+            }),
+            _ => {
+                if get("until").is_some() {
+                    losses.push("until".to_string());
+                }
+                let (date, time) = date.unwrap_or((self.today, None));
+                Todo::new_specific_date(body.clone(), date).map_err(|e| e.to_string()).map(|mut todo| {
+                    todo.set_time(time);
+                    todo.set_notes(notes);
+                    match get("priority") {
+                        Some("H") => todo.set_priority(3),
+                        Some("M") => todo.set_priority(2),
+                        Some("L") => todo.set_priority(1),
+                        _ => {}
+                    }
+                    todo.set_done(get("status") == Some("completed"));
+                    ImportedItem::Todo(todo)
+                })
+            }
+        };
+        entry(item, losses)
+    }
+
+    fn task(&self, body: &str, recur: &str, date: Option<(NaiveDate, Option<NaiveTime>)>, until: Option<&str>, losses: &mut Vec<String>) -> std::result::Result<Task, String> {
+        let (start, time) = date.ok_or_else(|| "A recurring task needs a due date.".to_string())?;
+        let (weekdays, every_weeks) = match recur {
+            "daily" | "day" | "1d" => (vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun], 1),
+            "weekdays" => (vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri], 1),
+            "weekly" | "week" | "1w" => (vec![start.weekday()], 1),
+            "biweekly" | "fortnight" | "2w" => (vec![start.weekday()], 2),
+            _ => return Err(format!("Repeating \"{}\" isn't supported, only daily, on weekdays or weekly.", recur)),
+        };
+
+        let mut task = Task::new(body.to_string(), weekdays).map_err(|e| e.to_string())?;
+        task.set_time(time);
+        if let Some(until) = until {
+            match taskwarrior_date(until) {
+                Some((until, _)) => task.set_until(Some(until)),
+                None => losses.push("until".to_string()),
+            }
+        }
+        if every_weeks > 1 {
+            task.set_every_weeks(every_weeks, start).map_err(|e| e.to_string())?;
+        }
+        Ok(task)
+    }
+}
+
+impl Importer for TaskwarriorImporter {
+    fn read(&self, text: &str) -> Vec<Entry> {
+        // Older versions of Taskwarrior export a task per line instead of an array.
+        let objects = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(objects)) => objects,
+            Ok(object) => vec![object],
+            Err(_) => text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line.trim().trim_end_matches(',')).unwrap_or(Value::Null))
+                .collect(),
+        };
+        objects.iter().enumerate().map(|(i, object)| self.entry(i + 1, object)).collect()
+    }
+}
+
+/// Parses a date like `2024-08-15`.
+fn iso_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+}
+
+/// Parses a time like `18:00` or `18:00:30`.
+fn clock_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M").or_else(|_| NaiveTime::parse_from_str(text, "%H:%M:%S")).ok()
+}
+
+/// Parses a todo.txt priority like `(A)` into a priority from 26 for A to 1 for Z.
+fn todo_txt_priority(word: &str) -> Option<u8> {
+    match word.as_bytes() {
+        [b'(', letter @ b'A'..=b'Z', b')'] => Some(26 - (letter - b'A')),
+        _ => None,
+    }
+}
+
+/// Parses a date of Taskwarrior like `20240815T220000Z`. A time of midnight means the whole day.
+fn taskwarrior_date(text: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let time = local_time(DateTime::<Utc>::from_utc(NaiveDateTime::parse_from_str(text, "%Y%m%dT%H%M%SZ").ok()?, Utc));
+    Some((time.date(), Some(time.time()).filter(|time| *time != NaiveTime::from_hms(0, 0, 0))))
+}
+
+/// Parses an iCalendar date like `20240815` or date-time like `20240815T180000`. Times in UTC, which
+/// end in `Z`, are converted to the time zone of mtd. Other times are taken as they are.
+fn ical_date_time(text: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    if let Some(utc) = text.strip_suffix('Z') {
+        let time = local_time(DateTime::<Utc>::from_utc(NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?, Utc));
+        return Some((time.date(), Some(time.time())));
+    }
+    match NaiveDateTime::parse_from_str(text, "%Y%m%dT%H%M%S") {
+        Ok(time) => Some((time.date(), Some(time.time()))),
+        Err(_) => NaiveDate::parse_from_str(text, "%Y%m%d").ok().map(|date| (date, None)),
+    }
+}
+
+fn ical_weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Joins the folded lines of an iCalendar file. Returns the content lines with the numbers of their
+/// first lines.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some((_, last))) => last.push_str(continued),
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits an iCalendar content line into the upper case name and the value of the property. The
+/// parameters of the property are left out.
+fn property(line: &str) -> Option<(String, String)> {
+    let mut quoted = false;
+    let colon = line.find(|c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+    let name = line[..colon].split(';').next()?.to_uppercase();
+    Some((name, line[colon + 1..].to_string()))
+}
+
+/// Reverses the escaping of an iCalendar property value.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some(escaped @ ('\\' | ';' | ','))) => {
+                unescaped.push(escaped);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Splits CSV text into rows of fields. Quoted fields may contain commas, quotes and line breaks.
+/// Returns the rows with the numbers of their first lines.
+fn csv_rows(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut row_line = 1;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            '\n' => {
+                field.push(c);
+                line += 1;
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+    use crate::{AutoRule, Task, TdList, Todo};
+    use crate::export::{CsvExporter, Exporter, IcalExporter};
+    use crate::import::{Action, csv_rows, CsvImporter, IcalImporter, import, ImportedItem, Importer, TaskwarriorImporter, TodoTxtImporter, unfold};
+
+    fn today() -> NaiveDate {
+        // 2024-8-15 is a Thursday.
+        NaiveDate::from_ymd(2024, 8, 15)
+    }
+
+    fn todo(item: &Result<ImportedItem, String>) -> &Todo {
+        match item {
+            Ok(ImportedItem::Todo(todo)) => todo,
+            other => panic!("not a todo: {:?}", other),
+        }
+    }
+
+    fn task(item: &Result<ImportedItem, String>) -> &Task {
+        match item {
+            Ok(ImportedItem::Task(task)) => task,
+            other => panic!("not a task: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn todo_txt_lines_become_todos() {
+        let text = "(A) 2024-08-01 Call the bank +errands due:2024-08-16\n\nx 2024-08-14 Pay rent pri:C\nWater plants rec:1w\n";
+        let entries = TodoTxtImporter::new(today()).read(text);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].source, 1);
+        let bank = todo(&entries[0].item);
+        assert_eq!((bank.body(), bank.date(), bank.priority()), ("Call the bank +errands", NaiveDate::from_ymd(2024, 8, 16), 26));
+        assert_eq!(entries[0].losses, vec!["creation date"]);
+
+        let rent = todo(&entries[1].item);
+        assert_eq!((rent.body(), rent.done(), rent.priority(), rent.date()), ("Pay rent", true, 24, today()));
+        assert_eq!(entries[1].losses, vec!["completion date"]);
+
+        assert_eq!(entries[2].losses, vec!["recurrence"]);
+    }
+
+    #[test]
+    fn ical_entries_become_todos_and_tasks() {
+        let text = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VTODO\r\nSUMMARY:Call\\, then write\r\nDUE:20240816T093000\r\nPRIORITY:1\r\nDESCRIPTION:Line 1\\nLine 2\r\n\
+            LOCATION:Office\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nEND:VALARM\r\nEND:VTODO\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Run\r\nDTSTART;TZID=Europe/Helsinki:20240812T070000\r\n\
+            RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;UNTIL=20241231\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Pay rent\r\nDTSTART;VALUE=DATE:20240901\r\nRRULE:FREQ=MONTHLY\r\nEND:VEVENT\r\n\
+            BEGIN:VJOURNAL\r\nSUMMARY:Notes\r\nEND:VJOURNAL\r\nEND:VCALENDAR\r\n";
+        let entries = IcalImporter::new(today()).read(text);
+
+        assert_eq!(entries.len(), 4);
+        let call = todo(&entries[0].item);
+        assert_eq!((call.body(), call.date(), call.time()), ("Call, then write", NaiveDate::from_ymd(2024, 8, 16), Some(NaiveTime::from_hms(9, 30, 0))));
+        assert_eq!((call.priority(), call.notes()), (9, Some("Line 1\nLine 2")));
+        assert_eq!(entries[0].losses, vec!["alarm", "location"]);
+
+        let run = task(&entries[1].item);
+        assert_eq!(run.weekdays(), &vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!((run.every_weeks(), run.until(), run.time()), (2, Some(NaiveDate::from_ymd(2024, 12, 31)), Some(NaiveTime::from_hms(7, 0, 0))));
+
+        assert!(entries[2].item.is_err());
+        assert_eq!((entries[3].source, entries[3].item.is_err()), (23, true));
+    }
+
+    #[test]
+    fn folded_ical_lines_are_joined() {
+        assert_eq!(unfold("SUMMARY:A long\r\n  line\r\nEND:VTODO\r\n"), vec![
+            (1, "SUMMARY:A long line".to_string()),
+            (3, "END:VTODO".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn importing_an_export_updates_the_items_it_came_from() {
+        let mut list = TdList::new_client();
+        let mut todo = Todo::new_specific_date("Pay rent".to_string(), today()).unwrap();
+        todo.set_priority(12);
+        list.add_todo(todo);
+        list.add_task(Task::new("Gym".to_string(), vec![Weekday::Mon, Weekday::Fri]).unwrap());
+
+        let ical = IcalExporter::new(today(), Utc.ymd(2024, 8, 15).and_hms(12, 0, 0)).export(&list);
+        let outcomes = import(&mut list, IcalImporter::new(today()).read(&ical), &[]);
+        assert!(outcomes.iter().all(|outcome| outcome.action == Action::Unchanged), "{:?}", outcomes);
+
+        let csv = CsvExporter::new(today()).export(&list).replace("Pay rent", "Pay the rent") + "todo,,Buy milk,2024-08-16,,,,,,,,,,\r\n,,Read,,Sat,,,,,,,,,\r\n";
+        let outcomes = import(&mut list, CsvImporter::new(today()).read(&csv), &[]);
+        let actions: Vec<(Action, Option<&str>)> = outcomes.iter().map(|outcome| (outcome.action, outcome.item_type)).collect();
+        assert_eq!(actions, vec![
+            (Action::Update, Some("todo")),
+            (Action::Unchanged, Some("task")),
+            (Action::Create, Some("todo")),
+            (Action::Create, Some("task")),
+        ]);
+        assert_eq!(list.get_todo(0).unwrap().body(), "Pay the rent");
+        assert_eq!(list.get_todo(0).unwrap().priority(), 12);
+        assert_eq!(list.todos().len(), 2);
+        assert_eq!(list.tasks().len(), 2);
+    }
+
+    #[test]
+    fn csv_export_keeps_the_weeks_of_a_biweekly_task() {
+        let mut list = TdList::new_client();
+        let mut task = Task::new("Take out recycling".to_string(), vec![Weekday::Tue]).unwrap();
+        task.set_every_weeks(2, NaiveDate::from_ymd(2024, 8, 6)).unwrap();
+        list.add_task(task);
+        let csv = CsvExporter::new(today()).export(&list);
+
+        // Imported a week later on another device, the task is still for the same weeks.
+        let mut other = TdList::new_client();
+        let outcomes = import(&mut other, CsvImporter::new(today() + chrono::Duration::days(7)).read(&csv), &[]);
+        assert_eq!(outcomes[0].action, Action::Create);
+        assert!(!outcomes[0].losses.iter().any(|loss| loss.contains("anchor")), "{:?}", outcomes[0].losses);
+
+        let imported = &other.tasks()[0];
+        assert_eq!((imported.every_weeks(), imported.anchor()), (2, Some(NaiveDate::from_ymd(2024, 8, 6))));
+        assert!(imported.for_date(NaiveDate::from_ymd(2024, 8, 20)));
+        assert!(!imported.for_date(NaiveDate::from_ymd(2024, 8, 27)));
+    }
+
+    #[test]
+    fn csv_rows_with_invalid_values_are_skipped() {
+        let csv = "Body,Date,Colour\r\nDentist,2024-08-40,\r\nDentist,2024-08-20,red\r\n,,\r\n";
+        let entries = CsvImporter::new(today()).read(csv);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].item.as_ref().unwrap_err(), "Invalid date \"2024-08-40\".");
+        assert_eq!(todo(&entries[1].item).date(), NaiveDate::from_ymd(2024, 8, 20));
+        assert_eq!(entries[1].losses, vec!["colour"]);
+
+        assert!(CsvImporter::new(today()).read("title\r\nDentist\r\n")[0].item.is_err());
+    }
+
+    #[test]
+    fn quoted_csv_fields_may_contain_separators() {
+        assert_eq!(csv_rows("a,\"b, \"\"c\"\"\nd\"\r\ne,f"), vec![
+            (1, vec!["a".to_string(), "b, \"c\"\nd".to_string()]),
+            (3, vec!["e".to_string(), "f".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn taskwarrior_tasks_become_todos_and_tasks() {
+        let json = r#"[
+            {"id":1,"description":"Call the bank","status":"pending","due":"20240816T120000Z","priority":"H","tags":["phone"],
+             "annotations":[{"entry":"20240801T100000Z","description":"Ask about fees"}],"uuid":"a8b0f6a4-6a1c-4f0e-9d9b-1d0c5e8f2b11"},
+            {"id":0,"description":"Pay rent","status":"completed","end":"20240814T100000Z"},
+            {"id":0,"description":"Old","status":"deleted"},
+            {"id":2,"description":"Water plants","status":"recurring","recur":"weekly","due":"20240812T120000Z"},
+            {"id":3,"description":"Water plants","status":"pending","parent":"b1c2","due":"20240812T120000Z"},
+            {"id":4,"description":"Taxes","status":"recurring","recur":"yearly","due":"20240812T120000Z"}
+        ]"#;
+        let entries = TaskwarriorImporter::new(today()).read(json);
+
+        assert_eq!(entries.len(), 6);
+        let bank = todo(&entries[0].item);
+        assert_eq!((bank.body(), bank.priority(), bank.notes()), ("Call the bank", 3, Some("Ask about fees")));
+        assert_eq!(entries[0].losses, vec!["tags"]);
+        assert!(todo(&entries[1].item).done());
+        assert!(entries[2].item.is_err());
+        assert_eq!(task(&entries[3].item).weekdays().len(), 1);
+        assert!(entries[4].item.is_err());
+        assert!(entries[5].item.is_err());
+
+        let mut list = TdList::new_client();
+        let outcomes = import(&mut list, entries, &[]);
+        assert_eq!(outcomes.iter().filter(|outcome| outcome.action == Action::Skip).count(), 3);
+        assert_eq!((list.todos().len(), list.tasks().len()), (2, 1));
+    }
+
+    #[test]
+    fn rules_apply_to_imported_todos_without_a_priority() {
+        let rules = [AutoRule { pattern: "rent".to_string(), priority: Some(3) }];
+        let entries = TodoTxtImporter::new(today()).read("Pay rent\n(A) Pay the rent of the garage\nCall the bank\n");
+
+        let mut list = TdList::new_client();
+        let outcomes = import(&mut list, entries, &rules);
+
+        assert!(outcomes.iter().all(|outcome| outcome.action == Action::Create));
+        let priorities: Vec<u8> = list.todos().iter().map(|todo| todo.priority()).collect();
+        assert_eq!(priorities, vec![3, 26, 0]);
+    }
+}
@@ -31,10 +31,10 @@ see <https://www.gnu.org/licenses/>.
 //! let mut client = TdList::new_client();
 //!
 //! // Adds a new Todo that should be done the next Friday.
-//! client.add_todo(Todo::new_dated("Install MTD".to_string(), Weekday::Fri));
+//! client.add_todo(Todo::new_dated("Install MTD".to_string(), Weekday::Fri).unwrap());
 //!
 //! // Adds a new Task that should be done every Wednesday and Saturday.
-//! client.add_task(Task::new("Clean the house.".to_string(), vec![Weekday::Wed, Weekday::Sat]));
+//! client.add_task(Task::new("Clean the house.".to_string(), vec![Weekday::Wed, Weekday::Sat]).unwrap());
 //!
 //! // This TdList should be the one got from the server. It usually shouldn't be modified directly
 //! // because all modifications made on the client will be synced to the server.
@@ -43,8 +43,8 @@ see <https://www.gnu.org/licenses/>.
 //! // The new added items will be *copied* to the server.
 //! client.sync(&mut server);
 //!
-//! assert!(server.todos().contains(&&Todo::new_dated("Install MTD".to_string(), Weekday::Fri)));
-//! assert!(server.tasks().contains(&&Task::new("Clean the house.".to_string(), vec![Weekday::Wed, Weekday::Sat])));
+//! assert!(server.todos().contains(&&Todo::new_dated("Install MTD".to_string(), Weekday::Fri).unwrap()));
+//! assert!(server.tasks().contains(&&Task::new("Clean the house.".to_string(), vec![Weekday::Wed, Weekday::Sat]).unwrap()));
 //!
 //! // Modifications such as setting a Todo done are also copied to the server.
 //! client.get_todo_mut(0).unwrap().set_done(true);
@@ -57,17 +57,55 @@ see <https://www.gnu.org/licenses/>.
 #![warn(missing_docs)]
 
 use std::{io, result};
-use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::fmt::{Debug, Display, Formatter};
 
-use chrono::{Datelike, Local, NaiveDate, Weekday};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
 use rand::random;
 use serde::{Deserialize, Serialize};
-
-pub use network::{Config, MtdNetMgr};
-
+pub use uuid::Uuid;
+
+pub use backup::Backup;
+pub use devices::{Device, DeviceRegistry, PAIRING_CODE_MINUTES, pairing_payload, parse_pairing_code, split_pairing_payload};
+pub use network::{backup_files, Config, LogLevel, MtdNetMgr, ServerUser, SESSION_IDLE_TIMEOUT};
+#[cfg(feature = "fault-injection")]
+pub use network::{Fault, FaultInjector};
+pub use clock::{Clock, current_date, current_time, FixedClock, SystemClock};
+pub use plan::DayPlan;
+pub use rules::AutoRule;
+pub use share::SharedItem;
+pub use storage::{DataEncryption, KeyEncryption, PasswordEncryption, read_data_file, StorageEncryption, write_data_file};
+pub use stats::{StaleReport, StaleTask, StaleTodo, Statistics, TaskStatistics, WeekStatistics};
+pub use theme::{Style, Theme, ThemePreset};
+pub use view::{DayView, WeekView};
+pub use transport::{Connection, Listener, MemoryTransport, Readiness, TcpTransport, Transport};
+#[cfg(unix)]
+pub use transport::UnixTransport;
+
+pub mod control;
+pub mod dateparse;
+pub mod engine;
+pub mod export;
+pub mod import;
 mod network;
+mod proxy;
+mod srv;
+mod tor;
+mod transport;
+mod stats;
+mod theme;
+mod clock;
+mod view;
+mod rules;
+mod plan;
+mod share;
+mod health;
+mod backup;
+mod storage;
+mod devices;
 // Methods ending with _wtd are used for unit testing and internal implementations. They allow
 // supplying today with any date.
 
@@ -81,6 +119,12 @@ pub enum Error {
     NoTodoWithGivenId(u64),
     /// No `Task` with the given `id` exists.
     NoTaskWithGivenId(u64),
+    /// No `Todo` with the given `uuid` exists.
+    NoTodoWithGivenUuid(Uuid),
+    /// No `Task` with the given `uuid` exists.
+    NoTaskWithGivenUuid(Uuid),
+    /// No `DayPlan` with the given `id` exists.
+    NoPlanWithGivenId(u64),
     /// Encrypting data failed.
     EncryptingFailed,
     /// Decrypting data failed. The two common reasons for this error are incorrect
@@ -92,12 +136,41 @@ pub enum Error {
     SerdeErr(serde_json::Error),
     /// Authentication of the client/server failed.
     AuthFailed,
+    /// An invalid argument was given. Contains a description of the problem.
+    InvalidArgument(String),
     /// Trying to do a server only operation as a client.
     ServerOnlyOperation,
     /// Trying to do a client only operation as a server.
     ClientOnlyOperation,
     /// Operation not supported for local-only instances.
     OnlineOnlyOperation,
+    /// Adding a dependency between `Todo`s would make a `Todo` depend on itself.
+    DependencyCycle,
+    /// The body of an item is empty or only whitespace.
+    EmptyBody,
+    /// The body of an item is longer than `MAX_BODY_LENGTH` characters.
+    BodyTooLong,
+    /// A `Task` was given no weekdays.
+    NoWeekdays,
+    /// Another server process is already using the same save file. Contains the process id of the
+    /// other server and the path of its lock file.
+    ServerAlreadyRunning(u32, PathBuf),
+    /// Looking up a DNS SRV record failed. Contains a description of the problem.
+    SrvLookupFailed(String),
+    /// Connecting through a proxy failed. Contains a description of the problem.
+    ProxyFailed(String),
+    /// Publishing an onion service through Tor failed. Contains a description of the problem.
+    TorFailed(String),
+    /// A `TdList` has more items or longer bodies than a server accepts. Contains a description of
+    /// each offending item.
+    LimitsExceeded(Vec<String>),
+    /// The client and the server have no sync protocol version in common.
+    IncompatibleProtocol {
+        /// The newest protocol version of the client.
+        client: u32,
+        /// The newest protocol version of the server.
+        server: u32,
+    },
     /// Unspecified error for rare edge cases that most of the time are handled internally.
     Unknown,
 }
@@ -111,6 +184,15 @@ impl Display for Error {
             Error::NoTaskWithGivenId(id) => {
                 write!(f, "No Task with the given id: \"{}\" found.", id)
             }
+            Error::NoTodoWithGivenUuid(uuid) => {
+                write!(f, "No Todo with the given uuid: \"{}\" found.", uuid)
+            }
+            Error::NoTaskWithGivenUuid(uuid) => {
+                write!(f, "No Task with the given uuid: \"{}\" found.", uuid)
+            }
+            Error::NoPlanWithGivenId(id) => {
+                write!(f, "No day plan with the given id: \"{}\" found.", id)
+            }
             Error::EncryptingFailed => {
                 write!(f, "Encrypting data failed.")
             }
@@ -126,6 +208,9 @@ impl Display for Error {
             Error::AuthFailed => {
                 write!(f, "Remote authentication failed.")
             }
+            Error::InvalidArgument(msg) => {
+                write!(f, "Invalid argument: {}", msg)
+            }
             Error::Unknown => {
                 write!(f, "Unknown error.")
             }
@@ -138,6 +223,42 @@ impl Display for Error {
             Error::OnlineOnlyOperation => {
                 write!(f, "Operation not permitted for local-only instances.")
             }
+            Error::DependencyCycle => {
+                write!(f, "Dependency would create a cycle.")
+            }
+            Error::EmptyBody => {
+                write!(f, "The body of an item cannot be empty.")
+            }
+            Error::BodyTooLong => {
+                write!(f, "The body of an item cannot be longer than {} characters.", MAX_BODY_LENGTH)
+            }
+            Error::NoWeekdays => {
+                write!(f, "A task must have at least one weekday.")
+            }
+            Error::SrvLookupFailed(msg) => {
+                write!(f, "DNS SRV lookup failed: {}", msg)
+            }
+            Error::ProxyFailed(msg) => {
+                write!(f, "Connecting through the proxy failed: {}", msg)
+            }
+            Error::TorFailed(msg) => {
+                write!(f, "Publishing the onion service failed: {}", msg)
+            }
+            Error::LimitsExceeded(problems) => {
+                write!(f, "The server rejected the sync: {}", problems.join(" "))
+            }
+            Error::IncompatibleProtocol { client, server } => {
+                let older = if server < client { "the server" } else { "this mtd" };
+                write!(f, "The server uses sync protocol version {} and this mtd version {}, which don't work together. Update {}.", server, client, older)
+            }
+            Error::ServerAlreadyRunning(pid, lock_file) => {
+                write!(
+                    f,
+                    "A server (process {}) is already using the same data file. If no server is running, remove the lock file \"{}\".",
+                    pid,
+                    lock_file.display()
+                )
+            }
         }
     }
 }
@@ -156,10 +277,23 @@ impl From<serde_json::Error> for Error {
 
 impl std::error::Error for Error {}
 
+/// The maximum length of the body of a `Todo` or a `Task` in characters.
+pub const MAX_BODY_LENGTH: usize = 1000;
+
+fn validate_body(body: &str) -> Result<()> {
+    if body.trim().is_empty() {
+        Err(Error::EmptyBody)
+    } else if body.chars().count() > MAX_BODY_LENGTH {
+        Err(Error::BodyTooLong)
+    } else {
+        Ok(())
+    }
+}
+
 /// Gets the date that represents the upcoming weekday. Given tomorrow’s weekday, this should return
 /// tomorrows date. Today is represented by the current weekday.
 pub fn weekday_to_date(weekday: Weekday) -> NaiveDate {
-    weekday_to_date_wtd(weekday, Local::today().naive_local())
+    weekday_to_date_wtd(weekday, current_date())
 }
 
 fn weekday_to_date_wtd(weekday: Weekday, mut today: NaiveDate) -> NaiveDate {
@@ -171,9 +305,9 @@ fn weekday_to_date_wtd(weekday: Weekday, mut today: NaiveDate) -> NaiveDate {
     }
 }
 
-/// Represents a one-time task to be done at a specific date. The date is specified as a weekday
-/// from now. If no weekday is given, the current weekday will be used. After the given weekday, the
-/// `Todo` will show up for the current day.
+/// Represents a one-time task to be done at a specific date. The date is specified either as a weekday
+/// from now or as an exact date. If no date is given, the current date will be used. After the given
+/// date, the `Todo` will show up for the current day.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Todo {
     body: String,
@@ -182,44 +316,123 @@ pub struct Todo {
     done: Option<NaiveDate>,
     sync_id: u64,
     state: ItemState,
+    // Items saved before uuids existed get a new one when loaded. Syncing then unifies the uuids
+    // between the client and the server.
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    time: Option<NaiveTime>,
+    #[serde(default)]
+    priority: u8,
+    // Todos saved before creation times were recorded don't have one.
+    #[serde(default)]
+    created: Option<NaiveDateTime>,
+    // Position in the manual order. Todos that have never been moved sort after moved ones.
+    #[serde(default = "default_position")]
+    position: u64,
+    // Uuids of the todos that must be done before this one.
+    #[serde(default)]
+    blocked_by: BTreeSet<Uuid>,
+    #[serde(default)]
+    postponed: u32,
+    // How many times the todo has been changed on the server. A client that changed an older
+    // version than the server has conflicts with another client.
+    #[serde(default)]
+    version: u64,
+    // When the todo was last changed in UTC.
+    #[serde(default)]
+    modified: Option<NaiveDateTime>,
+    // The revision of the server's list the todo last changed in. Only kept by servers.
+    #[serde(default)]
+    revision: u64,
+}
+
+fn default_position() -> u64 {
+    u64::MAX
+}
+
+/// The order in which `Todo`s are returned by `TdList` queries that accept a `SortOrder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order set with `TdList::move_todo`. `Todo`s that have never been moved are in insertion
+    /// order after the moved ones.
+    Manual,
+    /// Alphabetical order of the bodies ignoring case.
+    Alphabetical,
+    /// Oldest first. `Todo`s without a creation time are first.
+    Created,
+    /// Highest priority first.
+    Priority,
+}
+
+/// How the ids of items are shown and given in commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdStyle {
+    /// The numbers of the items, which are short but differ between devices.
+    #[default]
+    Number,
+    /// The shortest beginnings of the uuids that tell the items apart, like short commit hashes.
+    /// They are the same on every device and grow longer only as items are added.
+    Short,
 }
 
+/// The fewest hexadecimal digits of a uuid that `TdList::short_id_len` returns.
+const MIN_SHORT_ID_LEN: usize = 4;
+
 impl Todo {
-    /// Creates a new `Todo` that shows up to be done for the current day.
-    pub fn new_undated(body: String) -> Todo {
-        Todo {
-            body,
-            date: Local::today().naive_local(),
-            id: 0,
-            done: None,
-            sync_id: random(),
-            state: ItemState::Unchanged,
-        }
+    /// Creates a new `Todo` that shows up to be done for the current day. Returns an
+    /// `Error::EmptyBody` or `Error::BodyTooLong` if the body isn't valid.
+    pub fn new_undated(body: String) -> Result<Todo> {
+        Todo::new_specific_date(body, current_date())
     }
 
-    /// Creates a new `Todo` that shows up to be done at a specific weekday.
-    pub fn new_dated(body: String, weekday: Weekday) -> Todo {
-        Todo {
-            body,
-            date: weekday_to_date_wtd(weekday, Local::today().naive_local()),
-            id: 0,
-            done: None,
-            sync_id: random(),
-            state: ItemState::Unchanged,
-        }
+    /// Creates a new `Todo` that shows up to be done at a specific weekday. Returns an
+    /// `Error::EmptyBody` or `Error::BodyTooLong` if the body isn't valid.
+    pub fn new_dated(body: String, weekday: Weekday) -> Result<Todo> {
+        Todo::new_specific_date(body, weekday_to_date_wtd(weekday, current_date()))
     }
 
-    // Used for unit testing with non-today dependant date
-    #[cfg(test)]
-    fn new_specific_date(body: String, date: NaiveDate) -> Todo {
-        Todo {
+    /// Creates a new `Todo` that shows up to be done at a specific date. Returns an
+    /// `Error::EmptyBody` or `Error::BodyTooLong` if the body isn't valid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use mtd::{Error, Todo};
+    ///
+    /// let todo = Todo::new_specific_date("Dentist".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap();
+    ///
+    /// assert_eq!(todo.date(), NaiveDate::from_ymd(2024, 8, 15));
+    ///
+    /// let empty = Todo::new_specific_date("  ".to_string(), NaiveDate::from_ymd(2024, 8, 15));
+    ///
+    /// assert!(matches!(empty, Err(Error::EmptyBody)));
+    /// ```
+    pub fn new_specific_date(body: String, date: NaiveDate) -> Result<Todo> {
+        validate_body(&body)?;
+        Ok(Todo {
             body,
             date,
             id: 0,
             done: None,
             sync_id: random(),
             state: ItemState::Unchanged,
-        }
+            uuid: Uuid::new_v4(),
+            notes: None,
+            time: None,
+            priority: 0,
+            created: Some(current_time()),
+            position: default_position(),
+            blocked_by: BTreeSet::new(),
+            postponed: 0,
+            version: 0,
+            modified: None,
+            revision: 0,
+        })
     }
 
     /// Returns `true` if the `Todo` is for a given date.
@@ -227,30 +440,36 @@ impl Todo {
     /// # Example
     ///
     /// ```
-    /// use chrono::{Datelike, Local};
+    /// use chrono::Datelike;
     /// use mtd::Todo;
     ///
-    /// let todo_for_today = Todo::new_undated("I am for today".to_string());
+    /// let todo_for_today = Todo::new_undated("I am for today".to_string()).unwrap();
     ///
-    /// assert!(todo_for_today.for_date(Local::today().naive_local()));
+    /// assert!(todo_for_today.for_date(mtd::current_date()));
     ///
-    /// let todo_for_tomorrow = Todo::new_dated("I am for tomorrow".to_string(), Local::today().naive_local().succ().weekday());
+    /// let todo_for_tomorrow = Todo::new_dated("I am for tomorrow".to_string(), mtd::current_date().succ().weekday()).unwrap();
     ///
-    /// assert!(!todo_for_tomorrow.for_date(Local::today().naive_local()));
-    /// assert!(todo_for_tomorrow.for_date(Local::today().naive_local().succ()));
+    /// assert!(!todo_for_tomorrow.for_date(mtd::current_date()));
+    /// assert!(todo_for_tomorrow.for_date(mtd::current_date().succ()));
     /// ```
     pub fn for_date(&self, date: NaiveDate) -> bool {
-        self.for_date_wtd(date, Local::today().naive_local())
+        self.for_date_wtd(date, current_date())
     }
 
     fn for_date_wtd(&self, date: NaiveDate, today: NaiveDate) -> bool {
         if self.date < date {
             date == today
         } else {
-            date.weekday() == self.date.weekday()
+            date == self.date
         }
     }
 
+    /// Marks the `Todo` changed since the last sync.
+    fn mark_changed(&mut self) {
+        self.state = ItemState::Changed;
+        self.modified = Some(Utc::now().naive_utc());
+    }
+
     /// Gets the `body` of the `Todo`.
     pub fn body(&self) -> &str {
         &self.body
@@ -261,21 +480,153 @@ impl Todo {
         self.date.weekday()
     }
 
+    /// Gets the date the `Todo` is scheduled for. The date stays the same when an undone `Todo`
+    /// rolls forward to the current day.
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// Returns how many days ago the `Todo` should have been done. Returns 0 if the `Todo` is done or
+    /// its date hasn't passed yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use mtd::Todo;
+    ///
+    /// let todo = Todo::new_specific_date("Pay rent".to_string(), NaiveDate::from_ymd(2024, 8, 1)).unwrap();
+    ///
+    /// assert_eq!(todo.days_overdue(NaiveDate::from_ymd(2024, 8, 4)), 3);
+    /// assert_eq!(todo.days_overdue(NaiveDate::from_ymd(2024, 7, 30)), 0);
+    /// ```
+    pub fn days_overdue(&self, today: NaiveDate) -> u64 {
+        if self.done() {
+            0
+        } else {
+            (today - self.date).num_days().max(0) as u64
+        }
+    }
+
     /// Gets the `id` of the `Todo`.
     pub fn id(&self) -> u64 {
         self.id
     }
 
-    /// Sets the `body` of the `Todo`.
-    pub fn set_body(&mut self, body: String) {
+    /// Gets the `uuid` of the `Todo`. Unlike the `id`, the `uuid` never changes and is the same on
+    /// every synchronized device.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Gets the synchronization state of the `Todo`, which tells whether it has changes that
+    /// haven't been synchronized yet. Only meaningful for `Todo`s of a client `TdList`.
+    pub fn sync_state(&self) -> SyncState {
+        self.state.into()
+    }
+
+    /// Sets the `body` of the `Todo`. Returns an `Error::EmptyBody` or `Error::BodyTooLong` and
+    /// leaves the `Todo` unchanged if the body isn't valid.
+    pub fn set_body(&mut self, body: String) -> Result<()> {
+        validate_body(&body)?;
         self.body = body;
-        self.state = ItemState::Changed;
+        self.mark_changed();
+        Ok(())
+    }
+
+    /// Gets the `notes` of the `Todo`. Notes are an optional longer description of the `Todo` and
+    /// may span multiple lines.
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    /// Sets the `notes` of the `Todo`. `None` removes the notes.
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+        self.mark_changed();
+    }
+
+    /// Gets the priority of the `Todo`. Higher numbers are more important. The default priority is 0.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Sets the priority of the `Todo`. Higher numbers are more important.
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority;
+        self.mark_changed();
+    }
+
+    /// Gets the time the `Todo` was created or `None` if the `Todo` was saved before creation times
+    /// were recorded.
+    pub fn created(&self) -> Option<NaiveDateTime> {
+        self.created
+    }
+
+    /// Gets the time of day the `Todo` is scheduled for. `None` means any time of the day.
+    pub fn time(&self) -> Option<NaiveTime> {
+        self.time
+    }
+
+    /// Sets the time of day the `Todo` is scheduled for. `None` means any time of the day.
+    pub fn set_time(&mut self, time: Option<NaiveTime>) {
+        self.time = time;
+        self.mark_changed();
+    }
+
+    /// Gets the `uuid`s of the `Todo`s that must be done before this `Todo`. Use
+    /// `TdList::add_todo_dependency` to add dependencies.
+    pub fn blocked_by(&self) -> &BTreeSet<Uuid> {
+        &self.blocked_by
     }
 
     /// Sets the weekday of the `Todo`.
     pub fn set_weekday(&mut self, weekday: Weekday) {
-        self.date = weekday_to_date_wtd(weekday, Local::today().naive_local());
-        self.state = ItemState::Changed;
+        self.date = weekday_to_date_wtd(weekday, current_date());
+        self.mark_changed();
+    }
+
+    /// Sets the date the `Todo` is scheduled for.
+    pub fn set_date(&mut self, date: NaiveDate) {
+        self.date = date;
+        self.mark_changed();
+    }
+
+    /// Moves the `Todo` forward by `days` days and counts the postponement. The days are counted
+    /// from the date of the `Todo` or from today if the date has already passed, so postponing an
+    /// overdue `Todo` by a day moves it to tomorrow. Postponing by 0 days does nothing. Returns an
+    /// `Error::InvalidArgument` if the new date would be too far in the future to be represented.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Duration;
+    /// use mtd::Todo;
+    ///
+    /// let mut todo = Todo::new_undated("Clean the garage".to_string()).unwrap();
+    /// todo.postpone(2).unwrap();
+    ///
+    /// assert_eq!(todo.date(), mtd::current_date() + Duration::days(2));
+    /// assert_eq!(todo.postponed(), 1);
+    /// ```
+    pub fn postpone(&mut self, days: u32) -> Result<()> {
+        self.postpone_wtd(days, current_date())
+    }
+
+    fn postpone_wtd(&mut self, days: u32, today: NaiveDate) -> Result<()> {
+        if days == 0 {
+            return Ok(());
+        }
+        self.date = self.date.max(today).checked_add_signed(Duration::days(days as i64))
+            .ok_or_else(|| Error::InvalidArgument(format!("A todo can't be postponed by {} days.", days)))?;
+        self.postponed += 1;
+        self.mark_changed();
+        Ok(())
+    }
+
+    /// Gets the number of times the `Todo` has been postponed with `Todo::postpone`.
+    pub fn postponed(&self) -> u32 {
+        self.postponed
     }
 
     /// Returns `true` if the `Todo` is done.
@@ -283,9 +634,14 @@ impl Todo {
         self.done.is_some()
     }
 
+    /// Gets the date the `Todo` was set as done or `None` if it isn't done.
+    pub fn done_date(&self) -> Option<NaiveDate> {
+        self.done
+    }
+
     /// Sets the done state of the `Todo`.
     pub fn set_done(&mut self, done: bool) {
-        self.set_done_wtd(done, Local::today().naive_local());
+        self.set_done_wtd(done, current_date());
     }
 
     fn set_done_wtd(&mut self, done: bool, today: NaiveDate) {
@@ -294,17 +650,13 @@ impl Todo {
         } else {
             self.done = None;
         }
-        self.state = ItemState::Changed;
-    }
-
-    fn set_id(&mut self, id: u64) {
-        self.id = id;
+        self.mark_changed();
     }
 
     /// Returns `true` if the `Todo` can be removed. A `Todo` can be removed one day after its
     /// completion.
     pub fn can_remove(&self) -> bool {
-        self.can_remove_wtd(Local::today().naive_local())
+        self.can_remove_wtd(current_date())
     }
 
     fn can_remove_wtd(&self, today: NaiveDate) -> bool {
@@ -326,7 +678,13 @@ impl PartialEq for Todo {
     fn eq(&self, other: &Self) -> bool {
         self.body == other.body &&
             self.date == other.date &&
-            self.done == other.done
+            self.done == other.done &&
+            self.notes == other.notes &&
+            self.time == other.time &&
+            self.priority == other.priority &&
+            self.position == other.position &&
+            self.blocked_by == other.blocked_by &&
+            self.postponed == other.postponed
     }
 }
 
@@ -339,19 +697,90 @@ pub struct Task {
     id: u64,
     state: ItemState,
     sync_id: u64,
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
+    #[serde(default)]
+    until: Option<NaiveDate>,
+    #[serde(default)]
+    paused: bool,
+    #[serde(default)]
+    paused_until: Option<NaiveDate>,
+    #[serde(default = "default_every_weeks", deserialize_with = "deserialize_every_weeks")]
+    every_weeks: u32,
+    #[serde(default)]
+    anchor: Option<NaiveDate>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    time: Option<NaiveTime>,
+    // Times that override `time` on specific weekdays.
+    #[serde(default)]
+    weekday_times: HashMap<Weekday, NaiveTime>,
+    // Every date the task has been done for. Tasks saved before this was recorded only have their
+    // later completions.
+    #[serde(default)]
+    completions: BTreeSet<NaiveDate>,
+    #[serde(default)]
+    created: Option<NaiveDate>,
+    #[serde(default)]
+    backfill_days: u32,
+    // See the same fields of `Todo`.
+    #[serde(default)]
+    version: u64,
+    #[serde(default)]
+    modified: Option<NaiveDateTime>,
+    #[serde(default)]
+    revision: u64,
+}
+
+fn default_every_weeks() -> u32 {
+    1
+}
+
+/// Reads `every_weeks` of a saved or synced `Task`. A task can't repeat every 0 weeks, so 0 is read
+/// as every week.
+fn deserialize_every_weeks<'de, D: serde::Deserializer<'de>>(deserializer: D) -> result::Result<u32, D::Error> {
+    Ok(u32::deserialize(deserializer)?.max(1))
 }
 
 impl Task {
-    /// Creates a new task for the given weekday(s).
-    ///
-    /// # Panics
-    ///
-    /// If the given weekdays list is empty.
-    pub fn new(body: String, weekdays: Vec<Weekday>) -> Task {
+    /// Creates a new task for the given weekday(s). Returns an `Error::NoWeekdays` if the given
+    /// weekdays list is empty and an `Error::EmptyBody` or `Error::BodyTooLong` if the body isn't
+    /// valid.
+    pub fn new(body: String, weekdays: Vec<Weekday>) -> Result<Task> {
+        validate_body(&body)?;
         if weekdays.is_empty() {
-            panic!("Cannot create a task without specifying at least one weekday.")
+            return Err(Error::NoWeekdays);
         }
-        Task { body, weekdays, id: 0, done_map: HashMap::new(), sync_id: random(), state: ItemState::Unchanged }
+        Ok(Task {
+            body,
+            weekdays,
+            id: 0,
+            done_map: HashMap::new(),
+            weekday_times: HashMap::new(),
+            sync_id: random(),
+            state: ItemState::Unchanged,
+            uuid: Uuid::new_v4(),
+            until: None,
+            paused: false,
+            paused_until: None,
+            every_weeks: 1,
+            anchor: None,
+            notes: None,
+            time: None,
+            completions: BTreeSet::new(),
+            created: Some(current_date()),
+            backfill_days: 0,
+            version: 0,
+            modified: None,
+            revision: 0,
+        })
+    }
+
+    /// Marks the `Task` changed since the last sync.
+    fn mark_changed(&mut self) {
+        self.state = ItemState::Changed;
+        self.modified = Some(Utc::now().naive_utc());
     }
 
     /// Gets the `body` of the `Task`.
@@ -369,30 +798,251 @@ impl Task {
         self.id
     }
 
-    /// Sets the `body` of the `Task`.
-    pub fn set_body(&mut self, body: String) {
+    /// Gets the `uuid` of the `Task`. Unlike the `id`, the `uuid` never changes and is the same on
+    /// every synchronized device.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Gets the synchronization state of the `Task`, which tells whether it has changes that
+    /// haven't been synchronized yet. Only meaningful for `Task`s of a client `TdList`.
+    pub fn sync_state(&self) -> SyncState {
+        self.state.into()
+    }
+
+    /// Sets the `body` of the `Task`. Returns an `Error::EmptyBody` or `Error::BodyTooLong` and
+    /// leaves the `Task` unchanged if the body isn't valid.
+    pub fn set_body(&mut self, body: String) -> Result<()> {
+        validate_body(&body)?;
         self.body = body;
-        self.state = ItemState::Changed;
+        self.mark_changed();
+        Ok(())
     }
 
-    fn set_id(&mut self, id: u64) {
-        self.id = id;
+    /// Gets the `notes` of the `Task`. Notes are an optional longer description of the `Task` and
+    /// may span multiple lines.
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    /// Sets the `notes` of the `Task`. `None` removes the notes.
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+        self.mark_changed();
+    }
+
+    /// Gets the time of day the `Task` is scheduled for. `None` means any time of the day.
+    pub fn time(&self) -> Option<NaiveTime> {
+        self.time
+    }
+
+    /// Sets the time of day the `Task` is scheduled for. `None` means any time of the day.
+    pub fn set_time(&mut self, time: Option<NaiveTime>) {
+        self.time = time;
+        self.mark_changed();
+    }
+
+    /// Gets the times of day set for specific weekdays with `set_weekday_time`.
+    pub fn weekday_times(&self) -> &HashMap<Weekday, NaiveTime> {
+        &self.weekday_times
+    }
+
+    /// Sets the time of day the `Task` is scheduled for on the given weekday. On other weekdays the
+    /// `Task` keeps its normal time. `None` removes the weekday's own time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveTime, Weekday};
+    /// use mtd::Task;
+    ///
+    /// let mut gym = Task::new("Gym".to_string(), vec![Weekday::Mon, Weekday::Wed]).unwrap();
+    /// gym.set_time(Some(NaiveTime::from_hms(18, 0, 0)));
+    /// gym.set_weekday_time(Weekday::Wed, Some(NaiveTime::from_hms(7, 0, 0)));
+    ///
+    /// assert_eq!(gym.time_for(Weekday::Mon), Some(NaiveTime::from_hms(18, 0, 0)));
+    /// assert_eq!(gym.time_for(Weekday::Wed), Some(NaiveTime::from_hms(7, 0, 0)));
+    /// ```
+    pub fn set_weekday_time(&mut self, weekday: Weekday, time: Option<NaiveTime>) {
+        match time {
+            Some(time) => self.weekday_times.insert(weekday, time),
+            None => self.weekday_times.remove(&weekday),
+        };
+        self.mark_changed();
+    }
+
+    /// Gets the time of day the `Task` is scheduled for on the given weekday.
+    pub fn time_for(&self, weekday: Weekday) -> Option<NaiveTime> {
+        self.weekday_times.get(&weekday).copied().or(self.time)
     }
 
-    /// Sets the `weekdays` of the `Task`.
-    pub fn set_weekdays(&mut self, weekdays: Vec<Weekday>) {
+    /// Sets the `weekdays` of the `Task`. Returns an `Error::NoWeekdays` if `weekdays` is empty.
+    pub fn set_weekdays(&mut self, weekdays: Vec<Weekday>) -> Result<()> {
+        if weekdays.is_empty() {
+            return Err(Error::NoWeekdays);
+        }
+        self.weekday_times.retain(|wd, _| weekdays.contains(wd));
         self.weekdays = weekdays;
-        self.state = ItemState::Changed;
+        self.mark_changed();
+        Ok(())
+    }
+
+    /// Gets the end date of the `Task`. The `Task` isn't for any date after its end date.
+    pub fn until(&self) -> Option<NaiveDate> {
+        self.until
+    }
+
+    /// Sets the end date of the `Task`. `None` means that the `Task` repeats indefinitely. After the
+    /// end date has passed, the `Task` is removed automatically when syncing.
+    pub fn set_until(&mut self, until: Option<NaiveDate>) {
+        self.until = until;
+        self.mark_changed();
+    }
+
+    /// Gets the number of weeks between the weeks the `Task` is for. For example 2 means every other
+    /// week.
+    pub fn every_weeks(&self) -> u32 {
+        self.every_weeks
+    }
+
+    /// Gets the date the `Task`'s cadence is anchored to. The `Task` is for the week of the anchor
+    /// date and every `every_weeks` weeks before and after it.
+    pub fn anchor(&self) -> Option<NaiveDate> {
+        self.anchor
+    }
+
+    /// Makes the `Task` repeat every `weeks` weeks starting from the week of the `anchor` date.
+    /// Returns an `Error::InvalidArgument` if `weeks` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, Weekday};
+    /// use mtd::Task;
+    ///
+    /// let mut task = Task::new("Take out recycling".to_string(), vec![Weekday::Tue]).unwrap();
+    /// task.set_every_weeks(2, NaiveDate::from_ymd(2022, 6, 7)).unwrap();
+    ///
+    /// assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 7)));
+    /// assert!(!task.for_date(NaiveDate::from_ymd(2022, 6, 14)));
+    /// assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 21)));
+    /// ```
+    pub fn set_every_weeks(&mut self, weeks: u32, anchor: NaiveDate) -> Result<()> {
+        if weeks == 0 {
+            return Err(Error::InvalidArgument("A task can't repeat every 0 weeks.".to_string()));
+        }
+        self.every_weeks = weeks;
+        self.anchor = Some(anchor);
+        self.mark_changed();
+        Ok(())
+    }
+
+    /// Returns `true` if the week of the given date is one of the weeks the `Task` repeats on.
+    fn in_active_week(&self, date: NaiveDate) -> bool {
+        let anchor = match self.anchor {
+            Some(anchor) if self.every_weeks > 1 => anchor,
+            _ => return true,
+        };
+
+        let week_start = |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+        let weeks = (week_start(date) - week_start(anchor)).num_weeks();
+
+        weeks.rem_euclid(self.every_weeks as i64) == 0
+    }
+
+    /// Pauses or resumes the `Task`. A paused `Task` isn't listed as done or undone for any date
+    /// until it is resumed.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.paused_until = None;
+        self.mark_changed();
+    }
+
+    /// Pauses the `Task` until the given date. The `Task` is listed again starting from the given
+    /// date.
+    pub fn pause_until(&mut self, date: NaiveDate) {
+        self.paused = true;
+        self.paused_until = Some(date);
+        self.mark_changed();
+    }
+
+    /// Returns the date the `Task` is paused until or `None` if the `Task` isn't paused or is paused
+    /// indefinitely.
+    pub fn paused_until(&self) -> Option<NaiveDate> {
+        self.paused_until
+    }
+
+    /// Gets the number of days a missed date of the `Task` is shown after it. 0 means that missed
+    /// dates aren't shown.
+    pub fn backfill_days(&self) -> u32 {
+        self.backfill_days
+    }
+
+    /// Sets the number of days a missed date of the `Task` is shown after it, for example 1 for rent
+    /// that must be paid even if a day late. At most 6 days are used, because the `Task` only
+    /// remembers the latest date it was done for on each weekday.
+    pub fn set_backfill_days(&mut self, days: u32) {
+        self.backfill_days = days;
+        self.mark_changed();
+    }
+
+    /// Returns the latest date before `today` that the `Task` was for but wasn't done, if the date
+    /// is at most `backfill_days` days before `today`. Dates before the `Task` was created and dates
+    /// it was paused for aren't missed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Datelike, Duration};
+    /// use mtd::Task;
+    ///
+    /// let today = mtd::current_date();
+    /// let mut rent = Task::new("Pay rent".to_string(), vec![today.weekday()]).unwrap();
+    /// rent.set_backfill_days(2);
+    ///
+    /// assert_eq!(rent.missed_date(today + Duration::days(1)), Some(today));
+    /// assert_eq!(rent.missed_date(today + Duration::days(3)), None); // Too late
+    ///
+    /// rent.set_done(true, today);
+    /// assert_eq!(rent.missed_date(today + Duration::days(1)), None);
+    /// ```
+    pub fn missed_date(&self, today: NaiveDate) -> Option<NaiveDate> {
+        (1..=self.backfill_days.min(6) as i64)
+            .map(|days| today - Duration::days(days))
+            .take_while(|date| self.created.is_none_or(|created| *date >= created))
+            .find(|date| self.for_date(*date) && !self.paused(*date) && !self.done(*date))
+    }
+
+    /// Returns `true` if the `Task` is paused for the given date.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, Weekday};
+    /// use mtd::Task;
+    ///
+    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
+    /// task.pause_until(NaiveDate::from_ymd(2022, 6, 20));
+    ///
+    /// assert!(task.paused(NaiveDate::from_ymd(2022, 6, 13)));
+    /// assert!(!task.paused(NaiveDate::from_ymd(2022, 6, 20)));
+    ///
+    /// task.set_paused(true);
+    /// assert!(task.paused(NaiveDate::from_ymd(2022, 6, 20)));
+    /// ```
+    pub fn paused(&self, date: NaiveDate) -> bool {
+        self.paused && self.paused_until.is_none_or(|until| date < until)
     }
 
     /// Adds a weekday to the weekdays list.
     pub fn add_weekday(&mut self, weekday: Weekday) {
         // It doesn't matter if there are duplicate weekdays.
         self.weekdays.push(weekday);
-        self.state = ItemState::Changed;
+        self.mark_changed();
     }
 
-    /// Removes a weekday from the weekdays list. Removes all duplicates as well.
+    /// Removes a weekday from the weekdays list. Removes all duplicates as well. Returns an
+    /// `Error::NoWeekdays` and keeps the `Task` as it is if no weekdays would be left.
     ///
     /// # Example
     ///
@@ -400,27 +1050,27 @@ impl Task {
     /// use chrono::Weekday;
     /// use mtd::Task;
     ///
-    /// let mut task = Task::new("Test task".to_string(), vec![Weekday::Mon, Weekday::Tue, Weekday::Wed]);
-    /// task.remove_weekday(Weekday::Wed);
+    /// let mut task = Task::new("Test task".to_string(), vec![Weekday::Mon, Weekday::Tue, Weekday::Wed]).unwrap();
+    /// task.remove_weekday(Weekday::Wed).unwrap();
     ///
     /// // Removing a weekday that isn't listed does nothing.
-    /// task.remove_weekday(Weekday::Fri);
+    /// task.remove_weekday(Weekday::Fri).unwrap();
     ///
     /// assert!(task.weekdays().contains(&Weekday::Mon));
     /// assert!(task.weekdays().contains(&Weekday::Tue));
     /// // Doesn't contain wed anymore
     /// assert!(!task.weekdays().contains(&Weekday::Wed));
     /// ```
-    pub fn remove_weekday(&mut self, removed_wd: Weekday) {
+    pub fn remove_weekday(&mut self, removed_wd: Weekday) -> Result<()> {
         let mut new_weekdays = Vec::new();
 
         for wd in &self.weekdays {
             if wd != &removed_wd {
-                new_weekdays.push(wd.clone());
+                new_weekdays.push(*wd);
             }
         }
 
-        self.set_weekdays(new_weekdays);
+        self.set_weekdays(new_weekdays)
     }
 
     /// Returns `true` if the `Task` is for a given date.
@@ -431,14 +1081,57 @@ impl Task {
     /// use chrono::{NaiveDate, Weekday};
     /// use mtd::Task;
     ///
-    /// let task = Task::new("Task".to_string(), vec![Weekday::Fri, Weekday::Sun]);
+    /// let task = Task::new("Task".to_string(), vec![Weekday::Fri, Weekday::Sun]).unwrap();
     ///
     /// assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 10))); // 2022-6-10 is a Friday
     /// assert!(!task.for_date(NaiveDate::from_ymd(2022, 6, 11))); // Saturday
     /// assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 12))); // Sunday
+    ///
+    /// let mut course = Task::new("Course".to_string(), vec![Weekday::Fri]).unwrap();
+    /// course.set_until(Some(NaiveDate::from_ymd(2022, 6, 10)));
+    ///
+    /// assert!(course.for_date(NaiveDate::from_ymd(2022, 6, 10)));
+    /// assert!(!course.for_date(NaiveDate::from_ymd(2022, 6, 17))); // After the end date
     /// ```
     pub fn for_date(&self, date: NaiveDate) -> bool {
-        self.weekdays.contains(&date.weekday())
+        self.weekdays.contains(&date.weekday()) && self.until.is_none_or(|until| date <= until) && self.in_active_week(date)
+    }
+
+    /// Returns the first date on or after the given date that the `Task` is for. Returns `None` if
+    /// the `Task` isn't for any date after the given date, for example if its end date has passed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, Weekday};
+    /// use mtd::Task;
+    ///
+    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Fri]).unwrap();
+    ///
+    /// // 2022-6-8 is a Wednesday
+    /// assert_eq!(task.next_date(NaiveDate::from_ymd(2022, 6, 8)), Some(NaiveDate::from_ymd(2022, 6, 10)));
+    ///
+    /// task.set_until(Some(NaiveDate::from_ymd(2022, 6, 9)));
+    /// assert_eq!(task.next_date(NaiveDate::from_ymd(2022, 6, 8)), None);
+    /// ```
+    pub fn next_date(&self, from: NaiveDate) -> Option<NaiveDate> {
+        // The cadence of a task repeats after every_weeks weeks so there is no need to look further.
+        (0..7 * self.every_weeks as i64)
+            .map(|days| from + Duration::days(days))
+            .find(|date| self.for_date(*date))
+    }
+
+    /// Returns `true` if the `Task` can be removed. A `Task` can be removed after its end date.
+    pub fn can_remove(&self) -> bool {
+        self.can_remove_wtd(current_date())
+    }
+
+    fn can_remove_wtd(&self, today: NaiveDate) -> bool {
+        if let Some(until) = self.until {
+            today > until
+        } else {
+            false
+        }
     }
 
     /// Returns `true` if the `Task` is done for the given date. Always returns `true` if the task
@@ -450,7 +1143,7 @@ impl Task {
     /// use chrono::{NaiveDate, Weekday};
     /// use mtd::Task;
     ///
-    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Wed, Weekday::Thu]);
+    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Wed, Weekday::Thu]).unwrap();
     ///
     /// task.set_done(true, NaiveDate::from_ymd(2022, 6, 13));
     /// task.set_done(true, NaiveDate::from_ymd(2022, 6, 16));
@@ -489,7 +1182,7 @@ impl Task {
     /// use chrono::{NaiveDate, Weekday};
     /// use mtd::Task;
     ///
-    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]);
+    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
     ///
     /// task.set_done(true, NaiveDate::from_ymd(2022, 6, 13));
     /// assert!(task.done(NaiveDate::from_ymd(2022, 6, 13)));
@@ -500,25 +1193,179 @@ impl Task {
     pub fn set_done(&mut self, done: bool, date: NaiveDate) {
         if done {
             self.done_map.insert(date.weekday(), date);
+            self.completions.insert(date);
         } else {
             self.done_map.remove(&date.weekday());
+            self.completions.remove(&date);
         }
+        self.mark_changed();
     }
-}
-
-impl Display for Task {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}. {}", self.id, self.body)
-    }
-}
 
-impl PartialEq for Task {
-    fn eq(&self, other: &Self) -> bool {
-        self.body == other.body &&
-            self.weekdays == other.weekdays &&
-            self.done_map == other.done_map
-    }
-}
+    /// Gets the latest date the `Task` has been done for on each of its weekdays. Weekdays the `Task`
+    /// has never been done on are missing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, Weekday};
+    /// use mtd::Task;
+    ///
+    /// let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Tue]).unwrap();
+    /// task.set_done(true, NaiveDate::from_ymd(2022, 6, 13));
+    ///
+    /// assert_eq!(task.done_map().get(&Weekday::Mon), Some(&NaiveDate::from_ymd(2022, 6, 13)));
+    /// assert_eq!(task.done_map().get(&Weekday::Tue), None);
+    /// ```
+    pub fn done_map(&self) -> &HashMap<Weekday, NaiveDate> {
+        &self.done_map
+    }
+
+    /// Gets every date the `Task` has been done for.
+    pub fn completions(&self) -> &BTreeSet<NaiveDate> {
+        &self.completions
+    }
+
+    /// Gets the date the `Task` was created or `None` if the `Task` was saved before creation dates
+    /// were recorded.
+    pub fn created(&self) -> Option<NaiveDate> {
+        self.created
+    }
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}. {}", self.id, self.body)
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.body == other.body &&
+            self.weekdays == other.weekdays &&
+            self.done_map == other.done_map &&
+            self.until == other.until &&
+            self.paused == other.paused &&
+            self.paused_until == other.paused_until &&
+            self.every_weeks == other.every_weeks &&
+            self.anchor == other.anchor &&
+            self.notes == other.notes &&
+            self.time == other.time &&
+            self.weekday_times == other.weekday_times &&
+            self.completions == other.completions &&
+            self.backfill_days == other.backfill_days
+    }
+}
+
+/// A reference to either a `Todo` or a `Task`. Returned by operations such as `TdList::search` that
+/// go over both kinds of items.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemRef<'a> {
+    /// A reference to a `Todo`.
+    Todo(&'a Todo),
+    /// A reference to a `Task`.
+    Task(&'a Task),
+}
+
+impl<'a> ItemRef<'a> {
+    /// Gets the `body` of the referenced item.
+    pub fn body(&self) -> &'a str {
+        match self {
+            ItemRef::Todo(todo) => todo.body(),
+            ItemRef::Task(task) => task.body(),
+        }
+    }
+
+    /// Gets the `id` of the referenced item.
+    pub fn id(&self) -> u64 {
+        match self {
+            ItemRef::Todo(todo) => todo.id(),
+            ItemRef::Task(task) => task.id(),
+        }
+    }
+}
+
+/// A difference between two `TdList`s. Returned by `TdList::diff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemChange<'a> {
+    /// The item exists only in the newer list.
+    Added(ItemRef<'a>),
+    /// The item exists only in the older list.
+    Removed(ItemRef<'a>),
+    /// The item exists in both lists but its values differ.
+    Modified {
+        /// The item in the older list.
+        old: ItemRef<'a>,
+        /// The item in the newer list.
+        new: ItemRef<'a>,
+    },
+}
+
+/// A local change of a client `TdList` that hasn't been synchronized yet. Returned by
+/// `TdList::pending_changes`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PendingChange<'a> {
+    /// The item was added after the last sync.
+    New(ItemRef<'a>),
+    /// The item was modified after the last sync.
+    Edited(ItemRef<'a>),
+    /// The item was removed after the last sync.
+    Removed(ItemRef<'a>),
+}
+
+/// How a sync resolves an item that was changed both locally and on the server since the last sync.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The version changed last is kept. The server's version is kept if neither has a time.
+    #[default]
+    LastWriterWins,
+    /// The server's version is kept.
+    ServerWins,
+    /// The local version is kept.
+    ClientWins,
+    /// The server's version is kept and the local version is added as a new item, leaving the
+    /// conflict for the user to resolve.
+    KeepBoth,
+}
+
+/// Which version of an item a sync kept when resolving a `Conflict`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The local version replaced the server's version.
+    KeptLocal,
+    /// The server's version replaced the local version.
+    KeptRemote,
+    /// Both versions were kept as separate items.
+    KeptBoth,
+}
+
+/// An item that was changed both locally and on the server since the last sync. Returned by
+/// `TdList::sync_with_policy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The uuid of the item.
+    pub uuid: Uuid,
+    /// The body of the local version.
+    pub local: String,
+    /// The body of the server's version.
+    pub remote: String,
+    /// How the conflict was resolved.
+    pub resolution: ConflictResolution,
+}
+
+impl Conflict {
+    /// Returns `false` if both versions were kept and the user should merge them.
+    pub fn is_resolved(&self) -> bool {
+        self.resolution != ConflictResolution::KeptBoth
+    }
+}
+
+/// Returns `true` if all characters of the query appear in the text in the same order. Both should
+/// already be lowercase.
+fn fuzzy_matches(text: &str, query: &str) -> bool {
+    let mut text_chars = text.chars();
+    query.chars().all(|q| text_chars.any(|t| t == q))
+}
 
 #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 enum ItemState {
@@ -528,12 +1375,55 @@ enum ItemState {
     Changed,
 }
 
+/// The synchronization state of an item. Returned by `Todo::sync_state` and `Task::sync_state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    /// The item hasn't changed since the last sync.
+    Synced,
+    /// The item was added after the last sync.
+    New,
+    /// The item was modified after the last sync.
+    Edited,
+    /// The item was removed after the last sync.
+    Removed,
+}
+
+impl From<ItemState> for SyncState {
+    fn from(state: ItemState) -> Self {
+        match state {
+            ItemState::New => SyncState::New,
+            ItemState::Removed => SyncState::Removed,
+            ItemState::Unchanged => SyncState::Synced,
+            ItemState::Changed => SyncState::Edited,
+        }
+    }
+}
+
 trait SyncItem {
     fn set_state(&mut self, state: ItemState);
     fn state(&self) -> ItemState;
+    fn id(&self) -> u64;
     fn set_id(&mut self, id: u64);
     fn sync_id(&self) -> u64;
+    fn uuid(&self) -> Uuid;
+    fn body(&self) -> &str;
     fn update_old(&self, old: &mut Self);
+
+    // Items that are never edited don't have versions and never conflict.
+    fn version(&self) -> u64 {
+        0
+    }
+    fn set_version(&mut self, _version: u64) {}
+    fn modified(&self) -> Option<NaiveDateTime> {
+        None
+    }
+    // Day plans are always sent whole, so they don't need revisions.
+    fn revision(&self) -> u64 {
+        0
+    }
+    fn set_revision(&mut self, _revision: u64) {}
+    // Gives the item a new uuid so that it can be added as a copy of itself.
+    fn reidentify(&mut self) {}
 }
 
 impl SyncItem for Todo {
@@ -545,6 +1435,10 @@ impl SyncItem for Todo {
         self.state
     }
 
+    fn id(&self) -> u64 {
+        self.id
+    }
+
     fn set_id(&mut self, id: u64) {
         self.id = id;
     }
@@ -552,10 +1446,53 @@ impl SyncItem for Todo {
         self.sync_id
     }
 
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn body(&self) -> &str {
+        &self.body
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    fn modified(&self) -> Option<NaiveDateTime> {
+        self.modified
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    fn reidentify(&mut self) {
+        self.uuid = Uuid::new_v4();
+        self.sync_id = random();
+    }
+
     fn update_old(&self, old: &mut Self) {
         old.body = self.body.clone();
-        old.date = self.date.clone();
-        old.done = self.done.clone();
+        old.date = self.date;
+        old.done = self.done;
+        old.uuid = self.uuid;
+        old.notes = self.notes.clone();
+        old.time = self.time;
+        old.priority = self.priority;
+        old.created = self.created;
+        old.position = self.position;
+        old.blocked_by = self.blocked_by.clone();
+        old.postponed = self.postponed;
+        old.version = self.version;
+        old.modified = self.modified;
     }
 }
 
@@ -568,6 +1505,10 @@ impl SyncItem for Task {
         self.state
     }
 
+    fn id(&self) -> u64 {
+        self.id
+    }
+
     fn set_id(&mut self, id: u64) {
         self.id = id;
     }
@@ -575,17 +1516,77 @@ impl SyncItem for Task {
         self.sync_id
     }
 
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn body(&self) -> &str {
+        &self.body
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    fn modified(&self) -> Option<NaiveDateTime> {
+        self.modified
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    fn reidentify(&mut self) {
+        self.uuid = Uuid::new_v4();
+        self.sync_id = random();
+    }
+
     fn update_old(&self, old: &mut Self) {
         old.body = self.body.clone();
         old.weekdays = self.weekdays.clone();
         old.done_map = self.done_map.clone();
+        old.uuid = self.uuid;
+        old.until = self.until;
+        old.paused = self.paused;
+        old.paused_until = self.paused_until;
+        old.every_weeks = self.every_weeks;
+        old.anchor = self.anchor;
+        old.notes = self.notes.clone();
+        old.time = self.time;
+        old.weekday_times = self.weekday_times.clone();
+        old.completions = self.completions.clone();
+        old.created = self.created;
+        old.backfill_days = self.backfill_days;
+        old.version = self.version;
+        old.modified = self.modified;
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct SyncList<T: SyncItem + Clone> {
     items: Vec<T>,
     server: bool,
+    // The id given to the next added item. Ids are never reused.
+    #[serde(default)]
+    next_id: u64,
+}
+
+impl<T: SyncItem + Clone> Default for SyncList<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            server: false,
+            next_id: 0,
+        }
+    }
 }
 
 impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
@@ -593,38 +1594,36 @@ impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
         Self {
             items: Vec::new(),
             server,
+            next_id: 0,
         }
     }
-    fn add(&mut self, mut item: T) {
+    fn add(&mut self, mut item: T) -> u64 {
+        let id = self.allocate_id();
+        item.set_id(id);
         item.set_state(ItemState::New);
         self.items.push(item);
+        id
+    }
+    fn allocate_id(&mut self) -> u64 {
+        // Lists saved before the counter existed start after their largest id.
+        let id = self.items.iter()
+            .map(|item| item.id() + 1)
+            .fold(self.next_id, u64::max);
+        self.next_id = id + 1;
+        id
     }
     fn mark_removed(&mut self, id: u64) -> Result<()> {
-        if id >= self.items.len() as u64 {
-            return Err(Error::Unknown);
-        }
-        let item = self.items[id as usize].borrow_mut();
-
-        // Do not allow the removal of items already removed.
-        if item.state() == ItemState::Removed {
-            return Err(Error::Unknown);
-        }
-
+        // Items already removed cannot be removed again.
+        let item = self.get_item_mut(id).ok_or(Error::Unknown)?;
         item.set_state(ItemState::Removed);
 
         // Servers remove the items immediately.
         if self.server {
             self.items.retain(|item| item.state() != ItemState::Removed);
-            self.map_indices_to_ids();
         }
 
         Ok(())
     }
-    fn map_indices_to_ids(&mut self) {
-        for (new_id, item) in self.items.iter_mut().enumerate() {
-            item.set_id(new_id as u64);
-        }
-    }
     fn items(&self) -> Vec<&T> {
         let mut items = Vec::new();
         for item in &self.items {
@@ -635,17 +1634,38 @@ impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
 
         items
     }
+    fn removed_items(&self) -> Vec<&T> {
+        self.items.iter().filter(|item| item.state() == ItemState::Removed).collect()
+    }
+    fn restore(&mut self, id: u64) -> Result<()> {
+        match self.items.iter_mut().find(|item| item.id() == id) {
+            // The item may or may not exist on the server, marking it changed handles both cases.
+            Some(item) if item.state() == ItemState::Removed => {
+                item.set_state(ItemState::Changed);
+                Ok(())
+            }
+            _ => Err(Error::Unknown),
+        }
+    }
+    fn get_item(&self, id: u64) -> Option<&T> {
+        self.items.iter().find(|i| i.id() == id && i.state() != ItemState::Removed)
+    }
     fn get_item_mut(&mut self, id: u64) -> Option<&mut T> {
-        self.items.get_mut(id as usize)
+        self.items.iter_mut().find(|i| i.id() == id && i.state() != ItemState::Removed)
+    }
+    fn get_item_by_uuid(&self, uuid: Uuid) -> Option<&T> {
+        self.items.iter().find(|i| i.uuid() == uuid && i.state() != ItemState::Removed)
+    }
+    fn get_item_by_uuid_mut(&mut self, uuid: Uuid) -> Option<&mut T> {
+        self.items.iter_mut().find(|i| i.uuid() == uuid && i.state() != ItemState::Removed)
     }
     fn sync_self(&mut self) {
         self.items.retain(|item| item.state() != ItemState::Removed);
-        self.map_indices_to_ids();
         for item in self.items.iter_mut() {
             item.set_state(ItemState::Unchanged);
         }
     }
-    fn sync(&mut self, other: &mut Self) {
+    fn sync(&mut self, other: &mut Self, policy: ConflictPolicy) -> Vec<Conflict> {
         if self.server && other.server {
             panic!("Both self and other are servers.");
         } else if !self.server && !other.server {
@@ -662,11 +1682,10 @@ impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
             client_list = self;
         }
 
+        let mut conflicts = Vec::new();
+        let mut copies = Vec::new();
         for item in client_list.items.iter_mut() {
             match item.state() {
-                ItemState::New => {
-                    server_list.add(item.clone());
-                }
                 ItemState::Removed => {
                     if let Some(s_item) = server_list.get_item_by_sync_id(item.sync_id()) {
                         s_item.set_state(ItemState::Removed);
@@ -674,8 +1693,9 @@ impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
                 }
                 ItemState::Unchanged => {
                     if let Some(s_item) = server_list.get_item_by_sync_id(item.sync_id()) {
-                        // If this is false then the item has been modified on the server.
-                        if s_item != item {
+                        // If this is false then the item has been modified on the server. Differing
+                        // uuids are only possible with items saved before uuids existed.
+                        if s_item != item || s_item.uuid() != item.uuid() || s_item.version() != item.version() {
                             // Update the client item to match the server item.
                             s_item.update_old(item);
                         }
@@ -683,58 +1703,197 @@ impl<T: SyncItem + Clone + PartialEq> SyncList<T> {
                         item.set_state(ItemState::Removed);
                     }
                 }
-                ItemState::Changed => {
+                // A new item can already exist on the server if an earlier sync was interrupted
+                // after the server saved it. Updating it instead of adding it again makes retrying
+                // the sync safe.
+                ItemState::New | ItemState::Changed => {
                     if let Some(s_item) = server_list.get_item_by_sync_id(item.sync_id()) {
+                        // The server's item has changed since this client last synced it. An equal
+                        // item is this client's own change saved by an interrupted sync.
+                        if item.version() < s_item.version() && s_item != item {
+                            let resolution = match policy {
+                                ConflictPolicy::LastWriterWins if item.modified() > s_item.modified() => ConflictResolution::KeptLocal,
+                                ConflictPolicy::LastWriterWins | ConflictPolicy::ServerWins => ConflictResolution::KeptRemote,
+                                ConflictPolicy::ClientWins => ConflictResolution::KeptLocal,
+                                ConflictPolicy::KeepBoth => ConflictResolution::KeptBoth,
+                            };
+                            conflicts.push(Conflict {
+                                uuid: item.uuid(),
+                                local: item.body().to_string(),
+                                remote: s_item.body().to_string(),
+                                resolution,
+                            });
+                            if resolution == ConflictResolution::KeptBoth {
+                                let mut copy = item.clone();
+                                copy.reidentify();
+                                copies.push(copy);
+                            }
+                            if resolution != ConflictResolution::KeptLocal {
+                                s_item.update_old(item);
+                                continue;
+                            }
+                        }
+                        item.set_version(s_item.version() + 1);
                         item.update_old(s_item);
                     } else {
-                        // The modified item doesn't exist on the server therefore it needs to be
-                        // added.
+                        // The item doesn't exist on the server therefore it needs to be added.
                         server_list.add(item.clone());
                     }
                 }
             }
         }
 
+        for copy in copies {
+            server_list.add(copy);
+        }
+
         for item in server_list.items.iter() {
-            if item.state() != ItemState::Removed {
-                if client_list.get_item_by_sync_id(item.sync_id()).is_none() {
-                    client_list.add(item.clone());
-                }
+            if item.state() != ItemState::Removed && client_list.get_item_by_sync_id(item.sync_id()).is_none() {
+                client_list.add(item.clone());
             }
         }
 
         client_list.sync_self();
         server_list.sync_self();
+        conflicts
     }
 
     fn get_item_by_sync_id(&mut self, sync_id: u64) -> Option<&mut T> {
-        self.items.iter_mut().filter(|i| i.sync_id() == sync_id).next()
+        self.items.iter_mut().find(|i| i.sync_id() == sync_id)
+    }
+
+    // Returns the sync ids of the items that aren't removed in their order.
+    fn sync_ids(&self) -> Vec<u64> {
+        self.items.iter().filter(|i| i.state() != ItemState::Removed).map(|i| i.sync_id()).collect()
+    }
+
+    // Returns the items changed after a revision, or all items without one.
+    fn changed_since(&self, revision: Option<u64>) -> Vec<T> {
+        self.items.iter()
+            .filter(|i| i.state() != ItemState::Removed && revision.is_none_or(|revision| i.revision() > revision))
+            .cloned()
+            .collect()
+    }
+
+    // Returns the items that were added or changed compared to an earlier copy of the list.
+    fn changed_from(&self, before: &Self) -> Vec<T> {
+        let before: HashMap<u64, &T> = before.items.iter().map(|i| (i.sync_id(), i)).collect();
+        self.items.iter()
+            .filter(|i| i.state() != ItemState::Removed && !before.get(&i.sync_id()).is_some_and(|b| is_same_version(*b, i)))
+            .cloned()
+            .collect()
+    }
+
+    // Builds a list of the items with the given sync ids in their order. Changed items replace the
+    // items of this list, and items keep the ids they have in this list. Returns `None` if an item
+    // is in neither.
+    fn rebuild(&self, changed: Vec<T>, sync_ids: &[u64], server: bool) -> Option<Self> {
+        let mut changed: HashMap<u64, T> = changed.into_iter().map(|i| (i.sync_id(), i)).collect();
+        let next_id = self.items.iter().map(|i| i.id() + 1).fold(self.next_id, u64::max);
+        let mut list = SyncList { items: Vec::new(), server, next_id };
+
+        for sync_id in sync_ids {
+            let base = self.items.iter().find(|i| i.sync_id() == *sync_id);
+            let mut item = match (changed.remove(sync_id), base) {
+                (Some(mut item), Some(base)) => {
+                    item.set_id(base.id());
+                    item
+                }
+                (Some(mut item), None) => {
+                    item.set_id(list.allocate_id());
+                    item
+                }
+                (None, Some(base)) => base.clone(),
+                (None, None) => return None,
+            };
+            item.set_state(ItemState::Unchanged);
+            list.items.push(item);
+        }
+        Some(list)
+    }
+
+    // Gives the items added or changed compared to an earlier copy of the list the revision and
+    // the rest of the items the revisions they had.
+    fn stamp(&mut self, before: &Self, revision: u64) {
+        let before: HashMap<u64, &T> = before.items.iter().map(|i| (i.sync_id(), i)).collect();
+        for item in self.items.iter_mut() {
+            match before.get(&item.sync_id()) {
+                Some(b) if is_same_version(*b, item) => item.set_revision(b.revision()),
+                _ => item.set_revision(revision),
+            }
+        }
+    }
+}
+
+// Returns `true` if two copies of an item have the same contents and version.
+fn is_same_version<T: SyncItem + PartialEq>(a: &T, b: &T) -> bool {
+    a == b && a.uuid() == b.uuid() && a.version() == b.version()
+}
+
+/// Identifies a state of a server's items. A client that syncs with deltas keeps the token of its
+/// last sync and only gets the items changed after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncToken {
+    /// A random number of the server's list that tells the tokens of different lists apart.
+    pub epoch: u64,
+    /// The number of syncs saved to the server's list.
+    pub revision: u64,
+}
+
+/// The items changed in a `TdList` and the sync ids of all of its items, which is enough to rebuild
+/// the list from an earlier copy of it. Sent instead of whole lists by delta syncs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListDelta {
+    // The token of the server's items. `None` in deltas sent by clients.
+    token: Option<SyncToken>,
+    todos: Vec<Todo>,
+    tasks: Vec<Task>,
+    // Day plans are few and always sent whole.
+    plans: SyncList<DayPlan>,
+    todo_ids: Vec<u64>,
+    task_ids: Vec<u64>,
+}
+
+impl ListDelta {
+    /// Returns the number of changed items in the delta.
+    pub fn item_count(&self) -> usize {
+        self.todos.len() + self.tasks.len() + self.plans.items.len()
     }
 }
 
 /// A synchronizable list used for containing and managing all `Todo`s and `Task`s. `Todo`s and
-/// `Task`s have `id`s that match their `id`s within the `TdList`.
-#[derive(Debug, Serialize, Deserialize)]
+/// `Task`s have `id`s that are unique within the `TdList`. Ids are given in increasing order and
+/// never reused, so removing or synchronizing items doesn't change the `id`s of other items.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TdList {
     todos: SyncList<Todo>,
     tasks: SyncList<Task>,
+    // Lists saved before day plans existed don't have them. Whether the plans are a server's is
+    // always taken from `server`.
+    #[serde(default)]
+    plans: SyncList<DayPlan>,
     server: bool,
+    // On a client the token of the last delta sync, on a server the token of its items.
+    #[serde(default)]
+    sync_token: Option<SyncToken>,
 }
 
 impl TdList {
     /// Creates a new empty client `TdList`.
     pub fn new_client() -> Self {
-        Self { todos: SyncList::new(false), tasks: SyncList::new(false), server: false }
+        Self { todos: SyncList::new(false), tasks: SyncList::new(false), plans: SyncList::new(false), server: false, sync_token: None }
     }
 
     /// Creates a new empty server `TdList`.
     pub fn new_server() -> Self {
-        Self { todos: SyncList::new(true), tasks: SyncList::new(true), server: true }
+        Self { todos: SyncList::new(true), tasks: SyncList::new(true), plans: SyncList::new(true), server: true, sync_token: None }
     }
 
     /// Creates a ´TdList` from a JSON string.
     pub fn new_from_json(json: &str) -> Result<Self> {
-        Ok(serde_json::from_str(json)?)
+        let mut list: TdList = serde_json::from_str(json)?;
+        list.plans.server = list.server;
+        Ok(list)
     }
 
     /// Creates a JSON string from the `TdList`.
@@ -742,122 +1901,1075 @@ impl TdList {
         Ok(serde_json::to_string(self)?)
     }
 
-    /// Gets all the `Todo`s in the list.
-    pub fn todos(&self) -> Vec<&Todo> {
-        self.todos.items()
+    /// Checks that the `TdList` has at most `max_items` items of any kind together and that no body
+    /// is longer than `max_body_len` characters. `None` means no limit. Returns an
+    /// `Error::LimitsExceeded` describing every offending item otherwise.
+    pub fn check_limits(&self, max_items: Option<usize>, max_body_len: Option<usize>) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let item_count = self.item_count();
+        if let Some(max_items) = max_items.filter(|max| item_count > *max) {
+            problems.push(format!("The list has {} items but at most {} are allowed.", item_count, max_items));
+        }
+
+        if let Some(max_body_len) = max_body_len {
+            let bodies = self.todos.items.iter().map(|todo| ("Todo", todo.uuid(), todo.body()))
+                .chain(self.tasks.items.iter().map(|task| ("Task", task.uuid(), task.body())))
+                .chain(self.plans.items.iter().map(|plan| ("Day plan", plan.uuid(), plan.body())));
+            for (kind, uuid, body) in bodies {
+                let len = body.chars().count();
+                if len > max_body_len {
+                    problems.push(format!("{} {} has a body of {} characters but at most {} are allowed.", kind, uuid, len, max_body_len));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::LimitsExceeded(problems))
+        }
     }
 
-    /// Gets all the `Task`s in the list.
-    pub fn tasks(&self) -> Vec<&Task> {
-        self.tasks.items()
+    /// Returns the number of items including removed items that haven't been synced yet.
+    pub(crate) fn item_count(&self) -> usize {
+        self.todos.items.len() + self.tasks.items.len() + self.plans.items.len()
     }
 
-    /// Returns `true` if the `TdList` is a server.
-    pub fn is_server(&self) -> bool {
-        self.server
+    /// Returns the token of the last delta sync of a client or the token of a server's items.
+    pub fn sync_token(&self) -> Option<SyncToken> {
+        self.sync_token
     }
 
-    /// Adds a `Todo` to the list and updates its id.
-    pub fn add_todo(&mut self, mut todo: Todo) {
-        todo.set_id(self.todos.items.len() as u64);
-        self.todos.add(todo);
+    pub(crate) fn set_sync_token(&mut self, token: Option<SyncToken>) {
+        self.sync_token = token;
     }
 
-    /// Adds a `Task` to the list and updates its id.
-    pub fn add_task(&mut self, mut task: Task) {
-        task.set_id(self.tasks.items.len() as u64);
-        self.tasks.add(task)
+    /// Returns the items of a server changed after a client's token. All items are returned if
+    /// the token is of another list or of a state the server doesn't know about, such as one newer
+    /// than a restored backup.
+    pub(crate) fn delta_since(&mut self, token: Option<SyncToken>) -> ListDelta {
+        let current = *self.sync_token.get_or_insert_with(|| SyncToken { epoch: random(), revision: 0 });
+        let since = token
+            .filter(|token| token.epoch == current.epoch && token.revision <= current.revision)
+            .map(|token| token.revision);
+
+        ListDelta {
+            token: Some(current),
+            todos: self.todos.changed_since(since),
+            tasks: self.tasks.changed_since(since),
+            plans: self.plans.clone(),
+            todo_ids: self.todos.sync_ids(),
+            task_ids: self.tasks.sync_ids(),
+        }
     }
 
-    /// Removes the `Todo` that matches the given id. If no `Todo` with the given `id` exists, returns
-    /// a `MtdError`.
-    pub fn remove_todo(&mut self, id: u64) -> Result<()> {
-        self.todos.mark_removed(id).map_err(|_| Error::NoTodoWithGivenId(id))
+    /// Returns the items of the list added or changed compared to an earlier copy of it.
+    pub(crate) fn delta_from(&self, before: &TdList) -> ListDelta {
+        ListDelta {
+            token: None,
+            todos: self.todos.changed_from(&before.todos),
+            tasks: self.tasks.changed_from(&before.tasks),
+            plans: self.plans.clone(),
+            todo_ids: self.todos.sync_ids(),
+            task_ids: self.tasks.sync_ids(),
+        }
     }
 
-    /// Removes the `Task` that matches the given id. If no `Task` with the given `id` exists, returns
-    /// a `MtdError`.
-    pub fn remove_task(&mut self, id: u64) -> Result<()> {
-        self.tasks.mark_removed(id).map_err(|_| Error::NoTaskWithGivenId(id))
+    /// Rebuilds a server's list from a delta, taking the items that didn't change from this list.
+    /// Returns `None` if this list doesn't have all of them.
+    pub(crate) fn apply_delta(&self, delta: ListDelta) -> Option<TdList> {
+        let mut plans = delta.plans;
+        plans.server = true;
+        Some(TdList {
+            todos: self.todos.rebuild(delta.todos, &delta.todo_ids, true)?,
+            tasks: self.tasks.rebuild(delta.tasks, &delta.task_ids, true)?,
+            plans,
+            server: true,
+            sync_token: delta.token,
+        })
     }
 
-    /// Returns a mutable reference to a `Todo` by its `id`. If no `Todo` with the given `id` exists
-    /// returns a `Error::NoTodoWithGivenId`.
-    pub fn get_todo_mut(&mut self, id: u64) -> Result<&mut Todo> {
-        self.todos.get_item_mut(id).ok_or(Error::NoTodoWithGivenId(id))
+    /// Replaces the todos and tasks of a server with the ones committed by a client. The items that
+    /// changed get a new revision so that delta syncs find them.
+    pub(crate) fn commit(&mut self, mut committed: TdList) {
+        let mut token = *self.sync_token.get_or_insert_with(|| SyncToken { epoch: random(), revision: 0 });
+        token.revision += 1;
+
+        committed.todos.stamp(&self.todos, token.revision);
+        committed.tasks.stamp(&self.tasks, token.revision);
+        self.todos = committed.todos;
+        self.tasks = committed.tasks;
+        self.sync_token = Some(token);
     }
 
-    /// Returns a mutable reference to a `Task` by its `id`. If no `Task` with the given `id` exists
-    /// returns a `Error::NoTodoWithGivenId`.
-    pub fn get_task_mut(&mut self, id: u64) -> Result<&mut Task> {
-        self.tasks.get_item_mut(id).ok_or(Error::NoTaskWithGivenId(id))
+    /// Returns how many hexadecimal digits from the start of their uuids tell all the `Todo`s and
+    /// `Task`s apart, but at least four. Removed items that haven't been synced yet count too so
+    /// that they can be restored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// let id = list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+    ///
+    /// let short_id = &list.get_todo(id).unwrap().uuid().simple().to_string()[..list.short_id_len()];
+    /// assert_eq!(short_id.len(), 4);
+    /// ```
+    pub fn short_id_len(&self) -> usize {
+        let mut uuids: Vec<String> = self.todos.items.iter().map(|todo| todo.uuid)
+            .chain(self.tasks.items.iter().map(|task| task.uuid))
+            .map(|uuid| uuid.simple().to_string())
+            .collect();
+        uuids.sort();
+
+        let longest_shared = uuids.windows(2)
+            .map(|pair| pair[0].bytes().zip(pair[1].bytes()).take_while(|(a, b)| a == b).count())
+            .max()
+            .unwrap_or(0);
+        (longest_shared + 1).clamp(MIN_SHORT_ID_LEN, 32)
     }
 
-    /// Returns all `Todo`s for a given date that are not yet done.
-    pub fn undone_todos_for_date(&self, date: NaiveDate) -> Vec<&Todo> {
-        self.undone_todos_for_date_wtd(date, Local::today().naive_local())
+    /// Gets all the `Todo`s in the list.
+    pub fn todos(&self) -> Vec<&Todo> {
+        self.todos.items()
     }
 
-    /// Returns all `Todo`s for a given date that are done.
-    pub fn done_todos_for_date(&self, date: NaiveDate) -> Vec<&Todo> {
-        self.done_todos_for_date_wtd(date, Local::today().naive_local())
+    /// Gets all the `Task`s in the list.
+    pub fn tasks(&self) -> Vec<&Task> {
+        self.tasks.items()
     }
 
-    fn undone_todos_for_date_wtd(&self, date: NaiveDate, today: NaiveDate) -> Vec<&Todo> {
-        let mut undone_todos = Vec::new();
+    /// Gets all the `Todo`s in the list in the given order regardless of their dates.
+    pub fn todos_sorted(&self, order: SortOrder) -> Vec<&Todo> {
+        let mut todos = self.todos.items();
+        TdList::sort_todos(&mut todos, order);
+        todos
+    }
 
-        for todo in self.todos.items() {
-            if todo.for_date_wtd(date, today) && !todo.done() {
-                undone_todos.push(todo);
-            }
+    /// Gets all the `Task`s in the list in the given order. `Task`s have no manual order or priority,
+    /// so with `SortOrder::Manual` and `SortOrder::Priority` they are in the order they were added.
+    pub fn tasks_sorted(&self, order: SortOrder) -> Vec<&Task> {
+        let mut tasks = self.tasks.items();
+        match order {
+            SortOrder::Manual | SortOrder::Priority => {}
+            SortOrder::Alphabetical => tasks.sort_by_cached_key(|task| task.body.to_lowercase()),
+            SortOrder::Created => tasks.sort_by_key(|task| task.created),
         }
-
-        undone_todos
+        tasks
     }
 
-    fn done_todos_for_date_wtd(&self, date: NaiveDate, today: NaiveDate) -> Vec<&Todo> {
-        let mut done_todos = Vec::new();
+    /// Returns `true` if the `TdList` is a server.
+    pub fn is_server(&self) -> bool {
+        self.server
+    }
 
-        for todo in self.todos.items() {
-            if todo.for_date_wtd(date, today) && todo.done() {
-                done_todos.push(todo);
-            }
-        }
+    /// Adds a `Todo` to the list and updates its id. Returns the `id` of the added `Todo`.
+    pub fn add_todo(&mut self, todo: Todo) -> u64 {
+        self.todos.add(todo)
+    }
 
-        done_todos
+    /// Adds a `Task` to the list and updates its id. Returns the `id` of the added `Task`.
+    pub fn add_task(&mut self, task: Task) -> u64 {
+        self.tasks.add(task)
     }
 
-    /// Returns all `Task`s for a given date that are not yet done.
-    pub fn undone_tasks_for_date(&self, date: NaiveDate) -> Vec<&Task> {
-        let mut undone_tasks = Vec::new();
+    /// Adds a `DayPlan` to the list and updates its id. Returns the `id` of the added `DayPlan`.
+    pub fn add_plan(&mut self, plan: DayPlan) -> u64 {
+        self.plans.add(plan)
+    }
 
-        for task in self.tasks.items() {
-            if task.for_date(date) && !task.done(date) {
-                undone_tasks.push(task);
+    /// Adds a received `SharedItem` to the list as a new `Todo` or `Task`. Returns the `id` of the
+    /// added item, which is a `Todo` id for shared todos and a `Task` id for shared tasks.
+    pub fn add_shared(&mut self, item: &SharedItem) -> Result<u64> {
+        match item {
+            SharedItem::Todo { body, date, time, notes } => {
+                let mut todo = Todo::new_specific_date(body.clone(), *date)?;
+                todo.set_time(*time);
+                todo.set_notes(notes.clone());
+                Ok(self.add_todo(todo))
+            }
+            SharedItem::Task { body, weekdays, time, weekday_times, notes } => {
+                let mut task = Task::new(body.clone(), weekdays.clone())?;
+                task.set_time(*time);
+                for (weekday, time) in weekday_times {
+                    task.set_weekday_time(*weekday, Some(*time));
+                }
+                task.set_notes(notes.clone());
+                Ok(self.add_task(task))
             }
         }
+    }
 
-        undone_tasks
+    /// Gets all the `DayPlan`s in the list.
+    pub fn plans(&self) -> Vec<&DayPlan> {
+        self.plans.items()
     }
 
-    /// Returns all `Task`s for a given date that are done.
-    pub fn done_tasks_for_date(&self, date: NaiveDate) -> Vec<&Task> {
-        let mut done_tasks = Vec::new();
+    /// Returns a reference to a `DayPlan` by its `id`. If no `DayPlan` with the given `id` exists
+    /// returns a `Error::NoPlanWithGivenId`.
+    pub fn get_plan(&self, id: u64) -> Result<&DayPlan> {
+        self.plans.get_item(id).ok_or(Error::NoPlanWithGivenId(id))
+    }
 
-        for task in self.tasks.items() {
-            if task.for_date(date) && task.done(date) {
-                done_tasks.push(task);
-            }
-        }
+    /// Removes the `DayPlan` that matches the given id. If no `DayPlan` with the given `id` exists,
+    /// returns a `Error::NoPlanWithGivenId`.
+    pub fn remove_plan(&mut self, id: u64) -> Result<()> {
+        self.plans.mark_removed(id).map_err(|_| Error::NoPlanWithGivenId(id))
+    }
 
-        done_tasks
+    /// Returns the `DayPlan`s for the weekday of the given date, leaving out those that already have a
+    /// `Todo` with the same body ignoring case on that date.
+    pub fn suggestions_for_date(&self, date: NaiveDate) -> Vec<&DayPlan> {
+        let planned: Vec<String> = self.todos.items().into_iter()
+            .filter(|todo| todo.date == date)
+            .map(|todo| todo.body.to_lowercase())
+            .collect();
+
+        self.plans.items().into_iter()
+            .filter(|plan| plan.weekday() == date.weekday() && !planned.contains(&plan.body().to_lowercase()))
+            .collect()
+    }
+
+    /// Adds a copy of the `Todo` with the given id for `date`, or for the date of the original if
+    /// `date` is `None`. The copy isn't done and gets a new uuid and creation time, but has the body,
+    /// notes, time, priority and dependencies of the original. Returns the id of the copy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// let id = list.add_todo(Todo::new_undated("Water the plants".to_string()).unwrap());
+    ///
+    /// let copy = list.duplicate_todo(id, Some(NaiveDate::from_ymd(2024, 8, 15))).unwrap();
+    ///
+    /// assert_eq!(list.get_todo(copy).unwrap().body(), "Water the plants");
+    /// assert_eq!(list.get_todo(copy).unwrap().date(), NaiveDate::from_ymd(2024, 8, 15));
+    /// ```
+    pub fn duplicate_todo(&mut self, id: u64, date: Option<NaiveDate>) -> Result<u64> {
+        let original = self.get_todo(id)?;
+        let mut copy = Todo::new_specific_date(original.body.clone(), date.unwrap_or(original.date))?;
+        copy.notes = original.notes.clone();
+        copy.time = original.time;
+        copy.priority = original.priority;
+        copy.blocked_by = original.blocked_by.clone();
+        Ok(self.add_todo(copy))
+    }
+
+    /// Adds a copy of the `Task` with the given id for `weekdays`, or for the weekdays of the
+    /// original if `weekdays` is `None`. The copy isn't done or paused and gets a new uuid, but has
+    /// the body, notes, times, end date and repetition of the original. Returns the id of the copy or
+    /// an `Error::NoWeekdays` if `weekdays` is empty.
+    pub fn duplicate_task(&mut self, id: u64, weekdays: Option<Vec<Weekday>>) -> Result<u64> {
+        let original = self.get_task(id)?;
+        let weekdays = weekdays.unwrap_or_else(|| original.weekdays.clone());
+        let mut copy = Task::new(original.body.clone(), weekdays)?;
+        copy.notes = original.notes.clone();
+        copy.time = original.time;
+        copy.weekday_times = original.weekday_times.iter()
+            .filter(|(day, _)| copy.weekdays.contains(day))
+            .map(|(day, time)| (*day, *time))
+            .collect();
+        copy.until = original.until;
+        copy.every_weeks = original.every_weeks;
+        copy.anchor = original.anchor;
+        Ok(self.add_task(copy))
+    }
+
+    /// Replaces the `Todo` with the given id with a `Task` for `weekdays`, or for the weekday of the
+    /// `Todo` if `weekdays` is empty. The `Task` gets the body, notes and time of the `Todo`. Returns
+    /// the id of the new `Task`.
+    ///
+    /// `Task`s have no priority or dependencies, so the priority of the `Todo` and the `Todo`s it
+    /// depends on are dropped. `Todo`s that depend on the converted `Todo` no longer do.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Weekday;
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// let id = list.add_todo(Todo::new_undated("Water the plants".to_string()).unwrap());
+    ///
+    /// let task = list.convert_todo_to_task(id, vec![Weekday::Mon, Weekday::Thu]).unwrap();
+    ///
+    /// assert!(list.todos().is_empty());
+    /// assert_eq!(list.get_task(task).unwrap().weekdays(), &vec![Weekday::Mon, Weekday::Thu]);
+    /// ```
+    pub fn convert_todo_to_task(&mut self, id: u64, weekdays: Vec<Weekday>) -> Result<u64> {
+        let todo = self.get_todo(id)?;
+        let weekdays = if weekdays.is_empty() { vec![todo.weekday()] } else { weekdays };
+        let mut task = Task::new(todo.body.clone(), weekdays)?;
+        task.notes = todo.notes.clone();
+        task.time = todo.time;
+
+        self.remove_todo(id)?;
+        Ok(self.add_task(task))
+    }
+
+    /// Replaces the `Task` with the given id with a `Todo` for `date`, or for the next date of the
+    /// `Task` from today if `date` is `None`. The `Todo` gets the body and notes of the `Task` and its
+    /// time on that date. Returns the id of the new `Todo` or an `Error::InvalidArgument` if no date
+    /// is given and the `Task` isn't for any upcoming date. The new `Todo` has the default priority
+    /// and no dependencies.
+    pub fn convert_task_to_todo(&mut self, id: u64, date: Option<NaiveDate>) -> Result<u64> {
+        let task = self.get_task(id)?;
+        let date = match date {
+            Some(date) => date,
+            None => task.next_date(current_date())
+                .ok_or_else(|| Error::InvalidArgument(format!("Task {} isn't for any upcoming date.", id)))?,
+        };
+        let mut todo = Todo::new_specific_date(task.body.clone(), date)?;
+        todo.notes = task.notes.clone();
+        todo.time = task.time_for(date.weekday());
+
+        self.remove_task(id)?;
+        Ok(self.add_todo(todo))
+    }
+
+    /// Removes the `Todo` that matches the given id. If no `Todo` with the given `id` exists, returns
+    /// a `MtdError`.
+    pub fn remove_todo(&mut self, id: u64) -> Result<()> {
+        self.todos.mark_removed(id).map_err(|_| Error::NoTodoWithGivenId(id))
+    }
+
+    /// Removes the `Task` that matches the given id. If no `Task` with the given `id` exists, returns
+    /// a `MtdError`.
+    pub fn remove_task(&mut self, id: u64) -> Result<()> {
+        self.tasks.mark_removed(id).map_err(|_| Error::NoTaskWithGivenId(id))
+    }
+
+    /// Returns all `Todo`s and `Task`s whose body contains the query ignoring case. `Todo`s are
+    /// returned before `Task`s.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Weekday;
+    /// use mtd::{ItemRef, Task, TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Buy Groceries".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Call mom".to_string()).unwrap());
+    /// list.add_task(Task::new("Groceries for the week".to_string(), vec![Weekday::Sat]).unwrap());
+    ///
+    /// let found = list.search("groceries");
+    ///
+    /// assert_eq!(found.len(), 2);
+    /// assert!(matches!(found[0], ItemRef::Todo(todo) if todo.body() == "Buy Groceries"));
+    /// assert!(matches!(found[1], ItemRef::Task(task) if task.body() == "Groceries for the week"));
+    /// ```
+    pub fn search(&self, query: &str) -> Vec<ItemRef<'_>> {
+        let query = query.to_lowercase();
+        self.search_with(|body| body.contains(&query))
+    }
+
+    /// Returns all `Todo`s and `Task`s whose body contains all characters of the query in the same
+    /// order ignoring case. For example "grcrs" matches "Buy groceries". `Todo`s are returned before
+    /// `Task`s.
+    pub fn search_fuzzy(&self, query: &str) -> Vec<ItemRef<'_>> {
+        let query = query.to_lowercase();
+        self.search_with(|body| fuzzy_matches(body, &query))
+    }
+
+    fn search_with<F: Fn(&str) -> bool>(&self, matches: F) -> Vec<ItemRef<'_>> {
+        let mut found = Vec::new();
+
+        for todo in self.todos() {
+            if matches(&todo.body().to_lowercase()) {
+                found.push(ItemRef::Todo(todo));
+            }
+        }
+        for task in self.tasks() {
+            if matches(&task.body().to_lowercase()) {
+                found.push(ItemRef::Task(task));
+            }
+        }
+
+        found
+    }
+
+    /// Returns the `Todo`s that have been removed but not yet synchronized. Only clients keep removed
+    /// items until the next sync.
+    pub fn removed_todos(&self) -> Vec<&Todo> {
+        self.todos.removed_items()
+    }
+
+    /// Returns the `Task`s that have been removed but not yet synchronized. Only clients keep removed
+    /// items until the next sync.
+    pub fn removed_tasks(&self) -> Vec<&Task> {
+        self.tasks.removed_items()
+    }
+
+    /// Restores a `Todo` that has been removed but not yet synchronized. If no such `Todo` with the
+    /// given `id` exists returns a `Error::NoTodoWithGivenId`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+    /// list.remove_todo(0).unwrap();
+    ///
+    /// assert_eq!(list.removed_todos().len(), 1);
+    ///
+    /// list.restore_todo(0).unwrap();
+    ///
+    /// assert!(list.removed_todos().is_empty());
+    /// assert_eq!(list.todos()[0].body(), "Todo");
+    /// ```
+    pub fn restore_todo(&mut self, id: u64) -> Result<()> {
+        self.todos.restore(id).map_err(|_| Error::NoTodoWithGivenId(id))
+    }
+
+    /// Restores a `Task` that has been removed but not yet synchronized. If no such `Task` with the
+    /// given `id` exists returns a `Error::NoTaskWithGivenId`.
+    pub fn restore_task(&mut self, id: u64) -> Result<()> {
+        self.tasks.restore(id).map_err(|_| Error::NoTaskWithGivenId(id))
+    }
+
+    /// Returns all changes that will be sent to the server on the next sync. Unlike the other
+    /// queries, the returned changes include removed items. Server lists never have pending changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{ItemRef, PendingChange, TdList, Todo};
+    ///
+    /// let mut client = TdList::new_client();
+    /// let mut server = TdList::new_server();
+    ///
+    /// client.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+    /// client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+    /// client.sync(&mut server);
+    ///
+    /// assert!(client.pending_changes().is_empty());
+    ///
+    /// client.get_todo_mut(0).unwrap().set_body("New Todo 0".to_string()).unwrap();
+    /// client.remove_todo(1).unwrap();
+    ///
+    /// let changes = client.pending_changes();
+    ///
+    /// assert_eq!(changes.len(), 2);
+    /// assert!(matches!(changes[0], PendingChange::Edited(ItemRef::Todo(todo)) if todo.body() == "New Todo 0"));
+    /// assert!(matches!(changes[1], PendingChange::Removed(ItemRef::Todo(todo)) if todo.body() == "Todo 1"));
+    /// ```
+    pub fn pending_changes(&self) -> Vec<PendingChange<'_>> {
+        if self.server {
+            return Vec::new();
+        }
+
+        let mut changes = TdList::pending_items(&self.todos.items, ItemRef::Todo);
+        changes.append(&mut TdList::pending_items(&self.tasks.items, ItemRef::Task));
+        changes
+    }
+
+    /// Returns `true` if the list has changes that haven't been synchronized yet. Server lists never
+    /// have unsynchronized changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{SyncState, TdList, Todo};
+    ///
+    /// let mut client = TdList::new_client();
+    /// let mut server = TdList::new_server();
+    ///
+    /// client.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+    ///
+    /// assert!(client.has_unsynced_changes());
+    /// assert_eq!(client.todos()[0].sync_state(), SyncState::New);
+    ///
+    /// client.sync(&mut server);
+    ///
+    /// assert!(!client.has_unsynced_changes());
+    /// assert_eq!(client.todos()[0].sync_state(), SyncState::Synced);
+    /// ```
+    pub fn has_unsynced_changes(&self) -> bool {
+        !self.pending_changes().is_empty()
+    }
+
+    fn pending_items<'a, T, F>(items: &'a [T], to_ref: F) -> Vec<PendingChange<'a>>
+        where T: SyncItem, F: Fn(&'a T) -> ItemRef<'a> {
+        items.iter()
+            .filter_map(|item| match item.state() {
+                ItemState::New => Some(PendingChange::New(to_ref(item))),
+                ItemState::Changed => Some(PendingChange::Edited(to_ref(item))),
+                ItemState::Removed => Some(PendingChange::Removed(to_ref(item))),
+                ItemState::Unchanged => None,
+            })
+            .collect()
+    }
+
+    /// Compares the list to a newer version of it and returns the added, removed and modified items.
+    /// Items are matched by their sync ids, which stay the same when a list is saved, loaded or
+    /// synchronized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{ItemChange, ItemRef, TdList, Todo};
+    ///
+    /// let mut old = TdList::new_client();
+    /// old.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+    /// old.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+    ///
+    /// let mut new = TdList::new_from_json(&old.to_json().unwrap()).unwrap();
+    /// new.remove_todo(0).unwrap();
+    /// new.get_todo_mut(1).unwrap().set_body("New Todo 1".to_string()).unwrap();
+    ///
+    /// let changes = old.diff(&new);
+    ///
+    /// assert_eq!(changes.len(), 2);
+    /// assert!(matches!(changes[0], ItemChange::Removed(ItemRef::Todo(todo)) if todo.body() == "Todo 0"));
+    /// assert!(matches!(changes[1], ItemChange::Modified { new: ItemRef::Todo(todo), .. } if todo.body() == "New Todo 1"));
+    /// ```
+    pub fn diff<'a>(&'a self, newer: &'a TdList) -> Vec<ItemChange<'a>> {
+        let mut changes = TdList::diff_items(&self.todos(), &newer.todos(), ItemRef::Todo);
+        changes.append(&mut TdList::diff_items(&self.tasks(), &newer.tasks(), ItemRef::Task));
+        changes
+    }
+
+    fn diff_items<'a, T, F>(old: &[&'a T], new: &[&'a T], to_ref: F) -> Vec<ItemChange<'a>>
+        where T: SyncItem + PartialEq, F: Fn(&'a T) -> ItemRef<'a> {
+        let mut changes = Vec::new();
+
+        for old_item in old {
+            match new.iter().find(|new_item| new_item.sync_id() == old_item.sync_id()) {
+                None => changes.push(ItemChange::Removed(to_ref(old_item))),
+                Some(new_item) if new_item != old_item => {
+                    changes.push(ItemChange::Modified { old: to_ref(old_item), new: to_ref(new_item) })
+                }
+                Some(_) => {}
+            }
+        }
+        for new_item in new {
+            if !old.iter().any(|old_item| old_item.sync_id() == new_item.sync_id()) {
+                changes.push(ItemChange::Added(to_ref(new_item)));
+            }
+        }
+
+        changes
+    }
+
+    /// Removes all `Todo`s that match the given ids. If any of the ids doesn't match a `Todo`, returns
+    /// an `Error::NoTodoWithGivenId` and nothing is removed. Duplicate ids are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
+    ///
+    /// assert!(list.remove_todos(&[0, 3]).is_err());
+    /// assert_eq!(list.todos().len(), 3);
+    ///
+    /// list.remove_todos(&[0, 2]).unwrap();
+    /// assert_eq!(list.todos().len(), 1);
+    /// assert_eq!(list.todos()[0].body(), "Todo 1");
+    /// ```
+    pub fn remove_todos(&mut self, ids: &[u64]) -> Result<()> {
+        self.transaction(|tx| {
+            for id in TdList::unique(ids) {
+                tx.remove_todo(id)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes all `Task`s that match the given ids. If any of the ids doesn't match a `Task`, returns
+    /// an `Error::NoTaskWithGivenId` and nothing is removed. Duplicate ids are ignored.
+    pub fn remove_tasks(&mut self, ids: &[u64]) -> Result<()> {
+        self.transaction(|tx| {
+            for id in TdList::unique(ids) {
+                tx.remove_task(id)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets the done state of all `Todo`s that match the given ids. If any of the ids doesn't match
+    /// a `Todo`, returns an `Error::NoTodoWithGivenId` and nothing is changed.
+    pub fn set_todos_done(&mut self, ids: &[u64], done: bool) -> Result<()> {
+        self.transaction(|tx| {
+            for id in ids {
+                tx.get_todo_mut(*id)?.set_done(done);
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets the done state of all `Task`s that match the given ids for the given date. If any of the
+    /// ids doesn't match a `Task`, returns an `Error::NoTaskWithGivenId` and nothing is changed.
+    pub fn set_tasks_done(&mut self, ids: &[u64], done: bool, date: NaiveDate) -> Result<()> {
+        self.transaction(|tx| {
+            for id in ids {
+                tx.get_task_mut(*id)?.set_done(done, date);
+            }
+            Ok(())
+        })
+    }
+
+    fn unique(ids: &[u64]) -> Vec<u64> {
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Returns a reference to a `Todo` by its `id`. If no `Todo` with the given `id` exists returns a
+    /// `Error::NoTodoWithGivenId`.
+    pub fn get_todo(&self, id: u64) -> Result<&Todo> {
+        self.todos.get_item(id).ok_or(Error::NoTodoWithGivenId(id))
+    }
+
+    /// Returns a reference to a `Task` by its `id`. If no `Task` with the given `id` exists returns a
+    /// `Error::NoTaskWithGivenId`.
+    pub fn get_task(&self, id: u64) -> Result<&Task> {
+        self.tasks.get_item(id).ok_or(Error::NoTaskWithGivenId(id))
+    }
+
+    /// Returns a mutable reference to a `Todo` by its `id`. If no `Todo` with the given `id` exists
+    /// returns a `Error::NoTodoWithGivenId`.
+    pub fn get_todo_mut(&mut self, id: u64) -> Result<&mut Todo> {
+        self.todos.get_item_mut(id).ok_or(Error::NoTodoWithGivenId(id))
+    }
+
+    /// Returns a mutable reference to a `Task` by its `id`. If no `Task` with the given `id` exists
+    /// returns a `Error::NoTodoWithGivenId`.
+    pub fn get_task_mut(&mut self, id: u64) -> Result<&mut Task> {
+        self.tasks.get_item_mut(id).ok_or(Error::NoTaskWithGivenId(id))
+    }
+
+    /// Applies a group of mutations to the list atomically. The closure is given a transaction view of
+    /// the list. If the closure returns an `Err`, none of the mutations are applied and the error is
+    /// returned. Otherwise all mutations are applied at once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+    ///
+    /// // Removing a nonexistent todo fails, so the first removal is not applied either.
+    /// let result = list.transaction(|tx| {
+    ///     tx.remove_todo(0)?;
+    ///     tx.remove_todo(1)
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// assert_eq!(list.todos().len(), 1);
+    ///
+    /// list.transaction(|tx| {
+    ///     tx.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+    ///     tx.remove_todo(0)
+    /// }).unwrap();
+    ///
+    /// assert_eq!(list.todos().len(), 1);
+    /// assert_eq!(list.todos()[0].body(), "Todo 1");
+    /// ```
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut TdList) -> Result<T> {
+        let mut tx = self.clone();
+        let result = f(&mut tx)?;
+        *self = tx;
+        Ok(result)
+    }
+
+    /// Returns a reference to a `Todo` by its `uuid`. If no `Todo` with the given `uuid` exists
+    /// returns a `Error::NoTodoWithGivenUuid`.
+    pub fn get_todo_by_uuid(&self, uuid: Uuid) -> Result<&Todo> {
+        self.todos.get_item_by_uuid(uuid).ok_or(Error::NoTodoWithGivenUuid(uuid))
+    }
+
+    /// Returns a reference to a `Task` by its `uuid`. If no `Task` with the given `uuid` exists
+    /// returns a `Error::NoTaskWithGivenUuid`.
+    pub fn get_task_by_uuid(&self, uuid: Uuid) -> Result<&Task> {
+        self.tasks.get_item_by_uuid(uuid).ok_or(Error::NoTaskWithGivenUuid(uuid))
+    }
+
+    /// Returns a mutable reference to a `Todo` by its `uuid`. If no `Todo` with the given `uuid`
+    /// exists returns a `Error::NoTodoWithGivenUuid`.
+    pub fn get_todo_by_uuid_mut(&mut self, uuid: Uuid) -> Result<&mut Todo> {
+        self.todos.get_item_by_uuid_mut(uuid).ok_or(Error::NoTodoWithGivenUuid(uuid))
+    }
+
+    /// Returns a mutable reference to a `Task` by its `uuid`. If no `Task` with the given `uuid`
+    /// exists returns a `Error::NoTaskWithGivenUuid`.
+    pub fn get_task_by_uuid_mut(&mut self, uuid: Uuid) -> Result<&mut Task> {
+        self.tasks.get_item_by_uuid_mut(uuid).ok_or(Error::NoTaskWithGivenUuid(uuid))
+    }
+
+    /// Makes the `Todo` with the id `id` depend on the `Todo` with the id `blocker`. Until the blocker
+    /// is done or removed, the `Todo` is blocked and not returned by the undone `Todo` queries.
+    /// Returns a `Error::DependencyCycle` if the blocker already depends on the `Todo` or if they
+    /// are the same `Todo`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{Error, TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Test".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Deploy".to_string()).unwrap());
+    ///
+    /// list.add_todo_dependency(1, 0).unwrap();
+    ///
+    /// let today = mtd::current_date();
+    /// assert_eq!(list.undone_todos_for_date(today).len(), 1);
+    /// assert!(matches!(list.add_todo_dependency(0, 1), Err(Error::DependencyCycle)));
+    ///
+    /// list.get_todo_mut(0).unwrap().set_done(true);
+    /// assert_eq!(list.undone_todos_for_date(today)[0].body(), "Deploy");
+    /// ```
+    pub fn add_todo_dependency(&mut self, id: u64, blocker: u64) -> Result<()> {
+        let uuid = self.get_todo(id)?.uuid;
+        let blocker_uuid = self.get_todo(blocker)?.uuid;
+
+        if self.todo_depends_on(blocker_uuid, uuid) {
+            return Err(Error::DependencyCycle);
+        }
+
+        let todo = self.get_todo_mut(id)?;
+        if todo.blocked_by.insert(blocker_uuid) {
+            todo.mark_changed();
+        }
+        Ok(())
+    }
+
+    /// Removes the dependency of the `Todo` with the id `id` on the `Todo` with the id `blocker`.
+    pub fn remove_todo_dependency(&mut self, id: u64, blocker: u64) -> Result<()> {
+        let blocker_uuid = self.get_todo(blocker)?.uuid;
+
+        let todo = self.get_todo_mut(id)?;
+        if todo.blocked_by.remove(&blocker_uuid) {
+            todo.mark_changed();
+        }
+        Ok(())
+    }
+
+    /// Returns the undone `Todo`s that block the `Todo` with the given `id`.
+    pub fn blocked_by(&self, id: u64) -> Result<Vec<&Todo>> {
+        let todo = self.get_todo(id)?;
+        Ok(self.blockers(todo).collect())
+    }
+
+    /// Returns the `Todo`s that depend on the `Todo` with the given `id`.
+    pub fn blocks(&self, id: u64) -> Result<Vec<&Todo>> {
+        let uuid = self.get_todo(id)?.uuid;
+        Ok(self.todos.items().into_iter().filter(|todo| todo.blocked_by.contains(&uuid)).collect())
+    }
+
+    fn blockers<'a>(&'a self, todo: &'a Todo) -> impl Iterator<Item=&'a Todo> {
+        todo.blocked_by.iter()
+            .filter_map(|uuid| self.todos.get_item_by_uuid(*uuid))
+            .filter(|blocker| !blocker.done())
+    }
+
+    fn is_blocked(&self, todo: &Todo) -> bool {
+        self.blockers(todo).next().is_some()
+    }
+
+    // Returns true if the todo with the uuid `from` depends directly or indirectly on `to`.
+    fn todo_depends_on(&self, from: Uuid, to: Uuid) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![from];
+
+        while let Some(uuid) = stack.pop() {
+            if uuid == to {
+                return true;
+            }
+            if !visited.insert(uuid) {
+                continue;
+            }
+            if let Some(todo) = self.todos.get_item_by_uuid(uuid) {
+                stack.extend(todo.blocked_by.iter().copied());
+            }
+        }
+
+        false
+    }
+
+    /// Returns all `Todo`s for a given date that are not yet done in the manual order.
+    pub fn undone_todos_for_date(&self, date: NaiveDate) -> Vec<&Todo> {
+        self.undone_todos_for_date_sorted(date, SortOrder::Manual)
+    }
+
+    /// Returns all `Todo`s for a given date that are done in the manual order.
+    pub fn done_todos_for_date(&self, date: NaiveDate) -> Vec<&Todo> {
+        self.done_todos_for_date_sorted(date, SortOrder::Manual)
+    }
+
+    /// Returns all `Todo`s for a given date that are not yet done in the given order. Blocked `Todo`s
+    /// are not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{SortOrder, TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("b".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("C".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("a".to_string()).unwrap());
+    ///
+    /// let today = mtd::current_date();
+    /// let bodies: Vec<&str> = list.undone_todos_for_date_sorted(today, SortOrder::Alphabetical)
+    ///     .iter()
+    ///     .map(|todo| todo.body())
+    ///     .collect();
+    ///
+    /// assert_eq!(bodies, vec!["a", "b", "C"]);
+    /// ```
+    pub fn undone_todos_for_date_sorted(&self, date: NaiveDate, order: SortOrder) -> Vec<&Todo> {
+        self.undone_todos_for_date_wtd_sorted(date, current_date(), order)
+    }
+
+    /// Returns all `Todo`s for a given date that are done in the given order.
+    pub fn done_todos_for_date_sorted(&self, date: NaiveDate, order: SortOrder) -> Vec<&Todo> {
+        self.done_todos_for_date_wtd_sorted(date, current_date(), order)
+    }
+
+    /// Returns the items for today grouped into done and undone `Todo`s and `Task`s, along with the
+    /// overdue `Todo`s. The `Todo`s are in the manual order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{SystemClock, TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+    ///
+    /// let view = list.today(&SystemClock);
+    ///
+    /// assert_eq!(view.undone_count(), 1);
+    /// assert!(view.overdue_todos.is_empty());
+    /// ```
+    pub fn today(&self, clock: &dyn Clock) -> DayView<'_> {
+        self.day_view(clock.today(), clock, SortOrder::Manual)
+    }
+
+    /// Returns the items for the given date grouped like `TdList::today` does, with the `Todo`s in
+    /// the given order. The `clock` decides which `Todo`s are overdue.
+    pub fn day_view(&self, date: NaiveDate, clock: &dyn Clock, order: SortOrder) -> DayView<'_> {
+        DayView::new(self, date, clock, order)
+    }
+
+    /// Returns the items of the 7 days starting from `start` grouped like `TdList::today` does. The
+    /// `Todo`s are in the manual order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{Clock, SystemClock, TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+    ///
+    /// let week = list.week(&SystemClock, SystemClock.today());
+    ///
+    /// assert_eq!(week.days.len(), 7);
+    /// assert_eq!(week.days[0].undone_count(), 1);
+    /// assert_eq!(week.undone_count(), 1);
+    /// ```
+    pub fn week(&self, clock: &dyn Clock, start: NaiveDate) -> WeekView<'_> {
+        self.week_view(start, clock, SortOrder::Manual)
+    }
+
+    /// Returns the items of the 7 days starting from `start` like `TdList::week` does, with the
+    /// `Todo`s in the given order.
+    pub fn week_view(&self, start: NaiveDate, clock: &dyn Clock, order: SortOrder) -> WeekView<'_> {
+        WeekView::new(self, start, clock, order)
+    }
+
+    pub(crate) fn undone_todos_for_date_wtd_sorted(&self, date: NaiveDate, today: NaiveDate, order: SortOrder) -> Vec<&Todo> {
+        let mut todos = self.undone_todos_for_date_wtd(date, today);
+        TdList::sort_todos(&mut todos, order);
+        todos
+    }
+
+    pub(crate) fn done_todos_for_date_wtd_sorted(&self, date: NaiveDate, today: NaiveDate, order: SortOrder) -> Vec<&Todo> {
+        let mut todos = self.done_todos_for_date_wtd(date, today);
+        TdList::sort_todos(&mut todos, order);
+        todos
+    }
+
+    fn sort_todos(todos: &mut [&Todo], order: SortOrder) {
+        // The sort is stable and the manual order is the tiebreaker for every other order.
+        todos.sort_by_key(|todo| (todo.position, todo.id));
+        match order {
+            SortOrder::Manual => {}
+            SortOrder::Alphabetical => todos.sort_by_cached_key(|todo| todo.body.to_lowercase()),
+            SortOrder::Created => todos.sort_by_key(|todo| todo.created),
+            SortOrder::Priority => todos.sort_by_key(|todo| Reverse(todo.priority)),
+        }
+    }
+
+    /// Computes completion statistics for the dates in the given range. Only dates up to today are
+    /// counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Datelike, Duration};
+    /// use mtd::{Task, TdList};
+    ///
+    /// let today = mtd::current_date();
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_task(Task::new("Task".to_string(), vec![today.weekday()]).unwrap());
+    /// list.get_task_mut(0).unwrap().set_done(true, today);
+    ///
+    /// let stats = list.statistics(today - Duration::weeks(4)..=today);
+    ///
+    /// assert_eq!(stats.tasks[0].completed, 1);
+    /// assert_eq!(stats.tasks[0].current_streak, 1);
+    /// ```
+    pub fn statistics(&self, range: RangeInclusive<NaiveDate>) -> Statistics {
+        Statistics::compute_wtd(self, range, current_date())
+    }
+
+    /// Lists the undone `Todo`s by how often they have been postponed, how long they are overdue and
+    /// how old they are, and the `Task`s by how many occurrences in a row have been missed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Clean the garage".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Call mom".to_string()).unwrap());
+    /// list.get_todo_mut(0).unwrap().postpone(1).unwrap();
+    ///
+    /// let report = list.stale_report();
+    ///
+    /// assert_eq!(report.todos[0].body, "Clean the garage");
+    /// assert_eq!(report.todos[0].postponed, 1);
+    /// ```
+    pub fn stale_report(&self) -> StaleReport {
+        StaleReport::compute_wtd(self, current_date())
+    }
+
+    /// Moves a `Todo` to the given position in the manual order. Positions start from 0 and a
+    /// position past the end moves the `Todo` last. The order is synchronized like other changes.
+    /// If no `Todo` with the given `id` exists, returns an `Error::NoTodoWithGivenId`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{TdList, Todo};
+    ///
+    /// let mut list = TdList::new_client();
+    /// list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+    /// list.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
+    ///
+    /// list.move_todo(2, 0).unwrap();
+    ///
+    /// let today = mtd::current_date();
+    /// let bodies: Vec<&str> = list.undone_todos_for_date(today).iter().map(|todo| todo.body()).collect();
+    ///
+    /// assert_eq!(bodies, vec!["Todo 2", "Todo 0", "Todo 1"]);
+    /// ```
+    pub fn move_todo(&mut self, id: u64, new_position: usize) -> Result<()> {
+        self.get_todo(id)?;
+
+        let mut todos = self.todos.items();
+        todos.sort_by_key(|todo| (todo.position, todo.id));
+        let mut order: Vec<u64> = todos.iter().map(|todo| todo.id).collect();
+
+        order.retain(|other| *other != id);
+        order.insert(new_position.min(order.len()), id);
+
+        for (position, id) in order.into_iter().enumerate() {
+            let todo = self.get_todo_mut(id)?;
+            if todo.position != position as u64 {
+                todo.position = position as u64;
+                todo.mark_changed();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn undone_todos_for_date_wtd(&self, date: NaiveDate, today: NaiveDate) -> Vec<&Todo> {
+        let mut undone_todos = Vec::new();
+
+        for todo in self.todos.items() {
+            if todo.for_date_wtd(date, today) && !todo.done() && !self.is_blocked(todo) {
+                undone_todos.push(todo);
+            }
+        }
+
+        undone_todos
+    }
+
+    fn done_todos_for_date_wtd(&self, date: NaiveDate, today: NaiveDate) -> Vec<&Todo> {
+        let mut done_todos = Vec::new();
+
+        for todo in self.todos.items() {
+            if todo.for_date_wtd(date, today) && todo.done() {
+                done_todos.push(todo);
+            }
+        }
+
+        done_todos
+    }
+
+    /// Returns all `Task`s for a given date that are not yet done. Paused `Task`s are not included.
+    pub fn undone_tasks_for_date(&self, date: NaiveDate) -> Vec<&Task> {
+        let mut undone_tasks = Vec::new();
+
+        for task in self.tasks.items() {
+            if task.for_date(date) && !task.paused(date) && !task.done(date) {
+                undone_tasks.push(task);
+            }
+        }
+
+        undone_tasks
+    }
+
+    /// Returns all `Task`s that were missed on an earlier date and are still shown on the given date
+    /// because of their `backfill_days`. See `Task::missed_date`.
+    pub fn missed_tasks(&self, today: NaiveDate) -> Vec<&Task> {
+        self.tasks.items().into_iter().filter(|task| task.missed_date(today).is_some()).collect()
+    }
+
+    /// Returns all `Task`s for a given date that are done. Paused `Task`s are not included.
+    pub fn done_tasks_for_date(&self, date: NaiveDate) -> Vec<&Task> {
+        let mut done_tasks = Vec::new();
+
+        for task in self.tasks.items() {
+            if task.for_date(date) && !task.paused(date) && task.done(date) {
+                done_tasks.push(task);
+            }
+        }
+
+        done_tasks
     }
 
     /// Removes all `Todo`s that are done and at least a day has passed since their completion.
     /// Basically remove all `Todo`s which `Todo.can_remove()` returns `true`. This is called
     /// automatically every sync.
     pub fn remove_old_todos(&mut self) {
-        self.remove_old_todos_wtd(Local::today().naive_local());
+        self.remove_old_todos_wtd(current_date());
     }
 
     fn remove_old_todos_wtd(&mut self, today: NaiveDate) {
@@ -871,18 +2983,39 @@ impl TdList {
         }
     }
 
-    /// Synchronizes the list with itself actually removing items. Synchronizing may change the `id`s
-    /// of both `Todo`s and `Task`s. Additionally removes old `Todo`s.
+    /// Removes all `Task`s whose end date has passed. Basically remove all `Task`s which
+    /// `Task.can_remove()` returns `true`. This is called automatically every sync.
+    pub fn remove_expired_tasks(&mut self) {
+        self.remove_expired_tasks_wtd(current_date());
+    }
+
+    fn remove_expired_tasks_wtd(&mut self, today: NaiveDate) {
+        for task in &mut self.tasks.items {
+            if task.can_remove_wtd(today) {
+                task.state = ItemState::Removed;
+            }
+        }
+        if self.server {
+            self.tasks.items.retain(|task| task.state != ItemState::Removed);
+        }
+    }
+
+    /// Synchronizes the list with itself actually removing items. Additionally removes old `Todo`s and
+    /// expired `Task`s.
     pub fn self_sync(&mut self) {
         self.remove_old_todos();
+        self.remove_expired_tasks();
         self.todos.sync_self();
         self.tasks.sync_self();
+        self.plans.sync_self();
     }
 
     // This method is only unit tested using Todos which is fine as long as the internal sync impl
     // of todos and tasks is the same because then these tests cover Tasks as well.
-    /// Synchronizes the list with another list actually removing items. Synchronizing may change the `id`s
-    /// of both `Todo`s and `Task`s. Additionally removes old `Todo`s.
+    /// Synchronizes the list with another list actually removing items. Items keep their `id`s, and
+    /// items copied from the other list get new `id`s in this list. Additionally removes old `Todo`s
+    /// and expired `Task`s. Synchronizing a client again against a server that already received its
+    /// changes doesn't duplicate any items, so an interrupted sync can simply be retried.
     ///
     /// # Example
     ///
@@ -892,19 +3025,19 @@ impl TdList {
     /// let mut client = TdList::new_client();
     /// let mut server = TdList::new_server();
     ///
-    /// client.add_todo(Todo::new_undated("Todo 1".to_string()));
+    /// client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
     ///
-    /// server.add_todo(Todo::new_undated("Todo 2".to_string()));
+    /// server.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
     ///
     /// // New todos are added to both the server and the client.
     /// client.sync(&mut server);
     ///
-    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 1".to_string())));
-    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
+    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 1".to_string()).unwrap()));
+    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
     /// assert_eq!(client.todos().len(), 2);
     ///
-    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 1".to_string())));
-    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
+    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 1".to_string()).unwrap()));
+    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
     /// assert_eq!(server.todos().len(), 2);
     ///
     /// client.remove_todo(0).unwrap();
@@ -912,37 +3045,56 @@ impl TdList {
     /// // The removed item gets removed from both the server and the client.
     /// client.sync(&mut server);
     ///
-    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
+    /// assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
     /// assert_eq!(client.todos().len(), 1);
     ///
-    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
+    /// assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
     /// assert_eq!(server.todos().len(), 1);
     ///
-    /// client.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string());
+    /// // Todo 2 was given the id 1 when it was copied to the client.
+    /// client.get_todo_mut(1).unwrap().set_body("New Todo 1".to_string()).unwrap();
     ///
     /// // Modifications are synchronized as well.
     /// client.sync(&mut server);
     ///
-    /// assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+    /// assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
     /// assert_eq!(client.todos().len(), 1);
     ///
-    /// assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+    /// assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
     /// assert_eq!(server.todos().len(), 1);
     /// ```
     pub fn sync(&mut self, other: &mut Self) {
+        self.sync_with_policy(other, ConflictPolicy::default());
+    }
+
+    /// Synchronizes the `TdList` like `TdList::sync`, resolving items changed both on the client
+    /// and on the server since the client's last sync with the given policy. Returns the conflicts.
+    ///
+    /// # Panics
+    ///
+    /// If both or neither of the lists are servers.
+    pub fn sync_with_policy(&mut self, other: &mut Self, policy: ConflictPolicy) -> Vec<Conflict> {
         self.remove_old_todos();
         other.remove_old_todos();
-
-        self.todos.sync(&mut other.todos);
-        self.tasks.sync(&mut other.tasks);
+        self.remove_expired_tasks();
+        other.remove_expired_tasks();
+
+        let mut conflicts = self.todos.sync(&mut other.todos, policy);
+        conflicts.extend(self.tasks.sync(&mut other.tasks, policy));
+        // Lists received from servers that don't know about day plans have a client's plans.
+        self.plans.server = self.server;
+        other.plans.server = other.server;
+        self.plans.sync(&mut other.plans, policy);
+        conflicts
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
-    use chrono::{NaiveDate, Weekday};
+    use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
 
-    use crate::{Task, TdList, Todo, weekday_to_date_wtd};
+    use crate::{ConflictPolicy, ConflictResolution, current_date, Error, ItemChange, ItemRef, MAX_BODY_LENGTH, PendingChange, SortOrder, Task, TdList, Todo, Uuid, weekday_to_date_wtd};
 
     // Unit test a private function to remove the need to pass today into the Todo constructor
     #[test]
@@ -962,22 +3114,59 @@ mod tests {
 
     #[test]
     fn todo_for_date_tests() {
-        let todo = Todo::new_specific_date("Friday".to_string(), NaiveDate::from_ymd(2022, 6, 10));
+        let todo = Todo::new_specific_date("Friday".to_string(), NaiveDate::from_ymd(2022, 6, 10)).unwrap();
 
         let today = NaiveDate::from_ymd(2022, 6, 10);
 
         // The following 4 asserts could each be their own unit test but I'm to lazy to do it so
         // instead I just added some comments explaining the tests
 
-        assert!(todo.for_date_wtd(today, today)); // Todo is for the given date on the same day
-        assert!(todo.for_date_wtd(today, today.pred())); // Todo is for the given date before the given date
-        assert!(todo.for_date_wtd(today.succ(), today.succ())); // Todo is for the following date one day after the given date
-        assert!(!todo.for_date_wtd(today.succ(), today)); // Todo is not for the following date because it is already for today
+        assert!(todo.for_date_wtd(today, today)); // Todo is for the given date on the same day
+        assert!(todo.for_date_wtd(today, today.pred())); // Todo is for the given date before the given date
+        assert!(todo.for_date_wtd(today.succ(), today.succ())); // Todo is for the following date one day after the given date
+        assert!(!todo.for_date_wtd(today.succ(), today)); // Todo is not for the following date because it is already for today
+    }
+
+    #[test]
+    fn todo_postpone_counts_from_today_when_overdue() {
+        let today = NaiveDate::from_ymd(2022, 6, 10);
+        let mut todo = Todo::new_specific_date("Todo".to_string(), today - Duration::days(3)).unwrap();
+
+        todo.postpone_wtd(1, today).unwrap();
+        assert_eq!(todo.date(), today.succ());
+
+        todo.postpone_wtd(7, today).unwrap();
+        assert_eq!(todo.date(), today + Duration::days(8));
+
+        todo.postpone_wtd(0, today).unwrap();
+        assert_eq!(todo.date(), today + Duration::days(8));
+        assert_eq!(todo.postponed(), 2);
+    }
+
+    #[test]
+    fn todo_postpone_fails_past_the_last_date() {
+        let today = NaiveDate::from_ymd(2022, 6, 10);
+        let mut todo = Todo::new_specific_date("Todo".to_string(), today).unwrap();
+
+        assert!(matches!(todo.postpone_wtd(4_000_000_000, today), Err(Error::InvalidArgument(_))));
+        assert_eq!(todo.date(), today);
+        assert_eq!(todo.postponed(), 0);
+    }
+
+    #[test]
+    fn todo_for_date_far_in_future_is_only_for_that_date() {
+        let todo = Todo::new_specific_date("Dentist".to_string(), NaiveDate::from_ymd(2022, 8, 18)).unwrap();
+
+        let today = NaiveDate::from_ymd(2022, 6, 10);
+
+        // 2022-6-16 is a Thursday like the todo's date.
+        assert!(!todo.for_date_wtd(NaiveDate::from_ymd(2022, 6, 16), today));
+        assert!(todo.for_date_wtd(NaiveDate::from_ymd(2022, 8, 18), today));
     }
 
     #[test]
     fn todo_can_remove_returns_true_only_after_one_day_from_completion() {
-        let mut todo = Todo::new_specific_date("Todo".to_string(), NaiveDate::from_ymd(2022, 4, 25));
+        let mut todo = Todo::new_specific_date("Todo".to_string(), NaiveDate::from_ymd(2022, 4, 25)).unwrap();
         todo.set_done_wtd(true, NaiveDate::from_ymd(2022, 4, 26));
 
         assert!(!todo.can_remove_wtd(NaiveDate::from_ymd(2022, 4, 26)));
@@ -986,42 +3175,164 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn task_new_panics_if_empty_weekday_vec() {
-        Task::new("Panic!".to_string(), vec![]);
+    fn task_new_fails_with_empty_weekday_vec() {
+        assert!(matches!(Task::new("Task".to_string(), vec![]), Err(Error::NoWeekdays)));
+    }
+
+    #[test]
+    fn item_bodies_are_validated() {
+        assert!(matches!(Task::new("".to_string(), vec![Weekday::Mon]), Err(Error::EmptyBody)));
+        assert!(matches!(Todo::new_undated("x".repeat(MAX_BODY_LENGTH + 1)), Err(Error::BodyTooLong)));
+        assert!(Todo::new_undated("ä".repeat(MAX_BODY_LENGTH)).is_ok());
+
+        let mut todo = Todo::new_undated("Todo".to_string()).unwrap();
+        assert!(matches!(todo.set_body("\t\n".to_string()), Err(Error::EmptyBody)));
+        assert_eq!(todo.body(), "Todo");
+    }
+
+    #[test]
+    fn td_list_duplicates_items_as_new_undone_items() {
+        let mut list = TdList::new_client();
+        let id = list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        list.get_todo_mut(id).unwrap().set_done(true);
+        list.get_todo_mut(id).unwrap().set_priority(3);
+
+        let copy = list.duplicate_todo(id, None).unwrap();
+        let (original, copy) = (list.get_todo(id).unwrap(), list.get_todo(copy).unwrap());
+        assert_ne!(original.uuid(), copy.uuid());
+        assert!(!copy.done());
+        assert_eq!(copy.priority(), 3);
+        assert_eq!(copy.date(), original.date());
+
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Fri]).unwrap();
+        task.set_weekday_time(Weekday::Mon, Some(NaiveTime::from_hms(9, 0, 0)));
+        task.set_weekday_time(Weekday::Fri, Some(NaiveTime::from_hms(17, 0, 0)));
+        let id = list.add_task(task);
+
+        let copy = list.duplicate_task(id, Some(vec![Weekday::Fri, Weekday::Sat])).unwrap();
+        let copy = list.get_task(copy).unwrap();
+        assert_eq!(copy.weekdays(), &vec![Weekday::Fri, Weekday::Sat]);
+        assert_eq!(copy.time_for(Weekday::Fri), Some(NaiveTime::from_hms(17, 0, 0)));
+        assert_eq!(copy.time_for(Weekday::Mon), None);
+        assert!(matches!(list.duplicate_task(id, Some(vec![])), Err(Error::NoWeekdays)));
+        assert!(matches!(list.duplicate_todo(7, None), Err(Error::NoTodoWithGivenId(7))));
+    }
+
+    #[test]
+    fn td_list_short_ids_grow_until_they_tell_items_apart() {
+        let mut list = TdList::new_client();
+        assert_eq!(list.short_id_len(), 4);
+
+        let mut todo = Todo::new_undated("Todo".to_string()).unwrap();
+        todo.uuid = Uuid::from_u128(0xabcdef12_0000_0000_0000_000000000000);
+        list.add_todo(todo);
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
+        task.uuid = Uuid::from_u128(0xabc00000_0000_0000_0000_000000000000);
+        list.add_task(task);
+        assert_eq!(list.short_id_len(), 4);
+
+        let mut todo = Todo::new_undated("Another todo".to_string()).unwrap();
+        todo.uuid = Uuid::from_u128(0xabcdef34_0000_0000_0000_000000000000);
+        list.add_todo(todo);
+        assert_eq!(list.short_id_len(), 7);
+    }
+
+    #[test]
+    fn td_list_converts_between_todos_and_tasks() {
+        let mut list = TdList::new_client();
+        let mut todo = Todo::new_specific_date("Stretch".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap();
+        todo.set_time(Some(NaiveTime::from_hms(7, 0, 0)));
+        todo.set_priority(3);
+        let id = list.add_todo(todo);
+        let dependent = list.add_todo(Todo::new_undated("Run".to_string()).unwrap());
+        list.add_todo_dependency(dependent, id).unwrap();
+
+        let task = list.convert_todo_to_task(id, vec![]).unwrap();
+        assert_eq!(list.todos().len(), 1);
+        assert!(list.blocked_by(dependent).unwrap().is_empty());
+        list.remove_todo(dependent).unwrap();
+        let converted = list.get_task(task).unwrap();
+        assert_eq!(converted.weekdays(), &vec![Weekday::Thu]);
+        assert_eq!(converted.time_for(Weekday::Thu), Some(NaiveTime::from_hms(7, 0, 0)));
+
+        let todo = list.convert_task_to_todo(task, Some(NaiveDate::from_ymd(2024, 9, 1))).unwrap();
+        assert!(list.tasks().is_empty());
+        assert_eq!(list.get_todo(todo).unwrap().date(), NaiveDate::from_ymd(2024, 9, 1));
+        assert_eq!(list.get_todo(todo).unwrap().body(), "Stretch");
+        assert_eq!(list.get_todo(todo).unwrap().priority(), 0);
+
+        assert!(matches!(list.convert_task_to_todo(task, None), Err(Error::NoTaskWithGivenId(_))));
+    }
+
+    #[test]
+    fn td_list_check_limits_lists_offending_items() {
+        let mut list = TdList::new_server();
+        list.add_todo(Todo::new_undated("Short".to_string()).unwrap());
+        list.add_task(Task::new("A longer task".to_string(), vec![Weekday::Mon]).unwrap());
+
+        assert!(list.check_limits(Some(2), Some(13)).is_ok());
+        assert!(list.check_limits(None, None).is_ok());
+        match list.check_limits(Some(1), Some(5)) {
+            Err(Error::LimitsExceeded(problems)) => {
+                assert_eq!(problems.len(), 2);
+                assert!(problems[1].starts_with("Task "));
+            }
+            _ => panic!("limits weren't enforced"),
+        }
     }
 
     #[test]
     fn task_remove_weekday_removes_all_duplicates() {
-        let mut task = Task::new("Test task".to_string(), vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Wed]);
+        let mut task = Task::new("Test task".to_string(), vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Wed]).unwrap();
 
-        task.remove_weekday(Weekday::Wed);
+        task.remove_weekday(Weekday::Wed).unwrap();
 
         assert!(task.weekdays().contains(&Weekday::Mon));
         assert!(task.weekdays().contains(&Weekday::Tue));
         assert!(!task.weekdays().contains(&Weekday::Wed));
     }
 
+    #[test]
+    fn task_refuses_to_be_left_without_weekdays() {
+        let mut task = Task::new("Test task".to_string(), vec![Weekday::Wed, Weekday::Wed]).unwrap();
+
+        assert!(matches!(task.set_weekdays(vec![]), Err(Error::NoWeekdays)));
+        assert!(matches!(task.remove_weekday(Weekday::Wed), Err(Error::NoWeekdays)));
+        assert_eq!(task.weekdays(), &vec![Weekday::Wed, Weekday::Wed]);
+    }
+
     #[test]
     fn tdlist_add_todo_updates_ids() {
         let mut list = TdList::new_client();
 
-        list.add_todo(Todo::new_undated("Todo 0".to_string()));
-        list.add_todo(Todo::new_undated("Todo 1".to_string()));
-        list.add_todo(Todo::new_undated("Todo 2".to_string()));
+        list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
 
         assert_eq!(list.todos()[0].id(), 0);
         assert_eq!(list.todos()[1].id(), 1);
         assert_eq!(list.todos()[2].id(), 2);
     }
 
+    #[test]
+    fn tdlist_add_returns_ids_of_added_items() {
+        let mut list = TdList::new_client();
+
+        assert_eq!(list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap()), 0);
+        assert_eq!(list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap()), 1);
+        assert_eq!(list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap()), 0);
+
+        assert_eq!(list.get_todo_mut(1).unwrap().body(), "Todo 1");
+        assert_eq!(list.get_task_mut(0).unwrap().body(), "Task 0");
+    }
+
     #[test]
     fn tdlist_removed_todos_not_visible() {
         let mut list = TdList::new_client();
 
-        list.add_todo(Todo::new_undated("Todo 0".to_string()));
-        list.add_todo(Todo::new_undated("Todo 1".to_string()));
-        list.add_todo(Todo::new_undated("Todo 2".to_string()));
+        list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
 
         list.remove_todo(1).unwrap();
 
@@ -1034,19 +3345,82 @@ mod tests {
     fn tdlist_remove_todo_returns_err_nonexistent_id() {
         let mut list = TdList::new_client();
 
-        list.add_todo(Todo::new_undated("Todo 0".to_string()));
-        list.add_todo(Todo::new_undated("Todo 1".to_string()));
+        list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
         assert!(list.remove_todo(2).is_err());
     }
 
+    #[test]
+    fn task_every_three_weeks_is_for_anchor_week_and_every_third_week() {
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Sun]).unwrap();
+        // The anchor is a Wednesday, so the whole week from 2022-6-13 to 2022-6-19 is active.
+        task.set_every_weeks(3, NaiveDate::from_ymd(2022, 6, 15)).unwrap();
+
+        assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 13)));
+        assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 19)));
+        assert!(!task.for_date(NaiveDate::from_ymd(2022, 6, 20)));
+        assert!(!task.for_date(NaiveDate::from_ymd(2022, 6, 27)));
+        assert!(task.for_date(NaiveDate::from_ymd(2022, 7, 4)));
+
+        // Weeks before the anchor follow the same cadence.
+        assert!(!task.for_date(NaiveDate::from_ymd(2022, 6, 6)));
+        assert!(task.for_date(NaiveDate::from_ymd(2022, 5, 23)));
+    }
+
+    #[test]
+    fn task_set_every_weeks_fails_if_zero() {
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
+
+        assert!(matches!(task.set_every_weeks(0, NaiveDate::from_ymd(2022, 6, 13)), Err(Error::InvalidArgument(_))));
+        assert_eq!(task.every_weeks(), 1);
+    }
+
+    #[test]
+    fn task_saved_with_zero_every_weeks_repeats_every_week() {
+        let mut json = serde_json::to_value(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap()).unwrap();
+        json["every_weeks"] = serde_json::json!(0);
+        json["anchor"] = serde_json::json!("2022-06-13");
+        let task: Task = serde_json::from_value(json).unwrap();
+
+        assert_eq!(task.every_weeks(), 1);
+        assert!(task.for_date(NaiveDate::from_ymd(2022, 6, 20)));
+    }
+
+    #[test]
+    fn task_can_remove_returns_true_only_after_end_date() {
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
+
+        assert!(!task.can_remove_wtd(NaiveDate::from_ymd(2100, 1, 1)));
+
+        task.set_until(Some(NaiveDate::from_ymd(2022, 6, 13)));
+
+        assert!(!task.can_remove_wtd(NaiveDate::from_ymd(2022, 6, 13)));
+        assert!(task.can_remove_wtd(NaiveDate::from_ymd(2022, 6, 14)));
+    }
+
+    #[test]
+    fn tdlist_remove_expired_tasks_removes_only_expired() {
+        let mut list = TdList::new_client();
+
+        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        list.get_task_mut(1).unwrap().set_until(Some(NaiveDate::from_ymd(2022, 6, 13)));
+
+        list.remove_expired_tasks_wtd(NaiveDate::from_ymd(2022, 6, 14));
+        list.tasks.sync_self();
+
+        assert_eq!(list.tasks().len(), 1);
+        assert_eq!(list.tasks()[0].body(), "Task 0");
+    }
+
     #[test]
     fn tdlist_add_task_updates_ids() {
         let mut list = TdList::new_client();
 
-        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]));
-        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]));
-        list.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]));
+        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]).unwrap());
 
         assert_eq!(list.tasks()[0].id(), 0);
         assert_eq!(list.tasks()[1].id(), 1);
@@ -1057,9 +3431,9 @@ mod tests {
     fn tdlist_removed_tasks_not_visible() {
         let mut list = TdList::new_client();
 
-        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]));
-        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]));
-        list.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]));
+        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]).unwrap());
 
         list.remove_task(1).unwrap();
 
@@ -1072,25 +3446,54 @@ mod tests {
     fn tdlist_remove_task_returns_err_with_nonexistent_id() {
         let mut list = TdList::new_client();
 
-        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]));
-        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]));
+        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
 
         assert!(list.remove_todo(2).is_err());
     }
 
+    #[test]
+    fn tdlist_transaction_applies_nothing_on_err() {
+        let mut list = tdlist_with_done_and_undone();
+
+        let result: Result<(), Error> = list.transaction(|tx| {
+            tx.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+            tx.get_task_mut(0)?.set_body("New body".to_string())?;
+            tx.remove_task(5)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(list.todos(), tdlist_with_done_and_undone().todos());
+        assert_eq!(list.tasks(), tdlist_with_done_and_undone().tasks());
+    }
+
+    #[test]
+    fn tdlist_transaction_applies_everything_on_ok() {
+        let mut list = tdlist_with_done_and_undone();
+
+        let id = list.transaction(|tx| {
+            tx.remove_task(1)?;
+            Ok(tx.add_todo(Todo::new_undated("Todo".to_string()).unwrap()))
+        }).unwrap();
+
+        assert_eq!(id, 4);
+        assert_eq!(list.todos().len(), 5);
+        assert_eq!(list.tasks().len(), 1);
+    }
+
     fn tdlist_with_done_and_undone() -> TdList {
         let mut list = TdList::new_client();
 
-        list.add_todo(Todo::new_specific_date("Undone 1".to_string(), NaiveDate::from_ymd(2021, 4, 1)));
-        list.add_todo(Todo::new_specific_date("Undone 2".to_string(), NaiveDate::from_ymd(2021, 3, 29)));
-        list.add_todo(Todo::new_specific_date("Done 1".to_string(), NaiveDate::from_ymd(2021, 4, 1)));
-        list.add_todo(Todo::new_specific_date("Done 2".to_string(), NaiveDate::from_ymd(2021, 3, 30)));
+        list.add_todo(Todo::new_specific_date("Undone 1".to_string(), NaiveDate::from_ymd(2021, 4, 1)).unwrap());
+        list.add_todo(Todo::new_specific_date("Undone 2".to_string(), NaiveDate::from_ymd(2021, 3, 29)).unwrap());
+        list.add_todo(Todo::new_specific_date("Done 1".to_string(), NaiveDate::from_ymd(2021, 4, 1)).unwrap());
+        list.add_todo(Todo::new_specific_date("Done 2".to_string(), NaiveDate::from_ymd(2021, 3, 30)).unwrap());
 
         list.get_todo_mut(2).unwrap().set_done_wtd(true, NaiveDate::from_ymd(2021, 4, 1));
         list.get_todo_mut(3).unwrap().set_done_wtd(true, NaiveDate::from_ymd(2021, 4, 1));
 
-        list.add_task(Task::new("Undone 1".to_string(), vec![Weekday::Thu]));
-        list.add_task(Task::new("Done 1".to_string(), vec![Weekday::Thu]));
+        list.add_task(Task::new("Undone 1".to_string(), vec![Weekday::Thu]).unwrap());
+        list.add_task(Task::new("Done 1".to_string(), vec![Weekday::Thu]).unwrap());
 
         list.get_task_mut(1).unwrap().set_done(true, NaiveDate::from_ymd(2021, 4, 1));
 
@@ -1145,6 +3548,20 @@ mod tests {
         assert_eq!(returned.len(), 1);
     }
 
+    #[test]
+    fn tdlist_tasks_for_date_doesnt_return_paused() {
+        let mut list = tdlist_with_done_and_undone();
+
+        list.get_task_mut(0).unwrap().pause_until(NaiveDate::from_ymd(2021, 4, 8));
+        list.get_task_mut(1).unwrap().set_paused(true);
+
+        assert_eq!(list.undone_tasks_for_date(NaiveDate::from_ymd(2021, 4, 1)).len(), 0);
+        assert_eq!(list.done_tasks_for_date(NaiveDate::from_ymd(2021, 4, 1)).len(), 0);
+
+        assert_eq!(list.undone_tasks_for_date(NaiveDate::from_ymd(2021, 4, 8)).len(), 1);
+        assert_eq!(list.undone_tasks_for_date(NaiveDate::from_ymd(2021, 4, 8))[0].body(), "Undone 1");
+    }
+
     #[test]
     fn tdlist_remove_old_todos_removes_done_after_1_day() {
         let mut list = tdlist_with_done_and_undone();
@@ -1219,7 +3636,7 @@ mod tests {
         let mut client = TdList::new_client();
         let mut server = TdList::new_server();
 
-        client.add_todo(Todo::new_undated("Todo 1".to_string()));
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
         client.sync(&mut server);
 
@@ -1236,20 +3653,20 @@ mod tests {
         let mut client = TdList::new_client();
         let mut server = TdList::new_server();
 
-        client.add_todo(Todo::new_undated("Todo 1".to_string()));
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
         client.sync(&mut server);
 
-        server.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string());
+        server.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string()).unwrap();
 
         client.sync(&mut server);
 
 
         assert_eq!(client.todos().len(), 1);
-        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
 
         assert_eq!(server.todos().len(), 1);
-        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
     }
 
     #[test]
@@ -1257,17 +3674,394 @@ mod tests {
         let mut client = TdList::new_client();
         let mut server = TdList::new_server();
 
-        client.add_todo(Todo::new_undated("Todo 1".to_string()));
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
-        client.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string());
+        client.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string()).unwrap();
 
         client.sync(&mut server);
 
         assert_eq!(client.todos().len(), 1);
-        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
 
         assert_eq!(server.todos().len(), 1);
-        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
+        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
+    }
+
+    #[test]
+    fn tdlist_sync_copies_postponements() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.sync(&mut server);
+
+        client.get_todo_mut(0).unwrap().postpone(2).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(server.get_todo(0).unwrap().postponed(), 1);
+        assert_eq!(server.get_todo(0).unwrap().date(), client.get_todo(0).unwrap().date());
+    }
+
+    #[test]
+    fn tdlist_sync_copies_notes_both_ways() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.add_task(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap());
+        client.get_todo_mut(0).unwrap().set_notes(Some("Line 1\nLine 2".to_string()));
+
+        client.sync(&mut server);
+
+        assert_eq!(server.get_todo(0).unwrap().notes(), Some("Line 1\nLine 2"));
+
+        server.get_task_mut(0).unwrap().set_notes(Some("Task notes".to_string()));
+
+        client.sync(&mut server);
+
+        assert_eq!(client.get_task(0).unwrap().notes(), Some("Task notes"));
+    }
+
+    #[test]
+    fn tdlist_sync_copies_backfill_days_both_ways() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_task(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap());
+        client.sync(&mut server);
+
+        client.get_task_mut(0).unwrap().set_backfill_days(2);
+        client.sync(&mut server);
+        assert_eq!(server.get_task(0).unwrap().backfill_days(), 2);
+
+        server.get_task_mut(0).unwrap().set_backfill_days(5);
+        client.sync(&mut server);
+        assert_eq!(client.get_task(0).unwrap().backfill_days(), 5);
+    }
+
+    #[test]
+    fn tdlist_remove_tasks_on_server_removes_the_right_tasks() {
+        let mut server = TdList::new_server();
+        server.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        server.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        server.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]).unwrap());
+
+        server.remove_tasks(&[0, 1, 1]).unwrap();
+
+        assert_eq!(server.tasks().len(), 1);
+        assert_eq!(server.tasks()[0].body(), "Task 2");
+    }
+
+    #[test]
+    fn tdlist_set_todos_done_changes_nothing_on_error() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+
+        assert!(matches!(list.set_todos_done(&[0, 5], true).unwrap_err(), Error::NoTodoWithGivenId(5)));
+        assert!(!list.get_todo(0).unwrap().done());
+
+        list.set_todos_done(&[0, 1], true).unwrap();
+        assert!(list.get_todo(0).unwrap().done());
+        assert!(list.get_todo(1).unwrap().done());
+    }
+
+    #[test]
+    fn tdlist_search_fuzzy_matches_characters_in_order() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Buy groceries".to_string()).unwrap());
+        list.add_task(Task::new("Go to the gym".to_string(), vec![Weekday::Mon]).unwrap());
+
+        assert_eq!(list.search_fuzzy("GRCRS").len(), 1);
+        assert_eq!(list.search_fuzzy("gtg").len(), 1);
+        assert_eq!(list.search_fuzzy("srg").len(), 0);
+        assert_eq!(list.search("grcrs").len(), 0);
+    }
+
+    #[test]
+    fn tdlist_search_skips_removed_items() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Groceries".to_string()).unwrap());
+        list.remove_todo(0).unwrap();
+
+        assert!(list.search("groceries").is_empty());
+    }
+
+    #[test]
+    fn tdlist_move_todo_order_is_synced() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        client.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
+        client.sync(&mut server);
+
+        client.move_todo(0, 10).unwrap();
+        client.sync(&mut server);
+
+        let today = current_date();
+        let bodies: Vec<&str> = server.undone_todos_for_date(today).iter().map(|todo| todo.body()).collect();
+        assert_eq!(bodies, vec!["Todo 1", "Todo 2", "Todo 0"]);
+
+        // New todos go after the moved ones.
+        client.add_todo(Todo::new_undated("Todo 3".to_string()).unwrap());
+        let bodies: Vec<&str> = client.undone_todos_for_date(today).iter().map(|todo| todo.body()).collect();
+        assert_eq!(bodies, vec!["Todo 1", "Todo 2", "Todo 0", "Todo 3"]);
+    }
+
+    #[test]
+    fn tdlist_sort_by_priority_keeps_manual_order_for_ties() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Low".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("High".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Also low".to_string()).unwrap());
+        list.get_todo_mut(1).unwrap().set_priority(2);
+
+        let today = current_date();
+        let bodies: Vec<&str> = list.undone_todos_for_date_sorted(today, SortOrder::Priority).iter().map(|todo| todo.body()).collect();
+        assert_eq!(bodies, vec!["High", "Low", "Also low"]);
+    }
+
+    #[test]
+    fn tdlist_sorted_items_include_every_date() {
+        let mut list = TdList::new_client();
+        let today = current_date();
+        list.add_todo(Todo::new_specific_date("b next year".to_string(), today + Duration::days(365)).unwrap());
+        list.add_todo(Todo::new_undated("C today".to_string()).unwrap());
+        list.add_todo(Todo::new_specific_date("a yesterday".to_string(), today - Duration::days(1)).unwrap());
+        list.add_task(Task::new("Walk".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Read".to_string(), vec![Weekday::Tue]).unwrap());
+
+        let bodies: Vec<&str> = list.todos_sorted(SortOrder::Alphabetical).iter().map(|todo| todo.body()).collect();
+        assert_eq!(bodies, vec!["a yesterday", "b next year", "C today"]);
+        let bodies: Vec<&str> = list.tasks_sorted(SortOrder::Priority).iter().map(|task| task.body()).collect();
+        assert_eq!(bodies, vec!["Walk", "Read"]);
+        let bodies: Vec<&str> = list.tasks_sorted(SortOrder::Alphabetical).iter().map(|task| task.body()).collect();
+        assert_eq!(bodies, vec!["Read", "Walk"]);
+    }
+
+    #[test]
+    fn tdlist_diff_finds_added_items_and_ignores_unchanged() {
+        let mut old = TdList::new_client();
+        old.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+
+        let mut new = TdList::new_from_json(&old.to_json().unwrap()).unwrap();
+        new.add_task(Task::new("Task 1".to_string(), vec![Weekday::Tue]).unwrap());
+
+        let changes = old.diff(&new);
+
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0], ItemChange::Added(ItemRef::Task(task)) if task.body() == "Task 1"));
+    }
+
+    #[test]
+    fn tdlist_pending_changes_lists_new_items_until_synced() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_task(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap());
+
+        assert!(matches!(client.pending_changes()[..], [PendingChange::New(ItemRef::Task(_))]));
+        assert!(server.pending_changes().is_empty());
+
+        client.sync(&mut server);
+
+        assert!(client.pending_changes().is_empty());
+        assert!(server.pending_changes().is_empty());
+    }
+
+    #[test]
+    fn tdlist_add_todo_dependency_rejects_indirect_cycles() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        list.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
+
+        list.add_todo_dependency(1, 0).unwrap();
+        list.add_todo_dependency(2, 1).unwrap();
+
+        assert!(matches!(list.add_todo_dependency(0, 2), Err(Error::DependencyCycle)));
+        assert!(matches!(list.add_todo_dependency(0, 0), Err(Error::DependencyCycle)));
+        assert!(list.get_todo(0).unwrap().blocked_by().is_empty());
+    }
+
+    #[test]
+    fn tdlist_removing_blocker_unblocks_todo() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+        client.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        client.add_todo_dependency(1, 0).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(server.blocks(0).unwrap().len(), 1);
+        assert_eq!(server.undone_todos_for_date(current_date()).len(), 1);
+
+        client.remove_todo(0).unwrap();
+        client.sync(&mut server);
+
+        assert!(server.blocked_by(1).unwrap().is_empty());
+        assert_eq!(server.undone_todos_for_date(current_date())[0].body(), "Todo 1");
+    }
+
+    #[test]
+    fn tdlist_restored_task_survives_sync() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+        client.add_task(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap());
+        client.sync(&mut server);
+
+        client.remove_task(0).unwrap();
+        client.restore_task(0).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(server.tasks().len(), 1);
+        assert_eq!(client.tasks().len(), 1);
+        assert!(client.restore_task(0).is_err());
+    }
+
+    #[test]
+    fn tdlist_ids_are_not_reused_after_removal() {
+        let mut list = TdList::new_server();
+        list.add_task(Task::new("Task 0".to_string(), vec![Weekday::Mon]).unwrap());
+        list.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        list.remove_task(1).unwrap();
+
+        assert_eq!(list.add_task(Task::new("Task 2".to_string(), vec![Weekday::Mon]).unwrap()), 2);
+
+        let mut list = TdList::new_from_json(&list.to_json().unwrap()).unwrap();
+        list.remove_task(0).unwrap();
+
+        assert_eq!(list.add_task(Task::new("Task 3".to_string(), vec![Weekday::Mon]).unwrap()), 3);
+        assert_eq!(list.get_task(2).unwrap().body(), "Task 2");
+    }
+
+    #[test]
+    fn tdlist_get_todo_fails_for_removed_todo() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        list.remove_todo(0).unwrap();
+
+        assert!(matches!(list.get_todo(0).unwrap_err(), Error::NoTodoWithGivenId(0)));
+    }
+
+    #[test]
+    fn tdlist_sync_retried_after_lost_ack_doesnt_duplicate_items() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+        client.add_task(Task::new("Task 1".to_string(), vec![Weekday::Mon]).unwrap());
+        server.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
+
+        // The server saves the synced list but the client never hears back so it keeps its old list.
+        client.clone().sync(&mut server);
+        assert_eq!(server.todos().len(), 2);
+
+        client.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string()).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(server.todos().len(), 2);
+        assert_eq!(server.tasks().len(), 1);
+        assert_eq!(client.todos().len(), 2);
+        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
+        assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
+    }
+
+    #[test]
+    fn tdlist_sync_keeps_ids_and_uuids_stable() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        client.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+
+        let uuid = client.todos()[1].uuid();
+
+        client.sync(&mut server);
+        client.remove_todo(0).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(client.get_todo_by_uuid(uuid).unwrap().id(), 1);
+        assert_eq!(client.get_todo(1).unwrap().body(), "Todo 1");
+        assert_eq!(server.get_todo_by_uuid(uuid).unwrap().body(), "Todo 1");
+
+        client.get_todo_by_uuid_mut(uuid).unwrap().set_body("New Todo 1".to_string()).unwrap();
+        client.sync(&mut server);
+
+        assert_eq!(server.get_todo_by_uuid(uuid).unwrap().body(), "New Todo 1");
+    }
+
+    #[test]
+    fn tdlist_sync_unifies_uuids_of_items_saved_without_uuids() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        client.add_task(Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap());
+        client.sync(&mut server);
+
+        // Simulate loading a list saved before uuids existed.
+        client.tasks.items[0].uuid = Uuid::new_v4();
+        assert_ne!(client.tasks()[0].uuid(), server.tasks()[0].uuid());
+
+        client.sync(&mut server);
+
+        assert_eq!(client.tasks()[0].uuid(), server.tasks()[0].uuid());
+    }
+
+    #[test]
+    fn tdlist_get_by_uuid_returns_err_for_removed() {
+        let mut list = TdList::new_client();
+
+        list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        let uuid = list.todos()[0].uuid();
+        list.remove_todo(0).unwrap();
+
+        assert!(list.get_todo_by_uuid(uuid).is_err());
+        assert!(list.get_task_by_uuid(uuid).is_err());
+    }
+
+    #[test]
+    fn tdlist_sync_resolves_concurrent_edits_with_the_policy() {
+        let mut a = TdList::new_client();
+        let mut b = TdList::new_client();
+        let mut server = TdList::new_server();
+
+        a.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        a.sync(&mut server);
+        b.sync(&mut server);
+
+        // The edit made last wins by default.
+        a.get_todo_mut(0).unwrap().set_body("A".to_string()).unwrap();
+        a.sync(&mut server);
+        b.get_todo_mut(0).unwrap().set_body("B".to_string()).unwrap();
+        let conflicts = b.sync_with_policy(&mut server, ConflictPolicy::LastWriterWins);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].local.as_str(), conflicts[0].remote.as_str()), ("B", "A"));
+        assert_eq!(conflicts[0].resolution, ConflictResolution::KeptLocal);
+        a.sync(&mut server);
+        assert_eq!(a.get_todo(0).unwrap().body(), "B");
+
+        // Edits made after syncing the other's edit aren't conflicts.
+        a.get_todo_mut(0).unwrap().set_body("A2".to_string()).unwrap();
+        assert!(a.sync_with_policy(&mut server, ConflictPolicy::ServerWins).is_empty());
+
+        b.get_todo_mut(0).unwrap().set_body("B2".to_string()).unwrap();
+        let conflicts = b.sync_with_policy(&mut server, ConflictPolicy::ServerWins);
+        assert_eq!(conflicts[0].resolution, ConflictResolution::KeptRemote);
+        assert_eq!(b.get_todo(0).unwrap().body(), "A2");
+
+        a.get_todo_mut(0).unwrap().set_body("A3".to_string()).unwrap();
+        a.sync(&mut server);
+        b.get_todo_mut(0).unwrap().set_body("B3".to_string()).unwrap();
+        let conflicts = b.sync_with_policy(&mut server, ConflictPolicy::KeepBoth);
+        assert!(!conflicts[0].is_resolved());
+        let mut bodies: Vec<&str> = b.todos().iter().map(|todo| todo.body()).collect();
+        bodies.sort();
+        assert_eq!(bodies, vec!["A3", "B3"]);
+        assert_eq!(server.todos().len(), 2);
+        assert_ne!(b.todos()[0].uuid(), b.todos()[1].uuid());
     }
 
     #[test]
@@ -1296,36 +4090,36 @@ mod tests {
         let mut server = TdList::new_server();
 
         // Test 1. Adding multiple works
-        client.add_task(Task::new("Task 1".to_string(), vec![Weekday::Fri]));
-        client.add_task(Task::new("Task 2".to_string(), vec![Weekday::Fri]));
-        client.add_task(Task::new("Task 3".to_string(), vec![Weekday::Fri]));
+        client.add_task(Task::new("Task 1".to_string(), vec![Weekday::Fri]).unwrap());
+        client.add_task(Task::new("Task 2".to_string(), vec![Weekday::Fri]).unwrap());
+        client.add_task(Task::new("Task 3".to_string(), vec![Weekday::Fri]).unwrap());
 
         server.sync(&mut client);
 
-        assert!(client.tasks().contains(&&Task::new("Task 1".to_string(), vec![Weekday::Fri])));
-        assert!(client.tasks().contains(&&Task::new("Task 2".to_string(), vec![Weekday::Fri])));
-        assert!(client.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri])));
+        assert!(client.tasks().contains(&&Task::new("Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(client.tasks().contains(&&Task::new("Task 2".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(client.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(client.tasks().len(), 3);
 
-        assert!(server.tasks().contains(&&Task::new("Task 1".to_string(), vec![Weekday::Fri])));
-        assert!(server.tasks().contains(&&Task::new("Task 2".to_string(), vec![Weekday::Fri])));
-        assert!(server.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri])));
+        assert!(server.tasks().contains(&&Task::new("Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(server.tasks().contains(&&Task::new("Task 2".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(server.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(server.tasks().len(), 3);
 
         // Test 2. Modifying multiple works
-        server.tasks.get_item_mut(0).unwrap().set_body("New Task 1".to_string());
-        server.tasks.get_item_mut(1).unwrap().set_body("New Task 2".to_string());
+        server.tasks.get_item_mut(0).unwrap().set_body("New Task 1".to_string()).unwrap();
+        server.tasks.get_item_mut(1).unwrap().set_body("New Task 2".to_string()).unwrap();
 
         client.sync(&mut server);
 
-        assert!(client.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri])));
-        assert!(client.tasks().contains(&&Task::new("New Task 2".to_string(), vec![Weekday::Fri])));
-        assert!(client.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri])));
+        assert!(client.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(client.tasks().contains(&&Task::new("New Task 2".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(client.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(client.tasks().len(), 3);
 
-        assert!(server.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri])));
-        assert!(server.tasks().contains(&&Task::new("New Task 2".to_string(), vec![Weekday::Fri])));
-        assert!(server.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri])));
+        assert!(server.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(server.tasks().contains(&&Task::new("New Task 2".to_string(), vec![Weekday::Fri]).unwrap()));
+        assert!(server.tasks().contains(&&Task::new("Task 3".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(server.tasks().len(), 3);
 
         // Test 3. Removing multiple works.
@@ -1334,10 +4128,10 @@ mod tests {
 
         server.sync(&mut client);
 
-        assert!(client.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri])));
+        assert!(client.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(client.tasks().len(), 1);
 
-        assert!(server.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri])));
+        assert!(server.tasks().contains(&&Task::new("New Task 1".to_string(), vec![Weekday::Fri]).unwrap()));
         assert_eq!(server.tasks().len(), 1);
     }
 
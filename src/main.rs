@@ -14,41 +14,234 @@ You should have received a copy of the GNU General Public License along with thi
 see <https://www.gnu.org/licenses/>.
  */
 
-use std::{fs, io, process};
-use std::io::Write;
+use std::{env, fmt, fs, io, iter, process};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::{IsTerminal, Write};
 use std::net::ToSocketAddrs;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use chrono::{Datelike, Local, NaiveDate};
-use clap::{ArgEnum, Parser, Subcommand};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use notify_rust::Notification;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use mtd::{Config, Error, MtdNetMgr, Result, Task, TdList, Todo};
+use mtd::{Backup, backup_files, Clock, Config, Conflict, ConflictResolution, current_date, current_time, DataEncryption, DayPlan, DayView, DeviceRegistry, dateparse, Error, IdStyle, ItemChange, ItemRef, KeyEncryption, MtdNetMgr, PAIRING_CODE_MINUTES, pairing_payload, PasswordEncryption, PendingChange, read_data_file, Result, SharedItem, SortOrder, StorageEncryption, Style, SyncState, SystemClock, Task, TdList, Theme, ThemePreset, Todo, Uuid, weekday_to_date, write_data_file};
+use mtd::control::{self, ControlRequest, ControlSocket};
+use mtd::engine::{SyncPhase, SyncProgress};
+use mtd::export::{CsvExporter, Exporter, IcalExporter, MarkdownExporter};
+use mtd::import::{Action, CsvImporter, IcalImporter, import, Importer, Outcome, TaskwarriorImporter, TodoTxtImporter};
+
+/// Clock differences between a client and the server larger than this are warned about.
+const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// How often `sync --watch` tells the server that its connection is still in use between syncs.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The longest `sync --watch` waits before retrying a failed sync, unless its interval is longer.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// How much mtd prints besides the output the command was run for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    /// Only errors, warnings, prompts and the requested output are printed.
+    Quiet,
+    Normal,
+    /// Also prints the files used, the changes made to the items and the details of syncs.
+    Verbose,
+}
+
+/// The verbosity set with `--quiet` or `--verbose`. It's global so that messages printed outside of
+/// an `MtdApp`, such as while initializing, follow it too.
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// The flag of the control socket of a running server or watch mode. It's set by `daemon stop`,
+/// `daemon reload` and Ctrl-C, and checked by the loops of those commands.
+static STOP_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// The encryption of the data files. It's set up once per process because it may ask for a
+/// password.
+static DATA_ENCRYPTION: OnceLock<Box<dyn StorageEncryption>> = OnceLock::new();
+
+/// Returns the encryption of the data files of the config. The first call asks for the password or
+/// reads the key from the keyring, creating a key if there is none yet. The password can also be given
+/// with the `MTD_DATA_PASSWORD` environment variable.
+fn data_encryption(conf: &Config) -> Result<Option<&'static dyn StorageEncryption>> {
+    if conf.data_encryption() == DataEncryption::None {
+        return Ok(None);
+    }
+    if let Some(encryption) = DATA_ENCRYPTION.get() {
+        return Ok(Some(encryption.as_ref()));
+    }
+
+    let encryption: Box<dyn StorageEncryption> = match conf.data_encryption() {
+        DataEncryption::Password => {
+            let password = match env::var("MTD_DATA_PASSWORD") {
+                Ok(password) => password,
+                Err(_) => rpassword::prompt_password("Input data file password: ")?,
+            };
+            Box::new(PasswordEncryption::new(password.into_bytes()))
+        }
+        _ => Box::new(keyring_key(conf)?),
+    };
+    Ok(Some(DATA_ENCRYPTION.get_or_init(|| encryption).as_ref()))
+}
+
+/// Reads the key of the data file of the config from the keyring of the operating system.
+fn keyring_key(conf: &Config) -> Result<KeyEncryption> {
+    let account = conf.save_location().map_or_else(|| "mtd".to_string(), |path| path.display().to_string());
+    let entry = keyring::Entry::new("mtd", &account).map_err(keyring_err)?;
+    match entry.get_password() {
+        Ok(text) => KeyEncryption::from_text(&text),
+        Err(keyring::Error::NoEntry) => {
+            let key = KeyEncryption::generate();
+            entry.set_password(&key.to_text()).map_err(keyring_err)?;
+            Ok(key)
+        }
+        Err(e) => Err(keyring_err(e)),
+    }
+}
+
+/// Returns the keyring entry of the encryption password of the config.
+fn keyring_password_entry(conf: &Config) -> Result<keyring::Entry> {
+    let account = conf.save_location().map_or_else(|| "mtd".to_string(), |path| path.display().to_string());
+    keyring::Entry::new("mtd-password", &account).map_err(keyring_err)
+}
+
+/// Turns an error of the keyring into an IO error.
+fn keyring_err(e: keyring::Error) -> Error {
+    Error::IOErr(io::Error::other(format!("The keyring couldn't be used: {}", e)))
+}
+
+/// Reads the items saved at a path, decrypting them if the config encrypts data files.
+fn read_list_file(conf: &Config, path: &Path) -> Result<TdList> {
+    TdList::new_from_json(&read_data_file(path, data_encryption(conf)?)?)
+}
+
+/// Returns `true` if a running server or watch mode has been told to stop.
+fn stop_requested() -> bool {
+    STOP_FLAG.get().is_some_and(|stop| stop.load(Ordering::SeqCst))
+}
+
+/// Prints an informational message to stdout unless `--quiet` is used.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if verbosity() > Verbosity::Quiet {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a detail to stderr when `--verbose` is used. Stderr keeps stdout usable in scripts.
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if verbosity() == Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct CliArgs {
     #[clap(value_parser, long)]
     config_file: Option<PathBuf>,
+    /// Config profile to use. Defaults to the value of MTD_PROFILE or the config file's default profile
+    #[clap(value_parser, long)]
+    profile: Option<String>,
+    /// Print plain ASCII without colors, and show done items with [x] and undone items with [ ]
+    #[clap(value_parser, long)]
+    ascii: bool,
+    /// When to use colors. With auto, colors are used only when printing to a terminal and the
+    /// NO_COLOR environment variable isn't set
+    #[clap(arg_enum, value_parser, long, default_value = "auto")]
+    color: ColorWhen,
+    /// Never use colors. Same as --color never
+    #[clap(value_parser, long, conflicts_with = "color")]
+    no_color: bool,
+    /// Preset of colors and styles to use instead of the one in the config. Styles set in the
+    /// config's theme are kept
+    #[clap(arg_enum, value_parser, long)]
+    theme: Option<ThemeArg>,
+    /// Print only errors, warnings and the output asked for, such as the items of show. Useful in
+    /// scripts
+    #[clap(value_parser, long, short, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print the files used, the changes made to the items and the details of syncs
+    #[clap(value_parser, long, short)]
+    verbose: bool,
     #[clap(subcommand)]
     command: Commands,
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 enum Commands {
     /// Shows specified items
     Show {
         /// Type of items to show.
         #[clap(arg_enum, value_parser, long, short)]
         item_type: Option<ItemType>,
-        /// Weekday to show
-        #[clap(arg_enum, value_parser, long, short, group = "show_days")]
-        weekday: Option<Weekday>,
+        /// Weekday or date to show, such as fri, tomorrow, "next fri" or "aug 20"
+        #[clap(value_parser = parse_date_arg, long, short, group = "show_days")]
+        weekday: Option<NaiveDate>,
         /// Show entire week starting from today
         #[clap(value_parser, long, group = "show_days")]
         week: bool,
+        /// With --week, show the calendar week that contains today instead of the next 7 days. The
+        /// first day of the week is set with week_starts_on in the config
+        #[clap(value_parser, long, requires = "week")]
+        calendar: bool,
+        /// Date to show, such as 2024-07-01 or "aug 20"
+        #[clap(value_parser = parse_date_arg, long, group = "show_days")]
+        date: Option<NaiveDate>,
+        /// First date of a range of dates to show. Defaults to today
+        #[clap(value_parser = parse_date_arg, long, requires = "to")]
+        from: Option<NaiveDate>,
+        /// Last date of a range of dates to show
+        #[clap(value_parser = parse_date_arg, long, group = "show_days")]
+        to: Option<NaiveDate>,
+        /// Show only todos whose date has passed without them being done
+        #[clap(value_parser, long, group = "show_days", conflicts_with = "item-type")]
+        overdue: bool,
+        /// Show the server's items instead of local ones. If the server is unreachable the last
+        /// fetched items are shown instead
+        #[clap(value_parser, long)]
+        remote: bool,
+        /// Order of the todos
+        #[clap(arg_enum, value_parser, long, short, default_value = "manual")]
+        sort: Sort,
+        /// Also show items that are removed on the next sync
+        #[clap(value_parser, long, conflicts_with_all = &["remote", "output"])]
+        include_removed: bool,
+        /// Output format. json and tsv are meant for scripts and never contain colors
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+        /// Keep showing the items until interrupted. The saved items are read again and the view is
+        /// redrawn every interval, so changes synced from other devices appear
+        #[clap(value_parser, long, conflicts_with = "output")]
+        watch: bool,
+        /// Seconds between redraws with --watch
+        #[clap(value_parser = clap::value_parser!(u64).range(1..), long, default_value = "60", requires = "watch")]
+        interval: u64,
     },
     /// Adds a new item
     Add {
@@ -56,38 +249,96 @@ enum Commands {
         #[clap(arg_enum, value_parser)]
         item_type: ItemType,
         /// Body of the item
+        #[clap(value_parser, required_unless_present_any = &["stdin", "file", "suggest"], conflicts_with_all = &["stdin", "file", "suggest"])]
+        body: Option<String>,
+        /// Weekday(s) of the item: mon, tue, wed, thu, fri, sat, sun, weekdays (mon-fri), weekend (sat
+        /// and sun) or daily. A time of day can be given for a weekday, such as mon@18:00. A todo can be
+        /// given a single date instead, such as tomorrow, "next fri", "in 3 days" or "aug 20"
+        #[clap(value_parser = parse_day_spec)]
+        weekdays: Vec<DaySpec>,
+        /// Read items from the standard input, one per line. A line may end with a weekday such as
+        /// mon or fri@18:00
+        #[clap(value_parser, long, conflicts_with = "file")]
+        stdin: bool,
+        /// Read items from a file, one per line. A line may end with a weekday such as mon or
+        /// fri@18:00
+        #[clap(value_parser, long)]
+        file: Option<PathBuf>,
+        /// Add the todos suggested by the day plans of the given day that haven't been added yet
+        #[clap(value_parser = parse_date_arg, long, conflicts_with_all = &["stdin", "file"])]
+        suggest: Option<NaiveDate>,
+        /// With --stdin or --file, report what would be added or skipped without adding anything
+        #[clap(value_parser, long, conflicts_with = "suggest")]
+        dry_run: bool,
+        /// Format of the --dry-run report
+        #[clap(arg_enum, value_parser, long, default_value = "text", requires = "dry-run")]
+        output: Output,
+        #[clap(flatten)]
+        options: AddOptions,
+    },
+    /// Manages day plans, which are todos suggested for every occurrence of a weekday
+    Plan {
+        #[clap(subcommand)]
+        action: PlanAction,
+    },
+    /// Copies an item as a new item, optionally for other days
+    Copy {
+        /// Type of item to copy
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to copy
         #[clap(value_parser)]
-        body: String,
-        /// Weekday(s) of the item
+        id: String,
+        /// Weekday(s) of the copy, such as mon or fri@18:00. A todo is copied once for each weekday
+        /// and can be given a single date instead. The copy is for the same days if none are given
+        #[clap(value_parser = parse_day_spec)]
+        weekdays: Vec<DaySpec>,
+    },
+    /// Converts a todo into a task or a task into a todo
+    Convert {
+        /// Type of item to convert
         #[clap(arg_enum, value_parser)]
-        weekdays: Vec<Weekday>,
+        item_type: ItemType,
+        /// Id of the item to convert
+        #[clap(value_parser)]
+        id: String,
+        /// Weekday(s) of the new task or the weekday or date of the new todo. A new task is for the
+        /// weekday of the todo and a new todo for the next date of the task if none are given. Tasks
+        /// have no priority or dependencies, so those of a todo are dropped
+        #[clap(value_parser = parse_day_spec)]
+        weekdays: Vec<DaySpec>,
     },
-    /// Removes an item
+    /// Removes an item. The items are listed and confirmation is asked first
     Remove {
         /// Type of item to remove
         #[clap(arg_enum, value_parser)]
         item_type: ItemType,
-        /// Id of the item to remove
-        #[clap(value_parser)]
-        id: u64,
+        /// Id(s) of the item(s) to remove
+        #[clap(value_parser, required = true)]
+        ids: Vec<String>,
+        /// Don't ask for confirmation
+        #[clap(value_parser, long, short)]
+        yes: bool,
     },
     /// Sets an item as done
     Do {
-        /// Type of item to set the value(s) of
+        /// Type of item to set as done
         #[clap(arg_enum, value_parser)]
         item_type: ItemType,
-        /// Id of the item to set the value(s) of
-        #[clap(value_parser)]
-        id: u64,
+        /// Id(s) of the item(s) to set as done. A part of the body matching a single item can be given
+        /// instead of an id
+        #[clap(value_parser, required = true)]
+        ids: Vec<String>,
     },
     /// Sets an item as undone
     Undo {
-        /// Type of item to set the value(s) of
+        /// Type of item to set as undone
         #[clap(arg_enum, value_parser)]
         item_type: ItemType,
-        /// Id of the item to set the value(s) of
-        #[clap(value_parser)]
-        id: u64,
+        /// Id(s) of the item(s) to set as undone. A part of the body matching a single item can be
+        /// given instead of an id
+        #[clap(value_parser, required = true)]
+        ids: Vec<String>,
     },
     /// Sets the value(s) of an item
     Set {
@@ -96,647 +347,5941 @@ enum Commands {
         item_type: ItemType,
         /// Id of the item to set the value(s) of
         #[clap(value_parser)]
-        id: u64,
-        /// Set the body of the item
-        #[clap(value_parser, long, short)]
-        body: Option<String>,
-        /// Set the weekday(s) of the item
-        #[clap(arg_enum, value_parser, long, short)]
-        weekdays: Vec<Weekday>,
+        id: String,
+        #[clap(flatten)]
+        values: SetValues,
+    },
+    /// Finds items whose body contains the given text
+    Find {
+        /// Text to search for. Case is ignored
+        #[clap(value_parser)]
+        query: String,
+        /// Match items containing the characters of the query in order even if not next to each other
+        #[clap(value_parser, long)]
+        fuzzy: bool,
+        /// Output format. json and tsv are meant for scripts and never contain colors
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+    },
+    /// Lists every item of a type with all its details regardless of its date, including todos dated
+    /// in the future and paused tasks
+    List {
+        /// Type of items to list
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Order of the items. Tasks have no manual order or priority, so with those they are in the
+        /// order they were added
+        #[clap(arg_enum, value_parser, long, short, default_value = "manual")]
+        sort: Sort,
+        /// List only items whose body contains the text. Case is ignored
+        #[clap(value_parser, long)]
+        filter: Option<String>,
+        /// Output format. json and tsv are meant for scripts and never contain colors
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+    },
+    /// Shows completion statistics of tasks and the number of overdue todos
+    Stats {
+        /// Number of weeks to show statistics for, including the current week
+        #[clap(value_parser = clap::value_parser!(u32).range(1..), long, short, default_value_t = 4)]
+        weeks: u32,
+        /// Output format. json and tsv are meant for scripts and never contain colors
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+    },
+    /// Shows reports about the history of items
+    Report {
+        #[clap(subcommand)]
+        report: ReportKind,
+    },
+    /// Shows items added, removed or modified between two data files. Without files, compares the
+    /// latest backup to the current items. With one file, compares it to the current items
+    Diff {
+        /// Older data file
+        #[clap(value_parser)]
+        old: Option<PathBuf>,
+        /// Newer data file
+        #[clap(value_parser)]
+        new: Option<PathBuf>,
+    },
+    /// Shows the changes that will be sent to the server on the next sync
+    Status,
+    /// Shows all details of an item including its notes
+    Info {
+        /// Type of the item
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Moves a todo to a position in the manual order used by show
+    Move {
+        /// Type of item to move
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to move
+        #[clap(value_parser)]
+        id: String,
+        /// New position of the item starting from 0
+        #[clap(value_parser)]
+        position: usize,
+    },
+    /// Restores an item that was removed after the last sync
+    Restore {
+        /// Type of item to restore
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to restore
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Pauses a task so that it isn't shown until it is resumed
+    Pause {
+        /// Type of item to pause
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to pause
+        #[clap(value_parser)]
+        id: String,
+        /// Resume the task automatically on the given date (YYYY-MM-DD)
+        #[clap(value_parser, long)]
+        until: Option<NaiveDate>,
+    },
+    /// Resumes a paused task
+    Resume {
+        /// Type of item to resume
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to resume
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Postpones a todo to a later date
+    Postpone {
+        /// Type of item to postpone
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to postpone
+        #[clap(value_parser)]
+        id: String,
+        /// Number of days to postpone by, such as +3, or a weekday or date to postpone to. Overdue
+        /// todos are postponed from today. The default is +1
+        #[clap(value_parser = parse_postponement, default_value = "+1")]
+        to: Postponement,
+    },
+    /// Exports saved items as JSON or in another format
+    Export {
+        /// Format to export to. ical exports todos as to-dos and tasks as recurring events for
+        /// calendar apps, csv exports a row per item for spreadsheets and md exports a checklist of
+        /// the current calendar week
+        #[clap(arg_enum, value_parser, default_value = "json")]
+        format: ExportFormat,
+        /// Export everything mtd stores: items, config (without passwords), cached server items and a
+        /// list of backups
+        #[clap(value_parser, long)]
+        everything: bool,
+        /// Export the tasks as printable markdown routine cards instead
+        #[clap(value_parser, long, conflicts_with = "everything")]
+        routines: bool,
+        /// File to write the export to instead of stdout
+        #[clap(value_parser, long, short, alias = "out")]
+        output: Option<PathBuf>,
+    },
+    /// Imports items from a file written by another program or exported by mtd. Items exported by
+    /// mtd update the items they came from
+    Import {
+        /// Format of the file. todo-txt reads a todo per line, ical reads to-dos and events, csv
+        /// reads a row per item with a header row and taskwarrior reads the JSON of task export
+        #[clap(arg_enum, value_parser)]
+        format: ImportFormat,
+        /// File to import
+        #[clap(value_parser)]
+        file: PathBuf,
+        /// Report what would be created, updated or skipped without changing anything
+        #[clap(value_parser, long)]
+        dry_run: bool,
+        /// Format of the report of what was imported
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+    },
+    /// Prints a single item as an encrypted text that another mtd user can add with receive. The
+    /// body, date or weekdays, time and notes of the item are shared
+    Share {
+        /// Type of the item to share
+        #[clap(arg_enum, value_parser)]
+        item_type: ItemType,
+        /// Id of the item to share
+        #[clap(value_parser)]
+        id: String,
+        /// Passphrase to encrypt the item with. A random passphrase is generated and printed by
+        /// default. Send the passphrase over a different channel than the item
+        #[clap(value_parser, long)]
+        passphrase: Option<String>,
+    },
+    /// Adds an item shared with share as a new item
+    Receive {
+        /// The shared item. Read from the standard input if not given
+        #[clap(value_parser)]
+        item: Option<String>,
+        /// Passphrase of the shared item. Asked for if not given
+        #[clap(value_parser, long)]
+        passphrase: Option<String>,
+    },
+    /// Sends a desktop notification that lists today's undone todos and tasks. Meant to be run
+    /// from cron or a systemd timer
+    Notify {
+        /// Don't send a notification if there is nothing left to do today
+        #[clap(value_parser, long)]
+        if_nonempty: bool,
     },
     /// Synchronizes local items with a server
-    Sync,
+    Sync {
+        /// Keep syncing until interrupted. The connection to the server is kept open between syncs
+        #[clap(value_parser, long)]
+        watch: bool,
+        /// Seconds between syncs with --watch
+        #[clap(value_parser = clap::value_parser!(u64).range(1..), long, default_value = "300", requires = "watch")]
+        interval: u64,
+        /// Times to try again if the server can't be reached. The wait between tries doubles each
+        /// time
+        #[clap(value_parser, long, default_value = "0", conflicts_with = "watch")]
+        retries: u32,
+        /// Seconds to wait before the first retry with --retries
+        #[clap(value_parser = clap::value_parser!(u64).range(1..), long, default_value = "2", requires = "retries")]
+        backoff: u64,
+    },
     /// Runs mtd as a server
-    Server,
-    /// Re-initializes mtd
+    Server {
+        /// TCP socket address to answer the /healthz and /readyz health probes on, such as
+        /// 0.0.0.0:8080. Overrides health_addr in the config. The address also serves Prometheus
+        /// metrics on /metrics
+        #[clap(value_parser, long)]
+        health_addr: Option<String>,
+        /// Least serious messages to log. debug also logs every connection
+        #[clap(arg_enum, value_parser, long, default_value = "info")]
+        log_level: LogLevelArg,
+        #[clap(subcommand)]
+        action: Option<DeviceAction>,
+    },
+    /// Pairs this device with the server using a code printed by `mtd server add-device` on the
+    /// server. The device then syncs with a token of its own instead of the server's password, at
+    /// the server address in the code
+    Pair {
+        /// The pairing code, such as laptop:ABCD-EFGH-JKLM@mtd.example.com:4000
+        #[clap(value_parser)]
+        code: String,
+    },
+    /// Initializes mtd using a guided setup
+    Init {
+        /// Change the config of an already initialized profile without removing saved items
+        #[clap(value_parser, long)]
+        reconfigure: bool,
+    },
+    /// Creates or restores a password-encrypted backup of the items and the config of the profile
+    Backup {
+        #[clap(subcommand)]
+        action: BackupAction,
+    },
+    /// Tells running servers and watch modes (sync --watch and show --watch) to stop or to reload
+    /// their config
+    Daemon {
+        #[clap(subcommand)]
+        action: DaemonAction,
+    },
+    /// Lists, creates and removes profiles
+    Profile {
+        #[clap(subcommand)]
+        action: ProfileAction,
+    },
+    /// Shows or changes the config of the profile
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+    /// Tries mtd with sample items kept only in memory. Without arguments, starts a prompt for
+    /// running commands. Nothing is read from or written to the config or the save file
+    #[clap(trailing_var_arg = true)]
+    Demo {
+        /// A single command to run with the sample items, such as `show --week`
+        #[clap(value_parser, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Runs a script of mtd commands, one per line or as a JSON array, with a single load and save
+    /// of the items. If a command fails, none of the changes are saved
+    Eval {
+        /// Script file to run. Reads the script from the standard input if not given or -
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+        /// Sync once after all commands have run
+        #[clap(value_parser, long)]
+        sync: bool,
+    },
+    /// Re-initializes mtd. A backup of the config and saved items is created first
     /// (WARNING! This will completely delete all saved items!)
-    ReInit,
+    ReInit {
+        /// Don't ask to type 'erase' for confirmation
+        #[clap(value_parser, long, short, alias = "force")]
+        yes: bool,
+        /// Keep the config and only delete the saved items. With --yes, nothing is asked
+        #[clap(value_parser, long)]
+        keep_config: bool,
+    },
+    /// Runs an extension. `mtd foo` runs the program `mtd-foo` found on PATH
+    #[clap(external_subcommand)]
+    External(Vec<String>),
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
-enum ItemType {
-    Todo,
-    Task,
+/// Actions of the `config` command. Settings are named like the keys of the config file and nested
+/// settings with dots, such as `theme.header`.
+#[derive(Subcommand, Clone)]
+enum ConfigAction {
+    /// Lists all settings
+    List {
+        /// Show passwords instead of hiding them
+        #[clap(value_parser, long)]
+        show_secrets: bool,
+    },
+    /// Shows the value of a setting
+    Get {
+        #[clap(value_parser)]
+        key: String,
+        /// Show passwords instead of hiding them
+        #[clap(value_parser, long)]
+        show_secrets: bool,
+    },
+    /// Changes a setting. Durations are given in seconds. Leave out the value of a password to be
+    /// asked for it instead
+    Set {
+        #[clap(value_parser)]
+        key: String,
+        #[clap(value_parser)]
+        value: Option<String>,
+    },
+    /// Removes the value of an optional setting
+    Unset {
+        #[clap(value_parser)]
+        key: String,
+    },
 }
 
-// Define custom weekday for clap to parse weekdays.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
-enum Weekday {
-    Mon,
-    Tue,
-    Wed,
-    Thu,
-    Fri,
-    Sat,
-    Sun,
+/// Actions of the `plan` command.
+#[derive(Subcommand, Clone)]
+enum PlanAction {
+    /// Shows the todos suggested for a day that haven't been added yet. Add them with
+    /// `add todo --suggest`
+    Show {
+        /// Day to show the suggestions of, such as sun or tomorrow. The default is today
+        #[clap(value_parser = parse_date_arg)]
+        day: Option<NaiveDate>,
+    },
+    /// Lists all day plans by weekday
+    List,
+    /// Adds a todo suggested for every occurrence of the weekday(s)
+    Add {
+        /// Body of the suggested todo
+        #[clap(value_parser)]
+        body: String,
+        /// Weekday(s) to suggest the todo for: mon, tue, wed, thu, fri, sat, sun, weekdays, weekend
+        /// or daily
+        #[clap(arg_enum, value_parser, required = true)]
+        weekdays: Vec<Days>,
+    },
+    /// Removes day plans
+    Remove {
+        /// Id(s) of the day plan(s) to remove
+        #[clap(value_parser, required = true)]
+        ids: Vec<u64>,
+    },
 }
 
-impl Into<chrono::Weekday> for Weekday {
-    fn into(self) -> chrono::Weekday {
-        match self {
-            Weekday::Mon => { chrono::Weekday::Mon }
-            Weekday::Tue => { chrono::Weekday::Tue }
-            Weekday::Wed => { chrono::Weekday::Wed }
-            Weekday::Thu => { chrono::Weekday::Thu }
-            Weekday::Fri => { chrono::Weekday::Fri }
-            Weekday::Sat => { chrono::Weekday::Sat }
-            Weekday::Sun => { chrono::Weekday::Sun }
+/// Reports of the `report` command.
+#[derive(Subcommand, Clone)]
+enum ReportKind {
+    /// Lists the undone todos postponed or overdue the most and the tasks missed the most times in a
+    /// row
+    Stale {
+        /// Number of todos and tasks to show at most
+        #[clap(value_parser, long, short, default_value_t = 10)]
+        limit: usize,
+        /// Output format. json and tsv are meant for scripts and never contain colors
+        #[clap(arg_enum, value_parser, long, default_value = "text")]
+        output: Output,
+    },
+}
+
+/// Actions of the `profile` command.
+#[derive(Subcommand, Clone)]
+enum ProfileAction {
+    /// Lists the profiles and their save paths. The default profile is marked with *
+    List,
+    /// Creates a new profile using the guided setup
+    Create {
+        #[clap(value_parser)]
+        name: String,
+        /// Make the new profile the default profile
+        #[clap(value_parser, long)]
+        default: bool,
+    },
+    /// Removes a profile and its saved items. A backup of the saved items is created first
+    Remove {
+        #[clap(value_parser)]
+        name: String,
+        /// Keep the saved items of the profile and only remove its config
+        #[clap(value_parser, long)]
+        keep_items: bool,
+        /// Don't ask for confirmation
+        #[clap(value_parser, long)]
+        force: bool,
+    },
+    /// Makes a profile the default profile
+    Default {
+        #[clap(value_parser)]
+        name: String,
+    },
+}
+
+/// Actions of the `backup` command.
+#[derive(Subcommand, Clone)]
+enum BackupAction {
+    /// Writes the items and the config to an encrypted file. The encryption password is left out
+    /// unless --include-secrets is given
+    Create {
+        #[clap(value_parser)]
+        file: PathBuf,
+        /// Also back up the encryption password and the Tor control password
+        #[clap(value_parser, long)]
+        include_secrets: bool,
+        /// Password the backup is encrypted with. Asked for if not given
+        #[clap(value_parser, long)]
+        password: Option<String>,
+    },
+    /// Replaces the items and the config of the profile with a backup. The current saved items are
+    /// backed up first. The save path of the profile is kept
+    Restore {
+        #[clap(value_parser)]
+        file: PathBuf,
+        /// Password of the backup. Asked for if not given
+        #[clap(value_parser, long)]
+        password: Option<String>,
+        /// Don't ask for confirmation before replacing saved items
+        #[clap(value_parser, long, short)]
+        yes: bool,
+    },
+}
+
+/// Actions of the `server` command for managing the devices paired with the server.
+#[derive(Subcommand, Clone)]
+enum DeviceAction {
+    /// Prints a one-time code for pairing a new device with `mtd pair`
+    AddDevice {
+        /// Name of the device, such as laptop
+        #[clap(value_parser)]
+        name: String,
+    },
+    /// Lists the paired devices and the devices waiting to be paired
+    Devices,
+    /// Revokes the token of a device so that it can't sync anymore
+    RevokeDevice {
+        /// Name of the device
+        #[clap(value_parser)]
+        name: String,
+    },
+}
+
+/// Actions of the `daemon` command.
+#[derive(Subcommand, Clone, Copy)]
+enum DaemonAction {
+    /// Saves the items and exits
+    Stop,
+    /// Saves the items and starts again with the config read again
+    Reload,
+}
+
+impl From<DaemonAction> for ControlRequest {
+    fn from(action: DaemonAction) -> Self {
+        match action {
+            DaemonAction::Stop => ControlRequest::Stop,
+            DaemonAction::Reload => ControlRequest::Reload,
         }
     }
 }
 
-fn main() {
-    if let Err(e) = MtdApp::run() {
-        eprintln!("{}", e);
-        process::exit(1);
-    } else {
-        process::exit(0);
+/// Settings that are hidden unless explicitly asked for.
+const SECRET_SETTINGS: [&str; 3] = ["encryption_password", "tor_control_password", "users"];
+
+/// Formats the value of a setting for `config get` and `config list`. Strings are shown without
+/// quotes, durations in seconds and the encryption password as text.
+fn format_setting(key: &str, value: &Value, show_secrets: bool) -> String {
+    if SECRET_SETTINGS.contains(&key) && !value.is_null() && !show_secrets {
+        return "(hidden, use --show-secrets to show)".to_string();
+    }
+    if key == "encryption_password" {
+        let bytes: Vec<u8> = serde_json::from_value(value.clone()).unwrap_or_default();
+        return String::from_utf8_lossy(&bytes).to_string();
+    }
+    match value {
+        Value::String(s) => s.clone(),
+        _ => match duration_secs(value) {
+            Some(secs) => secs.to_string(),
+            None => value.to_string(),
+        },
     }
 }
 
-struct MtdApp {
-    conf: Config,
-    list: TdList,
+/// Returns the seconds of a serialized `Duration`.
+fn duration_secs(value: &Value) -> Option<u64> {
+    let object = value.as_object().filter(|object| object.len() == 2 && object.contains_key("nanos"))?;
+    object.get("secs")?.as_u64()
 }
 
-impl MtdApp {
-    /// Initializes a new MtdApp. Reads/creates config and saved items.
-    fn init(config_path: &PathBuf) -> Result<Self> {
-        let conf;
+/// Returns the value of a setting from a serialized `Config`.
+fn setting<'a>(json: &'a Value, key: &str) -> Result<&'a Value> {
+    key.split('.').try_fold(json, |json, part| json.get(part))
+        .ok_or_else(|| Error::InvalidArgument(format!("Unknown setting \"{}\".", key)))
+}
 
-        if config_path.exists() {
-            conf = Config::new_from_json(&fs::read_to_string(config_path)?)?;
-        } else {
-            conf = MtdApp::create_new_config(config_path)?;
-        }
+/// Returns a `Config` with the setting changed to the given value, or removed if the value is
+/// `None`. Values are read as JSON if possible and as text otherwise.
+fn change_setting(conf: &Config, key: &str, value: Option<&str>) -> Result<Config> {
+    let json = serde_json::to_value(conf)?;
+    let setting = setting(&json, key)?;
 
-        let list;
+    let candidates = match value {
+        None => vec![Value::Null],
+        Some(value) if key == "encryption_password" => vec![serde_json::to_value(value.as_bytes())?],
+        Some(value) if duration_secs(setting).is_some() => {
+            let secs: u64 = value.parse()
+                .map_err(|_| Error::InvalidArgument(format!("\"{}\" isn't a number of seconds.", value)))?;
+            vec![serde_json::to_value(Duration::from_secs(secs))?]
+        }
+        // Text such as `localhost:55995` isn't JSON, and some JSON such as `5` is also valid text.
+        Some(value) => serde_json::from_str(value).into_iter().chain([Value::String(value.to_string())]).collect(),
+    };
 
-        // It is possible that a save_location has not been defined which needs to be checked before
-        // checking if the path even exists.
-        if let Some(list_path) = conf.save_location() {
-            if list_path.exists() {
-                list = TdList::new_from_json(
-                    &fs::read_to_string(
-                        list_path
-                    )?
-                )?;
-            } else {
-                list = MtdApp::create_new_list(&conf)?;
-            }
-        } else {
-            list = MtdApp::create_new_list(&conf)?;
+    for candidate in candidates {
+        let mut changed = json.clone();
+        if let Some(setting) = key.split('.').try_fold(&mut changed, |json, part| json.get_mut(part)) {
+            *setting = candidate;
         }
+        if let Ok(changed) = serde_json::from_value::<Config>(changed) {
+            changed.validate()?;
+            return Ok(changed);
+        }
+    }
 
-        Ok(Self {
-            conf,
-            list,
-        })
+    match value {
+        None => Err(Error::InvalidArgument(format!("\"{}\" can't be unset.", key))),
+        Some(value) => Err(Error::InvalidArgument(format!("Invalid value \"{}\" for \"{}\".", value, key))),
     }
+}
 
-    /// Creates a new TdList as a server or a client depending on user input.
-    fn create_new_list(config: &Config) -> Result<TdList> {
-        let mut buffer = String::new();
-        let stdin = io::stdin();
-        let mut stdout = io::stdout();
+/// Optional values of a new item.
+#[derive(Args, Default, Clone)]
+struct AddOptions {
+    /// Exact date of a todo instead of weekday(s), such as 2024-08-20, tomorrow or "aug 20"
+    #[clap(value_parser = parse_date_arg, long, short, conflicts_with = "weekdays")]
+    date: Option<NaiveDate>,
+    /// Last date of a task (YYYY-MM-DD) after which the task is removed
+    #[clap(value_parser, long)]
+    until: Option<NaiveDate>,
+    /// Repeat a task every N weeks starting from the current week
+    #[clap(value_parser = clap::value_parser!(u32).range(1..), long)]
+    every: Option<u32>,
+    /// Keep showing a missed date of a task for up to N days (at most 6) instead of skipping it
+    #[clap(value_parser = clap::value_parser!(u32).range(0..7), long)]
+    backfill: Option<u32>,
+    /// Longer notes of the item, may span multiple lines
+    #[clap(value_parser, long, short)]
+    notes: Option<String>,
+    /// Time of day of the item (HH:MM)
+    #[clap(value_parser = parse_time, long, short)]
+    time: Option<NaiveTime>,
+    /// Priority of a todo. Higher numbers are more important
+    #[clap(value_parser, long, short)]
+    priority: Option<u8>,
+    /// Id(s) of the todos that must be done before the todo is shown
+    #[clap(value_parser, long)]
+    after: Vec<String>,
+    /// Times of day for specific weekdays. Set from weekday arguments such as mon@18:00
+    #[clap(skip)]
+    weekday_times: Vec<(Weekday, NaiveTime)>,
+}
 
-        if config.local_only() {
-            buffer = "c".to_string();
-        } else {
-            loop {
-                print!("Initialize as a server or a client (s/c)? ");
-                stdout.flush()?;
-                buffer.clear();
-                stdin.read_line(&mut buffer)?;
-                buffer = buffer.to_lowercase().trim().to_string();
+/// New values of an item. Values that aren't given are left unchanged.
+#[derive(Args, Default, Clone)]
+struct SetValues {
+    /// Set the body of the item
+    #[clap(value_parser, long, short)]
+    body: Option<String>,
+    /// Set the weekday(s) of the item
+    #[clap(arg_enum, value_parser, long, short)]
+    weekdays: Vec<Weekday>,
+    /// Set the exact date of a todo, such as 2024-08-20, tomorrow or "aug 20"
+    #[clap(value_parser = parse_date_arg, long, short, conflicts_with = "weekdays")]
+    date: Option<NaiveDate>,
+    /// Set the last date of a task (YYYY-MM-DD) after which the task is removed
+    #[clap(value_parser, long)]
+    until: Option<NaiveDate>,
+    /// Remove the last date of a task so that it repeats indefinitely
+    #[clap(value_parser, long, conflicts_with = "until")]
+    no_until: bool,
+    /// Set how many days (at most 6) a missed date of a task is shown for. 0 skips missed dates
+    #[clap(value_parser = clap::value_parser!(u32).range(0..7), long)]
+    backfill: Option<u32>,
+    /// Set the notes of the item. An empty value removes the notes
+    #[clap(value_parser, long, short)]
+    notes: Option<String>,
+    /// Set the time of day of the item (HH:MM)
+    #[clap(value_parser = parse_time, long, short)]
+    time: Option<NaiveTime>,
+    /// Set the priority of a todo. Higher numbers are more important
+    #[clap(value_parser, long, short)]
+    priority: Option<u8>,
+}
 
-                if &buffer != "s" && &buffer != "c" {
-                    eprintln!("Invalid option.");
-                    continue;
+/// Splits a command line into arguments. Arguments can be quoted with double or single quotes.
+fn split_args(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
                 }
-                break;
             }
-        }
-
-        if &buffer == "c" {
-            Ok(TdList::new_client())
-        } else {
-            Ok(TdList::new_server())
+            None => current.get_or_insert_with(String::new).push(c),
         }
     }
 
-    /// Returns the path to the config.
-    fn default_config_path() -> Result<PathBuf> {
-        Ok(dirs::config_dir().ok_or(Error::Unknown)?.join("mtd/conf.json"))
+    if quote.is_some() {
+        return Err("Unterminated quote.".to_string());
     }
+    args.extend(current);
+    Ok(args)
+}
 
-    /// Returns the path to the default save location.
-    fn default_save_path() -> Result<PathBuf> {
-        Ok(dirs::data_dir().ok_or(Error::Unknown)?.join("mtd/data.json"))
-    }
+/// Returns the error of a clap error without the usage and help that follow it.
+fn clap_error_summary(e: &clap::Error) -> String {
+    let text = e.to_string();
+    let summary = text.split("\n\n").next().unwrap_or_default();
+    summary.trim_start_matches("error: ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
-    /// Initializes a new config and writes it to a file.
-    fn create_new_config(config_path: &PathBuf) -> Result<Config> {
-        println!("Creating a new config.");
+/// Splits a script of `eval` into the arguments of its commands, each with where it is in the
+/// script for error messages. A script is either a JSON array of command lines or of argument
+/// arrays, or has a command on each line. Empty lines and lines starting with # are skipped.
+fn parse_script(script: &str) -> Result<Vec<(String, Vec<String>)>> {
+    if script.trim_start().starts_with('[') {
+        let commands: Vec<Value> = serde_json::from_str(script)?;
+        return commands.into_iter().enumerate().map(|(i, command)| {
+            let args = match command {
+                Value::String(line) => split_args(&line).map_err(Error::InvalidArgument)?,
+                Value::Array(args) => args.into_iter().map(|arg| match arg {
+                    Value::String(arg) => Ok(arg),
+                    _ => Err(Error::InvalidArgument(format!("Command {}: Arguments must be strings.", i + 1))),
+                }).collect::<Result<_>>()?,
+                _ => return Err(Error::InvalidArgument(format!("Command {}: A command must be a string or an array of strings.", i + 1))),
+            };
+            Ok((format!("Command {}", i + 1), args))
+        }).collect();
+    }
 
-        let stdin = io::stdin();
-        let mut stdout = io::stdout();
-        let mut local_only_inp_buf = String::new();
+    let mut commands = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let args = split_args(line).map_err(|msg| Error::InvalidArgument(format!("Line {}: {}", i + 1, msg)))?;
+        if !args.is_empty() {
+            commands.push((format!("Line {}", i + 1), args));
+        }
+    }
+    Ok(commands)
+}
 
-        loop {
-            print!("Create a local only instance (y/n)? ");
-            stdout.flush()?;
-            local_only_inp_buf.clear();
-            stdin.read_line(&mut local_only_inp_buf)?;
-            local_only_inp_buf = local_only_inp_buf.to_lowercase().trim().to_string();
+/// Parses a time of day given as HH:MM.
+fn parse_time(s: &str) -> std::result::Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| format!("invalid time '{}', expected HH:MM", s))
+}
 
-            if &local_only_inp_buf != "y" && &local_only_inp_buf != "n" {
-                eprintln!("Invalid option.");
-                continue;
-            }
-            break;
+/// Parses weekday(s) or a date with an optional time of day, such as mon, weekend, mon@18:00 or
+/// "next fri@18:00".
+fn parse_day_spec(s: &str) -> std::result::Result<DaySpec, String> {
+    let (days, time) = match s.split_once('@') {
+        Some((days, time)) => (days, Some(parse_time(time)?)),
+        None => (s, None),
+    };
+    let when = match Days::from_str(days, true) {
+        Ok(days) => When::Days(days),
+        Err(_) => When::Date(parse_date_arg(days)?),
+    };
+    Ok(DaySpec { when, time })
+}
+
+/// Parses the target of `postpone`, either a number of days such as `+3` or a date.
+fn parse_postponement(s: &str) -> std::result::Result<Postponement, String> {
+    match s.strip_prefix('+') {
+        Some(days) => days.parse()
+            .map(Postponement::Days)
+            .map_err(|_| format!("invalid number of days '{}'", days)),
+        None => parse_date_arg(s).map(Postponement::Date),
+    }
+}
+
+/// Formats the progress of a sync as a single line with a progress bar while receiving items.
+fn format_progress(progress: &SyncProgress) -> String {
+    match progress.phase {
+        SyncPhase::Connecting => "Connecting to the server...".to_string(),
+        SyncPhase::Handshake => "Authenticating...".to_string(),
+        SyncPhase::Receiving if progress.bytes_expected > 0 => {
+            const WIDTH: u64 = 20;
+            let filled = progress.bytes_received * WIDTH / progress.bytes_expected;
+            format!(
+                "Receiving items [{}{}] {} / {}",
+                "#".repeat(filled as usize),
+                ".".repeat((WIDTH - filled) as usize),
+                format_bytes(progress.bytes_received),
+                format_bytes(progress.bytes_expected)
+            )
         }
+        SyncPhase::Receiving => "Receiving items...".to_string(),
+        SyncPhase::Merging => format!("Merging {} items...", progress.items_received),
+        SyncPhase::Sending => format!("Sending {} items ({})...", progress.items_sent, format_bytes(progress.bytes_sent)),
+        SyncPhase::Done => "Done.".to_string(),
+    }
+}
 
-        let local_only = &local_only_inp_buf == "y";
-        let mut encryption_passwd;
-        let mut socket_addr = String::new();
+/// Formats what was transferred during a sync that took the given time.
+fn format_sync_details(progress: SyncProgress, elapsed: Duration) -> String {
+    format!(
+        "Sent {} item(s) ({}) and received {} item(s) ({}) in {} ms.",
+        progress.items_sent,
+        format_bytes(progress.bytes_sent),
+        progress.items_received,
+        format_bytes(progress.bytes_received),
+        elapsed.as_millis()
+    )
+}
 
-        if local_only {
-            socket_addr = "127.0.0.1:55995".to_string();
-            // Even though the random password wont be used in local only instances, I feel that
-            // it is better to create a random password rather than hardcode some value.
-            encryption_passwd = rand::thread_rng()
-                .sample_iter(&Alphanumeric)
-                .take(16)
-                .map(char::from)
-                .collect();
-        } else {
-            loop {
-                print!("Input server socket address (ADDRESS:PORT): ");
-                stdout.flush()?;
-                socket_addr.clear();
-                stdin.read_line(&mut socket_addr)?;
-                socket_addr = socket_addr.trim().to_string();
+/// Tells which items were changed both locally and on the server and how the sync resolved them.
+/// Conflicts that were left for the user are always told.
+fn report_conflicts(conflicts: &[Conflict]) {
+    for conflict in conflicts {
+        match conflict.resolution {
+            ConflictResolution::KeptLocal => detail!("'{}' was also changed on the server as '{}'. Kept the local version.", conflict.local, conflict.remote),
+            ConflictResolution::KeptRemote => detail!("'{}' was also changed on the server as '{}'. Kept the server's version.", conflict.local, conflict.remote),
+            ConflictResolution::KeptBoth => say!("'{}' was also changed on the server as '{}'. Kept both, merge them by hand.", conflict.local, conflict.remote),
+        }
+    }
+}
 
-                if socket_addr.to_socket_addrs().is_err() {
-                    eprintln!("Cannot parse '{}' to socket address.", socket_addr);
-                    continue;
-                }
-                break;
-            }
+/// Returns how long `sync --watch` waits before the next sync, or `sync --retries` before the next
+/// try, after the given number of failed syncs in a row. The wait doubles with each failure so that
+/// an unreachable server isn't contacted every interval, but it never gets longer than
+/// `MAX_RETRY_DELAY` or the interval.
+fn retry_delay(interval: Duration, failures: u32) -> Duration {
+    let delay = interval.saturating_mul(2u32.saturating_pow(failures.min(16)));
+    delay.min(MAX_RETRY_DELAY.max(interval))
+}
 
-            println!("Note! Encryption password is stored in cleartext but obfuscated locally.");
+/// Returns `true` if a failed sync may succeed when tried again, such as when the server can't be
+/// reached. Wrong passwords and refused syncs fail the same way every time.
+fn is_transient(error: &Error) -> bool {
+    matches!(error, Error::IOErr(_) | Error::SrvLookupFailed(_) | Error::ProxyFailed(_) | Error::TorFailed(_))
+}
 
-            let mut encryption_passwd_again;
+/// Formats a number of bytes with a binary unit.
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
 
-            loop {
-                encryption_passwd = rpassword::prompt_password("Input encryption password: ")?;
-                encryption_passwd_again = rpassword::prompt_password("Input encryption password again: ")?;
+/// Splits a line of batch input into a body and a trailing weekday such as mon or fri@18:00.
+/// Returns `None` for empty lines.
+fn parse_batch_line(line: &str) -> Option<(String, Option<DaySpec>)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
 
-                if encryption_passwd != encryption_passwd_again {
-                    eprintln!("Passwords do not match.");
-                    continue;
-                } else if encryption_passwd.is_empty() {
-                    eprintln!("Password cannot be empty.");
-                    continue;
-                }
-                break;
+    if let Some((body, last)) = line.rsplit_once(char::is_whitespace) {
+        if let Ok(spec) = parse_day_spec(last) {
+            if matches!(spec.when, When::Days(_)) {
+                return Some((body.trim_end().to_string(), Some(spec)));
             }
         }
+    }
+    Some((line.to_string(), None))
+}
+
+/// Formats how many days late an item is.
+fn format_days_overdue(days: u64) -> String {
+    if days == 1 {
+        "1 day overdue".to_string()
+    } else {
+        format!("{} days overdue", days)
+    }
+}
 
-        let mut save_location_buf = String::new();
+/// Returns the summary and the body of a notification that lists the undone items of a day. Todos
+/// come first, each item on its own line.
+fn agenda_notification(day: &DayView) -> (String, String) {
+    let summary = match (day.undone_todos.len(), day.undone_tasks.len() + day.missed_tasks.len()) {
+        (0, 0) => "Nothing left to do today".to_string(),
+        (todos, 0) => format!("{} todo(s) for today", todos),
+        (0, tasks) => format!("{} task(s) for today", tasks),
+        (todos, tasks) => format!("{} todo(s) and {} task(s) for today", todos, tasks),
+    };
 
-        loop {
-            print!("Input save path (Leave empty for default): ");
-            stdout.flush()?;
-            save_location_buf.clear();
-            stdin.read_line(&mut save_location_buf)?;
-            save_location_buf = save_location_buf.trim().to_string();
+    let todos = day.undone_todos.iter().map(|todo| {
+        let mut line = todo.body().to_string();
+        if let Some(time) = todo.time() {
+            line = format!("{} {}", time.format("%H:%M"), line);
+        }
+        match todo.days_overdue(day.date) {
+            0 => line,
+            days => format!("{} ({})", line, format_days_overdue(days)),
+        }
+    });
+    let tasks = day.undone_tasks.iter().map(|task| match task.time_for(day.date.weekday()) {
+        Some(time) => format!("{} {}", time.format("%H:%M"), task.body()),
+        None => task.body().to_string(),
+    });
+    let missed = day.missed_tasks.iter().map(|task| format!("{} ({})", task.body(), format_days_overdue(days_missed(task, day.date))));
 
-            if save_location_buf.parse::<PathBuf>().is_err() && &save_location_buf != "" {
-                eprintln!("Cannot parse '{}' to path.", save_location_buf);
-                continue;
+    (summary, todos.chain(tasks).chain(missed).collect::<Vec<String>>().join("\n"))
+}
+
+/// Returns how many days before `date` a task shown late was missed.
+fn days_missed(task: &Task, date: NaiveDate) -> u64 {
+    task.missed_date(date).map_or(0, |missed| (date - missed).num_days() as u64)
+}
+
+/// Returns the dates from `from` to `to`, both included.
+fn date_range(from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>> {
+    if to < from {
+        return Err(Error::InvalidArgument("The end of the date range is before its start.".to_string()));
+    }
+    Ok(iter::successors(Some(from), |day| day.succ_opt()).take_while(|day| *day <= to).collect())
+}
+
+/// Parses a date such as 2024-08-20, tomorrow, "next fri", "in 3 days" or "aug 20".
+fn parse_date_arg(s: &str) -> std::result::Result<NaiveDate, String> {
+    dateparse::parse_date(s).map_err(|_| format!("unrecognized date '{}'", s))
+}
+
+/// The days shown by `show`.
+enum ShownDays {
+    /// The 7 days starting from the date.
+    Week(NaiveDate),
+    Dates(Vec<NaiveDate>),
+}
+
+/// How far `postpone` moves a todo.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Postponement {
+    Days(u32),
+    Date(NaiveDate),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+enum ItemType {
+    Todo,
+    Task,
+}
+
+// Define custom weekday for clap to parse weekdays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<Weekday> for chrono::Weekday {
+    fn from(val: Weekday) -> Self {
+        match val {
+            Weekday::Mon => { chrono::Weekday::Mon }
+            Weekday::Tue => { chrono::Weekday::Tue }
+            Weekday::Wed => { chrono::Weekday::Wed }
+            Weekday::Thu => { chrono::Weekday::Thu }
+            Weekday::Fri => { chrono::Weekday::Fri }
+            Weekday::Sat => { chrono::Weekday::Sat }
+            Weekday::Sun => { chrono::Weekday::Sun }
+        }
+    }
+}
+
+// Weekdays and groups of weekdays accepted when adding items.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum Days {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+    #[clap(alias = "weekday")]
+    Weekdays,
+    Weekend,
+    Daily,
+}
+
+/// Weekday(s) or a date given as an argument with an optional time of day.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct DaySpec {
+    when: When,
+    time: Option<NaiveTime>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum When {
+    Days(Days),
+    Date(NaiveDate),
+}
+
+impl DaySpec {
+    /// Returns the times given for weekdays. A later time for the same weekday overrides an earlier one.
+    fn weekday_times(specs: &[DaySpec]) -> Vec<(Weekday, NaiveTime)> {
+        let mut times: Vec<(Weekday, NaiveTime)> = Vec::new();
+        for spec in specs {
+            if let (When::Days(days), Some(time)) = (spec.when, spec.time) {
+                for wd in Days::expand(&[days]) {
+                    times.retain(|(other, _)| *other != wd);
+                    times.push((wd, time));
+                }
+            }
+        }
+        times
+    }
+}
+
+impl Days {
+    /// Expands the given days into weekdays. Duplicates are removed keeping the first occurrence.
+    fn expand(days: &[Days]) -> Vec<Weekday> {
+        let mut weekdays = Vec::new();
+        for day in days {
+            let group: &[Weekday] = match day {
+                Days::Mon => &[Weekday::Mon],
+                Days::Tue => &[Weekday::Tue],
+                Days::Wed => &[Weekday::Wed],
+                Days::Thu => &[Weekday::Thu],
+                Days::Fri => &[Weekday::Fri],
+                Days::Sat => &[Weekday::Sat],
+                Days::Sun => &[Weekday::Sun],
+                Days::Weekdays => &[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+                Days::Weekend => &[Weekday::Sat, Weekday::Sun],
+                Days::Daily => &[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun],
+            };
+            for wd in group {
+                if !weekdays.contains(wd) {
+                    weekdays.push(*wd);
+                }
             }
-            break;
         }
+        weekdays
+    }
+}
 
-        let save_path;
+/// When to use colors in the output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum ColorWhen {
+    Always,
+    Never,
+    Auto,
+}
 
-        if &save_location_buf == "" {
-            save_path = MtdApp::default_save_path()?;
-        } else {
-            save_path = save_location_buf.parse().unwrap();
+impl ColorWhen {
+    /// Returns `true` if colors should be used.
+    fn use_color(self) -> bool {
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal(),
         }
+    }
+}
 
-        let conf = Config::new(
-            socket_addr.parse().unwrap(),
-            encryption_passwd.into_bytes(),
-            Duration::from_secs(30),
-            Some(save_path),
-            local_only,
-        );
+/// Levels of the messages logged by `server`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum LogLevelArg {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
 
-        if let Some(conf_dir) = config_path.parent() {
-            fs::create_dir_all(conf_dir)?;
+impl From<LogLevelArg> for tracing::Level {
+    fn from(val: LogLevelArg) -> Self {
+        match val {
+            LogLevelArg::Error => tracing::Level::ERROR,
+            LogLevelArg::Warn => tracing::Level::WARN,
+            LogLevelArg::Info => tracing::Level::INFO,
+            LogLevelArg::Debug => tracing::Level::DEBUG,
+            LogLevelArg::Trace => tracing::Level::TRACE,
         }
-        fs::write(&config_path, conf.to_json()?)?;
+    }
+}
 
-        Ok(conf)
+/// Theme presets that can be chosen with `--theme`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum ThemeArg {
+    Default,
+    ColorblindSafe,
+    HighContrast,
+    Monochrome,
+}
+
+impl From<ThemeArg> for ThemePreset {
+    fn from(val: ThemeArg) -> Self {
+        match val {
+            ThemeArg::Default => ThemePreset::Default,
+            ThemeArg::ColorblindSafe => ThemePreset::ColorblindSafe,
+            ThemeArg::HighContrast => ThemePreset::HighContrast,
+            ThemeArg::Monochrome => ThemePreset::Monochrome,
+        }
     }
+}
 
-    /// Runs the mtd cli app.
-    fn run() -> Result<()> {
-        let cli = CliArgs::parse();
-        let config_path = cli.config_file.unwrap_or(MtdApp::default_config_path()?);
+/// Formats items can be exported to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum ExportFormat {
+    Json,
+    Ical,
+    Csv,
+    #[clap(alias = "markdown")]
+    Md,
+}
 
-        let app;
+/// Formats items can be imported from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum ImportFormat {
+    #[clap(alias = "todotxt")]
+    TodoTxt,
+    #[clap(alias = "ics")]
+    Ical,
+    Csv,
+    Taskwarrior,
+}
 
-        // Re-init is checked here because it should run without reading previous values.
-        if let Commands::ReInit = &cli.command {
-            app = MtdApp::re_init(&config_path)?;
-        } else {
-            app = MtdApp::init(&config_path)?.handle_command(cli.command)?;
+/// Output formats of commands that print items.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum Output {
+    /// Human-readable text
+    Text,
+    /// A JSON array of records, or an object for stats
+    Json,
+    /// Tab-separated records with a header line
+    Tsv,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ArgEnum)]
+enum Sort {
+    Manual,
+    Alpha,
+    Created,
+    Priority,
+}
+
+impl From<Sort> for SortOrder {
+    fn from(val: Sort) -> Self {
+        match val {
+            Sort::Manual => { SortOrder::Manual }
+            Sort::Alpha => { SortOrder::Alphabetical }
+            Sort::Created => { SortOrder::Created }
+            Sort::Priority => { SortOrder::Priority }
         }
+    }
+}
 
-        if let Some(path) = app.conf.save_location() {
-            if !path.exists() {
-                if let Some(parent) = path.parent() {
-                    fs::create_dir_all(parent)?;
+/// The contents of a config file. A config file contains one or more named profiles, each of which
+/// is a complete `Config` with its own server, save location and other settings.
+#[derive(Serialize, Deserialize)]
+struct ConfigFile {
+    default_profile: String,
+    profiles: BTreeMap<String, Config>,
+}
+
+impl ConfigFile {
+    /// Name of the profile used for config files created before profiles existed.
+    const LEGACY_PROFILE: &'static str = "default";
+
+    /// Creates a new `ConfigFile` with a single profile which is also the default profile.
+    fn new(profile: &str, config: Config) -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(profile.to_string(), config);
+        Self { default_profile: profile.to_string(), profiles }
+    }
+
+    /// Creates a `ConfigFile` from a JSON string. A JSON string containing only a single `Config` is
+    /// read as a config file with a single profile.
+    fn new_from_json(json: &str) -> Result<Self> {
+        match serde_json::from_str(json) {
+            Ok(file) => Ok(file),
+            Err(e) => {
+                if let Ok(config) = Config::new_from_json(json) {
+                    Ok(Self::new(ConfigFile::LEGACY_PROFILE, config))
+                } else {
+                    Err(e.into())
                 }
             }
-            fs::write(path, app.list.to_json()?)?;
         }
+    }
+
+    /// Creates a JSON string from the `ConfigFile`.
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the name of the profile that should be used. An explicitly given profile takes
+    /// precedence over the default profile.
+    fn profile_name<'a>(&'a self, profile: Option<&'a str>) -> &'a str {
+        profile.unwrap_or(&self.default_profile)
+    }
 
+    /// Makes an existing profile the default profile.
+    fn set_default_profile(&mut self, name: &str) -> Result<()> {
+        if !self.profiles.contains_key(name) {
+            return Err(Error::InvalidArgument(format!("No profile named '{}'.", name)));
+        }
+        self.default_profile = name.to_string();
         Ok(())
     }
 
-    // Needs to take ownership because syncing needs ownership
-    fn handle_command(mut self, command: Commands) -> Result<Self> {
-        match command {
-            Commands::Show { item_type, weekday, week } => {
-                self.show(item_type, weekday, week);
-            }
-            Commands::Add { item_type, weekdays, body } => {
-                self.add(item_type, weekdays, body);
-            }
-            Commands::Remove { item_type, id } => {
-                self.remove(item_type, id)?;
-            }
-            Commands::Do { item_type, id } => {
-                self.modify_done_state(item_type, id, true)?;
-            }
-            Commands::Undo { item_type, id } => {
-                self.modify_done_state(item_type, id, false)?;
-            }
-            Commands::Set { item_type, id, body, weekdays } => {
-                self.set(item_type, id, body, weekdays)?;
-            }
-            Commands::Sync {} => {
-                self.sync()?;
-            }
-            Commands::Server {} => {
-                self.server()?;
-            }
-            // Re-init is handled earlier
-            Commands::ReInit {} => {}
+    /// Removes a profile and returns its config. The default profile can't be removed.
+    fn remove_profile(&mut self, name: &str) -> Result<Config> {
+        if name == self.default_profile {
+            return Err(Error::InvalidArgument(format!("'{}' is the default profile. Make another profile the default first.", name)));
         }
+        self.profiles.remove(name).ok_or_else(|| Error::InvalidArgument(format!("No profile named '{}'.", name)))
+    }
+}
 
-        if self.conf.local_only() {
-            self.list.self_sync();
+/// Returns how a command that runs until stopped is described to `daemon stop` and `daemon reload`,
+/// or `None` if the command returns on its own.
+fn daemon_description(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Server { action: None, .. } => Some("server"),
+        Commands::Sync { watch: true, .. } => Some("sync --watch"),
+        Commands::Show { watch: true, .. } => Some("show --watch"),
+        _ => None,
+    }
+}
+
+/// Finds the program of the extension `name` from the directories of a PATH value.
+fn find_extension(name: &str, path: &OsStr) -> Option<PathBuf> {
+    let file_name = format!("mtd-{}{}", name, env::consts::EXE_SUFFIX);
+    env::split_paths(path).map(|dir| dir.join(&file_name)).find(|program| is_executable(program))
+}
+
+/// Returns `true` if the path is a file that can be run.
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Sleeps for the duration in short steps so that `daemon stop` is noticed quickly. Returns `true`
+/// if the process was told to stop.
+fn sleep_unless_stopped(duration: Duration) -> bool {
+    let end = Instant::now() + duration;
+    while let Some(remaining) = end.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+        if stop_requested() {
+            return true;
         }
+        thread::sleep(remaining.min(Duration::from_millis(200)));
+    }
+    stop_requested()
+}
 
-        Ok(self)
+/// Describes a change to an item on a single line, such as `+ todo "Buy milk"`.
+fn describe_change(change: &ItemChange) -> String {
+    let describe = |item: &ItemRef| match item {
+        ItemRef::Todo(todo) => format!("todo \"{}\"", todo.body()),
+        ItemRef::Task(task) => format!("task \"{}\"", task.body()),
+    };
+
+    match change {
+        ItemChange::Added(item) => format!("+ {}", describe(item)),
+        ItemChange::Removed(item) => format!("- {}", describe(item)),
+        ItemChange::Modified { old, new } if old.body() != new.body() => format!("~ {} -> \"{}\"", describe(old), new.body()),
+        ItemChange::Modified { new, .. } => format!("~ {}", describe(new)),
     }
+}
 
-    fn show(&self, item_type: Option<ItemType>, weekday_opt: Option<Weekday>, week: bool) {
-        // If item type is None, show everything.
-        let show_todos = item_type.is_none() || item_type.unwrap() == ItemType::Todo;
-        let show_tasks = item_type.is_none() || item_type.unwrap() == ItemType::Task;
+fn main() {
+    if let Err(e) = MtdApp::run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    } else {
+        process::exit(0);
+    }
+}
+
+/// The last server items fetched by a client. Used for showing the server's items when the server
+/// can't be reached.
+#[derive(Serialize, Deserialize)]
+struct RemoteCache {
+    fetched_at: NaiveDateTime,
+    list: TdList,
+}
+
+/// Syncs that have failed in a row since the last successful sync. Shown by `status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SyncFailures {
+    count: u32,
+    last_failed_at: NaiveDateTime,
+    last_error: String,
+}
+
+/// An item shown for a day.
+struct ShownItem {
+    /// `Style::Todo` or `Style::Task`.
+    kind: Style,
+    time: Option<NaiveTime>,
+    text: String,
+    done: bool,
+    days_overdue: u64,
+}
+
+/// A part of a day that items with a time are grouped into when showing a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DaySection {
+    Morning,
+    Afternoon,
+    Evening,
+    Anytime,
+}
+
+impl DaySection {
+    /// Returns the section of the day the given time belongs to using the boundaries in the `Config`.
+    fn of(time: Option<NaiveTime>, conf: &Config) -> DaySection {
+        match time {
+            None => DaySection::Anytime,
+            Some(time) if time < conf.afternoon_start() => DaySection::Morning,
+            Some(time) if time < conf.evening_start() => DaySection::Afternoon,
+            Some(_) => DaySection::Evening,
+        }
+    }
+}
+
+impl fmt::Display for DaySection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DaySection::Morning => "Morning",
+            DaySection::Afternoon => "Afternoon",
+            DaySection::Evening => "Evening",
+            DaySection::Anytime => "Anytime",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A record that can be printed as a line of tab-separated values.
+trait TsvRecord {
+    /// The names of the columns, printed as the header line.
+    const COLUMNS: &'static [&'static str];
+    /// Returns the values of the columns in the same order as `COLUMNS`.
+    fn values(&self) -> Vec<String>;
+}
+
+/// An item shown for a date in the machine-readable output of `show`.
+#[derive(Serialize)]
+struct ShowRecord {
+    date: NaiveDate,
+    #[serde(rename = "type")]
+    item_type: &'static str,
+    id: u64,
+    uuid: Uuid,
+    body: String,
+    done: bool,
+    /// Formatted as HH:MM.
+    time: Option<String>,
+    /// How many days ago an undone todo should have been done. Always 0 for tasks.
+    days_overdue: u64,
+}
+
+impl TsvRecord for ShowRecord {
+    const COLUMNS: &'static [&'static str] = &["date", "type", "id", "uuid", "body", "done", "time", "days_overdue"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.date.to_string(),
+            self.item_type.to_string(),
+            self.id.to_string(),
+            self.uuid.to_string(),
+            self.body.clone(),
+            self.done.to_string(),
+            self.time.clone().unwrap_or_default(),
+            self.days_overdue.to_string(),
+        ]
+    }
+}
+
+/// An item found in the machine-readable output of `find`.
+#[derive(Serialize)]
+struct FindRecord {
+    #[serde(rename = "type")]
+    item_type: &'static str,
+    id: u64,
+    uuid: Uuid,
+    body: String,
+    /// The date of a todo, `None` for tasks.
+    date: Option<NaiveDate>,
+    /// The weekdays of a task, empty for todos.
+    weekdays: Vec<String>,
+}
+
+impl TsvRecord for FindRecord {
+    const COLUMNS: &'static [&'static str] = &["type", "id", "uuid", "body", "date", "weekdays"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.item_type.to_string(),
+            self.id.to_string(),
+            self.uuid.to_string(),
+            self.body.clone(),
+            self.date.map(|date| date.to_string()).unwrap_or_default(),
+            self.weekdays.join(","),
+        ]
+    }
+}
+
+/// An item in the output of `list`. The fields that don't apply to the type of the item are empty.
+#[derive(Serialize)]
+struct ListRecord {
+    #[serde(rename = "type")]
+    item_type: &'static str,
+    id: u64,
+    uuid: Uuid,
+    body: String,
+    /// The date of a todo.
+    date: Option<NaiveDate>,
+    /// The weekdays of a task.
+    weekdays: Vec<String>,
+    time: Option<NaiveTime>,
+    /// The priority of a todo.
+    priority: Option<u8>,
+    created: Option<NaiveDate>,
+    /// The date a todo was done.
+    done: Option<NaiveDate>,
+    /// The last date of a task.
+    until: Option<NaiveDate>,
+    /// Whether a task is paused today.
+    paused: Option<bool>,
+}
+
+impl TsvRecord for ListRecord {
+    const COLUMNS: &'static [&'static str] = &["type", "id", "uuid", "body", "date", "weekdays", "time", "priority", "created", "done", "until", "paused"];
+
+    fn values(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.item_type.to_string(),
+            self.id.to_string(),
+            self.uuid.to_string(),
+            self.body.clone(),
+            optional(self.date.map(|date| date.to_string())),
+            self.weekdays.join(","),
+            optional(self.time.map(|time| time.format("%H:%M").to_string())),
+            optional(self.priority.map(|priority| priority.to_string())),
+            optional(self.created.map(|date| date.to_string())),
+            optional(self.done.map(|date| date.to_string())),
+            optional(self.until.map(|date| date.to_string())),
+            optional(self.paused.map(|paused| paused.to_string())),
+        ]
+    }
+}
+
+/// What adding a line of batch input would do, reported by `add --dry-run`.
+#[derive(Debug, Serialize)]
+struct BatchRecord {
+    /// The number of the line in the input, starting from 1.
+    line: usize,
+    /// `add` or `invalid`.
+    action: &'static str,
+    #[serde(rename = "type")]
+    item_type: &'static str,
+    body: String,
+    /// The date of a todo that would be added.
+    date: Option<NaiveDate>,
+    /// The weekdays of a task that would be added.
+    weekdays: Vec<String>,
+    /// Why the line is invalid.
+    reason: Option<String>,
+}
+
+impl TsvRecord for BatchRecord {
+    const COLUMNS: &'static [&'static str] = &["line", "action", "type", "body", "date", "weekdays", "reason"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.line.to_string(),
+            self.action.to_string(),
+            self.item_type.to_string(),
+            self.body.clone(),
+            self.date.map(|date| date.to_string()).unwrap_or_default(),
+            self.weekdays.join(","),
+            self.reason.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// A week or a task in the tab-separated output of `stats`. Weeks are named by their first day and
+/// have no id or streaks.
+struct StatsRecord {
+    kind: &'static str,
+    id: Option<u64>,
+    name: String,
+    scheduled: usize,
+    completed: usize,
+    streaks: Option<(usize, usize)>,
+}
+
+impl TsvRecord for StatsRecord {
+    const COLUMNS: &'static [&'static str] = &["kind", "id", "name", "scheduled", "completed", "current_streak", "longest_streak"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.kind.to_string(),
+            self.id.map(|id| id.to_string()).unwrap_or_default(),
+            self.name.clone(),
+            self.scheduled.to_string(),
+            self.completed.to_string(),
+            self.streaks.map(|(current, _)| current.to_string()).unwrap_or_default(),
+            self.streaks.map(|(_, longest)| longest.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+/// A todo or a task in the tab-separated output of `report stale`. Todos have no missed count and
+/// tasks no postponement count, overdue days or age.
+struct StaleRecord {
+    kind: &'static str,
+    id: u64,
+    body: String,
+    postponed: Option<u32>,
+    days_overdue: Option<u64>,
+    age_days: Option<i64>,
+    missed_in_row: Option<usize>,
+}
+
+impl TsvRecord for StaleRecord {
+    const COLUMNS: &'static [&'static str] = &["kind", "id", "body", "postponed", "days_overdue", "age_days", "missed_in_row"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.kind.to_string(),
+            self.id.to_string(),
+            self.body.clone(),
+            self.postponed.map(|n| n.to_string()).unwrap_or_default(),
+            self.days_overdue.map(|n| n.to_string()).unwrap_or_default(),
+            self.age_days.map(|n| n.to_string()).unwrap_or_default(),
+            self.missed_in_row.map(|n| n.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+/// Formats records as tab-separated values with a header line. Tabs and line breaks within values
+/// are replaced with spaces.
+fn format_tsv<R: TsvRecord>(records: &[R]) -> String {
+    let mut lines = vec![R::COLUMNS.join("\t")];
+    for record in records {
+        let values: Vec<String> = record.values().iter()
+            .map(|value| value.replace(['\t', '\n', '\r'], " "))
+            .collect();
+        lines.push(values.join("\t"));
+    }
+    lines.join("\n")
+}
+
+/// Prints records in the given machine-readable format.
+fn print_records<R: TsvRecord + Serialize>(records: &[R], output: Output) -> Result<()> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(records)?),
+        Output::Tsv => println!("{}", format_tsv(records)),
+        Output::Text => {}
+    }
+    Ok(())
+}
+
+impl TsvRecord for Outcome {
+    const COLUMNS: &'static [&'static str] = &["source", "action", "type", "body", "id", "losses", "reason"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.source.to_string(),
+            action_name(self.action).to_string(),
+            self.item_type.unwrap_or_default().to_string(),
+            self.body.clone(),
+            self.id.map(|id| id.to_string()).unwrap_or_default(),
+            self.losses.join(","),
+            self.reason.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Returns the name of an import action used in reports.
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Create => "create",
+        Action::Update => "update",
+        Action::Unchanged => "unchanged",
+        Action::Skip => "skip",
+    }
+}
+
+/// A complete human-readable dump of everything mtd stores for a profile.
+#[derive(Serialize)]
+struct DataExport {
+    exported_at: NaiveDateTime,
+    config: serde_json::Value,
+    items: TdList,
+    cached_server_items: Option<RemoteCache>,
+    backups: Vec<PathBuf>,
+}
+
+struct MtdApp {
+    conf: Config,
+    list: TdList,
+}
+
+impl MtdApp {
+    /// Initializes a new MtdApp. Reads/creates config and saved items. If the given profile doesn't
+    /// exist yet, it is created.
+    fn init(config_path: &PathBuf, profile: Option<&str>) -> Result<Self> {
+        let config_file = MtdApp::read_config_file(config_path)?;
+
+        let existing_conf = config_file.as_ref().and_then(|file| {
+            file.profiles.get(file.profile_name(profile)).cloned()
+        });
+
+        let conf = match existing_conf {
+            Some(conf) => conf,
+            None => MtdApp::create_new_config(config_path, profile, None)?,
+        };
+
+        let list;
+
+        // It is possible that a save_location has not been defined which needs to be checked before
+        // checking if the path even exists.
+        if let Some(list_path) = conf.save_location() {
+            if list_path.exists() {
+                list = read_list_file(&conf, list_path)?;
+            } else {
+                list = MtdApp::create_new_list(&conf)?;
+            }
+        } else {
+            list = MtdApp::create_new_list(&conf)?;
+        }
+
+        Ok(Self {
+            conf,
+            list,
+        })
+    }
+
+    /// Creates a new TdList as a server or a client depending on user input.
+    fn create_new_list(config: &Config) -> Result<TdList> {
+        let mut buffer = String::new();
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+
+        if config.local_only() {
+            buffer = "c".to_string();
+        } else {
+            loop {
+                print!("Initialize as a server or a client (s/c)? ");
+                stdout.flush()?;
+                buffer.clear();
+                // Without an answer, such as when stdin isn't a terminal, asking again won't help.
+                if stdin.read_line(&mut buffer)? == 0 {
+                    println!();
+                    return Err(Error::IOErr(io::Error::new(io::ErrorKind::UnexpectedEof, "no answer was given")));
+                }
+                buffer = buffer.to_lowercase().trim().to_string();
+
+                if &buffer != "s" && &buffer != "c" {
+                    eprintln!("Invalid option.");
+                    continue;
+                }
+                break;
+            }
+        }
+
+        if &buffer == "c" {
+            Ok(TdList::new_client())
+        } else {
+            Ok(TdList::new_server())
+        }
+    }
+
+    /// Reads the config file if it exists.
+    fn read_config_file(config_path: &PathBuf) -> Result<Option<ConfigFile>> {
+        if config_path.exists() {
+            Ok(Some(ConfigFile::new_from_json(&fs::read_to_string(config_path)?)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the profile to use from the command line argument or the MTD_PROFILE environment
+    /// variable.
+    fn selected_profile(arg: Option<String>) -> Option<String> {
+        arg.or_else(|| env::var("MTD_PROFILE").ok())
+    }
+
+    /// Returns the path to the config.
+    fn default_config_path() -> Result<PathBuf> {
+        Ok(dirs::config_dir().ok_or(Error::Unknown)?.join("mtd/conf.json"))
+    }
+
+    /// Returns the path to the default save location. Each profile other than the default one has
+    /// its own save location.
+    fn default_save_path(profile: Option<&str>) -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().ok_or(Error::Unknown)?.join("mtd");
+
+        match profile {
+            Some(name) if name != ConfigFile::LEGACY_PROFILE => Ok(data_dir.join(format!("data-{}.json", name))),
+            _ => Ok(data_dir.join("data.json")),
+        }
+    }
+
+    /// Asks a yes/no question until a valid answer is given.
+    fn prompt_yes_no(question: &str) -> Result<bool> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        let mut buffer = String::new();
+
+        loop {
+            print!("{} (y/n)? ", question);
+            stdout.flush()?;
+            buffer.clear();
+            // Without an answer, such as when stdin isn't a terminal, asking again won't help.
+            if stdin.read_line(&mut buffer)? == 0 {
+                println!();
+                return Err(Error::IOErr(io::Error::new(io::ErrorKind::UnexpectedEof, "no answer was given")));
+            }
+            buffer = buffer.to_lowercase().trim().to_string();
+
+            if &buffer != "y" && &buffer != "n" {
+                eprintln!("Invalid option.");
+                continue;
+            }
+            return Ok(&buffer == "y");
+        }
+    }
+
+    /// Asks for a value suggesting a default. Returns the default if the input is left empty.
+    fn prompt_with_default(question: &str, default: &str) -> Result<String> {
+        let mut buffer = String::new();
+
+        print!("{} [{}]: ", question, default);
+        io::stdout().flush()?;
+        io::stdin().read_line(&mut buffer)?;
+
+        let input = buffer.trim();
+        if input.is_empty() {
+            Ok(default.to_string())
+        } else {
+            Ok(input.to_string())
+        }
+    }
+
+    /// Asks for a new encryption password. If a previous password exists, leaving the password empty
+    /// keeps it.
+    fn prompt_encryption_password(previous: Option<&Vec<u8>>) -> Result<Vec<u8>> {
+        println!("Note! Unless it's stored in the system keyring, the encryption password is stored in cleartext but obfuscated locally.");
+
+        let question = if previous.is_some() {
+            "Input encryption password (Leave empty to keep the current one): "
+        } else {
+            "Input encryption password: "
+        };
+
+        loop {
+            let encryption_passwd = rpassword::prompt_password(question)?;
+
+            if encryption_passwd.is_empty() {
+                if let Some(passwd) = previous {
+                    return Ok(passwd.clone());
+                }
+                eprintln!("Password cannot be empty.");
+                continue;
+            }
+
+            let encryption_passwd_again = rpassword::prompt_password("Input encryption password again: ")?;
+
+            if encryption_passwd != encryption_passwd_again {
+                eprintln!("Passwords do not match.");
+                continue;
+            }
+            return Ok(encryption_passwd.into_bytes());
+        }
+    }
+
+    /// Asks for the password of a server's user until a non-empty one is given.
+    fn prompt_user_password(name: &str) -> Result<String> {
+        loop {
+            let password = rpassword::prompt_password(format!("Input the password of {}: ", name))?;
+            if password.is_empty() {
+                eprintln!("Password cannot be empty.");
+                continue;
+            }
+            if rpassword::prompt_password(format!("Input the password of {} again: ", name))? != password {
+                eprintln!("Passwords do not match.");
+                continue;
+            }
+            return Ok(password);
+        }
+    }
+
+    /// Runs the guided setup for a new config and writes it to the config file. If a profile is
+    /// given, the config is saved as that profile. Otherwise the config replaces the default profile.
+    /// Other profiles in the config file are kept as they are. When reconfiguring, the values of the
+    /// previous config are suggested as defaults.
+    fn create_new_config(config_path: &PathBuf, profile: Option<&str>, previous: Option<&Config>) -> Result<Config> {
+        match (profile, previous) {
+            (Some(name), None) => println!("Creating a new config for profile '{}'.", name),
+            (None, None) => println!("Creating a new config."),
+            (Some(name), Some(_)) => println!("Reconfiguring profile '{}'. Saved items are kept.", name),
+            (None, Some(_)) => println!("Reconfiguring mtd. Saved items are kept."),
+        }
+
+        let local_only = MtdApp::prompt_yes_no("Create a local only instance")?;
+        let encryption_passwd;
+        let mut socket_addr;
+        let mut in_keyring = false;
+
+        // Previous values are only useful as defaults if the previous config wasn't local only.
+        let previous_online = previous.filter(|conf| !conf.local_only());
+
+        if local_only {
+            socket_addr = "127.0.0.1:55995".to_string();
+            // Even though the random password wont be used in local only instances, I feel that
+            // it is better to create a random password rather than hardcode some value.
+            encryption_passwd = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(16)
+                .map(char::from)
+                .collect::<String>()
+                .into_bytes();
+        } else {
+            let mut default_addr = previous_online.map_or("localhost:55995", |conf| conf.socket_addr()).to_string();
+            let mut previous_passwd = previous_online.and_then(|conf| {
+                if conf.password_in_keyring() && conf.encryption_password().is_empty() {
+                    keyring_password_entry(conf).ok()?.get_password().ok().map(String::into_bytes)
+                } else {
+                    Some(conf.encryption_password().clone())
+                }
+            });
+
+            loop {
+                socket_addr = MtdApp::prompt_with_default("Input server socket address (ADDRESS:PORT)", &default_addr)?;
+
+                if socket_addr.to_socket_addrs().is_err() {
+                    eprintln!("Cannot parse '{}' to socket address.", socket_addr);
+                    continue;
+                }
+
+                let passwd = MtdApp::prompt_encryption_password(previous_passwd.as_ref())?;
+
+                // Validate the address and the password by doing a handshake with the server.
+                let test_conf = Config::new(socket_addr.clone(), passwd.clone(), Duration::from_secs(5), None, false);
+                print!("Testing the connection to '{}'... ", socket_addr);
+                io::stdout().flush()?;
+
+                match MtdNetMgr::new(&mut TdList::new_client(), &test_conf).check_connection() {
+                    Ok(_) => {
+                        println!("ok.");
+                    }
+                    Err(e) => {
+                        println!("failed: {}", e);
+                        println!("This is expected if you are configuring the server itself or the server isn't running yet.");
+                        if !MtdApp::prompt_yes_no("Use these values anyway")? {
+                            default_addr = socket_addr;
+                            previous_passwd = Some(passwd);
+                            continue;
+                        }
+                    }
+                }
+
+                encryption_passwd = passwd;
+                break;
+            }
+
+            in_keyring = MtdApp::prompt_yes_no("Store the encryption password in the system keyring instead of the config file")?;
+        }
+
+        let default_save_path = match previous.and_then(|conf| conf.save_location()) {
+            Some(path) => path.clone(),
+            None => MtdApp::default_save_path(profile)?,
+        };
+
+        let save_path: PathBuf = loop {
+            let input = MtdApp::prompt_with_default("Input save path", &default_save_path.to_string_lossy())?;
+
+            match input.parse() {
+                Ok(path) => break path,
+                Err(_) => eprintln!("Cannot parse '{}' to path.", input),
+            }
+        };
+
+        let mut conf = Config::new(
+            socket_addr,
+            encryption_passwd,
+            previous.map_or(Duration::from_secs(30), |conf| conf.timeout()),
+            Some(save_path),
+            local_only,
+        );
+        conf.set_password_in_keyring(in_keyring);
+
+        if in_keyring {
+            // Only the config file is written without the password, this process keeps using it.
+            let password = String::from_utf8_lossy(conf.encryption_password()).to_string();
+            keyring_password_entry(&conf)?.set_password(&password).map_err(keyring_err)?;
+            let mut stored = conf.clone();
+            stored.set_encryption_password(Vec::new());
+            MtdApp::write_profile_config(config_path, profile, &stored)?;
+        } else {
+            MtdApp::write_profile_config(config_path, profile, &conf)?;
+        }
+
+        Ok(conf)
+    }
+
+    /// Writes the config of a profile to the config file. If no profile is given, the config
+    /// replaces the default profile. Other profiles in the config file are kept as they are.
+    fn write_profile_config(config_path: &PathBuf, profile: Option<&str>, conf: &Config) -> Result<()> {
+        let config_file = match MtdApp::read_config_file(config_path)? {
+            Some(mut file) => {
+                let name = file.profile_name(profile).to_string();
+                file.profiles.insert(name, conf.clone());
+                file
+            }
+            None => ConfigFile::new(profile.unwrap_or(ConfigFile::LEGACY_PROFILE), conf.clone()),
+        };
+
+        MtdApp::write_config_file(config_path, &config_file)
+    }
+
+    /// Writes the config file, creating its directory if needed.
+    fn write_config_file(config_path: &PathBuf, config_file: &ConfigFile) -> Result<()> {
+        if let Some(conf_dir) = config_path.parent() {
+            fs::create_dir_all(conf_dir)?;
+        }
+        fs::write(config_path, config_file.to_json()?)?;
+
+        Ok(())
+    }
+
+    /// Lists, creates and removes profiles and changes the default profile.
+    fn profile_command(config_path: &PathBuf, action: ProfileAction) -> Result<()> {
+        match action {
+            ProfileAction::List => {
+                let file = match MtdApp::read_config_file(config_path)? {
+                    Some(file) => file,
+                    None => {
+                        println!("No profiles. Run 'mtd init' to create one.");
+                        return Ok(());
+                    }
+                };
+                for (name, conf) in &file.profiles {
+                    let marker = if *name == file.default_profile { "*" } else { " " };
+                    let save_location = conf.save_location().map_or("(not saved)".to_string(), |path| path.display().to_string());
+                    println!("{} {}  {}", marker, name, save_location);
+                }
+            }
+            ProfileAction::Create { name, default } => {
+                if MtdApp::read_config_file(config_path)?.is_some_and(|file| file.profiles.contains_key(&name)) {
+                    return Err(Error::InvalidArgument(format!("Profile '{}' already exists.", name)));
+                }
+
+                // Using a profile that doesn't exist yet creates it.
+                MtdApp::init(config_path, Some(&name))?.save_list()?;
+
+                if default {
+                    let mut file = MtdApp::read_config_file(config_path)?.ok_or(Error::Unknown)?;
+                    file.set_default_profile(&name)?;
+                    MtdApp::write_config_file(config_path, &file)?;
+                }
+            }
+            ProfileAction::Remove { name, keep_items, force } => {
+                let mut file = MtdApp::read_config_file(config_path)?
+                    .ok_or_else(|| Error::InvalidArgument(format!("No profile named '{}'.", name)))?;
+                let conf = file.remove_profile(&name)?;
+
+                if !force && !MtdApp::prompt_yes_no(&format!("Remove profile '{}'", name))? {
+                    println!("Abort!");
+                    return Ok(());
+                }
+
+                if let Some(path) = conf.save_location().filter(|_| !keep_items) {
+                    if let Some(backup_path) = MtdApp::backup_file(path)? {
+                        say!("Created a backup of '{}' at '{}'.", path.display(), backup_path.display());
+                        fs::remove_file(path)?;
+                    }
+                    let mut cache_name = path.file_name().unwrap_or_default().to_os_string();
+                    cache_name.push(".remote");
+                    let _ = fs::remove_file(path.with_file_name(cache_name));
+                }
+
+                MtdApp::write_config_file(config_path, &file)?;
+            }
+            ProfileAction::Default { name } => {
+                let mut file = MtdApp::read_config_file(config_path)?
+                    .ok_or_else(|| Error::InvalidArgument(format!("No profile named '{}'.", name)))?;
+                file.set_default_profile(&name)?;
+                MtdApp::write_config_file(config_path, &file)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows or changes the config of a profile. Changes are validated before they are written.
+    fn config_command(config_path: &PathBuf, profile: Option<&str>, action: ConfigAction) -> Result<Self> {
+        let mut app = MtdApp::init(config_path, profile)?;
+
+        match action {
+            ConfigAction::List { show_secrets } => {
+                if let Value::Object(settings) = serde_json::to_value(&app.conf)? {
+                    for (key, value) in settings {
+                        println!("{} = {}", key, format_setting(&key, &value, show_secrets));
+                    }
+                }
+            }
+            ConfigAction::Get { key, show_secrets } => {
+                let json = serde_json::to_value(&app.conf)?;
+                println!("{}", format_setting(&key, setting(&json, &key)?, show_secrets));
+            }
+            ConfigAction::Set { key, value } => {
+                let value = match value {
+                    Some(value) => value,
+                    None if SECRET_SETTINGS.contains(&key.as_str()) => rpassword::prompt_password(format!("Input {}: ", key))?,
+                    None => return Err(Error::InvalidArgument(format!("A value for \"{}\" is required.", key))),
+                };
+                app.conf = change_setting(&app.conf, &key, Some(&value))?;
+                MtdApp::write_profile_config(config_path, profile, &app.conf)?;
+            }
+            ConfigAction::Unset { key } => {
+                app.conf = change_setting(&app.conf, &key, None)?;
+                MtdApp::write_profile_config(config_path, profile, &app.conf)?;
+            }
+        }
+
+        Ok(app)
+    }
+
+    /// Runs the mtd cli app.
+    fn run() -> Result<()> {
+        let cli = CliArgs::parse();
+
+        if cli.quiet {
+            set_verbosity(Verbosity::Quiet);
+        } else if cli.verbose {
+            set_verbosity(Verbosity::Verbose);
+        }
+
+        let color = if cli.no_color { ColorWhen::Never } else { cli.color };
+
+        // The demo doesn't use the config or the save file at all.
+        if let Commands::Demo { args } = cli.command {
+            return MtdApp::demo(args, cli.ascii, color.use_color(), cli.theme.map(ThemePreset::from));
+        }
+
+        let config_path = cli.config_file.unwrap_or(MtdApp::default_config_path()?);
+        let profile = MtdApp::selected_profile(cli.profile);
+        detail!("Using config file '{}'.", config_path.display());
+        if let Some(name) = &profile {
+            detail!("Using profile '{}'.", name);
+        }
+
+        // Profiles are managed without using any of them.
+        if let Commands::Profile { action } = cli.command {
+            return MtdApp::profile_command(&config_path, action);
+        }
+
+        if let Commands::Daemon { action } = cli.command {
+            return MtdApp::daemon_command(action);
+        }
+
+        // Extensions read the items themselves, so the profile isn't initialized for them.
+        if let Commands::External(args) = cli.command {
+            return MtdApp::run_external(&config_path, profile.as_deref(), color.use_color(), args);
+        }
+
+        // Restoring works without an initialized profile, such as on a new device.
+        if let Commands::Backup { action: BackupAction::Restore { file, password, yes } } = cli.command {
+            return MtdApp::restore_backup(&config_path, profile.as_deref(), &file, password, yes);
+        }
+
+        let app;
+
+        // Re-init is checked here because it should run without reading previous values.
+        if let Commands::ReInit { yes, keep_config } = &cli.command {
+            app = MtdApp::re_init(&config_path, profile.as_deref(), *yes, *keep_config)?;
+        } else if let Commands::Init { reconfigure } = &cli.command {
+            app = MtdApp::init_command(&config_path, profile.as_deref(), *reconfigure)?;
+        } else if let Commands::Config { action } = cli.command {
+            app = MtdApp::config_command(&config_path, profile.as_deref(), action)?;
+        } else if let Commands::Pair { code } = &cli.command {
+            app = MtdApp::pair_command(&config_path, profile.as_deref(), code)?;
+        } else {
+            let control = match daemon_description(&cli.command) {
+                Some(description) => {
+                    let control = MtdApp::bind_control_socket(description);
+                    MtdApp::stop_on_interrupt();
+                    control
+                }
+                None => None,
+            };
+
+            loop {
+                let mut initialized = MtdApp::init(&config_path, profile.as_deref())?;
+                if cli.ascii {
+                    initialized.conf.set_ascii(true);
+                }
+                if let Some(preset) = cli.theme {
+                    let mut theme = initialized.conf.theme().clone();
+                    theme.set_preset(preset.into());
+                    initialized.conf.set_theme(theme);
+                }
+                if !color.use_color() {
+                    initialized.conf.set_theme(Theme::plain());
+                }
+                if let Some(path) = initialized.conf.save_location() {
+                    detail!("Using save file '{}'.", path.display());
+                }
+                let before = (verbosity() == Verbosity::Verbose).then(|| initialized.list.clone());
+                let handled = initialized.handle_command(cli.command.clone())?;
+                if let Some(before) = before {
+                    for change in before.diff(&handled.list) {
+                        detail!("{}", describe_change(&change));
+                    }
+                }
+
+                // Servers and watch modes only return on their own when told to stop or reload.
+                match control.as_ref().and_then(ControlSocket::take_request) {
+                    Some(ControlRequest::Reload) => {
+                        handled.save_list()?;
+                        say!("Reloading the config.");
+                    }
+                    _ => {
+                        app = handled;
+                        break;
+                    }
+                }
+            }
+        }
+
+        app.save_list()
+    }
+
+    /// Returns the directory of the control sockets of running servers and watch modes. Without a
+    /// runtime directory each user gets a directory of their own in the temporary directory, which
+    /// the control sockets only use if no one else can access it.
+    fn control_dir() -> PathBuf {
+        match dirs::runtime_dir() {
+            Some(dir) => dir.join("mtd"),
+            #[cfg(unix)]
+            None => env::temp_dir().join(format!("mtd-{}", nix::unistd::geteuid())),
+            #[cfg(not(unix))]
+            None => env::temp_dir().join("mtd"),
+        }
+    }
+
+    /// Binds a control socket so that `daemon stop` and `daemon reload` reach this process. Failing
+    /// to bind is only warned about because the command works without it.
+    fn bind_control_socket(description: &str) -> Option<ControlSocket> {
+        match ControlSocket::bind(&MtdApp::control_dir(), description) {
+            Ok(socket) => {
+                // Only one control socket is bound per process.
+                let _ = STOP_FLAG.set(socket.stop_flag());
+                Some(socket)
+            }
+            Err(e) => {
+                eprintln!("Warning: 'mtd daemon' can't reach this process: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Makes Ctrl-C and SIGTERM stop a server or watch mode like `daemon stop` so that the items are
+    /// saved.
+    fn stop_on_interrupt() {
+        let stop = STOP_FLAG.get_or_init(|| Arc::new(AtomicBool::new(false)));
+        if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone()) {
+            eprintln!("Warning: Ctrl-C stops this process without saving: {}", e);
+        }
+        if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone()) {
+            eprintln!("Warning: SIGTERM stops this process without saving: {}", e);
+        }
+    }
+
+    /// Sends a stop or reload request to the running servers and watch modes.
+    fn daemon_command(action: DaemonAction) -> Result<()> {
+        let received = control::send(&MtdApp::control_dir(), action.into())?;
+        if received.is_empty() {
+            return Err(Error::InvalidArgument("No running server or watch mode was found.".to_string()));
+        }
+        for description in received {
+            say!("Sent {} to '{}'.", ControlRequest::from(action), description);
+        }
+
+        Ok(())
+    }
+
+    /// Pairs the profile with its server using a pairing code and saves the device's token in place
+    /// of the encryption password.
+    fn pair_command(config_path: &PathBuf, profile: Option<&str>, code: &str) -> Result<Self> {
+        let mut app = MtdApp::init(config_path, profile)?;
+
+        // The server's address in the code is saved with the token once pairing succeeds.
+        let code = app.conf.use_pairing_server(code);
+        let (name, token) = MtdNetMgr::new(&mut app.list, &app.conf).pair(code)?;
+        app.conf.set_device(Some(name.clone()));
+        app.conf.set_username(None);
+        app.conf.set_encryption_password(token.into_bytes());
+        // The token is stored in the config, so the password isn't asked for anymore.
+        app.conf.set_password_command(None);
+        app.conf.set_password_in_keyring(false);
+        MtdApp::write_profile_config(config_path, profile, &app.conf)?;
+        say!("Paired as {}. The device now syncs with a token of its own.", name);
+
+        Ok(app)
+    }
+
+    /// Runs the extension `mtd-<name>` found on PATH with the rest of the arguments. The extension
+    /// gets the paths of mtd, the config file and the save file and the name of the profile in the
+    /// environment. Exits with the extension's exit code if it fails.
+    fn run_external(config_path: &Path, profile: Option<&str>, color: bool, args: Vec<String>) -> Result<()> {
+        let (name, args) = args.split_first().ok_or(Error::Unknown)?;
+        let program = env::var_os("PATH")
+            .and_then(|path| find_extension(name, &path))
+            .ok_or_else(|| Error::InvalidArgument(format!("'{}' isn't an mtd command and no 'mtd-{}' was found on PATH.", name, name)))?;
+        detail!("Running the extension '{}'.", program.display());
+
+        let config_file = MtdApp::read_config_file(&config_path.to_path_buf())?;
+        let profile_name = config_file.as_ref().map_or(profile.unwrap_or(ConfigFile::LEGACY_PROFILE), |file| file.profile_name(profile));
+        let save_location = config_file.as_ref().and_then(|file| file.profiles.get(profile_name)).and_then(|conf| conf.save_location());
+
+        let mut command = process::Command::new(&program);
+        command.args(args).env("MTD_BIN", env::current_exe()?).env("MTD_CONFIG_FILE", config_path).env("MTD_PROFILE", profile_name);
+        match save_location {
+            Some(path) => command.env("MTD_DATA_FILE", path),
+            None => command.env_remove("MTD_DATA_FILE"),
+        };
+        if !color {
+            command.env("NO_COLOR", "1");
+        }
+
+        let status = command.status()?;
+        if !status.success() {
+            process::exit(status.code().unwrap_or(1));
+        }
+        Ok(())
+    }
+
+    /// Reads the saved items again if the config defines a save location and the file exists.
+    fn reload_list(&mut self) -> Result<()> {
+        if let Some(path) = self.conf.save_location().filter(|path| path.exists()) {
+            self.list = read_list_file(&self.conf, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the items to the save location if the config defines one.
+    fn save_list(&self) -> Result<()> {
+        if let Some(path) = self.conf.save_location() {
+            if !path.exists() {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+            write_data_file(path, self.list.to_json()?.as_bytes(), data_encryption(&self.conf)?)?;
+            detail!("Saved the items to '{}'.", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Runs commands against sample items kept in memory. With arguments runs them as a single
+    /// command, otherwise reads commands from stdin until `exit` or end of input.
+    fn demo(args: Vec<String>, ascii: bool, color: bool, theme: Option<ThemePreset>) -> Result<()> {
+        let mut conf = Config::new(String::new(), Vec::new(), Duration::from_secs(30), None, true);
+        conf.set_ascii(ascii);
+        if let Some(preset) = theme {
+            conf.set_theme(Theme::from_preset(preset));
+        }
+        if !color {
+            conf.set_theme(Theme::plain());
+        }
+        let mut app = MtdApp { conf, list: MtdApp::demo_list() };
+
+        if !args.is_empty() {
+            let command = MtdApp::parse_demo_command(args).unwrap_or_else(|e| e.exit());
+            app.handle_demo_command(command)?;
+            return Ok(());
+        }
+
+        println!("Running mtd with sample items. Nothing is saved. Type commands without 'mtd', such as");
+        println!("'show --week', or 'exit' to quit.");
+
+        let stdin = io::stdin();
+        loop {
+            print!("mtd demo> ");
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if stdin.read_line(&mut line)? == 0 {
+                println!();
+                return Ok(());
+            }
+            let args = match split_args(&line) {
+                Ok(args) => args,
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    continue;
+                }
+            };
+            if args.is_empty() {
+                continue;
+            }
+            if args.len() == 1 && (args[0] == "exit" || args[0] == "quit") {
+                return Ok(());
+            }
+
+            match MtdApp::parse_demo_command(args) {
+                Ok(command) => {
+                    // A failed command can leave partial changes behind, so they are discarded.
+                    let list = app.list.clone();
+                    let conf = app.conf.clone();
+                    app = match app.handle_demo_command(command) {
+                        Ok(app) => app,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            MtdApp { conf, list }
+                        }
+                    };
+                }
+                Err(e) => {
+                    let _ = e.print();
+                }
+            }
+        }
+    }
+
+    fn parse_demo_command(args: Vec<String>) -> std::result::Result<Commands, clap::Error> {
+        let cli = CliArgs::try_parse_from(iter::once("mtd".to_string()).chain(args))?;
+        Ok(cli.command)
+    }
+
+    fn handle_demo_command(self, command: Commands) -> Result<Self> {
+        match command {
+            Commands::Init { .. } | Commands::ReInit { .. } | Commands::Demo { .. } | Commands::Config { .. } | Commands::Profile { .. } | Commands::Daemon { .. } | Commands::Backup { .. } | Commands::Server { .. } | Commands::Pair { .. } | Commands::Sync { .. } | Commands::Notify { .. } | Commands::Eval { .. } | Commands::External(_) => {
+                Err(Error::InvalidArgument("Command not available in the demo.".to_string()))
+            }
+            Commands::Show { watch: true, .. } => Err(Error::InvalidArgument("The demo doesn't watch for changes, show the items once instead.".to_string())),
+            Commands::Export { output: Some(_), .. } => {
+                Err(Error::InvalidArgument("The demo doesn't write files, export to stdout instead.".to_string()))
+            }
+            // Removing sample items can't lose anything, so there is nothing to confirm.
+            Commands::Remove { item_type, ids, .. } => self.handle_command(Commands::Remove { item_type, ids, yes: true }),
+            command => self.handle_command(command),
+        }
+    }
+
+    /// Returns a list of realistic sample items for the demo.
+    fn demo_list() -> TdList {
+        let today = current_date();
+        let mut list = TdList::new_client();
+
+        let sample_todo = |body: &str, days: i64| Todo::new_specific_date(body.to_string(), today + chrono::Duration::days(days)).unwrap();
+
+        let mut todo = sample_todo("Buy groceries", 0);
+        todo.set_time(Some(NaiveTime::from_hms(17, 30, 0)));
+        todo.set_priority(1);
+        list.add_todo(todo);
+
+        let mut todo = sample_todo("Call the dentist", 0);
+        todo.set_notes(Some("Ask about the cleaning appointment.\nThe number is on the fridge.".to_string()));
+        todo.set_time(Some(NaiveTime::from_hms(9, 0, 0)));
+        list.add_todo(todo);
+
+        let mut todo = sample_todo("Fix the bike tire", 0);
+        todo.set_done(true);
+        list.add_todo(todo);
+
+        list.add_todo(sample_todo("Return library books", 1));
+
+        let mut todo = sample_todo("Prepare presentation slides", 3);
+        todo.set_priority(2);
+        let slides = list.add_todo(todo);
+
+        let send = list.add_todo(sample_todo("Send slides to the team", 3));
+        list.add_todo_dependency(send, slides).unwrap();
+
+        list.add_todo(sample_todo("Plan the weekend trip", 5));
+
+        let mut task = Task::new("Morning run".to_string(), vec![chrono::Weekday::Mon, chrono::Weekday::Wed, chrono::Weekday::Fri]).unwrap();
+        task.set_time(Some(NaiveTime::from_hms(7, 0, 0)));
+        list.add_task(task);
+
+        let all_days = vec![
+            chrono::Weekday::Mon, chrono::Weekday::Tue, chrono::Weekday::Wed, chrono::Weekday::Thu,
+            chrono::Weekday::Fri, chrono::Weekday::Sat, chrono::Weekday::Sun,
+        ];
+        let mut task = Task::new("Read for 30 minutes".to_string(), all_days).unwrap();
+        task.set_time(Some(NaiveTime::from_hms(21, 0, 0)));
+        task.set_done(true, today);
+        list.add_task(task);
+
+        list.add_task(Task::new("Water the plants".to_string(), vec![chrono::Weekday::Tue, chrono::Weekday::Sat]).unwrap());
+
+        let mut task = Task::new("Take out recycling".to_string(), vec![chrono::Weekday::Thu]).unwrap();
+        task.set_every_weeks(2, today).unwrap();
+        list.add_task(task);
+
+        let mut task = Task::new("Weekly review".to_string(), vec![chrono::Weekday::Sun]).unwrap();
+        task.set_notes(Some("Go through the todos of the next week.".to_string()));
+        list.add_task(task);
+
+        list
+    }
+
+    // Needs to take ownership because syncing needs ownership
+    fn handle_command(mut self, command: Commands) -> Result<Self> {
+        match command {
+            Commands::Show { item_type, weekday, week, calendar, date, from, to, overdue, remote, sort, include_removed, output, watch, interval } => {
+                loop {
+                    if watch {
+                        self.reload_list()?;
+                        if io::stdout().is_terminal() {
+                            // Clears the screen and moves the cursor to the top left corner.
+                            print!("\x1b[2J\x1b[H");
+                        }
+                    }
+
+                    // Today is read again for every redraw so that watching continues past midnight.
+                    let today = current_date();
+                    let days = if week {
+                        ShownDays::Week(self.week_start(today, calendar))
+                    } else if let Some(to) = to {
+                        ShownDays::Dates(date_range(from.unwrap_or(today), to)?)
+                    } else {
+                        // If cli arg weekday is unspecified show today.
+                        ShownDays::Dates(vec![weekday.or(date).unwrap_or(today)])
+                    };
+                    if overdue {
+                        self.show_overdue(remote, sort.into(), output)?;
+                    } else {
+                        self.show(item_type, days, remote, sort.into(), output)?;
+                    }
+                    if include_removed {
+                        self.show_removed(item_type)?;
+                    }
+
+                    if !watch {
+                        break;
+                    }
+                    io::stdout().flush()?;
+                    if sleep_unless_stopped(Duration::from_secs(interval)) {
+                        break;
+                    }
+                }
+            }
+            Commands::Add { item_type, weekdays, body, stdin, file, suggest, dry_run, output, options } => {
+                match (body, file) {
+                    (Some(_), _) if dry_run => return Err(Error::InvalidArgument("--dry-run can only be used with --stdin or --file.".to_string())),
+                    (None, Some(file)) if dry_run => self.print_batch_report(item_type, &fs::read_to_string(file)?, options, output)?,
+                    (None, None) if stdin && dry_run => self.print_batch_report(item_type, &io::read_to_string(io::stdin())?, options, output)?,
+                    (Some(body), _) => self.add_with_day_specs(item_type, body, &weekdays, options)?,
+                    (None, None) if suggest.is_some() => self.add_suggestions(item_type, suggest.unwrap(), &weekdays, options)?,
+                    (None, Some(file)) => self.add_batch(item_type, &fs::read_to_string(file)?, options)?,
+                    (None, None) if stdin => self.add_batch(item_type, &io::read_to_string(io::stdin())?, options)?,
+                    (None, None) => return Err(Error::InvalidArgument("Give the body of the item.".to_string())),
+                }
+            }
+            Commands::Copy { item_type, id, weekdays } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.copy(item_type, id, &weekdays)?;
+            }
+            Commands::Convert { item_type, id, weekdays } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.convert(item_type, id, &weekdays)?;
+            }
+            Commands::Remove { item_type, ids, yes } => {
+                let ids = ids.iter().map(|id| self.resolve_id(item_type, id, false)).collect::<Result<Vec<u64>>>()?;
+                if !yes && !self.confirm_remove(item_type, &ids)? {
+                    say!("Nothing was removed.");
+                    return Ok(self);
+                }
+                self.remove(item_type, &ids)?;
+            }
+            Commands::Do { item_type, ids } => {
+                let ids = self.resolve_ids(item_type, &ids, true)?;
+                self.modify_done_state(item_type, &ids, true)?;
+            }
+            Commands::Undo { item_type, ids } => {
+                let ids = self.resolve_ids(item_type, &ids, false)?;
+                self.modify_done_state(item_type, &ids, false)?;
+            }
+            Commands::Set { item_type, id, values } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.set(item_type, id, values)?;
+            }
+            Commands::Find { query, fuzzy, output } => {
+                self.find(&query, fuzzy, output)?;
+            }
+            Commands::List { item_type, sort, filter, output } => {
+                self.list_items(item_type, sort.into(), filter.as_deref(), output)?;
+            }
+            Commands::Stats { weeks, output } => {
+                self.stats(weeks, output)?;
+            }
+            Commands::Plan { action } => {
+                self.plan(action)?;
+            }
+            Commands::Report { report: ReportKind::Stale { limit, output } } => {
+                self.report_stale(limit, output)?;
+            }
+            Commands::Diff { old, new } => {
+                self.diff(old, new)?;
+            }
+            Commands::Status => {
+                self.status()?;
+            }
+            Commands::Info { item_type, id } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.info(item_type, id)?;
+            }
+            Commands::Move { item_type, id, position } => {
+                if item_type == ItemType::Task {
+                    return Err(Error::InvalidArgument("Only todos can be moved.".to_string()));
+                }
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.list.move_todo(id, position)?;
+            }
+            Commands::Restore { item_type, id } => {
+                let id = self.resolve_id(item_type, &id, true)?;
+                match item_type {
+                    ItemType::Todo => self.list.restore_todo(id)?,
+                    ItemType::Task => self.list.restore_task(id)?,
+                }
+            }
+            Commands::Pause { item_type, id, until } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.modify_paused_state(item_type, id, true, until)?;
+            }
+            Commands::Resume { item_type, id } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.modify_paused_state(item_type, id, false, None)?;
+            }
+            Commands::Postpone { item_type, id, to } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.postpone(item_type, id, to)?;
+            }
+            Commands::Export { format, everything, routines, output } => {
+                self.export(format, everything, routines, output)?;
+            }
+            Commands::Import { format, file, dry_run, output } => {
+                self.import(format, &fs::read_to_string(file)?, dry_run, output)?;
+            }
+            Commands::Share { item_type, id, passphrase } => {
+                let id = self.resolve_id(item_type, &id, false)?;
+                self.share(item_type, id, passphrase)?;
+            }
+            Commands::Receive { item, passphrase } => {
+                self.receive(item, passphrase)?;
+            }
+            Commands::Notify { if_nonempty } => {
+                self.notify(if_nonempty)?;
+            }
+            Commands::Sync { watch: false, retries, backoff, .. } => {
+                self.sync_with_retries(retries, Duration::from_secs(backoff))?;
+            }
+            Commands::Sync { watch: true, interval, .. } => {
+                self.sync_watch(Duration::from_secs(interval))?;
+            }
+            Commands::Server { action: Some(action), .. } => {
+                self.device_command(action)?;
+            }
+            Commands::Eval { file, sync } => {
+                let script = match file.filter(|path| path != Path::new("-")) {
+                    Some(path) => fs::read_to_string(path)?,
+                    None => io::read_to_string(io::stdin())?,
+                };
+                return self.eval(&script, sync);
+            }
+            Commands::Server { health_addr, log_level, action: None } => {
+                // Reloading runs the command again, but the logger can only be set once.
+                let _ = tracing_subscriber::fmt()
+                    .with_max_level(tracing::Level::from(log_level))
+                    .with_writer(io::stderr)
+                    .with_ansi(io::stderr().is_terminal())
+                    .try_init();
+                if health_addr.is_some() {
+                    self.conf.set_health_addr(health_addr);
+                    self.conf.validate()?;
+                }
+                self.server()?;
+            }
+            Commands::Backup { action: BackupAction::Create { file, include_secrets, password } } => {
+                self.create_backup(&file, include_secrets, password)?;
+            }
+            // Init, re-init, demo, config, profile, daemon, pairing, extensions and restoring
+            // backups are handled earlier
+            Commands::Init { .. } | Commands::ReInit { .. } | Commands::Demo { .. } | Commands::Config { .. } | Commands::Profile { .. } | Commands::Daemon { .. } | Commands::Pair { .. } | Commands::Backup { .. } | Commands::External(_) => {}
+        }
+
+        if self.conf.local_only() {
+            self.list.self_sync();
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the first date of a week. The week is either the next 7 days from today or the calendar
+    /// week that contains today.
+    fn week_start(&self, today: NaiveDate, calendar: bool) -> NaiveDate {
+        if calendar {
+            let days_since_start = (7 + today.weekday().num_days_from_monday() - self.conf.week_starts_on().num_days_from_monday()) % 7;
+            today - chrono::Duration::days(days_since_start as i64)
+        } else {
+            today
+        }
+    }
+
+    /// Shows the items of the given days.
+    fn show(&mut self, item_type: Option<ItemType>, days: ShownDays, remote: bool, sort: SortOrder, output: Output) -> Result<()> {
+        let remote_list;
+        let list = if remote {
+            remote_list = self.remote_list()?;
+            &remote_list
+        } else {
+            &self.list
+        };
+
+        // If item type is None, show everything.
+        let show_todos = item_type.is_none() || item_type.unwrap() == ItemType::Todo;
+        let show_tasks = item_type.is_none() || item_type.unwrap() == ItemType::Task;
+
+        let views = match days {
+            ShownDays::Week(start) => list.week_view(start, &SystemClock, sort).days,
+            ShownDays::Dates(dates) => dates.iter().map(|date| list.day_view(*date, &SystemClock, sort)).collect(),
+        };
+
+        if output != Output::Text {
+            let records: Vec<ShowRecord> = views.iter()
+                .flat_map(|view| Self::show_records(view, show_todos, show_tasks))
+                .collect();
+            return print_records(&records, output);
+        }
+
+        for view in &views {
+            self.print_date(view, show_todos, show_tasks);
+            // Separate the days of a week.
+            if views.len() > 1 {
+                println!();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows the undone todos whose date has passed.
+    fn show_overdue(&mut self, remote: bool, sort: SortOrder, output: Output) -> Result<()> {
+        let remote_list;
+        let list = if remote {
+            remote_list = self.remote_list()?;
+            &remote_list
+        } else {
+            &self.list
+        };
+
+        if output != Output::Text {
+            let today = current_date();
+            let records: Vec<ShowRecord> = Self::show_records(&list.day_view(today, &SystemClock, sort), true, false).into_iter()
+                .filter(|record| record.days_overdue > 0)
+                .collect();
+            return print_records(&records, output);
+        }
+
+        let view = list.day_view(SystemClock.today(), &SystemClock, sort);
+        let today = view.date;
+
+        println!("{}", self.paint("Overdue todos:", Style::Header));
+        if view.overdue_todos.is_empty() {
+            println!("\tNo overdue todos.");
+        }
+        for todo in view.overdue_todos {
+            let text = format!("{} (scheduled for {}, {})", todo, todo.date(), format_days_overdue(todo.days_overdue(today)));
+            if self.conf.ascii() {
+                println!("\t[ ] {}", text);
+            } else {
+                println!("\t{}", self.paint(&text, Style::Overdue));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the items shown for a date in the same order as they are printed as text.
+    fn show_records(view: &DayView, show_todos: bool, show_tasks: bool) -> Vec<ShowRecord> {
+        let format_time = |time: Option<NaiveTime>| time.map(|time| time.format("%H:%M").to_string());
+        let date = view.date;
+        let today = SystemClock.today();
+        let mut records = Vec::new();
+
+        if show_todos {
+            let undone = view.undone_todos.iter().map(|todo| (*todo, false));
+            let done = view.done_todos.iter().map(|todo| (*todo, true));
+            for (todo, done) in undone.chain(done) {
+                records.push(ShowRecord {
+                    date,
+                    item_type: "todo",
+                    id: todo.id(),
+                    uuid: todo.uuid(),
+                    body: todo.body().to_string(),
+                    done,
+                    time: format_time(todo.time()),
+                    days_overdue: todo.days_overdue(today),
+                });
+            }
+        }
+        if show_tasks {
+            let undone = view.undone_tasks.iter().map(|task| (*task, false, 0));
+            let missed = view.missed_tasks.iter().map(|task| (*task, false, days_missed(task, date)));
+            let done = view.done_tasks.iter().map(|task| (*task, true, 0));
+            for (task, done, days_overdue) in undone.chain(missed).chain(done) {
+                records.push(ShowRecord {
+                    date,
+                    item_type: "task",
+                    id: task.id(),
+                    uuid: task.uuid(),
+                    body: task.body().to_string(),
+                    done,
+                    // A missed task is shown late without the time of its original date.
+                    time: format_time(task.time_for(date.weekday())).filter(|_| days_overdue == 0),
+                    days_overdue,
+                });
+            }
+        }
+
+        records
+    }
+
+    /// Prints the items that are removed on the next sync. Local-only instances and servers remove
+    /// items immediately so they never have any.
+    fn show_removed(&self, item_type: Option<ItemType>) -> Result<()> {
+        if self.conf.local_only() {
+            return Err(Error::OnlineOnlyOperation);
+        }
+        if self.list.is_server() {
+            return Err(Error::ClientOnlyOperation);
+        }
+
+        let mut items = Vec::new();
+        if item_type.is_none() || item_type == Some(ItemType::Todo) {
+            items.extend(self.list.removed_todos().iter().map(|todo| format!("Todo {}", self.todo_text(todo))));
+        }
+        if item_type.is_none() || item_type == Some(ItemType::Task) {
+            items.extend(self.list.removed_tasks().iter().map(|task| format!("Task {}", self.task_text(task))));
+        }
+
+        println!();
+        println!("{}", self.paint("Removed on next sync:", Style::Header));
+        if items.is_empty() {
+            println!("\tNo removed items.");
+        }
+        for item in items {
+            let marker = if self.conf.ascii() { "[-]" } else { "\u{1F5D1}" };
+            println!("\t{}", self.paint(&format!("{} {}", marker, item), Style::Removed));
+        }
+
+        Ok(())
+    }
+
+    /// Gets the encryption password from the keyring or the password command of the config if the
+    /// password isn't stored in the config. The command may ask for a hardware token to be touched,
+    /// so it is only run when the password is needed and its output is never saved.
+    fn unlock_password(&mut self) -> Result<()> {
+        if self.conf.password_in_keyring() && self.conf.encryption_password().is_empty() {
+            detail!("Reading the encryption password from the keyring.");
+            let password = keyring_password_entry(&self.conf)?.get_password().map_err(keyring_err)?;
+            self.conf.set_encryption_password(password.into_bytes());
+            return Ok(());
+        }
+        let command = match self.conf.password_command() {
+            Some(command) if self.conf.encryption_password().is_empty() => command,
+            _ => return Ok(()),
+        };
+        detail!("Running the password command '{}'.", command);
+
+        #[cfg(unix)]
+        let mut shell = process::Command::new("sh");
+        #[cfg(unix)]
+        shell.arg("-c");
+        #[cfg(not(unix))]
+        let mut shell = process::Command::new("cmd");
+        #[cfg(not(unix))]
+        shell.arg("/C");
+
+        // The command can prompt on the terminal, only its output is the password.
+        let output = shell.arg(command).stdin(process::Stdio::inherit()).stderr(process::Stdio::inherit()).output()?;
+        let password = String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string();
+        if !output.status.success() || password.is_empty() {
+            return Err(Error::InvalidArgument(format!("The password command '{}' didn't print a password.", command)));
+        }
+
+        self.conf.set_encryption_password(password.into_bytes());
+        Ok(())
+    }
+
+    /// Fetches the server's items and caches them. If the server can't be reached, the cached items are
+    /// returned instead with a warning that they may be stale.
+    fn remote_list(&mut self) -> Result<TdList> {
+        self.unlock_password()?;
+        let net_mgr = MtdNetMgr::new(&mut self.list, &self.conf);
+        let fetched = net_mgr.fetch_remote();
+        let clock_skew = net_mgr.clock_skew();
+        self.warn_about_clock_skew(clock_skew);
+
+        match fetched {
+            Ok(remote) => {
+                self.write_remote_cache(&remote)?;
+                Ok(remote)
+            }
+            Err(e @ (Error::OnlineOnlyOperation | Error::ClientOnlyOperation)) => Err(e),
+            Err(e) => {
+                let cache = match self.read_remote_cache()? {
+                    Some(cache) => cache,
+                    None => return Err(e),
+                };
+                // Print the warning in red.
+                eprintln!("{}", self.paint(&format!("Couldn't reach the server: {}", e), Style::Warning));
+                eprintln!(
+                    "{}\n",
+                    self.paint(
+                        &format!("Showing server items fetched at {}. They may be out of date.", cache.fetched_at.format("%Y-%m-%d %H:%M")),
+                        Style::Warning,
+                    )
+                );
+                Ok(cache.list)
+            }
+        }
+    }
+
+    /// Warns if the server's clock differs so much from the local clock that the devices may disagree
+    /// about the date, which makes items done on the wrong day.
+    fn warn_about_clock_skew(&self, skew: Option<chrono::Duration>) {
+        if let Some(skew) = skew.filter(|skew| skew.num_seconds().abs() > MAX_CLOCK_SKEW_SECS) {
+            let direction = if skew > chrono::Duration::zero() { "ahead of" } else { "behind" };
+            let warning = format!(
+                "The server's clock is {} minutes {} the local clock. Check the time settings of both devices.",
+                skew.num_minutes().abs(),
+                direction,
+            );
+            eprintln!("{}", self.paint(&warning, Style::Warning));
+        }
+    }
+
+    /// Returns the path of the cache of the last fetched server items. The cache is stored next to the
+    /// save file.
+    fn remote_cache_path(&self) -> Option<PathBuf> {
+        let save_location = self.conf.save_location()?;
+        let mut file_name = save_location.file_name()?.to_os_string();
+        file_name.push(".remote");
+        Some(save_location.with_file_name(file_name))
+    }
+
+    fn write_remote_cache(&self, list: &TdList) -> Result<()> {
+        if let Some(path) = self.remote_cache_path() {
+            let cache = RemoteCache { fetched_at: current_time(), list: list.clone() };
+            write_data_file(&path, serde_json::to_string(&cache)?.as_bytes(), data_encryption(&self.conf)?)?;
+        }
+        Ok(())
+    }
+
+    fn read_remote_cache(&self) -> Result<Option<RemoteCache>> {
+        match self.remote_cache_path() {
+            Some(path) if path.exists() => Ok(Some(serde_json::from_str(&read_data_file(&path, data_encryption(&self.conf)?)?)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the path of the record of failed syncs. The record is stored next to the save file.
+    fn sync_failures_path(&self) -> Option<PathBuf> {
+        let save_location = self.conf.save_location()?;
+        let mut file_name = save_location.file_name()?.to_os_string();
+        file_name.push(".failures");
+        Some(save_location.with_file_name(file_name))
+    }
+
+    fn read_sync_failures(&self) -> Result<Option<SyncFailures>> {
+        match self.sync_failures_path() {
+            Some(path) if path.exists() => Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Records a failed sync and returns the number of syncs that have failed in a row.
+    fn record_sync_failure(&self, error: &Error) -> Result<u32> {
+        let count = self.read_sync_failures()?.map_or(0, |failures| failures.count) + 1;
+        if let Some(path) = self.sync_failures_path() {
+            let failures = SyncFailures { count, last_failed_at: current_time(), last_error: error.to_string() };
+            fs::write(path, serde_json::to_string(&failures)?)?;
+        }
+        Ok(count)
+    }
+
+    /// Records a failed sync with `record_sync_failure`. If the record can't be written, that is
+    /// printed and `None` is returned so that the error of the sync itself isn't lost.
+    fn try_record_sync_failure(&self, error: &Error) -> Option<u32> {
+        match self.record_sync_failure(error) {
+            Ok(count) => Some(count),
+            Err(record_err) => {
+                eprintln!("Couldn't record the failed sync: {}", record_err);
+                None
+            }
+        }
+    }
+
+    /// Forgets the failed syncs after a successful one.
+    fn clear_sync_failures(&self) -> Result<()> {
+        match self.sync_failures_path() {
+            Some(path) if path.exists() => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Styles text for the terminal using the theme of the config. In ASCII mode the text is
+    /// returned as is.
+    fn paint(&self, text: &str, style: Style) -> String {
+        if self.conf.ascii() {
+            return text.to_string();
+        }
+        self.conf.theme().paint(text, style)
+    }
+
+    fn print_date(&self, view: &DayView, show_todos: bool, show_tasks: bool) {
+        let today = SystemClock.today();
+        let date = view.date;
+        // The weekday alone is ambiguous for dates outside the next 7 days.
+        let days_from_today = (date - today).num_days();
+        let header = if (0..7).contains(&days_from_today) {
+            format!("{}:", date.weekday().to_string().to_uppercase())
+        } else {
+            format!("{} {}:", date.weekday().to_string().to_uppercase(), date)
+        };
+        println!("{}", self.paint(&header, Style::Weekday));
+        if show_todos {
+            let mut items = Vec::new();
+            for todo in &view.undone_todos {
+                items.push(ShownItem { kind: Style::Todo, time: todo.time(), text: self.todo_text(todo), done: false, days_overdue: todo.days_overdue(today) });
+            }
+            for todo in &view.done_todos {
+                items.push(ShownItem { kind: Style::Todo, time: todo.time(), text: self.todo_text(todo), done: true, days_overdue: 0 });
+            }
+
+            println!("{}", self.paint("Todos:", Style::Header));
+
+            if items.is_empty() {
+                println!("\tNo todos for this day.");
+            } else {
+                self.print_items(items);
+            }
+        }
+        if show_tasks {
+            let mut items = Vec::new();
+            for task in &view.undone_tasks {
+                items.push(ShownItem { kind: Style::Task, time: task.time_for(date.weekday()), text: self.task_text(task), done: false, days_overdue: 0 });
+            }
+            for task in &view.missed_tasks {
+                items.push(ShownItem { kind: Style::Task, time: None, text: self.task_text(task), done: false, days_overdue: days_missed(task, date) });
+            }
+            for task in &view.done_tasks {
+                items.push(ShownItem { kind: Style::Task, time: task.time_for(date.weekday()), text: self.task_text(task), done: true, days_overdue: 0 });
+            }
+
+            println!("{}", self.paint("Tasks:", Style::Header));
+
+            if items.is_empty() {
+                println!("\tNo tasks for this day.");
+            } else {
+                self.print_items(items);
+            }
+        }
+    }
+
+    /// Returns the id of an item as it is shown: its number or its short id, depending on the config.
+    fn shown_id(&self, id: u64, uuid: Uuid) -> String {
+        match self.conf.id_style() {
+            IdStyle::Number => id.to_string(),
+            IdStyle::Short => uuid.simple().to_string()[..self.list.short_id_len()].to_string(),
+        }
+    }
+
+    fn todo_text(&self, todo: &Todo) -> String {
+        format!("{}. {}", self.shown_id(todo.id(), todo.uuid()), todo.body())
+    }
+
+    fn task_text(&self, task: &Task) -> String {
+        format!("{}. {}", self.shown_id(task.id(), task.uuid()), task.body())
+    }
+
+    /// Prints the items of a day. If any of the items has a time, the items are grouped into sections
+    /// by the time of day.
+    fn print_items(&self, mut items: Vec<ShownItem>) {
+        let print_item = |item: &ShownItem, indent: &str| {
+            let mut text = match item.time {
+                Some(time) => format!("{} ({})", item.text, time.format("%H:%M")),
+                None => item.text.clone(),
+            };
+            if item.days_overdue > 0 {
+                text = format!("{} ({})", text, format_days_overdue(item.days_overdue));
+            }
+            let marker = self.conf.theme().marker(item.kind);
+            if !self.conf.ascii() || marker.is_ascii() {
+                text = format!("{}{}", marker, text);
+            }
+            if self.conf.ascii() {
+                println!("{}[{}] {}", indent, if item.done { "x" } else { " " }, text);
+            } else if item.done {
+                println!("{}{}", indent, self.paint(&text, Style::Done));
+            } else if item.days_overdue > 0 {
+                println!("{}{}", indent, self.paint(&text, Style::Overdue));
+            } else {
+                println!("{}{}", indent, self.paint(&text, item.kind));
+            }
+        };
+
+        if items.iter().all(|item| item.time.is_none()) {
+            for item in &items {
+                print_item(item, "\t");
+            }
+            return;
+        }
+
+        // Undone items are shown first and each group is ordered by time.
+        items.sort_by_key(|item| (item.done, item.time));
+
+        for section in [DaySection::Morning, DaySection::Afternoon, DaySection::Evening, DaySection::Anytime] {
+            let section_items: Vec<&ShownItem> = items.iter()
+                .filter(|item| DaySection::of(item.time, &self.conf) == section)
+                .collect();
+
+            if !section_items.is_empty() {
+                println!("\t{}:", section);
+                for item in section_items {
+                    print_item(item, "\t\t");
+                }
+            }
+        }
+    }
+
+    /// Adds an item on the days given as weekday or date arguments.
+    fn add_with_day_specs(&mut self, item_type: ItemType, body: String, specs: &[DaySpec], options: AddOptions) -> Result<()> {
+        let (weekdays, options) = MtdApp::day_spec_options(specs, options)?;
+        self.add(item_type, weekdays, body, options)
+    }
+
+    /// Returns the weekdays and the options to add an item with for the day specs.
+    fn day_spec_options(specs: &[DaySpec], mut options: AddOptions) -> Result<(Vec<Weekday>, AddOptions)> {
+        let mut days = Vec::new();
+        for spec in specs {
+            match spec.when {
+                When::Days(d) => days.push(d),
+                // A date given instead of weekdays is the same as the date option.
+                When::Date(date) if specs.len() == 1 && options.date.is_none() => {
+                    options.date = Some(date);
+                    options.time = options.time.or(spec.time);
+                }
+                When::Date(_) => {
+                    return Err(Error::InvalidArgument("Give either weekdays or a single date.".to_string()));
+                }
+            }
+        }
+        options.weekday_times = DaySpec::weekday_times(specs);
+        Ok((Days::expand(&days), options))
+    }
+
+    /// Adds a todo for each day plan suggestion of the date that hasn't been added yet.
+    fn add_suggestions(&mut self, item_type: ItemType, date: NaiveDate, specs: &[DaySpec], mut options: AddOptions) -> Result<()> {
+        if item_type == ItemType::Task {
+            return Err(Error::InvalidArgument("Day plans only suggest todos.".to_string()));
+        }
+        if !specs.is_empty() || options.date.is_some() {
+            return Err(Error::InvalidArgument("Suggested todos are added for the suggested day.".to_string()));
+        }
+
+        let bodies: Vec<String> = self.list.suggestions_for_date(date).iter().map(|plan| plan.body().to_string()).collect();
+        if bodies.is_empty() {
+            println!("No suggestions for {}.", date);
+        }
+        options.date = Some(date);
+        for body in bodies {
+            self.add(ItemType::Todo, Vec::new(), body, options.clone())?;
+        }
+
+        Ok(())
+    }
+
+    fn plan(&mut self, action: PlanAction) -> Result<()> {
+        match action {
+            PlanAction::Show { day } => {
+                let date = day.unwrap_or_else(current_date);
+                let header = format!("Suggestions for {} {}:", date.weekday().to_string().to_uppercase(), date);
+                println!("{}", self.paint(&header, Style::Header));
+                let suggestions = self.list.suggestions_for_date(date);
+                if suggestions.is_empty() {
+                    println!("\tNo suggestions.");
+                }
+                for plan in suggestions {
+                    println!("\t{}", plan);
+                }
+            }
+            PlanAction::List => {
+                let plans = self.list.plans();
+                if plans.is_empty() {
+                    println!("No day plans.");
+                }
+                for weekday in Days::expand(&[Days::Daily]).into_iter().map(chrono::Weekday::from) {
+                    let day_plans: Vec<&&DayPlan> = plans.iter().filter(|plan| plan.weekday() == weekday).collect();
+                    if !day_plans.is_empty() {
+                        println!("{}", self.paint(&format!("{}:", weekday.to_string().to_uppercase()), Style::Weekday));
+                        for plan in day_plans {
+                            println!("\t{}", plan);
+                        }
+                    }
+                }
+            }
+            PlanAction::Add { body, weekdays } => {
+                for weekday in Days::expand(&weekdays).into_iter().map(chrono::Weekday::from) {
+                    let id = self.list.add_plan(DayPlan::new(body.clone(), weekday)?);
+                    say!("Added day plan {} for {}", id, weekday);
+                }
+            }
+            PlanAction::Remove { ids } => {
+                // Check every id first so that nothing is removed if any of them is invalid.
+                for id in &ids {
+                    self.list.get_plan(*id)?;
+                }
+                for id in ids {
+                    self.list.remove_plan(id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds an item for each non-empty line of `text`. A line may end with a weekday. Nothing is
+    /// added if any of the lines is invalid, because the list is only saved after all were added.
+    fn add_batch(&mut self, item_type: ItemType, text: &str, options: AddOptions) -> Result<()> {
+        let (records, messages) = self.add_lines(item_type, text, options);
+
+        let invalid: Vec<String> = records.iter()
+            .filter_map(|record| record.reason.as_ref().map(|reason| format!("Line {}: {}", record.line, reason)))
+            .collect();
+        if !invalid.is_empty() {
+            return Err(Error::InvalidArgument(invalid.join("\n")));
+        }
+        if records.is_empty() {
+            return Err(Error::InvalidArgument("No items to add were given.".to_string()));
+        }
+
+        for message in messages {
+            say!("{}", message);
+        }
+        Ok(())
+    }
+
+    /// Adds an item for each non-empty line of `text` without printing anything. Returns what was
+    /// done with each line and the messages of the added items. Lines after an invalid line are
+    /// still added, so that all invalid lines are found at once.
+    fn add_lines(&mut self, item_type: ItemType, text: &str, options: AddOptions) -> (Vec<BatchRecord>, Vec<String>) {
+        let type_name = match item_type {
+            ItemType::Todo => "todo",
+            ItemType::Task => "task",
+        };
+
+        let mut records = Vec::new();
+        let mut messages = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let (body, spec) = match parse_batch_line(line) {
+                Some(item) => item,
+                None => continue,
+            };
+            let specs: Vec<DaySpec> = spec.into_iter().collect();
+            let before = self.list.clone();
+            let added = MtdApp::day_spec_options(&specs, options.clone())
+                .and_then(|(weekdays, options)| self.add_quietly(item_type, weekdays, body.clone(), options));
+            match added {
+                Ok(added) => messages.extend(added),
+                Err(e) => {
+                    records.push(BatchRecord { line: i + 1, action: "invalid", item_type: type_name, body, date: None, weekdays: Vec::new(), reason: Some(e.to_string()) });
+                    continue;
+                }
+            }
+
+            for change in before.diff(&self.list) {
+                let record = match change {
+                    ItemChange::Added(ItemRef::Todo(todo)) => BatchRecord {
+                        line: i + 1,
+                        action: "add",
+                        item_type: "todo",
+                        body: todo.body().to_string(),
+                        date: Some(todo.date()),
+                        weekdays: Vec::new(),
+                        reason: None,
+                    },
+                    ItemChange::Added(ItemRef::Task(task)) => BatchRecord {
+                        line: i + 1,
+                        action: "add",
+                        item_type: "task",
+                        body: task.body().to_string(),
+                        date: None,
+                        weekdays: task.weekdays().iter().map(|wd| wd.to_string()).collect(),
+                        reason: None,
+                    },
+                    _ => continue,
+                };
+                records.push(record);
+            }
+        }
+
+        (records, messages)
+    }
+
+    /// Returns what `add_batch` would do with each line of the text. The items are added to a copy
+    /// of the list, so nothing is changed.
+    fn batch_report(&self, item_type: ItemType, text: &str, options: AddOptions) -> Vec<BatchRecord> {
+        let mut scratch = MtdApp { conf: self.conf.clone(), list: self.list.clone() };
+        scratch.add_lines(item_type, text, options).0
+    }
+
+    /// Prints what `add_batch` would do with each line of the text without adding anything. Like
+    /// `add_batch`, fails if any of the lines is invalid.
+    fn print_batch_report(&self, item_type: ItemType, text: &str, options: AddOptions, output: Output) -> Result<()> {
+        let records = self.batch_report(item_type, text, options);
+        if output != Output::Text {
+            print_records(&records, output)?;
+        } else {
+            for record in &records {
+                let day = match (record.date, record.weekdays.is_empty()) {
+                    (Some(date), _) => format!(" for {}", date),
+                    (None, false) => format!(" on {}", record.weekdays.join(", ")),
+                    (None, true) => String::new(),
+                };
+                match &record.reason {
+                    Some(reason) => println!("Line {}: invalid \"{}\": {}", record.line, record.body, reason),
+                    None => println!("Line {}: add {} \"{}\"{}", record.line, record.item_type, record.body, day),
+                }
+            }
+        }
+
+        let invalid = records.iter().filter(|record| record.reason.is_some()).count();
+        if invalid > 0 {
+            return Err(Error::InvalidArgument(format!("{} line(s) are invalid, so nothing would be added.", invalid)));
+        }
+        if records.is_empty() {
+            return Err(Error::InvalidArgument("No items to add were given.".to_string()));
+        }
+        say!("{} item(s) would be added. Nothing was changed.", records.len());
+
+        Ok(())
+    }
+
+    fn add(&mut self, item_type: ItemType, weekdays: Vec<Weekday>, body: String, options: AddOptions) -> Result<()> {
+        for message in self.add_quietly(item_type, weekdays, body, options)? {
+            say!("{}", message);
+        }
+        Ok(())
+    }
+
+    /// Adds items like `add`, but returns the messages `add` prints instead of printing them.
+    fn add_quietly(&mut self, item_type: ItemType, weekdays: Vec<Weekday>, body: String, options: AddOptions) -> Result<Vec<String>> {
+        let AddOptions { date, until, every, backfill, notes, time, priority, after, weekday_times } = options;
+        let weekday_times: Vec<(chrono::Weekday, NaiveTime)> = weekday_times.into_iter()
+            .map(|(wd, time)| (wd.into(), time))
+            .collect();
+        let time_for = |day: chrono::Weekday| weekday_times.iter()
+            .find(|(wd, _)| *wd == day)
+            .map(|(_, time)| *time);
+
+        if until.is_some() && item_type == ItemType::Todo {
+            return Err(Error::InvalidArgument("Todos cannot be given an end date.".to_string()));
+        }
+        if every.is_some() && item_type == ItemType::Todo {
+            return Err(Error::InvalidArgument("Todos cannot repeat.".to_string()));
+        }
+        if backfill.is_some() && item_type == ItemType::Todo {
+            return Err(Error::InvalidArgument("Todos are shown until done and cannot be backfilled.".to_string()));
+        }
+        if priority.is_some() && item_type == ItemType::Task {
+            return Err(Error::InvalidArgument("Tasks cannot be given a priority.".to_string()));
+        }
+        if !after.is_empty() && item_type == ItemType::Task {
+            return Err(Error::InvalidArgument("Tasks cannot depend on todos.".to_string()));
+        }
+        let after = after.iter().map(|arg| self.resolve_id(ItemType::Todo, arg, false)).collect::<Result<Vec<u64>>>()?;
+        for blocker in &after {
+            self.list.get_todo(*blocker)?;
+        }
+
+        if let Some(date) = date {
+            if item_type == ItemType::Task {
+                return Err(Error::InvalidArgument("Tasks cannot be given an exact date.".to_string()));
+            }
+            let mut todo = Todo::new_specific_date(body, date)?;
+            todo.set_notes(notes);
+            todo.set_time(time);
+            self.conf.apply_rules(&mut todo);
+            if let Some(priority) = priority {
+                todo.set_priority(priority);
+            }
+            let id = self.list.add_todo(todo);
+            for blocker in &after {
+                self.list.add_todo_dependency(id, *blocker)?;
+            }
+            return Ok(vec![format!("Added todo {} for {}", self.shown_id(id, self.list.get_todo(id)?.uuid()), date)]);
+        }
+
+        let mut chrono_weekdays: Vec<chrono::Weekday> = Vec::new();
+        for wd in weekdays {
+            chrono_weekdays.push(wd.into());
+        }
+
+        // If no weekdays are specified, add today's weekday.
+        if chrono_weekdays.is_empty() {
+            chrono_weekdays.push(current_date().weekday());
+        }
+
+        let mut added = Vec::new();
+        match item_type {
+            ItemType::Todo => {
+                for day in chrono_weekdays {
+                    let mut todo = Todo::new_dated(body.clone(), day)?;
+                    todo.set_notes(notes.clone());
+                    todo.set_time(time_for(day).or(time));
+                    self.conf.apply_rules(&mut todo);
+                    if let Some(priority) = priority {
+                        todo.set_priority(priority);
+                    }
+                    let id = self.list.add_todo(todo);
+                    for blocker in &after {
+                        self.list.add_todo_dependency(id, *blocker)?;
+                    }
+                    added.push(format!("Added todo {} for {}", self.shown_id(id, self.list.get_todo(id)?.uuid()), day));
+                }
+            }
+            ItemType::Task => {
+                let days = chrono_weekdays.iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(", ");
+                let mut task = Task::new(body, chrono_weekdays)?;
+                task.set_until(until);
+                task.set_notes(notes);
+                task.set_time(time);
+                for (wd, wd_time) in &weekday_times {
+                    task.set_weekday_time(*wd, Some(*wd_time));
+                }
+                if let Some(weeks) = every {
+                    task.set_every_weeks(weeks, current_date())?;
+                }
+                if let Some(days) = backfill {
+                    task.set_backfill_days(days);
+                }
+                let id = self.list.add_task(task);
+
+                let mut msg = format!("Added task {} for {}", self.shown_id(id, self.list.get_task(id)?.uuid()), days);
+                if let Some(weeks) = every.filter(|weeks| *weeks > 1) {
+                    msg.push_str(&format!(" every {} weeks", weeks));
+                }
+                if let Some(date) = until {
+                    msg.push_str(&format!(" until {}", date));
+                }
+                added.push(msg);
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Lists the items that would be removed and asks for confirmation. Fails without asking if any
+    /// of the ids is invalid.
+    fn confirm_remove(&self, item_type: ItemType, ids: &[u64]) -> Result<bool> {
+        let items = ids.iter()
+            .map(|id| match item_type {
+                ItemType::Todo => self.list.get_todo(*id).map(|todo| self.todo_text(todo)),
+                ItemType::Task => self.list.get_task(*id).map(|task| self.task_text(task)),
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        println!("This will remove:");
+        for item in items {
+            println!("\t{}", item);
+        }
+        let kind = match item_type {
+            ItemType::Todo => "todo",
+            ItemType::Task => "task",
+        };
+        MtdApp::prompt_yes_no(&format!("Remove {} {}(s)", ids.len(), kind))
+    }
+
+    fn remove(&mut self, item_type: ItemType, ids: &[u64]) -> Result<()> {
+        match item_type {
+            ItemType::Todo => {
+                self.list.remove_todos(ids)?;
+            }
+            ItemType::Task => {
+                self.list.remove_tasks(ids)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies an item. A todo is copied once for each given weekday or for a single date and a task
+    /// once for all the given weekdays.
+    fn copy(&mut self, item_type: ItemType, id: u64, specs: &[DaySpec]) -> Result<()> {
+        let times = DaySpec::weekday_times(specs);
+        let time_for = |day: chrono::Weekday| times.iter()
+            .find(|(wd, _)| chrono::Weekday::from(*wd) == day)
+            .map(|(_, time)| *time);
+        let mut days = Vec::new();
+        let mut date = None;
+        for spec in specs {
+            match spec.when {
+                When::Days(d) => days.push(d),
+                When::Date(d) if item_type == ItemType::Todo && specs.len() == 1 => date = Some((d, spec.time)),
+                When::Date(_) => return Err(Error::InvalidArgument("Give either weekdays or a single date for a todo.".to_string())),
+            }
+        }
+        let weekdays: Vec<chrono::Weekday> = Days::expand(&days).into_iter().map(chrono::Weekday::from).collect();
+
+        match item_type {
+            ItemType::Todo => {
+                let targets: Vec<(Option<NaiveDate>, Option<NaiveTime>)> = match date {
+                    Some((date, time)) => vec![(Some(date), time)],
+                    None if weekdays.is_empty() => vec![(None, None)],
+                    None => weekdays.iter().map(|day| (Some(weekday_to_date(*day)), time_for(*day))).collect(),
+                };
+                let original = self.shown_id(id, self.list.get_todo(id)?.uuid());
+                for (date, time) in targets {
+                    let copy = self.list.duplicate_todo(id, date)?;
+                    let todo = self.list.get_todo_mut(copy)?;
+                    self.conf.apply_rules(todo);
+                    if time.is_some() {
+                        todo.set_time(time);
+                    }
+                    let todo = self.list.get_todo(copy)?;
+                    say!("Copied todo {} as todo {} for {}", original, self.shown_id(copy, todo.uuid()), todo.date());
+                }
+            }
+            ItemType::Task => {
+                let copy = self.list.duplicate_task(id, (!weekdays.is_empty()).then_some(weekdays))?;
+                let task = self.list.get_task_mut(copy)?;
+                for (day, time) in times {
+                    task.set_weekday_time(day.into(), Some(time));
+                }
+                let (original, task) = (self.list.get_task(id)?, self.list.get_task(copy)?);
+                say!("Copied task {} as task {}", self.shown_id(id, original.uuid()), self.shown_id(copy, task.uuid()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a todo into a task for the given weekdays or a task into a todo for the given weekday
+    /// or date.
+    fn convert(&mut self, item_type: ItemType, id: u64, specs: &[DaySpec]) -> Result<()> {
+        match item_type {
+            ItemType::Todo => {
+                let mut days = Vec::new();
+                for spec in specs {
+                    match spec.when {
+                        When::Days(d) => days.push(d),
+                        When::Date(_) => return Err(Error::InvalidArgument("A task can only be given weekdays.".to_string())),
+                    }
+                }
+                let weekdays = Days::expand(&days).into_iter().map(chrono::Weekday::from).collect();
+                let todo = self.list.get_todo(id)?;
+                let original = self.shown_id(id, todo.uuid());
+                let dropped = todo.priority() > 0 || !todo.blocked_by().is_empty() || !self.list.blocks(id)?.is_empty();
+                let task_id = self.list.convert_todo_to_task(id, weekdays)?;
+                let task = self.list.get_task_mut(task_id)?;
+                for (day, time) in DaySpec::weekday_times(specs) {
+                    task.set_weekday_time(day.into(), Some(time));
+                }
+                say!("Converted todo {} into task {}", original, self.shown_id(task_id, self.list.get_task(task_id)?.uuid()));
+                if dropped {
+                    say!("Tasks have no priority or dependencies, so those of the todo were dropped");
+                }
+            }
+            ItemType::Task => {
+                let (date, time) = match specs {
+                    [] => (None, None),
+                    [DaySpec { when: When::Date(date), time }] => (Some(*date), *time),
+                    [DaySpec { when: When::Days(days), time }] => match Days::expand(&[*days]).as_slice() {
+                        [day] => (Some(weekday_to_date((*day).into())), *time),
+                        _ => return Err(Error::InvalidArgument("A todo can only be given a single weekday.".to_string())),
+                    },
+                    _ => return Err(Error::InvalidArgument("A todo can only be given a single weekday or date.".to_string())),
+                };
+                let original = self.shown_id(id, self.list.get_task(id)?.uuid());
+                let todo_id = self.list.convert_task_to_todo(id, date)?;
+                let todo = self.list.get_todo_mut(todo_id)?;
+                self.conf.apply_rules(todo);
+                if time.is_some() {
+                    todo.set_time(time);
+                }
+                let todo = self.list.get_todo(todo_id)?;
+                say!("Converted task {} into todo {} for {}", original, self.shown_id(todo_id, todo.uuid()), todo.date());
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the id of an item given as an argument. With short ids, or if the argument isn't a
+    /// number, the argument is the beginning of the uuid of exactly one item. Removed items are
+    /// looked up instead if `removed` is set.
+    fn resolve_id(&self, item_type: ItemType, arg: &str, removed: bool) -> Result<u64> {
+        if self.conf.id_style() == IdStyle::Number {
+            if let Ok(id) = arg.parse() {
+                return Ok(id);
+            }
+        }
+
+        let items: Vec<(u64, Uuid)> = match (item_type, removed) {
+            (ItemType::Todo, false) => self.list.todos().iter().map(|todo| (todo.id(), todo.uuid())).collect(),
+            (ItemType::Todo, true) => self.list.removed_todos().iter().map(|todo| (todo.id(), todo.uuid())).collect(),
+            (ItemType::Task, false) => self.list.tasks().iter().map(|task| (task.id(), task.uuid())).collect(),
+            (ItemType::Task, true) => self.list.removed_tasks().iter().map(|task| (task.id(), task.uuid())).collect(),
+        };
+        let prefix = arg.replace('-', "").to_lowercase();
+        let matching: Vec<u64> = items.into_iter()
+            .filter(|(_, uuid)| !prefix.is_empty() && uuid.simple().to_string().starts_with(&prefix))
+            .map(|(id, _)| id)
+            .collect();
+
+        let kind = if item_type == ItemType::Todo { "todo" } else { "task" };
+        match matching.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(Error::InvalidArgument(format!("No {} has the id \"{}\".", kind, arg))),
+            _ => Err(Error::InvalidArgument(format!("The id \"{}\" matches {} {}s. Give more of it.", arg, matching.len(), kind))),
+        }
+    }
+
+    /// Resolves ids given as arguments. An argument that isn't an id is matched against the bodies
+    /// of the items ignoring case. If it matches many todos, only the todos that aren't already in
+    /// the wanted done state are considered. An argument that still matches many items is an error
+    /// listing the candidates.
+    fn resolve_ids(&self, item_type: ItemType, args: &[String], to_done: bool) -> Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        for arg in args {
+            // Words are more likely bodies than uuids unless ids are shown as short ids.
+            let id = match self.conf.id_style() {
+                IdStyle::Number => arg.parse().ok(),
+                IdStyle::Short => self.resolve_id(item_type, arg, false).ok(),
+            };
+            if let Some(id) = id {
+                ids.push(id);
+                continue;
+            }
+
+            let mut candidates: Vec<(u64, String, bool)> = self.list.search(arg).into_iter()
+                .filter_map(|item| match (item, item_type) {
+                    (ItemRef::Todo(todo), ItemType::Todo) => Some((todo.id(), self.todo_text(todo), todo.done() != to_done)),
+                    (ItemRef::Task(task), ItemType::Task) => Some((task.id(), self.task_text(task), true)),
+                    _ => None,
+                })
+                .collect();
+            if candidates.len() > 1 && candidates.iter().any(|(_, _, changes)| *changes) {
+                candidates.retain(|(_, _, changes)| *changes);
+            }
+
+            match candidates.as_slice() {
+                [(id, _, _)] => ids.push(*id),
+                [] => return Err(Error::InvalidArgument(format!("No {} matches \"{}\".", if item_type == ItemType::Todo { "todo" } else { "task" }, arg))),
+                _ => {
+                    let list: String = candidates.iter().map(|(_, item, _)| format!("\n  {}", item)).collect();
+                    return Err(Error::InvalidArgument(format!("\"{}\" matches multiple items. Give one of their ids instead:{}", arg, list)));
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn modify_done_state(&mut self, item_type: ItemType, ids: &[u64], to_done: bool) -> Result<()> {
+        match item_type {
+            ItemType::Todo => {
+                self.list.set_todos_done(ids, to_done)?;
+            }
+            ItemType::Task => {
+                let today = current_date();
+                // Each task is set done for its own next date, so all of them are changed in one
+                // transaction instead of using set_tasks_done.
+                self.list.transaction(|tx| {
+                    for id in ids {
+                        let task = tx.get_task_mut(*id)?;
+                        // A missed date that is still shown is done before the upcoming ones.
+                        let missed_date = task.missed_date(today).filter(|_| to_done);
+                        let next_date_for_task = missed_date.or_else(|| task.next_date(today))
+                            .ok_or_else(|| Error::InvalidArgument(format!("Task {} isn't for any upcoming date.", id)))?;
+                        task.set_done(to_done, next_date_for_task);
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn modify_paused_state(&mut self, item_type: ItemType, id: u64, to_paused: bool, until: Option<NaiveDate>) -> Result<()> {
+        if item_type == ItemType::Todo {
+            return Err(Error::InvalidArgument("Only tasks can be paused.".to_string()));
+        }
+
+        let task = self.list.get_task_mut(id)?;
+
+        match (to_paused, until) {
+            (true, Some(date)) => task.pause_until(date),
+            _ => task.set_paused(to_paused),
+        }
+
+        Ok(())
+    }
+
+    fn postpone(&mut self, item_type: ItemType, id: u64, to: Postponement) -> Result<()> {
+        if item_type == ItemType::Task {
+            return Err(Error::InvalidArgument("Only todos can be postponed.".to_string()));
+        }
+
+        let todo = self.list.get_todo_mut(id)?;
+        let days = match to {
+            Postponement::Days(days) => days,
+            Postponement::Date(date) => {
+                let from = todo.date().max(current_date());
+                match u32::try_from((date - from).num_days()) {
+                    Ok(days) if days > 0 => days,
+                    _ => return Err(Error::InvalidArgument(format!("Todo {} can only be postponed to a date after {}.", id, from))),
+                }
+            }
+        };
+
+        todo.postpone(days)?;
+        say!("Postponed todo {} to {}", id, todo.date());
+
+        Ok(())
+    }
+
+    fn set(&mut self, item_type: ItemType, id: u64, values: SetValues) -> Result<()> {
+        let SetValues { body, weekdays, date, until, no_until, backfill, notes, time, priority } = values;
+        // An empty value removes the notes.
+        let notes = notes.map(|notes| Some(notes).filter(|notes| !notes.is_empty()));
+
+        let mut chrono_weekdays: Vec<chrono::Weekday> = Vec::new();
+        for wd in weekdays {
+            chrono_weekdays.push(wd.into());
+        }
+
+        match item_type {
+            ItemType::Todo => {
+                if until.is_some() || no_until {
+                    return Err(Error::InvalidArgument("Todos cannot be given an end date.".to_string()));
+                }
+                if backfill.is_some() {
+                    return Err(Error::InvalidArgument("Todos are shown until done and cannot be backfilled.".to_string()));
+                }
+                let todo = self.list.get_todo_mut(id)?;
+                if let Some(b) = body {
+                    todo.set_body(b)?;
+                }
+                if !chrono_weekdays.is_empty() {
+                    todo.set_weekday(chrono_weekdays[0]);
+                }
+                if let Some(d) = date {
+                    todo.set_date(d);
+                }
+                if let Some(n) = notes {
+                    todo.set_notes(n);
+                }
+                if time.is_some() {
+                    todo.set_time(time);
+                }
+                if let Some(p) = priority {
+                    todo.set_priority(p);
+                }
+            }
+            ItemType::Task => {
+                if date.is_some() {
+                    return Err(Error::InvalidArgument("Tasks cannot be given an exact date.".to_string()));
+                }
+                if priority.is_some() {
+                    return Err(Error::InvalidArgument("Tasks cannot be given a priority.".to_string()));
+                }
+                let task = self.list.get_task_mut(id)?;
+                if let Some(b) = body {
+                    task.set_body(b)?;
+                }
+                if !chrono_weekdays.is_empty() {
+                    task.set_weekdays(chrono_weekdays)?;
+                }
+                if until.is_some() || no_until {
+                    task.set_until(until);
+                }
+                if let Some(days) = backfill {
+                    task.set_backfill_days(days);
+                }
+                if let Some(n) = notes {
+                    task.set_notes(n);
+                }
+                if time.is_some() {
+                    task.set_time(time);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn find(&self, query: &str, fuzzy: bool, output: Output) -> Result<()> {
+        let found = if fuzzy { self.list.search_fuzzy(query) } else { self.list.search(query) };
+
+        if output != Output::Text {
+            let records: Vec<FindRecord> = found.into_iter().map(|item| match item {
+                ItemRef::Todo(todo) => FindRecord {
+                    item_type: "todo",
+                    id: todo.id(),
+                    uuid: todo.uuid(),
+                    body: todo.body().to_string(),
+                    date: Some(todo.date()),
+                    weekdays: Vec::new(),
+                },
+                ItemRef::Task(task) => FindRecord {
+                    item_type: "task",
+                    id: task.id(),
+                    uuid: task.uuid(),
+                    body: task.body().to_string(),
+                    date: None,
+                    weekdays: task.weekdays().iter().map(|wd| wd.to_string()).collect(),
+                },
+            }).collect();
+            return print_records(&records, output);
+        }
+
+        if found.is_empty() {
+            println!("No items found.");
+        }
+
+        for item in found {
+            match item {
+                ItemRef::Todo(todo) => println!("todo {} ({})", self.todo_text(todo), todo.date()),
+                ItemRef::Task(task) => {
+                    let days = task.weekdays().iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(", ");
+                    println!("task {} ({})", self.task_text(task), days);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn list_items(&self, item_type: ItemType, sort: SortOrder, filter: Option<&str>, output: Output) -> Result<()> {
+        let filter = filter.map(|filter| filter.to_lowercase());
+        let matches = |body: &str| filter.as_ref().is_none_or(|filter| body.to_lowercase().contains(filter));
+        let today = current_date();
+
+        let records: Vec<ListRecord> = match item_type {
+            ItemType::Todo => self.list.todos_sorted(sort).into_iter().filter(|todo| matches(todo.body())).map(|todo| ListRecord {
+                item_type: "todo",
+                id: todo.id(),
+                uuid: todo.uuid(),
+                body: todo.body().to_string(),
+                date: Some(todo.date()),
+                weekdays: Vec::new(),
+                time: todo.time(),
+                priority: Some(todo.priority()),
+                created: todo.created().map(|created| created.date()),
+                done: todo.done_date(),
+                until: None,
+                paused: None,
+            }).collect(),
+            ItemType::Task => self.list.tasks_sorted(sort).into_iter().filter(|task| matches(task.body())).map(|task| ListRecord {
+                item_type: "task",
+                id: task.id(),
+                uuid: task.uuid(),
+                body: task.body().to_string(),
+                date: None,
+                weekdays: task.weekdays().iter().map(|wd| wd.to_string()).collect(),
+                time: task.time(),
+                priority: None,
+                created: task.created(),
+                done: None,
+                until: task.until(),
+                paused: Some(task.paused(today)),
+            }).collect(),
+        };
+
+        if output != Output::Text {
+            return print_records(&records, output);
+        }
+
+        if records.is_empty() {
+            println!("No items found.");
+        }
+
+        for record in records {
+            let mut details = match record.date {
+                Some(date) => vec![date.to_string()],
+                None => vec![record.weekdays.join(", ")],
+            };
+            if let Some(time) = record.time {
+                details.push(time.format("%H:%M").to_string());
+            }
+            if let Some(priority) = record.priority.filter(|priority| *priority > 0) {
+                details.push(format!("priority {}", priority));
+            }
+            if let Some(done) = record.done {
+                details.push(format!("done on {}", done));
+            }
+            if let Some(until) = record.until {
+                details.push(format!("until {}", until));
+            }
+            if record.paused == Some(true) {
+                details.push("paused".to_string());
+            }
+            if let Some(created) = record.created {
+                details.push(format!("created {}", created));
+            }
+            println!("{} {}. {} ({})", record.item_type, self.shown_id(record.id, record.uuid), record.body, details.join(", "));
+        }
+
+        Ok(())
+    }
+
+    fn stats(&self, weeks: u32, output: Output) -> Result<()> {
+        let today = current_date();
+        let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+        let stats = self.list.statistics(monday - chrono::Duration::weeks(weeks as i64 - 1)..=today);
+
+        match output {
+            Output::Text => {}
+            Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            Output::Tsv => {
+                let weeks = stats.weeks.iter().map(|week| StatsRecord {
+                    kind: "week",
+                    id: None,
+                    name: week.start.to_string(),
+                    scheduled: week.scheduled,
+                    completed: week.completed,
+                    streaks: None,
+                });
+                let tasks = stats.tasks.iter().map(|task| StatsRecord {
+                    kind: "task",
+                    id: Some(task.id),
+                    name: task.body.clone(),
+                    scheduled: task.scheduled,
+                    completed: task.completed,
+                    streaks: Some((task.current_streak, task.longest_streak)),
+                });
+                println!("{}", format_tsv(&weeks.chain(tasks).collect::<Vec<StatsRecord>>()));
+                return Ok(());
+            }
+        }
+
+        let format_rate = |scheduled: usize, completed: usize, rate: Option<f64>| match rate {
+            Some(rate) => format!("{}/{} ({:.0}%)", completed, scheduled, rate * 100.0),
+            None => "nothing scheduled".to_string(),
+        };
+
+        println!("{}", self.paint("Weeks:", Style::Header));
+        for week in &stats.weeks {
+            println!("\t{}: {}", week.start, format_rate(week.scheduled, week.completed, week.completion_rate()));
+        }
+
+        println!("{}", self.paint("Tasks:", Style::Header));
+        if stats.tasks.is_empty() {
+            println!("\tNo tasks.");
+        }
+        for task in &stats.tasks {
+            println!(
+                "\t{}. {}: {}, current streak {}, longest streak {}",
+                task.id,
+                task.body,
+                format_rate(task.scheduled, task.completed, task.completion_rate()),
+                task.current_streak,
+                task.longest_streak,
+            );
+        }
+
+        println!("{} {}", self.paint("Overdue todos:", Style::Header), stats.overdue_todos);
+
+        Ok(())
+    }
+
+    fn report_stale(&self, limit: usize, output: Output) -> Result<()> {
+        let mut report = self.list.stale_report();
+        report.todos.truncate(limit);
+        report.tasks.truncate(limit);
+
+        match output {
+            Output::Text => {}
+            Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            Output::Tsv => {
+                let todos = report.todos.iter().map(|todo| StaleRecord {
+                    kind: "todo",
+                    id: todo.id,
+                    body: todo.body.clone(),
+                    postponed: Some(todo.postponed),
+                    days_overdue: Some(todo.days_overdue),
+                    age_days: todo.age_days,
+                    missed_in_row: None,
+                });
+                let tasks = report.tasks.iter().map(|task| StaleRecord {
+                    kind: "task",
+                    id: task.id,
+                    body: task.body.clone(),
+                    postponed: None,
+                    days_overdue: None,
+                    age_days: None,
+                    missed_in_row: Some(task.missed_in_row),
+                });
+                println!("{}", format_tsv(&todos.chain(tasks).collect::<Vec<StaleRecord>>()));
+                return Ok(());
+            }
+        }
+
+        println!("{}", self.paint("Todos:", Style::Header));
+        if report.todos.is_empty() {
+            println!("\tNo undone todos.");
+        }
+        for todo in &report.todos {
+            let mut details = vec![format!("postponed {} times", todo.postponed)];
+            if todo.days_overdue > 0 {
+                details.push(format_days_overdue(todo.days_overdue));
+            }
+            if let Some(age) = todo.age_days {
+                details.push(format!("added {} days ago", age));
+            }
+            println!("\t{}. {} ({})", todo.id, todo.body, details.join(", "));
+        }
+
+        println!("{}", self.paint("Tasks:", Style::Header));
+        if report.tasks.is_empty() {
+            println!("\tNo missed tasks.");
+        }
+        for task in &report.tasks {
+            let last_done = match task.last_done {
+                Some(date) => format!("last done for {}", date),
+                None => "never done".to_string(),
+            };
+            println!("\t{}. {} (missed {} times in a row, {})", task.id, task.body, task.missed_in_row, last_done);
+        }
+
+        Ok(())
+    }
+
+    fn diff(&self, old: Option<PathBuf>, new: Option<PathBuf>) -> Result<()> {
+        let read_list = |path: &Path| read_list_file(&self.conf, path);
+
+        let old_list = match old {
+            Some(path) => read_list(&path)?,
+            None => {
+                let latest_backup = match self.conf.save_location() {
+                    Some(path) => backup_files(path)?.pop(),
+                    None => None,
+                };
+                match latest_backup {
+                    Some(path) => {
+                        say!("Comparing to {}", path.display());
+                        read_list(&path)?
+                    }
+                    None => return Err(Error::InvalidArgument("No backup to compare to found.".to_string())),
+                }
+            }
+        };
+        let new_list = match new {
+            Some(path) => read_list(&path)?,
+            None => self.list.clone(),
+        };
+
+        let changes = old_list.diff(&new_list);
+
+        if changes.is_empty() {
+            println!("No changes.");
+        }
+
+        for change in changes {
+            println!("{}", describe_change(&change));
+        }
+
+        Ok(())
+    }
+
+    fn status(&self) -> Result<()> {
+        if self.conf.local_only() {
+            println!("Local-only instance, nothing is synchronized.");
+            return Ok(());
+        }
+        if self.list.is_server() {
+            return Err(Error::ClientOnlyOperation);
+        }
+
+        let changes = self.list.pending_changes();
+        if changes.is_empty() {
+            println!("No changes waiting for sync.");
+        } else {
+            println!("{}", self.paint(&format!("{} change(s) waiting for sync:", changes.len()), Style::Header));
+        }
+
+        let describe = |item: ItemRef| match item {
+            ItemRef::Todo(todo) => format!("todo \"{}\"", todo.body()),
+            ItemRef::Task(task) => format!("task \"{}\"", task.body()),
+        };
+
+        for change in changes {
+            match change {
+                PendingChange::New(item) => println!("\t+ {}", describe(item)),
+                PendingChange::Edited(item) => println!("\t~ {}", describe(item)),
+                PendingChange::Removed(item) => println!("\t- {}", describe(item)),
+            }
+        }
+
+        if let Some(cache) = self.read_remote_cache()? {
+            println!("Last contact with the server: {}", cache.fetched_at.format("%Y-%m-%d %H:%M"));
+        }
+        if let Some(failures) = self.read_sync_failures()? {
+            let warning = format!(
+                "{} sync(s) failed in a row, the last at {}: {}",
+                failures.count,
+                failures.last_failed_at.format("%Y-%m-%d %H:%M"),
+                failures.last_error
+            );
+            println!("{}", self.paint(&warning, Style::Warning));
+        }
+
+        Ok(())
+    }
+
+    fn info(&self, item_type: ItemType, id: u64) -> Result<()> {
+        let notes = match item_type {
+            ItemType::Todo => {
+                let todo = self.list.get_todo(id)?;
+                println!("{} {}", self.paint(&format!("Todo {}:", self.shown_id(todo.id(), todo.uuid())), Style::Header), todo.body());
+                println!("Date: {} ({})", todo.date(), todo.weekday());
+                if let Some(time) = todo.time() {
+                    println!("Time: {}", time.format("%H:%M"));
+                }
+                println!("Priority: {}", todo.priority());
+                if let Some(created) = todo.created() {
+                    println!("Created: {}", created.format("%Y-%m-%d %H:%M"));
+                }
+                match todo.done_date() {
+                    Some(date) => println!("Done: yes, on {}", date),
+                    None => println!("Done: no"),
+                }
+                if todo.postponed() > 0 {
+                    println!("Postponed: {} times", todo.postponed());
+                }
+                for blocker in self.list.blocked_by(id)? {
+                    println!("Blocked by: {}", blocker);
+                }
+                self.print_sync_state(todo.sync_state());
+                println!("Uuid: {}", todo.uuid());
+                todo.notes()
+            }
+            ItemType::Task => {
+                let task = self.list.get_task(id)?;
+                let days = task.weekdays().iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(", ");
+                println!("{} {}", self.paint(&format!("Task {}:", self.shown_id(task.id(), task.uuid())), Style::Header), task.body());
+                if task.every_weeks() > 1 {
+                    println!("Weekdays: {} every {} weeks", days, task.every_weeks());
+                } else {
+                    println!("Weekdays: {}", days);
+                }
+                if task.weekday_times().is_empty() {
+                    if let Some(time) = task.time() {
+                        println!("Time: {}", time.format("%H:%M"));
+                    }
+                } else {
+                    let times = task.weekdays().iter()
+                        .filter_map(|wd| task.time_for(*wd).map(|time| format!("{} {}", wd, time.format("%H:%M"))))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    println!("Time: {}", times);
+                }
+                if let Some(until) = task.until() {
+                    println!("Until: {}", until);
+                }
+                if task.backfill_days() > 0 {
+                    println!("Backfill: missed dates are shown for {} day(s)", task.backfill_days());
+                }
+                if task.paused(current_date()) {
+                    match task.paused_until() {
+                        Some(date) => println!("Paused until {}", date),
+                        None => println!("Paused"),
+                    }
+                }
+                if let Some(created) = task.created() {
+                    println!("Created: {}", created);
+                }
+                println!("Done:");
+                for weekday in task.weekdays() {
+                    match task.done_map().get(weekday) {
+                        Some(date) => println!("\t{}: last done for {}", weekday, date),
+                        None => println!("\t{}: never done", weekday),
+                    }
+                }
+                println!("Completions: {}", task.completions().len());
+                self.print_sync_state(task.sync_state());
+                println!("Uuid: {}", task.uuid());
+                task.notes()
+            }
+        };
+
+        if let Some(notes) = notes {
+            println!("Notes:");
+            for line in notes.lines() {
+                println!("\t{}", line);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the sync state of an item. Local-only instances and servers don't sync their own
+    /// changes, so nothing is printed for them.
+    fn print_sync_state(&self, state: SyncState) {
+        if self.conf.local_only() || self.list.is_server() {
+            return;
+        }
+        let state = match state {
+            SyncState::Synced => "synced",
+            SyncState::New => "added after the last sync",
+            SyncState::Edited => "modified after the last sync",
+            SyncState::Removed => "removed after the last sync",
+        };
+        println!("Sync state: {}", state);
+    }
+
+    fn export(&self, format: ExportFormat, everything: bool, routines: bool, output: Option<PathBuf>) -> Result<()> {
+        if format != ExportFormat::Json && (everything || routines) {
+            return Err(Error::InvalidArgument("--everything and --routines can only be used with the json format.".to_string()));
+        }
+
+        let today = current_date();
+        let exporter: Option<Box<dyn Exporter>> = match format {
+            ExportFormat::Ical => Some(Box::new(IcalExporter::new(today, Utc::now()))),
+            ExportFormat::Csv => Some(Box::new(CsvExporter::new(today))),
+            ExportFormat::Md => Some(Box::new(MarkdownExporter::new(self.week_start(today, true), today))),
+            ExportFormat::Json => None,
+        };
+
+        let exported = if let Some(exporter) = &exporter {
+            let losses = exporter.losses(&self.list);
+            if !losses.is_empty() {
+                eprintln!("{} item details can't be exported in this format. Use --verbose to list them.", losses.len());
+            }
+            for loss in losses {
+                detail!("Not exported: {} of \"{}\" ({})", loss.field, loss.body, loss.uuid);
+            }
+            exporter.export(&self.list)
+        } else if routines {
+            self.routine_cards()
+        } else if everything {
+            serde_json::to_string_pretty(&self.data_export()?)?
+        } else {
+            self.list.to_json()?
+        };
+
+        match output {
+            Some(path) => fs::write(path, exported)?,
+            // Lines of the other formats already end with a line break.
+            None if exported.ends_with('\n') => print!("{}", exported),
+            None => println!("{}", exported),
+        }
+
+        Ok(())
+    }
+
+    /// Imports the items of a file in the format and prints what was done with each entry. With
+    /// `dry_run`, the items are imported to a copy of the list, so nothing is changed.
+    fn import(&mut self, format: ImportFormat, text: &str, dry_run: bool, output: Output) -> Result<()> {
+        let mut scratch = MtdApp { conf: self.conf.clone(), list: self.list.clone() };
+        let target = if dry_run { &mut scratch } else { &mut *self };
+        let outcomes = target.import_entries(format, text);
+
+        if output != Output::Text {
+            print_records(&outcomes, output)?;
+        } else {
+            // Taskwarrior entries are numbered by task rather than by line.
+            let source = if format == ImportFormat::Taskwarrior { "Task" } else { "Line" };
+            for outcome in &outcomes {
+                let mut line = match (outcome.item_type, outcome.id) {
+                    (Some(item_type), Some(id)) => {
+                        format!("{} {}: {} {} {} \"{}\"", source, outcome.source, action_name(outcome.action), item_type, target.shown_id_of(item_type, id), outcome.body)
+                    }
+                    _ => format!("{} {}: skip \"{}\": {}", source, outcome.source, outcome.body, outcome.reason.clone().unwrap_or_default()),
+                };
+                if !outcome.losses.is_empty() {
+                    line.push_str(&format!(" (without {})", outcome.losses.join(", ")));
+                }
+                println!("{}", line);
+            }
+        }
+
+        let count = |action: Action| outcomes.iter().filter(|outcome| outcome.action == action).count();
+        say!("{} created, {} updated, {} unchanged and {} skipped.{}", count(Action::Create), count(Action::Update), count(Action::Unchanged), count(Action::Skip),
+            if dry_run { " Nothing was changed." } else { "" });
+
+        Ok(())
+    }
+
+    /// Imports the items of a file in the format to the list. Returns what was done with each entry.
+    fn import_entries(&mut self, format: ImportFormat, text: &str) -> Vec<Outcome> {
+        let today = current_date();
+        let importer: Box<dyn Importer> = match format {
+            ImportFormat::TodoTxt => Box::new(TodoTxtImporter::new(today)),
+            ImportFormat::Ical => Box::new(IcalImporter::new(today)),
+            ImportFormat::Csv => Box::new(CsvImporter::new(today)),
+            ImportFormat::Taskwarrior => Box::new(TaskwarriorImporter::new(today)),
+        };
+        import(&mut self.list, importer.read(text), self.conf.rules())
+    }
+
+    /// Returns the id of an item of the type as it is shown, or the number if there is no such item.
+    fn shown_id_of(&self, item_type: &str, id: u64) -> String {
+        let uuid = match item_type {
+            "todo" => self.list.get_todo(id).map(Todo::uuid),
+            _ => self.list.get_task(id).map(Task::uuid),
+        };
+        match uuid {
+            Ok(uuid) => self.shown_id(id, uuid),
+            Err(_) => id.to_string(),
+        }
+    }
+
+    /// Returns a markdown card of each task with its weekdays, streak, notes and a checkbox for
+    /// each weekday.
+    fn routine_cards(&self) -> String {
+        let today = current_date();
+        let stats = self.list.statistics(today..=today);
+
+        let mut cards = Vec::new();
+        for (task, task_stats) in self.list.tasks().into_iter().zip(stats.tasks) {
+            let mut card = format!("## {}\n\n", task.body());
+
+            let days = task.weekdays().iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(", ");
+            if task.every_weeks() > 1 {
+                card.push_str(&format!("- Weekdays: {} every {} weeks\n", days, task.every_weeks()));
+            } else {
+                card.push_str(&format!("- Weekdays: {}\n", days));
+            }
+            card.push_str(&format!("- Streak: {} (longest {})\n", task_stats.current_streak, task_stats.longest_streak));
+
+            if let Some(notes) = task.notes() {
+                card.push('\n');
+                for line in notes.lines() {
+                    card.push_str(&format!("> {}\n", line));
+                }
+            }
+
+            card.push('\n');
+            for wd in task.weekdays() {
+                match task.time_for(*wd) {
+                    Some(time) => card.push_str(&format!("- [ ] {} {}\n", wd, time.format("%H:%M"))),
+                    None => card.push_str(&format!("- [ ] {}\n", wd)),
+                }
+            }
+
+            cards.push(card);
+        }
+
+        format!("# Routines\n\n{}", cards.join("\n"))
+    }
+
+    fn data_export(&self) -> Result<DataExport> {
+        // Secrets aren't data about the user so they aren't exported.
+        let mut config = serde_json::to_value(self.conf.without_secrets())?;
+        if let Some(config) = config.as_object_mut() {
+            config.remove("encryption_password");
+        }
+
+        let backups = match self.conf.save_location() {
+            Some(path) => backup_files(path)?,
+            None => Vec::new(),
+        };
+
+        Ok(DataExport {
+            exported_at: current_time(),
+            config,
+            items: self.list.clone(),
+            cached_server_items: self.read_remote_cache()?,
+            backups,
+        })
+    }
+
+    /// Writes an encrypted backup of the items and the config to a file. The lists of a server's users
+    /// are included.
+    fn create_backup(&self, file: &Path, include_secrets: bool, password: Option<String>) -> Result<()> {
+        let password = match password {
+            Some(password) => password,
+            None => {
+                let password = rpassword::prompt_password("Backup password: ")?;
+                if rpassword::prompt_password("Repeat the backup password: ")? != password {
+                    return Err(Error::InvalidArgument("The passwords don't match.".to_string()));
+                }
+                password
+            }
+        };
+        if password.is_empty() {
+            return Err(Error::InvalidArgument("The backup password cannot be empty.".to_string()));
+        }
+
+        let mut backup = Backup::new(&self.conf, &self.list, include_secrets);
+        // A server keeps the list of each user in a file of its own.
+        let mut user_lists = BTreeMap::new();
+        for user in self.conf.users() {
+            if let Some(path) = self.conf.user_save_location(&user.name).filter(|path| path.exists()) {
+                user_lists.insert(user.name.clone(), TdList::new_from_json(&read_data_file(&path, data_encryption(&self.conf)?)?)?);
+            }
+        }
+        backup.set_user_lists(user_lists);
+        let sealed = backup.seal(password.as_bytes())?;
+        fs::write(file, sealed)?;
+        say!("Created a backup of the items and the config at '{}'.", file.display());
+
+        Ok(())
+    }
+
+    /// Replaces the items and the config of a profile, and the lists of a server's users, with a
+    /// backup. The save path of an existing profile is kept because paths of another device rarely
+    /// fit this one. Secrets left out of the backup are taken from the existing profile or asked for.
+    fn restore_backup(config_path: &PathBuf, profile: Option<&str>, file: &Path, password: Option<String>, yes: bool) -> Result<()> {
+        let password = match password {
+            Some(password) => password,
+            None => rpassword::prompt_password("Backup password: ")?,
+        };
+        let backup = Backup::open(&fs::read(file)?, password.as_bytes())?;
+        let created_at = backup.created_at();
+        let (mut conf, list, user_lists) = backup.into_parts();
+
+        let existing = MtdApp::read_config_file(config_path)?
+            .and_then(|file| file.profiles.get(file.profile_name(profile)).cloned());
+
+        let save_path = match existing.as_ref().and_then(|conf| conf.save_location()) {
+            Some(path) => path.clone(),
+            None => MtdApp::default_save_path(profile)?,
+        };
+        conf.set_save_location(Some(save_path.clone()));
+
+        if conf.encryption_password().is_empty() {
+            match &existing {
+                Some(existing) => {
+                    conf.set_encryption_password(existing.encryption_password().clone());
+                    conf.set_tor_control_password(existing.tor_control_password().map(str::to_string));
+                }
+                None if !conf.local_only() => conf.set_encryption_password(MtdApp::prompt_encryption_password(None)?),
+                // Local only instances don't use the password.
+                None => conf.set_encryption_password(rand::thread_rng().sample_iter(&Alphanumeric).take(16).collect()),
+            }
+        }
+        // A user without a password couldn't log in, so the passwords of the existing users are kept
+        // and the others asked for.
+        let mut users = conf.users().to_vec();
+        for user in users.iter_mut().filter(|user| user.password.is_empty()) {
+            let kept = existing.as_ref().and_then(|existing| existing.users().iter().find(|other| other.name == user.name));
+            user.password = match kept {
+                Some(kept) if !kept.password.is_empty() => kept.password.clone(),
+                _ => MtdApp::prompt_user_password(&user.name)?,
+            };
+        }
+        conf.set_users(users);
+        // The user names become file names.
+        conf.validate()?;
+
+        if save_path.exists() && !yes && !MtdApp::prompt_yes_no(&format!("Replace the items saved at '{}'", save_path.display()))? {
+            say!("Nothing was restored.");
+            return Ok(());
+        }
+        if let Some(backup_path) = MtdApp::backup_file(&save_path)? {
+            say!("Created a backup of '{}' at '{}'.", save_path.display(), backup_path.display());
+        }
+
+        let app = MtdApp { conf, list };
+        app.save_list()?;
+        for user in app.conf.users() {
+            if let (Some(path), Some(list)) = (app.conf.user_save_location(&user.name), user_lists.get(&user.name)) {
+                if let Some(backup_path) = MtdApp::backup_file(&path)? {
+                    say!("Created a backup of '{}' at '{}'.", path.display(), backup_path.display());
+                }
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                write_data_file(&path, list.to_json()?.as_bytes(), data_encryption(&app.conf)?)?;
+            }
+        }
+        MtdApp::write_profile_config(config_path, profile, &app.conf)?;
+        say!("Restored the items and the config backed up at {}.", created_at.format("%Y-%m-%d %H:%M"));
+
+        Ok(())
+    }
+
+    /// Prints an item sealed with the given passphrase or a generated one.
+    fn share(&self, item_type: ItemType, id: u64, passphrase: Option<String>) -> Result<()> {
+        let item = match item_type {
+            ItemType::Todo => SharedItem::from_todo(self.list.get_todo(id)?),
+            ItemType::Task => SharedItem::from_task(self.list.get_task(id)?),
+        };
+
+        let generated = passphrase.is_none();
+        let passphrase = passphrase.unwrap_or_else(|| rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect());
+        if passphrase.is_empty() {
+            return Err(Error::InvalidArgument("The passphrase cannot be empty.".to_string()));
+        }
+
+        println!("{}", item.seal(passphrase.as_bytes())?);
+        if generated {
+            // The passphrase goes to stderr so that the item alone can be piped or redirected.
+            eprintln!("Passphrase: {}", passphrase);
+        }
+
+        Ok(())
+    }
+
+    /// Opens a shared item and adds it to the items.
+    fn receive(&mut self, item: Option<String>, passphrase: Option<String>) -> Result<()> {
+        let blob = match item {
+            Some(item) => item,
+            None => io::read_to_string(io::stdin())?,
+        };
+        let passphrase = match passphrase {
+            Some(passphrase) => passphrase,
+            None => rpassword::prompt_password("Passphrase: ")?,
+        };
+
+        let item = SharedItem::open(&blob, passphrase.as_bytes())?;
+        let id = self.list.add_shared(&item)?;
+        match item {
+            SharedItem::Todo { date, .. } => {
+                self.conf.apply_rules(self.list.get_todo_mut(id)?);
+                say!("Received todo {} for {}", id, date);
+            }
+            SharedItem::Task { weekdays, .. } => {
+                say!("Received task {} for {}", id, weekdays.iter().map(|wd| wd.to_string()).collect::<Vec<String>>().join(", "))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a desktop notification of today's undone items. With `if_nonempty` nothing is sent if
+    /// everything is done.
+    fn notify(&self, if_nonempty: bool) -> Result<()> {
+        let day = self.list.today(&SystemClock);
+        if if_nonempty && day.undone_count() == 0 {
+            detail!("Nothing to do today, no notification sent.");
+            return Ok(());
+        }
+
+        let (summary, body) = agenda_notification(&day);
+        Notification::new()
+            .appname("mtd")
+            .summary(&summary)
+            .body(&body)
+            .show()
+            .map_err(|e| Error::IOErr(io::Error::other(format!("Couldn't send a notification: {}", e))))?;
+        detail!("Sent a notification: {}", summary);
+
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.unlock_password()?;
+        let conf = &self.conf;
+
+        let mut net_mgr = MtdNetMgr::new(&mut self.list, conf);
+        // Large first syncs would otherwise look frozen.
+        if io::stderr().is_terminal() && verbosity() > Verbosity::Quiet {
+            net_mgr.set_progress_callback(|progress| {
+                let line = format_progress(progress);
+                eprint!("\r{:<60}\r{}", "", line);
+                if progress.phase == SyncPhase::Done {
+                    eprint!("\r{:<60}\r", "");
+                }
+            });
+        }
+
+        detail!("Syncing with '{}'.", conf.socket_addr());
+        let started = Instant::now();
+        let synced = net_mgr.client_sync();
+        let clock_skew = net_mgr.clock_skew();
+        let progress = net_mgr.progress();
+        let conflicts = net_mgr.conflicts().to_vec();
+        self.warn_about_clock_skew(clock_skew);
+        if let Err(e) = synced {
+            self.try_record_sync_failure(&e);
+            return Err(e);
+        }
+        detail!("{}", format_sync_details(progress, started.elapsed()));
+        report_conflicts(&conflicts);
+
+        // After syncing the local items match the server's items.
+        self.clear_sync_failures()?;
+        self.write_remote_cache(&self.list)
+    }
+
+    /// Syncs and tries again up to `retries` times if the server can't be reached. The first retry
+    /// waits `backoff` and each later one twice as long as the previous one.
+    fn sync_with_retries(&mut self, retries: u32, backoff: Duration) -> Result<()> {
+        let mut tries = 0;
+        loop {
+            match self.sync() {
+                Err(e) if tries < retries && is_transient(&e) => {
+                    let delay = retry_delay(backoff, tries);
+                    tries += 1;
+                    say!("Sync failed: {}. Trying again in {} s ({}/{}).", e, delay.as_secs(), tries, retries);
+                    if sleep_unless_stopped(delay) {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Syncs every `interval` until interrupted or stopped with `daemon stop`. The connection to the
+    /// server is kept open between syncs and kept alive with keepalives. Items saved by other mtd
+    /// commands in the meantime are read again before each sync.
+    ///
+    /// Failed syncs are retried after waits that double with each failure, see `retry_delay`. Only
+    /// the first failure in a row is printed and `status` tells how many syncs have failed.
+    fn sync_watch(&mut self, interval: Duration) -> Result<()> {
+        self.unlock_password()?;
+        let conf = self.conf.clone();
+        let mut list = self.list.clone();
+        let mut net_mgr = MtdNetMgr::new(&mut list, &conf);
+        net_mgr.set_keep_alive(true);
+        let mut failures = 0;
+
+        loop {
+            if let Some(path) = conf.save_location().filter(|path| path.exists()) {
+                *net_mgr.td_list_mut() = read_list_file(&conf, path)?;
+            }
+
+            let started = Instant::now();
+            failures = match net_mgr.client_sync() {
+                Ok(()) => {
+                    self.list = net_mgr.td_list().clone();
+                    self.save_list()?;
+                    self.write_remote_cache(&self.list)?;
+                    self.clear_sync_failures()?;
+                    say!("Synced at {}.", current_time().format("%H:%M:%S"));
+                    detail!("{}", format_sync_details(net_mgr.progress(), started.elapsed()));
+                    report_conflicts(net_mgr.conflicts());
+                    0
+                }
+                Err(e) => {
+                    // Without a record the failures are still counted for as long as this runs.
+                    let failures = self.try_record_sync_failure(&e).unwrap_or(failures + 1);
+                    let delay = retry_delay(interval, failures);
+                    if failures == 1 {
+                        eprintln!("Sync failed: {}. Retrying in {} s and less often while it keeps failing.", e, delay.as_secs());
+                    } else {
+                        detail!("Sync failed {} times in a row: {}. Retrying in {} s.", failures, e, delay.as_secs());
+                    }
+                    failures
+                }
+            };
+            self.warn_about_clock_skew(net_mgr.clock_skew());
+
+            let next_sync = Instant::now() + retry_delay(interval, failures);
+            while let Some(remaining) = next_sync.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+                if sleep_unless_stopped(remaining.min(KEEPALIVE_INTERVAL)) {
+                    // The items were saved after the last sync.
+                    return Ok(());
+                }
+                if Instant::now() < next_sync {
+                    // A failed keepalive only means that the next sync connects again.
+                    let _ = net_mgr.send_keepalive();
+                }
+            }
+        }
+    }
+
+    fn server(&mut self) -> Result<()> {
+        self.unlock_password()?;
+        let conf = &self.conf;
+        conf.validate()?;
+
+        // The listening loop itself refuses to run for clients and local-only instances.
+        if !conf.local_only() && self.list.is_server() {
+            say!("Listening on {}. Clients should connect to {}.", conf.socket_addr(), conf.advertised_addr());
+        }
+
+        let encryption = data_encryption(conf)?;
+        let mut net_mgr = MtdNetMgr::new(&mut self.list, conf);
+        if let Some(stop) = STOP_FLAG.get() {
+            net_mgr.set_stop_flag(stop.clone());
+        }
+        if let Some(encryption) = encryption {
+            net_mgr.set_storage_encryption(encryption);
+        }
+
+        net_mgr.server_listening_loop()
+    }
+
+    /// Runs the commands of a script on the items and syncs once afterwards if asked to. Every
+    /// command is checked before any is run. The items are saved only if all commands succeed,
+    /// except that a failed sync still keeps the changes.
+    fn eval(mut self, script: &str, sync: bool) -> Result<Self> {
+        let mut commands = Vec::new();
+        for (line, args) in parse_script(script)? {
+            let command = CliArgs::try_parse_from(iter::once("mtd".to_string()).chain(args))
+                .map_err(|e| Error::InvalidArgument(format!("{}: {}", line, clap_error_summary(&e))))?
+                .command;
+            match command {
+                Commands::Show { watch: true, .. } | Commands::Sync { .. } | Commands::Server { .. } | Commands::Init { .. } | Commands::ReInit { .. }
+                | Commands::Demo { .. } | Commands::Eval { .. } | Commands::Config { .. } | Commands::Profile { .. } | Commands::Daemon { .. }
+                | Commands::Backup { .. } | Commands::Pair { .. } | Commands::Notify { .. } => {
+                    return Err(Error::InvalidArgument(format!("{}: The command can't be used in a script.", line)));
+                }
+                Commands::External(args) => {
+                    return Err(Error::InvalidArgument(format!("{}: '{}' isn't an mtd command.", line, args[0])));
+                }
+                // The script is what confirms removing.
+                Commands::Remove { item_type, ids, .. } => commands.push((line, Commands::Remove { item_type, ids, yes: true })),
+                command => commands.push((line, command)),
+            }
+        }
+
+        for (line, command) in commands {
+            self = self.handle_command(command).map_err(|e| Error::InvalidArgument(format!("{}: {}", line, e)))?;
+        }
+        detail!("Ran the script.");
+
+        if sync {
+            if let Err(e) = self.sync() {
+                // The changes are kept even though they couldn't be synced.
+                self.save_list()?;
+                return Err(e);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Adds, lists or revokes the devices paired with the server. A running server notices the
+    /// changes with its next connection.
+    fn device_command(&self, action: DeviceAction) -> Result<()> {
+        let path = self.conf.devices_location()
+            .ok_or_else(|| Error::InvalidArgument("The server needs a save location to keep its devices in.".to_string()))?;
+        let mut registry = DeviceRegistry::read(&path)?;
+
+        match action {
+            DeviceAction::AddDevice { name } => {
+                let code = registry.add_pairing(&name, current_time())?;
+                registry.write(&path)?;
+                say!("Run this on {} within {} minutes:", name, PAIRING_CODE_MINUTES);
+                println!("mtd pair {}", pairing_payload(&code, self.conf.advertised_addr()));
+            }
+            DeviceAction::Devices => {
+                for device in registry.devices() {
+                    println!("{}  paired {}", device.name, device.paired_at.format("%Y-%m-%d %H:%M"));
+                }
+                for (name, expires) in registry.pending() {
+                    let state = if expires > current_time() { "waiting to pair" } else { "pairing code expired" };
+                    println!("{}  {}", name, state);
+                }
+                if registry.devices().is_empty() && registry.pending().next().is_none() {
+                    say!("No devices are paired.");
+                }
+            }
+            DeviceAction::RevokeDevice { name } => {
+                registry.revoke(&name)?;
+                registry.write(&path)?;
+                say!("Revoked {}. It can't sync anymore.", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn init_command(config_path: &PathBuf, profile: Option<&str>, reconfigure: bool) -> Result<Self> {
+        let initialized = MtdApp::read_config_file(config_path)?
+            .is_some_and(|file| file.profiles.contains_key(file.profile_name(profile)));
+
+        // Initializing a new profile happens automatically when reading the config.
+        let mut app = MtdApp::init(config_path, profile)?;
+
+        if initialized {
+            if reconfigure {
+                // Saved items are written to the new save location afterwards.
+                app.conf = MtdApp::create_new_config(config_path, profile, Some(&app.conf))?;
+            } else {
+                println!("Mtd is already initialized. Use 'mtd init --reconfigure' to change the config.");
+            }
+        }
+
+        Ok(app)
+    }
+
+    /// Copies a file to a timestamped backup file in the same directory. Returns the path of the
+    /// backup or `None` if the file doesn't exist.
+    fn backup_file(path: &Path) -> Result<Option<PathBuf>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut backup_name = path.file_name().ok_or(Error::Unknown)?.to_os_string();
+        backup_name.push(format!(".{}.bak", current_time().format("%Y%m%d-%H%M%S")));
+
+        let backup_path = path.with_file_name(backup_name);
+        fs::copy(path, &backup_path)?;
+
+        Ok(Some(backup_path))
+    }
+
+    fn re_init(config_path: &PathBuf, profile: Option<&str>, yes: bool, keep_config: bool) -> Result<Self> {
+        let previous = MtdApp::read_config_file(config_path)?
+            .and_then(|file| file.profiles.get(file.profile_name(profile)).cloned());
+        let save_location = previous.as_ref().and_then(|conf| conf.save_location().cloned());
+        // Saved items that can't be read are deleted as well, so they aren't an error here.
+        let previous_list = match (&previous, &save_location) {
+            (Some(conf), Some(path)) => read_list_file(conf, path).ok(),
+            _ => None,
+        };
+
+        let previous_conf = match (keep_config, previous) {
+            (true, None) => return Err(Error::InvalidArgument("There is no config to keep.".to_string())),
+            (true, Some(conf)) => Some(conf),
+            (false, _) => None,
+        };
+        // Only the saved items tell whether a kept config is a server's or a client's, and --yes
+        // means that nothing can be asked.
+        if yes && previous_list.is_none() && previous_conf.as_ref().is_some_and(|conf| !conf.local_only()) {
+            return Err(Error::InvalidArgument(
+                "The saved items can't be read, so it isn't known whether this is a server or a client. Run re-init without --yes to choose.".to_string(),
+            ));
+        }
+
+        match (&previous_list, &save_location) {
+            (Some(list), Some(path)) => println!("This will delete {} todo(s) and {} task(s) saved in '{}'.", list.todos().len(), list.tasks().len(), path.display()),
+            (None, Some(path)) if path.exists() => println!("This will delete the saved items in '{}'.", path.display()),
+            _ => println!("There are no saved items."),
+        }
+        if !keep_config {
+            println!("This will erase the config in '{}'.", config_path.display());
+        }
+
+        if !yes {
+            let mut buffer = String::new();
+
+            print!("Type 'erase' to proceed: ");
+            io::stdout().flush()?;
+            io::stdin().read_line(&mut buffer)?;
+
+            if buffer.trim() != "erase" {
+                println!("Abort!");
+                // This is not optimal, but is the easiest way to implement this.
+                process::exit(0);
+                // Other option would be to call, but in some cases that could seem like the abort didn't do anything
+                // return Ok(MtdApp::new(config_path)?);
+            }
+        }
+
+        // Back up both the config and the saved items of the profile being re-initialized.
+        for path in [Some(config_path.clone()), save_location].into_iter().flatten() {
+            if let Some(backup_path) = MtdApp::backup_file(&path)? {
+                say!("Created a backup of '{}' at '{}'.", path.display(), backup_path.display());
+            }
+        }
+
+        let config = match previous_conf {
+            Some(conf) => conf,
+            None => MtdApp::create_new_config(config_path, profile, None)?,
+        };
+        // A kept config keeps its role as a server or a client if the saved items could be read.
+        let list = match previous_list {
+            Some(old) if keep_config && old.is_server() => TdList::new_server(),
+            Some(_) if keep_config => TdList::new_client(),
+            _ => MtdApp::create_new_list(&config)?,
+        };
+
+        Ok(Self {
+            list,
+            conf: config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, thread};
+    use std::time::Duration;
+
+    use chrono::{Datelike, NaiveDate, NaiveTime};
+    use clap::{CommandFactory, Parser};
+
+    use mtd::{backup_files, Config, ConflictPolicy, current_date, DataEncryption, Error, FixedClock, IdStyle, read_data_file, ServerUser, SharedItem, SortOrder, SystemClock, Task, TdList, Todo, Uuid};
+    use mtd::engine::{SyncPhase, SyncProgress};
+    use mtd::export::{CsvExporter, Exporter};
+    use mtd::import::Action;
+
+    use crate::{AddOptions, agenda_notification, change_setting, CliArgs, ColorWhen, Commands, ConfigFile, DaemonAction, daemon_description, DeviceAction, date_range, DaySection, Days, describe_change, find_extension, format_progress, format_setting, format_tsv, ImportFormat, is_transient, ItemType, LogLevelArg, MtdApp, Output, parse_batch_line, parse_day_spec, parse_postponement, parse_script, parse_time, PlanAction, Postponement, ReportKind, retry_delay, SetValues, ShowRecord, Sort, split_args, Style, Weekday, When};
+
+    fn create_client_app() -> MtdApp {
+        MtdApp {
+            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None),
+            list: TdList::new_client(),
+        }
+    }
+
+    fn create_server_app() -> MtdApp {
+        MtdApp {
+            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None),
+            list: TdList::new_server(),
+        }
+    }
+
+    #[test]
+    fn cli_args_are_valid() {
+        CliArgs::command().debug_assert();
+    }
+
+    #[test]
+    fn add_adds_todo_successfully() {
+        let mut client = create_client_app();
+        client.add(ItemType::Todo, vec![Weekday::Wed], "Todo".to_string(), AddOptions::default()).unwrap();
+        assert_eq!(client.list.todos()[0], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Wed).unwrap());
+    }
+
+    #[test]
+    fn add_adds_task_successfully() {
+        let mut client = create_client_app();
+        client.add(ItemType::Task, vec![Weekday::Wed, Weekday::Fri, Weekday::Sun], "Task".to_string(), AddOptions::default()).unwrap();
+        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![chrono::Weekday::Wed, chrono::Weekday::Fri, chrono::Weekday::Sun]).unwrap())
+    }
+
+    #[test]
+    fn add_adds_task_without_explicit_weekday() {
+        let mut client = create_client_app();
+        client.add(ItemType::Task, vec![], "Task".to_string(), AddOptions::default()).unwrap();
+        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![current_date().weekday()]).unwrap())
+    }
+
+    #[test]
+    fn add_adds_todo_to_multiple_weekdays() {
+        let mut client = create_client_app();
+        client.add(ItemType::Todo, vec![Weekday::Wed, Weekday::Fri, Weekday::Sun], "Todo".to_string(), AddOptions::default()).unwrap();
+        assert_eq!(client.list.todos()[0], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Wed).unwrap());
+        assert_eq!(client.list.todos()[1], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Fri).unwrap());
+        assert_eq!(client.list.todos()[2], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun).unwrap());
+    }
+
+    #[test]
+    fn add_adds_todo_for_exact_date() {
+        let mut client = create_client_app();
+        let date = NaiveDate::from_ymd(2024, 8, 15);
+        client.add(ItemType::Todo, vec![], "Dentist".to_string(), AddOptions { date: Some(date), ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0], &Todo::new_specific_date("Dentist".to_string(), date).unwrap());
+    }
+
+    #[test]
+    fn add_task_with_exact_date_fails() {
+        let mut client = create_client_app();
+        assert!(client.add(ItemType::Task, vec![], "Task".to_string(), AddOptions { date: Some(NaiveDate::from_ymd(2024, 8, 15)), ..Default::default() }).is_err());
+        assert_eq!(client.list.tasks().len(), 0);
+    }
+
+    #[test]
+    fn plan_add_and_add_suggest_add_suggested_todos_once() {
+        let mut client = create_client_app();
+        let today = current_date();
+        client.plan(PlanAction::Add { body: "Meal prep".to_string(), weekdays: vec![Days::Daily] }).unwrap();
+        assert_eq!(client.list.plans().len(), 7);
+
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "--suggest", "today"]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "--suggest", "today"]).unwrap();
+        client = client.handle_command(args.command).unwrap();
+
+        assert_eq!(client.list.todos().len(), 1);
+        assert_eq!(client.list.get_todo(0).unwrap().date(), today);
+        assert!(client.list.suggestions_for_date(today).is_empty());
+        assert!(client.plan(PlanAction::Remove { ids: vec![0, 7] }).is_err());
+        client.plan(PlanAction::Remove { ids: vec![0, 6] }).unwrap();
+        assert_eq!(client.list.plans().len(), 5);
+    }
+
+    #[test]
+    fn add_applies_rules_unless_priority_is_given() {
+        let mut client = create_client_app();
+        client.conf = change_setting(&client.conf, "rules", Some(r#"[{"pattern": "buy", "priority": 2}]"#)).unwrap();
+
+        client.add(ItemType::Todo, vec![], "Buy milk".to_string(), AddOptions::default()).unwrap();
+        client.add(ItemType::Todo, vec![], "Buy bread".to_string(), AddOptions { priority: Some(5), ..Default::default() }).unwrap();
+        client.add(ItemType::Todo, vec![], "Call mom".to_string(), AddOptions::default()).unwrap();
+
+        assert_eq!(client.list.get_todo(0).unwrap().priority(), 2);
+        assert_eq!(client.list.get_todo(1).unwrap().priority(), 5);
+        assert_eq!(client.list.get_todo(2).unwrap().priority(), 0);
+        assert!(change_setting(&client.conf, "rules", Some(r#"[{"pattern": ""}]"#)).is_err());
+    }
+
+    #[test]
+    fn add_adds_task_with_end_date() {
+        let mut client = create_client_app();
+        let until = NaiveDate::from_ymd(2024, 8, 15);
+        client.add(ItemType::Task, vec![Weekday::Mon], "Course".to_string(), AddOptions { until: Some(until), ..Default::default() }).unwrap();
+        assert_eq!(client.list.tasks()[0].until(), Some(until));
+
+        let args = CliArgs::try_parse_from(["mtd", "set", "task", "0", "--no-until"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        assert_eq!(client.list.tasks()[0].until(), None);
+        assert!(CliArgs::try_parse_from(["mtd", "set", "task", "0", "--no-until", "--until", "2024-08-15"]).is_err());
+    }
+
+    #[test]
+    fn backfill_is_set_only_for_tasks() {
+        let mut client = create_client_app();
+        client.add(ItemType::Task, vec![Weekday::Thu], "Pay rent".to_string(), AddOptions { backfill: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(client.list.get_task(0).unwrap().backfill_days(), 2);
+        client.set(ItemType::Task, 0, SetValues { backfill: Some(0), ..Default::default() }).unwrap();
+        assert_eq!(client.list.get_task(0).unwrap().backfill_days(), 0);
+
+        assert!(client.add(ItemType::Todo, vec![], "Todo".to_string(), AddOptions { backfill: Some(1), ..Default::default() }).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "add", "task", "Pay rent", "thu", "--backfill", "7"]).is_err());
+    }
+
+    #[test]
+    fn add_adds_task_every_other_week_starting_this_week() {
+        let mut client = create_client_app();
+        client.add(ItemType::Task, vec![], "Recycling".to_string(), AddOptions { every: Some(2), ..Default::default() }).unwrap();
+
+        let today = current_date();
+        let task = client.list.tasks()[0];
+        assert_eq!(task.every_weeks(), 2);
+        assert!(task.for_date(today));
+        assert!(!task.for_date(today + chrono::Duration::weeks(1)));
+        assert!(task.for_date(today + chrono::Duration::weeks(2)));
+    }
+
+    #[test]
+    fn add_todo_with_every_fails() {
+        let mut client = create_client_app();
+        assert!(client.add(ItemType::Todo, vec![], "Todo".to_string(), AddOptions { every: Some(2), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn add_adds_notes_to_item() {
+        let mut client = create_client_app();
+        client.add(ItemType::Task, vec![], "Task".to_string(), AddOptions { notes: Some("Notes".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(client.list.tasks()[0].notes(), Some("Notes"));
+    }
+
+    #[test]
+    fn set_with_empty_notes_removes_notes() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+
+        client.set(ItemType::Todo, 0, SetValues { notes: Some("Line 1\nLine 2".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0].notes(), Some("Line 1\nLine 2"));
+
+        client.set(ItemType::Todo, 0, SetValues { notes: Some(String::new()), ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0].notes(), None);
+    }
+
+    #[test]
+    fn report_stale_parses_and_runs() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.list.get_todo_mut(0).unwrap().postpone(3).unwrap();
+
+        let args = CliArgs::try_parse_from(["mtd", "report", "stale", "--limit", "5", "--output", "tsv"]).unwrap();
+        assert!(matches!(args.command, Commands::Report { report: ReportKind::Stale { limit: 5, output: Output::Tsv } }));
+        client.handle_command(args.command).unwrap();
+    }
+
+    #[test]
+    fn remove_and_re_init_accept_yes() {
+        let args = CliArgs::try_parse_from(["mtd", "remove", "todo", "1", "2", "-y"]).unwrap();
+        assert!(matches!(args.command, Commands::Remove { yes: true, .. }));
+        let args = CliArgs::try_parse_from(["mtd", "re-init", "--force", "--keep-config"]).unwrap();
+        assert!(matches!(args.command, Commands::ReInit { yes: true, keep_config: true }));
+    }
+
+    #[test]
+    fn agenda_notification_lists_undone_items() {
+        let today = NaiveDate::from_ymd(2024, 8, 15);
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_specific_date("Late".to_string(), today.pred().pred()).unwrap());
+        let mut todo = Todo::new_specific_date("Call".to_string(), today).unwrap();
+        todo.set_time(Some(NaiveTime::from_hms(9, 0, 0)));
+        list.add_todo(todo);
+        list.add_task(Task::new("Run".to_string(), vec![today.weekday()]).unwrap());
+
+        let (summary, body) = agenda_notification(&list.today(&FixedClock(today)));
+        assert_eq!(summary, "2 todo(s) and 1 task(s) for today");
+        assert_eq!(body, "Late (2 days overdue)\n09:00 Call\nRun");
+
+        let (summary, body) = agenda_notification(&TdList::new_client().today(&FixedClock(today)));
+        assert_eq!(summary, "Nothing left to do today");
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn receive_adds_shared_item() {
+        let mut client = create_client_app();
+        let todo = Todo::new_specific_date("Shared".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap();
+        let blob = SharedItem::from_todo(&todo).seal(b"passphrase").unwrap();
+
+        assert!(client.receive(Some(blob.clone()), Some("wrong".to_string())).is_err());
+        client.receive(Some(blob), Some("passphrase".to_string())).unwrap();
+
+        assert_eq!(client.list.todos().len(), 1);
+        assert_eq!(client.list.get_todo(0).unwrap().body(), "Shared");
+        assert_eq!(client.list.get_todo(0).unwrap().date(), NaiveDate::from_ymd(2024, 8, 15));
+        assert!(client.share(ItemType::Todo, 0, Some(String::new())).is_err());
+        assert!(client.share(ItemType::Task, 0, None).is_err());
+    }
+
+    #[test]
+    fn show_watch_takes_an_interval() {
+        let args = CliArgs::try_parse_from(["mtd", "show", "--week", "--watch", "--interval", "5"]).unwrap();
+        assert!(matches!(args.command, Commands::Show { watch: true, interval: 5, .. }));
+        let args = CliArgs::try_parse_from(["mtd", "show", "--watch"]).unwrap();
+        assert!(matches!(args.command, Commands::Show { watch: true, interval: 60, .. }));
+
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--interval", "5"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--watch", "--interval", "0"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--watch", "--output", "json"]).is_err());
+    }
+
+    #[test]
+    fn only_servers_and_watch_modes_are_daemons() {
+        let args = CliArgs::try_parse_from(["mtd", "daemon", "reload"]).unwrap();
+        assert!(matches!(args.command, Commands::Daemon { action: DaemonAction::Reload }));
+        assert!(CliArgs::try_parse_from(["mtd", "daemon"]).is_err());
+
+        let description = |args: &[&str]| daemon_description(&CliArgs::try_parse_from(args).unwrap().command);
+        assert_eq!(description(&["mtd", "server"]), Some("server"));
+        assert_eq!(description(&["mtd", "sync", "--watch"]), Some("sync --watch"));
+        assert_eq!(description(&["mtd", "show", "--watch"]), Some("show --watch"));
+        assert_eq!(description(&["mtd", "sync"]), None);
+        assert_eq!(description(&["mtd", "show"]), None);
+    }
+
+    #[test]
+    fn server_log_level_defaults_to_info() {
+        let args = CliArgs::try_parse_from(["mtd", "server"]).unwrap();
+        assert!(matches!(args.command, Commands::Server { log_level: LogLevelArg::Info, .. }));
+        let args = CliArgs::try_parse_from(["mtd", "server", "--log-level", "debug"]).unwrap();
+        assert!(matches!(args.command, Commands::Server { log_level: LogLevelArg::Debug, .. }));
+        assert_eq!(tracing::Level::from(LogLevelArg::Warn), tracing::Level::WARN);
+        assert!(CliArgs::try_parse_from(["mtd", "server", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn managing_devices_doesnt_run_the_server() {
+        let args = CliArgs::try_parse_from(["mtd", "server", "add-device", "laptop"]).unwrap();
+        assert!(matches!(&args.command, Commands::Server { action: Some(DeviceAction::AddDevice { name }), .. } if name == "laptop"));
+        assert_eq!(daemon_description(&args.command), None);
+        assert_eq!(daemon_description(&CliArgs::try_parse_from(["mtd", "server"]).unwrap().command), Some("server"));
+
+        let args = CliArgs::try_parse_from(["mtd", "pair", "laptop:ABCD-EFGH-JKLM@127.0.0.1:4000"]).unwrap();
+        assert!(matches!(args.command, Commands::Pair { code } if code == "laptop:ABCD-EFGH-JKLM@127.0.0.1:4000"));
+    }
+
+    #[test]
+    fn quiet_and_verbose_are_exclusive() {
+        let args = CliArgs::try_parse_from(["mtd", "-q", "show"]).unwrap();
+        assert!(args.quiet && !args.verbose);
+        let args = CliArgs::try_parse_from(["mtd", "--verbose", "sync"]).unwrap();
+        assert!(args.verbose && !args.quiet);
+        assert!(CliArgs::try_parse_from(["mtd", "-q", "-v", "show"]).is_err());
+    }
+
+    #[test]
+    fn describe_change_describes_each_kind_of_change() {
+        let old = TdList::new_client();
+        let mut new = old.clone();
+        new.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        assert_eq!(describe_change(&old.diff(&new)[0]), "+ todo \"Todo\"");
+        assert_eq!(describe_change(&new.diff(&old)[0]), "- todo \"Todo\"");
+
+        let mut edited = new.clone();
+        edited.get_todo_mut(0).unwrap().set_body("Edited".to_string()).unwrap();
+        assert_eq!(describe_change(&new.diff(&edited)[0]), "~ todo \"Todo\" -> \"Edited\"");
+    }
+
+    #[test]
+    fn confirm_remove_fails_for_invalid_ids_without_asking() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+
+        assert!(matches!(client.confirm_remove(ItemType::Todo, &[0, 5]), Err(Error::NoTodoWithGivenId(5))));
+    }
+
+    #[test]
+    fn info_shows_done_todos_and_tasks() {
+        let mut client = create_client_app();
+        let today = current_date();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.list.get_todo_mut(0).unwrap().set_done(true);
+        client.list.add_task(Task::new("Task".to_string(), vec![today.weekday(), today.succ().weekday()]).unwrap());
+        client.list.get_task_mut(0).unwrap().set_done(true, today);
+
+        assert_eq!(client.list.get_todo(0).unwrap().done_date(), Some(today));
+        client.info(ItemType::Todo, 0).unwrap();
+        client.info(ItemType::Task, 0).unwrap();
+    }
+
+    #[test]
+    fn info_fails_for_nonexistent_item() {
+        let client = create_client_app();
+        assert!(client.info(ItemType::Task, 0).is_err());
+    }
+
+    #[test]
+    fn add_adds_time_to_item() {
+        let mut client = create_client_app();
+        let time = NaiveTime::from_hms(18, 30, 0);
+        client.add(ItemType::Todo, vec![], "Todo".to_string(), AddOptions { time: Some(time), ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0].time(), Some(time));
+    }
+
+    #[test]
+    fn parse_time_accepts_only_hours_and_minutes() {
+        assert_eq!(parse_time("07:05"), Ok(NaiveTime::from_hms(7, 5, 0)));
+        assert!(parse_time("7").is_err());
+        assert!(parse_time("25:00").is_err());
+    }
+
+    #[test]
+    fn day_section_uses_config_boundaries() {
+        let mut conf = Config::new_default(Vec::new(), "127.0.0.1:55980".to_string(), None);
+
+        assert_eq!(DaySection::of(None, &conf), DaySection::Anytime);
+        assert_eq!(DaySection::of(Some(NaiveTime::from_hms(11, 59, 0)), &conf), DaySection::Morning);
+        assert_eq!(DaySection::of(Some(NaiveTime::from_hms(12, 0, 0)), &conf), DaySection::Afternoon);
+        assert_eq!(DaySection::of(Some(NaiveTime::from_hms(17, 0, 0)), &conf), DaySection::Evening);
+
+        conf.set_day_sections(NaiveTime::from_hms(11, 0, 0), NaiveTime::from_hms(19, 0, 0));
+
+        assert_eq!(DaySection::of(Some(NaiveTime::from_hms(11, 30, 0)), &conf), DaySection::Afternoon);
+        assert_eq!(DaySection::of(Some(NaiveTime::from_hms(18, 0, 0)), &conf), DaySection::Afternoon);
+    }
+
+    #[test]
+    fn paint_doesnt_style_in_ascii_mode() {
+        let mut client = create_client_app();
+        assert_eq!(client.paint("Todos:", Style::Header), "\x1B[32mTodos:\x1B[0m");
+
+        client.conf.set_ascii(true);
+        assert_eq!(client.paint("Todos:", Style::Header), "Todos:");
+        assert!(client.paint("Done", Style::Done).is_ascii());
+    }
+
+    #[test]
+    fn add_todo_after_hides_it_until_dependency_is_done() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Test".to_string()).unwrap());
+        client.add(ItemType::Todo, vec![], "Deploy".to_string(), AddOptions { after: vec!["0".to_string()], ..Default::default() }).unwrap();
+
+        let today = current_date();
+        assert_eq!(client.list.undone_todos_for_date(today).len(), 1);
+
+        client.modify_done_state(ItemType::Todo, &[0], true).unwrap();
+        assert_eq!(client.list.undone_todos_for_date(today)[0].body(), "Deploy");
+    }
+
+    #[test]
+    fn restore_command_restores_removed_todo() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.list.remove_todo(0).unwrap();
+
+        assert!(client.show_removed(None).is_ok());
+
+        let client = client.handle_command(Commands::Restore { item_type: ItemType::Todo, id: "0".to_string() }).unwrap();
+
+        assert_eq!(client.list.todos().len(), 1);
+        assert!(client.list.removed_todos().is_empty());
+    }
+
+    #[test]
+    fn days_expand_groups_without_duplicates() {
+        assert_eq!(Days::expand(&[Days::Weekend]), vec![Weekday::Sat, Weekday::Sun]);
+        assert_eq!(Days::expand(&[Days::Fri, Days::Weekdays, Days::Weekend]).len(), 7);
+        assert_eq!(Days::expand(&[Days::Daily])[0], Weekday::Mon);
+
+        let args = CliArgs::try_parse_from(["mtd", "add", "task", "standup", "weekday"]).unwrap();
+        assert!(matches!(args.command, Commands::Add { weekdays, .. } if weekdays[0].when == When::Days(Days::Weekdays)));
+    }
+
+    #[test]
+    fn add_task_with_weekday_times() {
+        let client = create_client_app();
+        let args = CliArgs::try_parse_from(["mtd", "add", "task", "gym", "mon@18:00", "WED@07:00", "fri"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+
+        let task = client.list.tasks()[0];
+        assert_eq!(task.weekdays(), &vec![chrono::Weekday::Mon, chrono::Weekday::Wed, chrono::Weekday::Fri]);
+        assert_eq!(task.time_for(chrono::Weekday::Mon), Some(NaiveTime::from_hms(18, 0, 0)));
+        assert_eq!(task.time_for(chrono::Weekday::Wed), Some(NaiveTime::from_hms(7, 0, 0)));
+        assert_eq!(task.time_for(chrono::Weekday::Fri), None);
+
+        assert!(parse_day_spec("mon@7").is_err());
+        assert!(parse_day_spec("someday").is_err());
+    }
+
+    #[test]
+    fn add_from_file_adds_a_todo_per_line() {
+        let path = env::temp_dir().join(format!("mtd-batch-{}.txt", process::id()));
+        fs::write(&path, "Buy milk\n\nCall mom fri@18:00\n  Read a book mon  \n").unwrap();
+
+        let client = create_client_app();
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "--file", path.to_str().unwrap(), "--priority", "2"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let todos = client.list.todos();
+        assert_eq!(todos.len(), 3);
+        assert!(todos.iter().all(|todo| todo.priority() == 2));
+        let call = todos.iter().find(|todo| todo.body() == "Call mom").unwrap();
+        assert_eq!(call.weekday(), chrono::Weekday::Fri);
+        assert_eq!(call.time(), Some(NaiveTime::from_hms(18, 0, 0)));
+        assert!(todos.iter().any(|todo| todo.body() == "Read a book" && todo.weekday() == chrono::Weekday::Mon));
+
+        // Only weekdays are split off, so bodies ending with other words that look like dates stay whole.
+        assert_eq!(parse_batch_line("Meet on monday"), Some(("Meet on monday".to_string(), None)));
+        assert_eq!(parse_batch_line("   "), None);
+        assert!(CliArgs::try_parse_from(["mtd", "add", "todo", "body", "--stdin"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "add", "todo"]).is_err());
+    }
+
+    #[test]
+    fn dry_run_reports_added_and_invalid_lines_without_adding() {
+        let mut client = create_client_app();
+        let text = format!("Gym mon\n\n{}\nRead fri\n", "x".repeat(mtd::MAX_BODY_LENGTH + 1));
+        let records = client.batch_report(ItemType::Task, &text, AddOptions::default());
+
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[0].line, records[0].action, records[0].weekdays.clone()), (1, "add", vec!["Mon".to_string()]));
+        assert_eq!((records[1].line, records[1].action), (3, "invalid"));
+        assert_eq!(records[1].reason, Some(Error::BodyTooLong.to_string()));
+        assert_eq!((records[2].line, records[2].action), (4, "add"));
+        assert!(client.list.tasks().is_empty());
+
+        // Like the real run, the dry run fails because of the invalid line.
+        assert!(client.print_batch_report(ItemType::Task, &text, AddOptions::default(), Output::Json).is_err());
+        match client.add_batch(ItemType::Task, &text, AddOptions::default()) {
+            Err(Error::InvalidArgument(msg)) => assert_eq!(msg, format!("Line 3: {}", Error::BodyTooLong)),
+            other => panic!("invalid line wasn't refused: {:?}", other),
+        }
+        assert!(client.print_batch_report(ItemType::Task, "Gym mon\n", AddOptions::default(), Output::Json).is_ok());
+
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "--stdin", "--dry-run", "--output", "json"]).unwrap();
+        assert!(matches!(args.command, Commands::Add { dry_run: true, output: Output::Json, .. }));
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "body", "--dry-run"]).unwrap();
+        assert!(create_client_app().handle_command(args.command).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "add", "todo", "--stdin", "--output", "json"]).is_err());
+    }
+
+    #[test]
+    fn routine_cards_have_checkbox_for_each_weekday() {
+        let mut client = create_client_app();
+        let mut task = Task::new("Brush teeth".to_string(), vec![chrono::Weekday::Mon, chrono::Weekday::Thu]).unwrap();
+        task.set_notes(Some("Two minutes\nUse floss".to_string()));
+        client.list.add_task(task);
+
+        let cards = client.routine_cards();
+
+        assert!(cards.contains("## Brush teeth\n"));
+        assert!(cards.contains("> Use floss\n"));
+        assert!(cards.contains("- [ ] Mon\n- [ ] Thu\n"));
+    }
+
+    #[test]
+    fn status_works_only_for_clients() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        assert!(client.status().is_ok());
+        assert!(create_server_app().status().is_err());
+    }
+
+    #[test]
+    fn failed_syncs_are_counted_until_a_sync_succeeds() {
+        let save_path = env::temp_dir().join("mtd-sync-failures-test.json");
+        let _ = fs::remove_file(env::temp_dir().join("mtd-sync-failures-test.json.failures"));
+        let app = MtdApp {
+            conf: Config::new_default(b"SecurePw".to_vec(), "127.0.0.1:55980".to_string(), Some(save_path)),
+            list: TdList::new_client(),
+        };
+
+        assert_eq!(app.record_sync_failure(&Error::Unknown).unwrap(), 1);
+        assert_eq!(app.record_sync_failure(&Error::OnlineOnlyOperation).unwrap(), 2);
+        let failures = app.read_sync_failures().unwrap().unwrap();
+        assert_eq!(failures.count, 2);
+        assert_eq!(failures.last_error, Error::OnlineOnlyOperation.to_string());
+        assert!(app.status().is_ok());
+
+        app.clear_sync_failures().unwrap();
+        assert!(app.read_sync_failures().unwrap().is_none());
+    }
+
+    #[test]
+    fn failing_to_record_a_failed_sync_keeps_the_error_of_the_sync() {
+        let save_path = env::temp_dir().join("mtd-missing-dir-test").join("data.json");
+        let mut app = MtdApp {
+            conf: Config::new_default(b"SecurePw".to_vec(), "127.0.0.1:1".to_string(), Some(save_path)),
+            list: TdList::new_client(),
+        };
+
+        // The directory of the record doesn't exist.
+        assert!(app.try_record_sync_failure(&Error::Unknown).is_none());
+        match app.sync() {
+            Err(Error::IOErr(e)) => assert_eq!(e.kind(), std::io::ErrorKind::ConnectionRefused),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn data_file_is_encrypted_with_the_data_password() {
+        let save_path = env::temp_dir().join("mtd-data-encryption-test.json");
+        env::set_var("MTD_DATA_PASSWORD", "data password");
+        let mut app = MtdApp {
+            conf: Config::new_default(b"SecurePw".to_vec(), "127.0.0.1:55980".to_string(), Some(save_path.clone())),
+            list: TdList::new_client(),
+        };
+        app.conf.set_data_encryption(DataEncryption::Password);
+        app.list.add_todo(Todo::new_undated("Secret todo".to_string()).unwrap());
+
+        app.save_list().unwrap();
+        assert!(!fs::read_to_string(&save_path).is_ok_and(|json| json.contains("Secret todo")));
+        assert!(read_data_file(&save_path, None).is_err());
+
+        app.list = TdList::new_client();
+        app.reload_list().unwrap();
+        assert_eq!(app.list.todos()[0].body(), "Secret todo");
+        fs::remove_file(save_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn password_command_unlocks_the_encryption_password() {
+        let mut app = create_client_app();
+        app.conf.set_encryption_password(Vec::new());
+        app.conf.set_password_command(Some("echo 'from token'".to_string()));
+        assert!(app.conf.validate().is_ok());
+
+        app.unlock_password().unwrap();
+        assert_eq!(app.conf.encryption_password(), &b"from token".to_vec());
+
+        // A stored password is used as it is.
+        app.conf.set_password_command(Some("exit 1".to_string()));
+        app.unlock_password().unwrap();
+        app.conf.set_encryption_password(Vec::new());
+        assert!(app.unlock_password().is_err());
+    }
+
+    #[test]
+    fn config_with_the_password_in_the_keyring_needs_no_password() {
+        let mut app = create_client_app();
+        app.conf.set_encryption_password(Vec::new());
+        assert!(app.conf.validate().is_err());
+
+        app.conf.set_password_in_keyring(true);
+        assert!(app.conf.validate().is_ok());
+
+        // A password that is already known isn't read from the keyring again.
+        app.conf.set_encryption_password(b"SecurePw".to_vec());
+        app.unlock_password().unwrap();
+        assert_eq!(app.conf.encryption_password(), &b"SecurePw".to_vec());
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_limit() {
+        let interval = Duration::from_secs(300);
+        assert_eq!(retry_delay(interval, 0), interval);
+        assert_eq!(retry_delay(interval, 1), Duration::from_secs(600));
+        assert_eq!(retry_delay(interval, 3), Duration::from_secs(2400));
+        assert_eq!(retry_delay(interval, 4), Duration::from_secs(3600));
+        assert_eq!(retry_delay(interval, u32::MAX), Duration::from_secs(3600));
+        // Intervals longer than the limit are kept.
+        assert_eq!(retry_delay(Duration::from_secs(7200), 2), Duration::from_secs(7200));
+    }
+
+    #[test]
+    fn sync_retries_only_unreachable_servers() {
+        let args = CliArgs::try_parse_from(["mtd", "sync", "--retries", "3", "--backoff", "5"]).unwrap();
+        assert!(matches!(args.command, Commands::Sync { watch: false, retries: 3, backoff: 5, .. }));
+        assert!(CliArgs::try_parse_from(["mtd", "sync", "--backoff", "5"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "sync", "--retries", "3", "--watch"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "sync", "--retries", "3", "--backoff", "0"]).is_err());
+
+        assert!(is_transient(&Error::IOErr(std::io::ErrorKind::ConnectionRefused.into())));
+        assert!(!is_transient(&Error::AuthFailed));
+    }
+
+    #[test]
+    fn sync_with_retries_gives_up_on_an_unreachable_server() {
+        let mut app = create_client_app();
+        // Nothing listens on port 1, so connecting fails right away.
+        app.conf = Config::new_default(b"SecurePw".to_vec(), "127.0.0.1:1".to_string(), None);
+
+        let started = std::time::Instant::now();
+        assert!(matches!(app.sync_with_retries(2, Duration::from_millis(50)), Err(Error::IOErr(_))));
+        // The retries waited 50 ms and 100 ms.
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn show_records_list_undone_items_first() {
+        let mut app = create_client_app();
+        let today = current_date();
+        app.list.add_todo(Todo::new_specific_date("Done".to_string(), today).unwrap());
+        app.list.add_todo(Todo::new_specific_date("Undone".to_string(), today).unwrap());
+        app.list.get_todo_mut(0).unwrap().set_done(true);
+
+        let records = MtdApp::show_records(&app.list.day_view(today, &SystemClock, SortOrder::Manual), true, true);
+        assert_eq!(records.iter().map(|r| (r.body.as_str(), r.done)).collect::<Vec<_>>(), vec![("Undone", false), ("Done", true)]);
+    }
+
+    #[test]
+    fn format_tsv_has_header_and_escapes_tabs() {
+        let record = ShowRecord {
+            date: NaiveDate::from_ymd(2022, 6, 6),
+            item_type: "todo",
+            id: 3,
+            uuid: Uuid::nil(),
+            body: "Tab\there\nand newline".to_string(),
+            done: false,
+            time: Some("09:30".to_string()),
+            days_overdue: 0,
+        };
+
+        let tsv = format_tsv(&[record]);
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], "date\ttype\tid\tuuid\tbody\tdone\ttime\tdays_overdue");
+        assert_eq!(lines[1], format!("2022-06-06\ttodo\t3\t{}\tTab here and newline\tfalse\t09:30\t0", Uuid::nil()));
+    }
+
+    #[test]
+    fn output_option_is_parsed_and_conflicts_with_include_removed() {
+        let args = CliArgs::try_parse_from(["mtd", "find", "milk", "--output", "json"]).unwrap();
+        assert!(matches!(args.command, Commands::Find { output: Output::Json, .. }));
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--include-removed"]).is_ok());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--include-removed", "--output", "tsv"]).is_err());
+        let args = CliArgs::try_parse_from(["mtd", "list", "task", "--sort", "alpha", "--filter", "walk", "--output", "tsv"]).unwrap();
+        assert!(matches!(args.command, Commands::List { item_type: ItemType::Task, sort: Sort::Alpha, filter: Some(_), output: Output::Tsv }));
+        assert!(CliArgs::try_parse_from(["mtd", "list"]).is_err());
+    }
+
+    #[test]
+    fn week_start_is_today_or_configured_weekday() {
+        let mut app = create_client_app();
+        // 2022-6-8 is a Wednesday.
+        let today = NaiveDate::from_ymd(2022, 6, 8);
+
+        assert_eq!(app.week_start(today, false), today);
+        assert_eq!(app.week_start(today, true), NaiveDate::from_ymd(2022, 6, 6));
+
+        app.conf.set_week_starts_on(chrono::Weekday::Sun);
+        assert_eq!(app.week_start(today, true), NaiveDate::from_ymd(2022, 6, 5));
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--calendar"]).is_err());
+    }
+
+    #[test]
+    fn date_range_includes_both_ends() {
+        let range = date_range(NaiveDate::from_ymd(2022, 6, 28), NaiveDate::from_ymd(2022, 7, 2)).unwrap();
+        assert_eq!(range.len(), 5);
+        assert_eq!(range[0], NaiveDate::from_ymd(2022, 6, 28));
+        assert_eq!(range[4], NaiveDate::from_ymd(2022, 7, 2));
+        assert!(date_range(NaiveDate::from_ymd(2022, 7, 2), NaiveDate::from_ymd(2022, 7, 1)).is_err());
+
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--from", "2022-06-28"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--to", "2022-06-28", "--week"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--from", "2022-06-28", "--to", "2022-07-02"]).is_ok());
+    }
+
+    #[test]
+    fn show_records_mark_overdue_todos() {
+        let mut app = create_client_app();
+        let today = current_date();
+        app.list.add_todo(Todo::new_specific_date("Late".to_string(), today - chrono::Duration::days(3)).unwrap());
+        app.list.add_todo(Todo::new_specific_date("Late but done".to_string(), today - chrono::Duration::days(3)).unwrap());
+        app.list.add_todo(Todo::new_undated("On time".to_string()).unwrap());
+        app.list.get_todo_mut(1).unwrap().set_done(true);
+
+        let records = MtdApp::show_records(&app.list.day_view(today, &SystemClock, SortOrder::Manual), true, false);
+        let overdue: Vec<(&str, u64)> = records.iter().map(|r| (r.body.as_str(), r.days_overdue)).collect();
+        assert_eq!(overdue, vec![("Late", 3), ("On time", 0), ("Late but done", 0)]);
+
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--overdue"]).is_ok());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--overdue", "--week"]).is_err());
+        assert!(CliArgs::try_parse_from(["mtd", "show", "--overdue", "-i", "task"]).is_err());
+    }
+
+    #[test]
+    fn color_options_are_parsed() {
+        assert!(ColorWhen::Always.use_color());
+        assert!(!ColorWhen::Never.use_color());
+        let args = CliArgs::try_parse_from(["mtd", "--color", "always", "show"]).unwrap();
+        assert_eq!(args.color, ColorWhen::Always);
+        assert!(CliArgs::try_parse_from(["mtd", "--no-color", "--color", "always", "show"]).is_err());
+    }
+
+    #[test]
+    fn split_args_handles_quotes() {
+        assert_eq!(split_args("add todo \"Buy milk\"  mon\n"), Ok(vec!["add".to_string(), "todo".to_string(), "Buy milk".to_string(), "mon".to_string()]));
+        assert_eq!(split_args("find ''"), Ok(vec!["find".to_string(), "".to_string()]));
+        assert!(split_args("add todo \"Buy milk").is_err());
+    }
+
+    #[test]
+    fn scripts_are_read_as_lines_or_json() {
+        let args = |line: &str| split_args(line).unwrap();
+        assert_eq!(parse_script("add todo \"Buy milk\"\n\n# Comment\nlist todo\n").unwrap(), vec![
+            ("Line 1".to_string(), args("add todo \"Buy milk\"")),
+            ("Line 4".to_string(), args("list todo")),
+        ]);
+        assert_eq!(parse_script(r#"["list todo", ["add", "todo", "Buy milk"]]"#).unwrap(), vec![
+            ("Command 1".to_string(), args("list todo")),
+            ("Command 2".to_string(), args("add todo \"Buy milk\"")),
+        ]);
+        assert!(parse_script(r#"["list todo", 5]"#).is_err());
+        assert!(parse_script("add todo \"Buy milk").is_err());
+    }
+
+    #[test]
+    fn eval_runs_every_command_or_none() {
+        let app = create_client_app().eval("add todo First\nadd task Run mon\nadd todo Second", false).unwrap();
+        assert_eq!(app.list.todos().len(), 2);
+        assert_eq!(app.list.tasks().len(), 1);
+
+        assert!(matches!(app.eval("add todo Third\nsync", false), Err(Error::InvalidArgument(msg)) if msg.starts_with("Line 2")));
+        let app = create_client_app();
+        assert!(matches!(app.eval("add todo Third\nset todo 5 --body Fourth", false), Err(Error::InvalidArgument(msg)) if msg.starts_with("Line 2")));
+    }
+
+    #[test]
+    fn format_progress_shows_bar_while_receiving() {
+        let progress = SyncProgress { phase: SyncPhase::Receiving, bytes_received: 1536, bytes_expected: 3072, ..Default::default() };
+        assert_eq!(format_progress(&progress), "Receiving items [##########..........] 1.5 KiB / 3.0 KiB");
+
+        let progress = SyncProgress { phase: SyncPhase::Sending, items_sent: 12, bytes_sent: 900, ..Default::default() };
+        assert_eq!(format_progress(&progress), "Sending 12 items (900 B)...");
+    }
 
-        if week {
-            // Iterate over the next 7-days.
-            let orig_wd = Local::today().weekday();
-            let mut day = Local::today().naive_local();
+    #[test]
+    fn demo_runs_commands_without_a_save_file() {
+        let app = MtdApp { conf: Config::new(String::new(), Vec::new(), Duration::from_secs(30), None, true), list: MtdApp::demo_list() };
+        let todos = app.list.todos().len();
 
-            loop {
-                // Print each day.
-                self.print_date(day, show_todos, show_tasks);
-                println!();
+        let command = MtdApp::parse_demo_command(vec!["add".to_string(), "todo".to_string(), "Demo".to_string()]).unwrap();
+        let app = app.handle_demo_command(command).unwrap();
 
-                day = day.succ();
-                if day.weekday() == orig_wd {
-                    break;
-                }
-            }
-        } else {
-            let weekday: chrono::Weekday;
+        assert_eq!(app.list.todos().len(), todos + 1);
+        assert!(app.conf.save_location().is_none());
+        assert!(app.handle_demo_command(Commands::Sync { watch: false, interval: 300, retries: 0, backoff: 2 }).is_err());
 
-            // If cli arg weekday is unspecified show today's weekday.
-            if let Some(wd) = weekday_opt {
-                weekday = wd.into();
-            } else {
-                weekday = Local::today().weekday();
-            }
+        let app = MtdApp { conf: Config::new(String::new(), Vec::new(), Duration::from_secs(30), None, true), list: MtdApp::demo_list() };
+        let command = MtdApp::parse_demo_command(vec!["show".to_string(), "--watch".to_string()]).unwrap();
+        assert!(app.handle_demo_command(command).is_err());
 
-            self.print_date(mtd::weekday_to_date(weekday), show_todos, show_tasks);
-        }
+        let app = MtdApp { conf: Config::new(String::new(), Vec::new(), Duration::from_secs(30), None, true), list: MtdApp::demo_list() };
+        let command = MtdApp::parse_demo_command(vec!["daemon".to_string(), "stop".to_string()]).unwrap();
+        assert!(app.handle_demo_command(command).is_err());
     }
 
-    fn print_date(&self, date: NaiveDate, show_todos: bool, show_tasks: bool) {
-        // Print weekday in yellow
-        println!("\x1B[33m{}:\x1B[39m", date.weekday().to_string().to_uppercase());
-        if show_todos {
-            let undone_todos = self.list.undone_todos_for_date(date);
-            let done_todos = self.list.done_todos_for_date(date);
+    #[test]
+    fn add_todo_with_natural_language_date() {
+        let client = create_client_app();
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "Deploy", "in 3 days@14:00"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
 
-            // Print header as green
-            println!("\x1B[32mTodos:\x1B[39m");
+        let todo = client.list.todos()[0];
+        assert_eq!(todo.date(), current_date() + chrono::Duration::days(3));
+        assert_eq!(todo.time(), Some(NaiveTime::from_hms(14, 0, 0)));
 
-            if undone_todos.len() + done_todos.len() == 0 {
-                println!("\tNo todos for this day.");
-            } else {
-                for todo in undone_todos {
-                    println!("\t{}", todo);
-                }
-                for todo in done_todos {
-                    // Strikethrough and dim done todos.
-                    println!("\t\x1B[2m\x1B[9m{}\x1B[0m", todo);
-                }
-            }
-        }
-        if show_tasks {
-            let undone_tasks = self.list.undone_tasks_for_date(date);
-            let done_tasks = self.list.done_tasks_for_date(date);
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "Deploy", "mon", "tomorrow"]).unwrap();
+        assert!(client.handle_command(args.command).is_err());
+    }
 
-            // Print header as green
-            println!("\x1B[32mTasks:\x1B[39m");
+    #[test]
+    fn add_with_empty_body_fails() {
+        let mut client = create_client_app();
+        let result = client.add(ItemType::Task, vec![Weekday::Mon], " ".to_string(), AddOptions::default());
+        assert!(matches!(result, Err(Error::EmptyBody)));
+        assert!(client.list.tasks().is_empty());
+    }
 
-            if undone_tasks.len() + done_tasks.len() == 0 {
-                println!("\tNo tasks for this day.");
-            } else {
-                for task in undone_tasks {
-                    println!("\t{}", task);
-                }
-                for task in done_tasks {
-                    // Strikethrough and dim done tasks.
-                    println!("\t\x1B[2m\x1B[9m{}\x1B[0m", task);
-                }
-            }
-        }
+    #[test]
+    fn add_task_with_priority_fails() {
+        let mut client = create_client_app();
+        assert!(client.add(ItemType::Task, vec![], "Task".to_string(), AddOptions { priority: Some(1), ..Default::default() }).is_err());
+        assert!(client.list.tasks().is_empty());
     }
 
-    fn add(&mut self, item_type: ItemType, weekdays: Vec<Weekday>, body: String) {
-        let mut chrono_weekdays: Vec<chrono::Weekday> = Vec::new();
-        for wd in weekdays {
-            chrono_weekdays.push(wd.into());
-        }
+    #[test]
+    fn move_command_reorders_todos() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        client.list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
-        // If no weekdays are specified, add today's weekday.
-        if chrono_weekdays.is_empty() {
-            chrono_weekdays.push(Local::today().weekday());
-        }
+        let client = client.handle_command(Commands::Move { item_type: ItemType::Todo, id: "1".to_string(), position: 0 }).unwrap();
 
-        match item_type {
-            ItemType::Todo => {
-                for day in chrono_weekdays {
-                    self.list.add_todo(Todo::new_dated(body.clone(), day));
-                }
-            }
-            ItemType::Task => {
-                self.list.add_task(Task::new(body, chrono_weekdays));
-            }
-        }
+        let todos = client.list.undone_todos_for_date(current_date());
+        assert_eq!(todos[0].body(), "Todo 1");
+        assert_eq!(todos[1].body(), "Todo 0");
     }
 
-    fn remove(&mut self, item_type: ItemType, id: u64) -> Result<()> {
-        match item_type {
-            ItemType::Todo => {
-                self.list.remove_todo(id)?;
-            }
-            ItemType::Task => {
-                self.list.remove_task(id)?;
-            }
-        }
-        Ok(())
+    #[test]
+    fn remove_removes_todo_successfully() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.remove(ItemType::Todo, &[0]).unwrap();
+        assert_eq!(client.list.todos().len(), 0);
     }
 
-    fn modify_done_state(&mut self, item_type: ItemType, id: u64, to_done: bool) -> Result<()> {
-        match item_type {
-            ItemType::Todo => {
-                self.list.get_todo_mut(id)?.set_done(to_done);
-            }
-            ItemType::Task => {
-                let task = self.list.get_task_mut(id)?;
-                let mut next_date_for_task = Local::today().naive_local();
-                while !task.for_date(next_date_for_task) {
-                    next_date_for_task = next_date_for_task.succ();
-                }
-                task.set_done(to_done, next_date_for_task);
-            }
-        }
-        Ok(())
+    #[test]
+    fn remove_removes_task_successfully() {
+        let mut client = create_client_app();
+        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]).unwrap());
+        client.remove(ItemType::Task, &[0]).unwrap();
+        assert_eq!(client.list.tasks().len(), 0);
     }
 
-    fn set(&mut self, item_type: ItemType, id: u64, body: Option<String>, weekdays: Vec<Weekday>) -> Result<()> {
-        let mut chrono_weekdays: Vec<chrono::Weekday> = Vec::new();
-        for wd in weekdays {
-            chrono_weekdays.push(wd.into());
-        }
-
-        match item_type {
-            ItemType::Todo => {
-                let todo = self.list.get_todo_mut(id)?;
-                if let Some(b) = body {
-                    todo.set_body(b);
-                }
-                if chrono_weekdays.len() >= 1 {
-                    todo.set_weekday(chrono_weekdays[0]);
-                }
-            }
-            ItemType::Task => {
-                let task = self.list.get_task_mut(id)?;
-                if let Some(b) = body {
-                    task.set_body(b);
-                }
-                if chrono_weekdays.len() >= 1 {
-                    task.set_weekdays(chrono_weekdays);
-                }
-            }
-        }
+    #[test]
+    fn modify_done_state_sets_todo_done() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.modify_done_state(ItemType::Todo, &[0], true).unwrap();
+        assert!(client.list.todos()[0].done());
+    }
 
-        Ok(())
+    #[test]
+    fn modify_done_state_sets_task_done_for_the_next_correct_date() {
+        let mut client = create_client_app();
+        client.list.add_task(Task::new("Task".to_string(), vec![current_date().weekday().succ().succ()]).unwrap());
+        client.modify_done_state(ItemType::Task, &[0], true).unwrap();
+        assert!(client.list.tasks()[0].done(current_date().succ().succ()));
     }
 
-    fn sync(&mut self) -> Result<()> {
-        let conf = &self.conf;
+    #[test]
+    fn modify_done_state_sets_multiple_todos_done_or_none() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo 0".to_string()).unwrap());
+        client.list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
-        let mut net_mgr = MtdNetMgr::new(&mut self.list, conf);
+        assert!(client.modify_done_state(ItemType::Todo, &[0, 2], true).is_err());
+        assert!(!client.list.todos()[0].done());
 
-        net_mgr.client_sync()
+        client.modify_done_state(ItemType::Todo, &[0, 1], true).unwrap();
+        assert!(client.list.todos().iter().all(|todo| todo.done()));
     }
 
-    fn server(&mut self) -> Result<()> {
-        let conf = &self.conf;
+    #[test]
+    fn copy_copies_todo_for_each_weekday_and_task_with_new_weekdays() {
+        let client = create_client_app();
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "Water plants", "--priority", "1"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        let args = CliArgs::try_parse_from(["mtd", "copy", "todo", "0", "mon", "thu@08:00"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
 
-        let mut net_mgr = MtdNetMgr::new(&mut self.list, &conf);
+        assert_eq!(client.list.todos().len(), 3);
+        let thursday = client.list.get_todo(2).unwrap();
+        assert_eq!(thursday.weekday(), chrono::Weekday::Thu);
+        assert_eq!(thursday.time(), Some(NaiveTime::from_hms(8, 0, 0)));
+        assert_eq!(thursday.priority(), 1);
 
-        net_mgr.server_listening_loop()
+        let args = CliArgs::try_parse_from(["mtd", "add", "task", "Gym", "mon", "--notes", "Leg day"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        let args = CliArgs::try_parse_from(["mtd", "copy", "task", "0", "weekend"]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
+
+        let copy = client.list.get_task(1).unwrap();
+        assert_eq!(copy.weekdays(), &vec![chrono::Weekday::Sat, chrono::Weekday::Sun]);
+        assert_eq!(copy.notes(), Some("Leg day"));
+        assert!(client.copy(ItemType::Task, 0, &[parse_day_spec("tomorrow").unwrap()]).is_err());
     }
 
-    fn re_init(config_path: &PathBuf) -> Result<Self> {
-        let stdin = io::stdin();
-        let mut stdout = io::stdout();
+    #[test]
+    fn convert_turns_todo_into_task_and_back() {
+        let client = create_client_app();
+        let args = CliArgs::try_parse_from(["mtd", "add", "todo", "Run", "--notes", "5 km"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        let args = CliArgs::try_parse_from(["mtd", "convert", "todo", "0", "tue", "sat@10:00"]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
 
-        let mut buffer = String::new();
+        assert!(client.list.todos().is_empty());
+        let task = client.list.get_task(0).unwrap();
+        assert_eq!(task.weekdays(), &vec![chrono::Weekday::Tue, chrono::Weekday::Sat]);
+        assert_eq!(task.time_for(chrono::Weekday::Sat), Some(NaiveTime::from_hms(10, 0, 0)));
+        assert_eq!(task.notes(), Some("5 km"));
 
-        loop {
-            print!("This will delete all items and erase the config. Proceed (y/n)? ");
-            stdout.flush()?;
-            buffer.clear();
-            stdin.read_line(&mut buffer)?;
-            buffer = buffer.to_lowercase().trim().to_string();
+        assert!(client.convert(ItemType::Task, 0, &[parse_day_spec("weekend").unwrap()]).is_err());
+        client.convert(ItemType::Task, 0, &[parse_day_spec("sat").unwrap()]).unwrap();
+        assert!(client.list.tasks().is_empty());
+        let todo = client.list.todos()[0];
+        assert_eq!(todo.weekday(), chrono::Weekday::Sat);
+        assert_eq!(todo.time(), Some(NaiveTime::from_hms(10, 0, 0)));
+    }
 
-            if &buffer != "y" && &buffer != "n" {
-                eprintln!("Invalid option.");
-                continue;
-            }
-            break;
-        }
+    #[test]
+    fn do_resolves_todo_by_unique_body_match() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Buy groceries".to_string()).unwrap());
+        client.list.add_todo(Todo::new_undated("Put away groceries".to_string()).unwrap());
+        client.list.add_todo(Todo::new_undated("Call mom".to_string()).unwrap());
 
-        if &buffer == "n" {
-            println!("Abort!");
-            // This is not optimal, but is the easiest way to implement this.
-            process::exit(0);
-            // Other option would be to call, but in some cases that could seem like the abort didn't do anything
-            // return Ok(MtdApp::new(config_path)?);
-        }
+        let args = CliArgs::try_parse_from(["mtd", "do", "todo", "MOM"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        assert!(client.list.get_todo(2).unwrap().done());
 
-        let config = MtdApp::create_new_config(&config_path)?;
+        match client.resolve_ids(ItemType::Todo, &["groceries".to_string()], true) {
+            Err(Error::InvalidArgument(msg)) => assert!(msg.contains("0. Buy groceries") && msg.contains("1. Put away groceries")),
+            _ => panic!("ambiguous match wasn't refused"),
+        }
+        assert!(client.resolve_ids(ItemType::Task, &["mom".to_string()], true).is_err());
 
-        Ok(Self {
-            list: MtdApp::create_new_list(&config)?,
-            conf: config,
-        })
+        // Todos already done don't make a match ambiguous.
+        let mut client = client;
+        client.list.get_todo_mut(0).unwrap().set_done(true);
+        assert_eq!(client.resolve_ids(ItemType::Todo, &["groceries".to_string(), "2".to_string()], true).unwrap(), vec![1, 2]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::thread;
-    use std::time::Duration;
+    #[test]
+    fn short_ids_are_shown_and_resolved_by_unambiguous_prefix() {
+        let mut client = create_client_app();
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.list.add_task(Task::new("Task".to_string(), vec![current_date().weekday()]).unwrap());
+        let uuid = client.list.get_todo(0).unwrap().uuid().simple().to_string();
 
-    use chrono::{Datelike, Local};
+        // Numbers are ids until short ids are turned on. A short prefix can be all digits, so the
+        // whole uuid is used.
+        assert_eq!(client.resolve_id(ItemType::Todo, "0", false).unwrap(), 0);
+        assert_eq!(client.resolve_id(ItemType::Todo, &uuid, false).unwrap(), 0);
+        assert_eq!(client.todo_text(client.list.get_todo(0).unwrap()), "0. Todo");
 
-    use mtd::{Config, Task, TdList, Todo};
+        client.conf.set_id_style(IdStyle::Short);
+        let len = client.list.short_id_len();
+        assert_eq!(client.todo_text(client.list.get_todo(0).unwrap()), format!("{}. Todo", &uuid[..len]));
+        assert_eq!(client.resolve_id(ItemType::Todo, &uuid[..len].to_uppercase(), false).unwrap(), 0);
+        assert!(client.resolve_id(ItemType::Task, &uuid[..len], false).is_err());
+        assert!(client.resolve_id(ItemType::Todo, "", false).is_err());
 
-    use crate::{Commands, ItemType, MtdApp, Weekday};
+        let args = CliArgs::try_parse_from(["mtd", "do", "todo", &uuid[..len]]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
+        assert!(client.list.get_todo(0).unwrap().done());
 
-    fn create_client_app() -> MtdApp {
-        MtdApp {
-            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None),
-            list: TdList::new_client(),
+        // A prefix shared by many todos is refused.
+        while client.list.todos().iter().filter(|todo| todo.uuid().simple().to_string().starts_with(&uuid[..1])).count() < 2 {
+            client.list.add_todo(Todo::new_undated("Another todo".to_string()).unwrap());
         }
-    }
-
-    fn create_server_app() -> MtdApp {
-        MtdApp {
-            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None),
-            list: TdList::new_server(),
+        match client.resolve_id(ItemType::Todo, &uuid[..1], false) {
+            Err(Error::InvalidArgument(msg)) => assert!(msg.contains("Give more of it")),
+            _ => panic!("ambiguous id wasn't refused"),
         }
     }
 
     #[test]
-    fn add_adds_todo_successfully() {
+    fn modify_done_state_fails_for_expired_task() {
         let mut client = create_client_app();
-        client.add(ItemType::Todo, vec![Weekday::Wed], "Todo".to_string());
-        assert_eq!(client.list.todos()[0], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Wed));
+        let mut task = Task::new("Task".to_string(), vec![current_date().weekday()]).unwrap();
+        task.set_until(Some(current_date().pred()));
+        client.list.add_task(task);
+
+        assert!(client.modify_done_state(ItemType::Task, &[0], true).is_err());
     }
 
     #[test]
-    fn add_adds_task_successfully() {
+    fn postpone_moves_todo_by_days_or_to_date() {
         let mut client = create_client_app();
-        client.add(ItemType::Task, vec![Weekday::Wed, Weekday::Fri, Weekday::Sun], "Task".to_string());
-        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![chrono::Weekday::Wed, chrono::Weekday::Fri, chrono::Weekday::Sun]))
+        let today = current_date();
+        client.list.add_todo(Todo::new_specific_date("Todo".to_string(), today).unwrap());
+
+        let args = CliArgs::try_parse_from(["mtd", "postpone", "todo", "0"]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
+        assert_eq!(client.list.get_todo(0).unwrap().date(), today.succ());
+
+        client.postpone(ItemType::Todo, 0, Postponement::Date(today + chrono::Duration::days(5))).unwrap();
+        assert_eq!(client.list.get_todo(0).unwrap().date(), today + chrono::Duration::days(5));
+        assert_eq!(client.list.get_todo(0).unwrap().postponed(), 2);
+
+        assert!(client.postpone(ItemType::Todo, 0, Postponement::Date(today)).is_err());
+        assert!(client.postpone(ItemType::Task, 0, Postponement::Days(1)).is_err());
+        assert_eq!(parse_postponement("+3"), Ok(Postponement::Days(3)));
+        assert!(parse_postponement("+x").is_err());
     }
 
     #[test]
-    fn add_adds_task_without_explicit_weekday() {
+    fn modify_paused_state_pauses_and_resumes_task() {
         let mut client = create_client_app();
-        client.add(ItemType::Task, vec![], "Task".to_string());
-        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![Local::today().weekday()]))
+        client.list.add_task(Task::new("Task".to_string(), vec![current_date().weekday()]).unwrap());
+
+        client.modify_paused_state(ItemType::Task, 0, true, None).unwrap();
+        assert_eq!(client.list.undone_tasks_for_date(current_date()).len(), 0);
+
+        client.modify_paused_state(ItemType::Task, 0, false, None).unwrap();
+        assert_eq!(client.list.undone_tasks_for_date(current_date()).len(), 1);
     }
 
     #[test]
-    fn add_adds_todo_to_multiple_weekdays() {
+    fn modify_paused_state_fails_for_todos() {
         let mut client = create_client_app();
-        client.add(ItemType::Todo, vec![Weekday::Wed, Weekday::Fri, Weekday::Sun], "Todo".to_string());
-        assert_eq!(client.list.todos()[0], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Wed));
-        assert_eq!(client.list.todos()[1], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Fri));
-        assert_eq!(client.list.todos()[2], &Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun));
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        assert!(client.modify_paused_state(ItemType::Todo, 0, true, None).is_err());
     }
 
     #[test]
-    fn remove_removes_todo_successfully() {
+    fn set_sets_todo_values_to_new() {
         let mut client = create_client_app();
-        client.list.add_todo(Todo::new_undated("Todo".to_string()));
-        client.remove(ItemType::Todo, 0).unwrap();
-        assert_eq!(client.list.todos().len(), 0);
+        client.list.add_todo(Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun).unwrap());
+        client.set(ItemType::Todo, 0, SetValues { body: Some("New Todo".to_string()), weekdays: vec![Weekday::Wed], ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0], &Todo::new_dated("New Todo".to_string(), chrono::Weekday::Wed).unwrap());
     }
 
     #[test]
-    fn remove_removes_task_successfully() {
+    fn set_sets_task_values_to_new() {
         let mut client = create_client_app();
-        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]));
-        client.remove(ItemType::Task, 0).unwrap();
-        assert_eq!(client.list.tasks().len(), 0);
+        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]).unwrap());
+        client.set(ItemType::Task, 0, SetValues { body: Some("New Task".to_string()), weekdays: vec![Weekday::Thu, Weekday::Fri], ..Default::default() }).unwrap();
+        assert_eq!(client.list.tasks()[0], &Task::new("New Task".to_string(), vec![chrono::Weekday::Thu, chrono::Weekday::Fri]).unwrap())
     }
 
     #[test]
-    fn modify_done_state_sets_todo_done() {
+    fn set_doesnt_modify_weekday_without_explicit_set() {
         let mut client = create_client_app();
-        client.list.add_todo(Todo::new_undated("Todo".to_string()));
-        client.modify_done_state(ItemType::Todo, 0, true).unwrap();
-        assert!(client.list.todos()[0].done());
+        client.list.add_todo(Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun).unwrap());
+        client.set(ItemType::Todo, 0, SetValues { body: Some("New Todo".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(client.list.todos()[0], &Todo::new_dated("New Todo".to_string(), chrono::Weekday::Sun).unwrap());
     }
 
     #[test]
-    fn modify_done_state_sets_task_done_for_the_next_correct_date() {
+    fn set_doesnt_modify_body_without_explicit_set() {
         let mut client = create_client_app();
-        client.list.add_task(Task::new("Task".to_string(), vec![Local::today().weekday().succ().succ()]));
-        client.modify_done_state(ItemType::Task, 0, true).unwrap();
-        assert!(client.list.tasks()[0].done(Local::today().naive_local().succ().succ()));
+        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]).unwrap());
+        client.set(ItemType::Task, 0, SetValues { weekdays: vec![Weekday::Thu, Weekday::Fri], ..Default::default() }).unwrap();
+        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![chrono::Weekday::Thu, chrono::Weekday::Fri]).unwrap())
     }
 
     #[test]
-    fn set_sets_todo_values_to_new() {
-        let mut client = create_client_app();
-        client.list.add_todo(Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun));
-        client.set(ItemType::Todo, 0, Some("New Todo".to_string()), vec![Weekday::Wed]).unwrap();
-        assert_eq!(client.list.todos()[0], &Todo::new_dated("New Todo".to_string(), chrono::Weekday::Wed));
+    fn remote_list_falls_back_to_cache_when_server_is_unreachable() {
+        let dir = env::temp_dir().join("mtd-remote-cache-test-dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Nothing listens on this port.
+        let mut client = MtdApp {
+            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55979".to_string(), Some(dir.join("data.json"))),
+            list: TdList::new_client(),
+        };
+
+        assert!(client.remote_list().is_err());
+
+        let mut cached = TdList::new_server();
+        cached.add_todo(Todo::new_undated("Shared todo".to_string()).unwrap());
+        client.write_remote_cache(&cached).unwrap();
+
+        let remote = client.remote_list().unwrap();
+        assert_eq!(remote.todos().len(), 1);
+        assert_eq!(remote.todos()[0].body(), "Shared todo");
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn set_sets_task_values_to_new() {
-        let mut client = create_client_app();
-        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]));
-        client.set(ItemType::Task, 0, Some("New Task".to_string()), vec![Weekday::Thu, Weekday::Fri]).unwrap();
-        assert_eq!(client.list.tasks()[0], &Task::new("New Task".to_string(), vec![chrono::Weekday::Thu, chrono::Weekday::Fri]))
+    fn data_export_contains_items_and_backups_but_not_password() {
+        let dir = env::temp_dir().join("mtd-export-test-dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let save_path = dir.join("data.json");
+        fs::write(&save_path, "").unwrap();
+        let backup = MtdApp::backup_file(&save_path).unwrap().unwrap();
+
+        let mut client = MtdApp {
+            conf: Config::new_default("SecurePw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), Some(save_path)),
+            list: TdList::new_client(),
+        };
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        client.conf.set_tor_control_password(Some("TorPw".to_string()));
+        client.conf.set_users(vec![ServerUser { name: "alice".to_string(), password: "AlicePw".to_string() }]);
+
+        let export = client.data_export().unwrap();
+        assert_eq!(export.items.todos().len(), 1);
+        assert_eq!(export.backups, vec![backup]);
+        assert!(export.cached_server_items.is_none());
+        assert!(export.config.get("encryption_password").is_none());
+        assert!(export.config["tor_control_password"].is_null());
+        assert_eq!(export.config["users"][0]["name"], "alice");
+        assert_eq!(export.config["users"][0]["password"], "");
+        assert!(!serde_json::to_string(&export.config).unwrap().contains("AlicePw"));
+        assert_eq!(export.config["socket_addr"], "127.0.0.1:55980");
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn set_doesnt_modify_weekday_without_explicit_set() {
+    fn backup_is_restored_keeping_save_path_and_password() {
+        let dir = env::temp_dir().join("mtd-backup-restore-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("conf.json");
+        let save_path = dir.join("data.json");
+        let backup_path = dir.join("backup.mtd");
+
+        let mut client = MtdApp {
+            conf: Config::new_default(b"SecurePw".to_vec(), "127.0.0.1:55980".to_string(), Some(save_path.clone())),
+            list: TdList::new_client(),
+        };
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        MtdApp::write_profile_config(&config_path, None, &client.conf).unwrap();
+        client.create_backup(&backup_path, false, Some("backup pw".to_string())).unwrap();
+        assert!(!fs::read(&backup_path).unwrap().windows(8).any(|bytes| bytes == b"SecurePw"));
+
+        // The items change after the backup.
+        fs::write(&save_path, TdList::new_client().to_json().unwrap()).unwrap();
+        assert!(MtdApp::restore_backup(&config_path, None, &backup_path, Some("wrong".to_string()), true).is_err());
+        MtdApp::restore_backup(&config_path, None, &backup_path, Some("backup pw".to_string()), true).unwrap();
+
+        let restored = MtdApp::init(&config_path, None).unwrap();
+        assert_eq!(restored.list.todos()[0].body(), "Todo");
+        assert_eq!(restored.conf.encryption_password(), &b"SecurePw".to_vec());
+        assert_eq!(restored.conf.save_location(), Some(&save_path));
+        assert_eq!(backup_files(&save_path).unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restored_servers_keep_the_lists_and_passwords_of_their_users() {
+        let dir = env::temp_dir().join("mtd-backup-users-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("conf.json");
+        let backup_path = dir.join("backup.mtd");
+
+        let mut conf = Config::new_default(Vec::new(), "127.0.0.1:55980".to_string(), Some(dir.join("data.json")));
+        conf.set_users(vec![ServerUser { name: "alice".to_string(), password: "alice's".to_string() }]);
+        let alices_path = conf.user_save_location("alice").unwrap();
+        let mut alices = TdList::new_server();
+        alices.add_todo(Todo::new_undated("Alice's todo".to_string()).unwrap());
+        fs::create_dir_all(alices_path.parent().unwrap()).unwrap();
+        fs::write(&alices_path, alices.to_json().unwrap()).unwrap();
+
+        let server = MtdApp { conf, list: TdList::new_server() };
+        MtdApp::write_profile_config(&config_path, None, &server.conf).unwrap();
+        server.create_backup(&backup_path, false, Some("backup pw".to_string())).unwrap();
+        fs::write(&alices_path, TdList::new_server().to_json().unwrap()).unwrap();
+        MtdApp::restore_backup(&config_path, None, &backup_path, Some("backup pw".to_string()), true).unwrap();
+
+        let mut restored = MtdApp::init(&config_path, None).unwrap();
+        assert_eq!(restored.conf.users()[0].password, "alice's");
+        let alices = TdList::new_from_json(&fs::read_to_string(&alices_path).unwrap()).unwrap();
+        assert_eq!(alices.todos()[0].body(), "Alice's todo");
+
+        // A user without a password would let anyone in as the user.
+        restored.conf.set_users(vec![ServerUser { name: "alice".to_string(), password: String::new() }]);
+        assert!(matches!(restored.server(), Err(Error::InvalidArgument(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn export_ical_writes_a_calendar() {
+        let path = env::temp_dir().join("mtd-export-ical-test.ics");
         let mut client = create_client_app();
-        client.list.add_todo(Todo::new_dated("Todo".to_string(), chrono::Weekday::Sun));
-        client.set(ItemType::Todo, 0, Some("New Todo".to_string()), vec![]).unwrap();
-        assert_eq!(client.list.todos()[0], &Todo::new_dated("New Todo".to_string(), chrono::Weekday::Sun));
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+
+        let args = CliArgs::try_parse_from(["mtd", "export", "ical", "--out", path.to_str().unwrap()]).unwrap();
+        client.handle_command(args.command).unwrap();
+
+        let ical = fs::read_to_string(&path).unwrap();
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.contains("SUMMARY:Todo\r\n"));
+        fs::remove_file(path).unwrap();
+
+        let args = CliArgs::try_parse_from(["mtd", "export", "ical", "--routines"]).unwrap();
+        assert!(create_client_app().handle_command(args.command).is_err());
     }
 
     #[test]
-    fn set_doesnt_modify_body_without_explicit_set() {
+    fn export_csv_and_md_write_items() {
+        let path = env::temp_dir().join("mtd-export-csv-test.csv");
         let mut client = create_client_app();
-        client.list.add_task(Task::new("Task".to_string(), vec![chrono::Weekday::Sun]));
-        client.set(ItemType::Task, 0, None, vec![Weekday::Thu, Weekday::Fri]).unwrap();
-        assert_eq!(client.list.tasks()[0], &Task::new("Task".to_string(), vec![chrono::Weekday::Thu, chrono::Weekday::Fri]))
+        client.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+
+        let args = CliArgs::try_parse_from(["mtd", "export", "csv", "--out", path.to_str().unwrap()]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(csv.starts_with("type,id,body,"));
+        assert!(csv.contains("\r\ntodo,0,Todo,"));
+
+        let args = CliArgs::try_parse_from(["mtd", "export", "markdown", "--out", path.to_str().unwrap()]).unwrap();
+        client.handle_command(args.command).unwrap();
+        let md = fs::read_to_string(&path).unwrap();
+        assert!(md.starts_with("# Week of "));
+        assert!(md.contains("- [ ] Todo\n"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn import_dry_run_changes_nothing() {
+        let path = env::temp_dir().join("mtd-import-test.txt");
+        fs::write(&path, "(A) Call the bank due:2024-08-16\n2024-08-01 Pay rent\n").unwrap();
+        let client = create_client_app();
+
+        let args = CliArgs::try_parse_from(["mtd", "import", "todo-txt", path.to_str().unwrap(), "--dry-run", "--output", "json"]).unwrap();
+        let client = client.handle_command(args.command).unwrap();
+        assert!(client.list.todos().is_empty());
+
+        let args = CliArgs::try_parse_from(["mtd", "import", "todotxt", path.to_str().unwrap()]).unwrap();
+        let mut client = client.handle_command(args.command).unwrap();
+        assert_eq!(client.list.todos().len(), 2);
+        assert_eq!(client.list.get_todo(0).unwrap().priority(), 26);
+
+        // Importing an export of the items again changes nothing.
+        let csv = CsvExporter::new(current_date()).export(&client.list);
+        let outcomes = client.import_entries(ImportFormat::Csv, &csv);
+        assert!(outcomes.iter().all(|outcome| outcome.action == Action::Unchanged), "{:?}", outcomes);
+        assert_eq!(client.list.todos().len(), 2);
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
@@ -753,7 +6298,7 @@ mod tests {
     fn syncing_works() {
         thread::spawn(|| {
             let mut server = create_server_app();
-            server.list.add_todo(Todo::new_undated("Todo".to_string()));
+            server.list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
             server.server().unwrap();
         });
 
@@ -764,7 +6309,152 @@ mod tests {
         client.sync().unwrap();
 
         assert_eq!(client.list.todos().len(), 1);
-        assert!(client.list.todos().contains(&&Todo::new_undated("Todo".to_string())));
+        assert!(client.list.todos().contains(&&Todo::new_undated("Todo".to_string()).unwrap()));
+    }
+
+    #[test]
+    fn config_file_reads_single_config_as_default_profile() {
+        let conf = Config::new_default("pw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None);
+        let file = ConfigFile::new_from_json(&conf.to_json().unwrap()).unwrap();
+
+        assert_eq!(file.profile_name(None), ConfigFile::LEGACY_PROFILE);
+        assert_eq!(file.profiles[ConfigFile::LEGACY_PROFILE].socket_addr(), "127.0.0.1:55980");
+    }
+
+    #[test]
+    fn config_file_selects_given_profile_over_default() {
+        let mut file = ConfigFile::new("home", Config::new_default("pw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None));
+        file.profiles.insert("work".to_string(), Config::new_default("pw".as_bytes().to_vec(), "10.0.0.1:55980".to_string(), None));
+
+        let file = ConfigFile::new_from_json(&file.to_json().unwrap()).unwrap();
+
+        assert_eq!(file.profile_name(None), "home");
+        assert_eq!(file.profile_name(Some("work")), "work");
+        assert_eq!(file.profiles[file.profile_name(Some("work"))].socket_addr(), "10.0.0.1:55980");
+    }
+
+    #[test]
+    fn change_setting_reads_json_text_and_seconds() {
+        let conf = Config::new_default("pw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None);
+
+        let conf = change_setting(&conf, "timeout", Some("5")).unwrap();
+        assert_eq!(conf.timeout(), Duration::from_secs(5));
+        let conf = change_setting(&conf, "max_backups", Some("3")).unwrap();
+        assert_eq!(conf.max_backups(), Some(3));
+        let conf = change_setting(&conf, "srv_record", Some("_mtd._tcp.example.com")).unwrap();
+        assert_eq!(conf.srv_record(), Some("_mtd._tcp.example.com"));
+        let conf = change_setting(&conf, "theme.header", Some("bold blue")).unwrap();
+        assert_eq!(conf.theme().style(Style::Header), "bold blue");
+        let conf = change_setting(&conf, "theme.preset", Some("high-contrast")).unwrap();
+        assert_eq!(conf.theme().style(Style::Header), "bold blue");
+        assert_eq!(conf.theme().style(Style::Overdue), "bold bright-red");
+        let conf = change_setting(&conf, "theme.header", None).unwrap();
+        assert_eq!(conf.theme().style(Style::Header), "bold underline bright-white");
+        let conf = change_setting(&conf, "srv_record", None).unwrap();
+        assert_eq!(conf.srv_record(), None);
+        let conf = change_setting(&conf, "encryption_password", Some("secret")).unwrap();
+        assert_eq!(conf.encryption_password(), &"secret".as_bytes().to_vec());
+        let conf = change_setting(&conf, "password_command", Some("pass show mtd")).unwrap();
+        assert_eq!(conf.password_command(), Some("pass show mtd"));
+        let conf = change_setting(&conf, "encryption_password", Some("")).unwrap();
+        assert!(conf.encryption_password().is_empty());
+        let conf = change_setting(&conf, "data_encryption", Some("keyring")).unwrap();
+        assert_eq!(conf.data_encryption(), DataEncryption::Keyring);
+        let conf = change_setting(&conf, "users", Some(r#"[{"name": "alice", "password": "alice's"}]"#)).unwrap();
+        assert_eq!(conf.users()[0].name, "alice");
+        let conf = change_setting(&conf, "username", Some("alice")).unwrap();
+        assert_eq!(conf.username(), Some("alice"));
+        let conf = change_setting(&conf, "id_style", Some("short")).unwrap();
+        assert_eq!(conf.id_style(), IdStyle::Short);
+        let conf = change_setting(&conf, "conflict_policy", Some("keep-both")).unwrap();
+        assert_eq!(conf.conflict_policy(), ConflictPolicy::KeepBoth);
+
+        assert!(change_setting(&conf, "timeout", Some("soon")).is_err());
+        assert!(change_setting(&conf, "socket_addr", Some("localhost")).is_err());
+        assert!(change_setting(&conf, "socket_addr", None).is_err());
+        assert!(change_setting(&conf, "theme.header", Some("sparkly")).is_err());
+        assert!(change_setting(&conf, "users", Some(r#"[{"name": "a/b", "password": "pw"}]"#)).is_err());
+        assert!(change_setting(&conf, "users", Some(r#"[{"name": "bob", "password": ""}]"#)).is_err());
+        assert!(change_setting(&conf, "no_such_setting", Some("1")).is_err());
+    }
+
+    #[test]
+    fn format_setting_hides_secrets_unless_asked() {
+        let conf = Config::new_default("pw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None);
+        let json = serde_json::to_value(&conf).unwrap();
+
+        assert_eq!(format_setting("encryption_password", &json["encryption_password"], true), "pw");
+        assert!(!format_setting("encryption_password", &json["encryption_password"], false).contains("pw"));
+        assert_eq!(format_setting("tor_control_password", &json["tor_control_password"], false), "null");
+        assert_eq!(format_setting("timeout", &json["timeout"], false), "30");
+        assert_eq!(format_setting("socket_addr", &json["socket_addr"], false), "127.0.0.1:55980");
+    }
+
+    #[test]
+    fn config_file_removes_only_existing_non_default_profiles() {
+        let mut file = ConfigFile::new("home", Config::new_default("pw".as_bytes().to_vec(), "127.0.0.1:55980".to_string(), None));
+        file.profiles.insert("work".to_string(), Config::new_default("pw".as_bytes().to_vec(), "10.0.0.1:55980".to_string(), None));
+
+        assert!(file.remove_profile("home").is_err());
+        assert!(file.remove_profile("school").is_err());
+        assert!(file.set_default_profile("school").is_err());
+
+        file.set_default_profile("work").unwrap();
+        assert_eq!(file.remove_profile("home").unwrap().socket_addr(), "127.0.0.1:55980");
+        assert_eq!(file.profiles.keys().collect::<Vec<_>>(), vec!["work"]);
+    }
+
+    #[test]
+    fn backup_file_copies_file_next_to_original() {
+        let dir = env::temp_dir().join("mtd-backup-file-test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+        fs::write(&path, "contents").unwrap();
+
+        let backup_path = MtdApp::backup_file(&path).unwrap().unwrap();
+
+        assert_eq!(backup_path.parent(), path.parent());
+        assert!(backup_path.file_name().unwrap().to_string_lossy().starts_with("data.json."));
+        assert_eq!(fs::read_to_string(backup_path).unwrap(), "contents");
+
+        assert!(MtdApp::backup_file(&dir.join("nonexistent")).unwrap().is_none());
+    }
+
+    #[test]
+    fn re_init_with_yes_refuses_to_guess_the_role_of_a_kept_config() {
+        let dir = env::temp_dir().join("mtd-re-init-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json");
+        let save_path = dir.join("data.json");
+        fs::write(&save_path, "not items").unwrap();
+        let conf = Config::new_default(b"pw".to_vec(), "127.0.0.1:55980".to_string(), Some(save_path.clone()));
+        MtdApp::write_config_file(&config_path, &ConfigFile::new("home", conf)).unwrap();
+
+        assert!(matches!(MtdApp::re_init(&config_path, None, true, true), Err(Error::InvalidArgument(_))));
+        assert_eq!(fs::read_to_string(&save_path).unwrap(), "not items");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_commands_run_extensions_from_path() {
+        let args = CliArgs::try_parse_from(["mtd", "chart", "--week", "2"]).unwrap();
+        assert!(matches!(args.command, Commands::External(args) if args == ["chart", "--week", "2"]));
+
+        let dir = env::temp_dir().join("mtd-extension-test");
+        fs::create_dir_all(&dir).unwrap();
+        let program = dir.join(format!("mtd-chart{}", env::consts::EXE_SUFFIX));
+        fs::write(&program, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let path = env::join_paths([env::temp_dir().join("mtd-nonexistent-dir"), dir]).unwrap();
+        assert_eq!(find_extension("chart", &path), Some(program));
+        assert_eq!(find_extension("web", &path), None);
     }
 
     #[test]
@@ -779,12 +6469,12 @@ mod tests {
                 true,
             ),
         };
-        app.list.add_todo(Todo::new_undated("This string doesn't remain if the todo is actually removed.".to_string()));
+        app.list.add_todo(Todo::new_undated("This string doesn't remain if the todo is actually removed.".to_string()).unwrap());
 
         // Do assert here to first check that the save format hasn't changed and will contain the todo in cleartext.
         assert!(app.list.to_json().unwrap().contains("This string doesn't remain if the todo is actually removed."));
 
-        let app = app.handle_command(Commands::Remove { item_type: ItemType::Todo, id: 0 }).unwrap();
+        let app = app.handle_command(Commands::Remove { item_type: ItemType::Todo, ids: vec!["0".to_string()], yes: true }).unwrap();
 
         assert!(!app.list.to_json().unwrap().contains("This string doesn't remain if the todo is actually removed."));
     }
@@ -17,20 +17,60 @@ see <https://www.gnu.org/licenses/>.
 //! A Module defining networking functions for MTD such as syncing with a remote server or running a
 //! server. Data transmitted over the network is encrypted.
 
-use std::{fs, io};
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::path::PathBuf;
-use std::time::Duration;
-
-use rand::random;
+use std::{fs, io, process};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, NaiveTime, Weekday};
+#[cfg(feature = "fault-injection")]
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "fault-injection")]
+use rand::rngs::StdRng;
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, Result, TdList};
-use crate::network::crypt::{decrypt, encrypt};
+use crate::{AutoRule, Conflict, ConflictPolicy, current_time, DataEncryption, Error, IdStyle, read_data_file, Result, StorageEncryption, TdList, Theme, Todo, write_data_file};
+use crate::storage::write_private_file;
+use crate::rules;
+use crate::devices::{DeviceRegistry, parse_pairing_code, split_pairing_payload};
+use crate::health::{HealthServer, HealthStatus, ServerMetrics};
+use crate::engine::{ClientAction, ClientEngine, ClientMessage, ClientOutcome, ClientRequest, ServerEngine, ServerMessage, ServerState, SyncProgress};
+use crate::proxy::Proxy;
+use crate::tor::{self, OnionService};
+use crate::transport::{self, Connection, Readiness, split_host_port, Transport};
+
+/// Logs a message of a server like `MtdNetMgr::log`. With the `tracing` feature, messages that don't
+/// go to a log callback are `tracing` events with the fields in the brackets.
+macro_rules! server_log {
+    (@level Debug) => { tracing::Level::DEBUG };
+    (@level Info) => { tracing::Level::INFO };
+    (@level Error) => { tracing::Level::ERROR };
+    ($mgr:expr, $level:ident, [$($field:tt)+], $($msg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        {
+            if $mgr.log.is_none() {
+                tracing::event!(server_log!(@level $level), $($field)+, $($msg)+);
+            } else {
+                $mgr.log(LogLevel::$level, &format!($($msg)+));
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        $mgr.log(LogLevel::$level, &format!($($msg)+));
+    };
+}
 
 /// A config specifying how a `MtdNetMgr` should function. Defining a `save_location` is optional.
-/// If it is `None` any `TdList` won't be saved.
+/// If it is `None` any `TdList` won't be saved. Clients connect to the `socket_addr` and servers
+/// listen on it. Servers behind NAT or port forwarding can additionally have an advertised address
+/// that clients should use instead. Clients can also find the server through a DNS SRV record.
+/// Servers can publish themselves as Tor onion services. A server can also host a separate list for
+/// each of its `users`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     socket_addr: String,
@@ -38,12 +78,141 @@ pub struct Config {
     timeout: Duration,
     save_location: Option<PathBuf>,
     local_only: bool,
+    #[serde(default = "default_maintenance_interval")]
+    maintenance_interval: Duration,
+    #[serde(default)]
+    max_backups: Option<usize>,
+    #[serde(default = "default_afternoon_start")]
+    afternoon_start: NaiveTime,
+    #[serde(default = "default_evening_start")]
+    evening_start: NaiveTime,
+    #[serde(default)]
+    ascii: bool,
+    #[serde(default = "default_week_starts_on")]
+    week_starts_on: Weekday,
+    #[serde(default)]
+    advertised_addr: Option<String>,
+    #[serde(default)]
+    theme: Theme,
+    #[serde(default)]
+    srv_record: Option<String>,
+    #[serde(default)]
+    proxy: Option<String>,
+    #[serde(default)]
+    tor_control_addr: Option<String>,
+    #[serde(default)]
+    tor_control_password: Option<String>,
+    #[serde(default)]
+    max_items: Option<usize>,
+    #[serde(default)]
+    max_body_len: Option<usize>,
+    #[serde(default)]
+    rules: Vec<AutoRule>,
+    #[serde(default)]
+    health_addr: Option<String>,
+    #[serde(default = "default_max_workers")]
+    max_workers: usize,
+    #[serde(default)]
+    data_encryption: DataEncryption,
+    #[serde(default)]
+    password_command: Option<String>,
+    #[serde(default)]
+    password_in_keyring: bool,
+    #[serde(default)]
+    users: Vec<ServerUser>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    device: Option<String>,
+    #[serde(default)]
+    id_style: IdStyle,
+    #[serde(default)]
+    conflict_policy: ConflictPolicy,
+}
+
+/// A user of a server with a list of their own. Clients log in as the user with the user's name and
+/// password.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerUser {
+    /// The name the user logs in with. The user's data file is named after it.
+    pub name: String,
+    /// The encryption password of the user.
+    pub password: String,
+}
+
+impl ServerUser {
+    /// Checks that the name can be used as a file name and that the password isn't empty.
+    fn validate(&self) -> Result<()> {
+        if !is_valid_name(&self.name) {
+            return Err(Error::InvalidArgument(format!("Invalid user name \"{}\". Use letters, numbers, '-', '_' and '.'.", self.name)));
+        }
+        if self.password.is_empty() {
+            return Err(Error::InvalidArgument(format!("The password of {} cannot be empty.", self.name)));
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if a user or device name can be used as a file name and in a login.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn default_afternoon_start() -> NaiveTime {
+    NaiveTime::from_hms(12, 0, 0)
+}
+
+fn default_evening_start() -> NaiveTime {
+    NaiveTime::from_hms(17, 0, 0)
+}
+
+fn default_week_starts_on() -> Weekday {
+    Weekday::Mon
+}
+
+fn default_maintenance_interval() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn default_max_workers() -> usize {
+    32
 }
 
 impl Config {
     /// Creates a new `Config` with explicit values.
     pub fn new(socket_addr: String, encryption_password: Vec<u8>, timeout: Duration, save_location: Option<PathBuf>, local_only: bool) -> Self {
-        Self { socket_addr, encryption_password, timeout, save_location, local_only }
+        Self {
+            socket_addr,
+            encryption_password,
+            timeout,
+            save_location,
+            local_only,
+            maintenance_interval: default_maintenance_interval(),
+            max_backups: None,
+            afternoon_start: default_afternoon_start(),
+            evening_start: default_evening_start(),
+            ascii: false,
+            week_starts_on: default_week_starts_on(),
+            advertised_addr: None,
+            theme: Theme::default(),
+            srv_record: None,
+            proxy: None,
+            tor_control_addr: None,
+            tor_control_password: None,
+            max_items: None,
+            max_body_len: None,
+            rules: Vec::new(),
+            health_addr: None,
+            max_workers: default_max_workers(),
+            data_encryption: DataEncryption::None,
+            password_command: None,
+            password_in_keyring: false,
+            users: Vec::new(),
+            username: None,
+            device: None,
+            id_style: IdStyle::Number,
+            conflict_policy: ConflictPolicy::LastWriterWins,
+        }
     }
     /// Creates a new `Config` with default values.
     pub fn new_default(encryption_password: Vec<u8>, socket_addr: String, save_location: Option<PathBuf>) -> Self {
@@ -53,12 +222,81 @@ impl Config {
             timeout: Duration::from_secs(30),
             save_location,
             local_only: false,
+            maintenance_interval: default_maintenance_interval(),
+            max_backups: None,
+            afternoon_start: default_afternoon_start(),
+            evening_start: default_evening_start(),
+            ascii: false,
+            week_starts_on: default_week_starts_on(),
+            advertised_addr: None,
+            theme: Theme::default(),
+            srv_record: None,
+            proxy: None,
+            tor_control_addr: None,
+            tor_control_password: None,
+            max_items: None,
+            max_body_len: None,
+            rules: Vec::new(),
+            health_addr: None,
+            max_workers: default_max_workers(),
+            data_encryption: DataEncryption::None,
+            password_command: None,
+            password_in_keyring: false,
+            users: Vec::new(),
+            username: None,
+            device: None,
+            id_style: IdStyle::Number,
+            conflict_policy: ConflictPolicy::LastWriterWins,
         }
     }
     /// Creates a ´Config` from a JSON string.
     pub fn new_from_json(json: &str) -> Result<Self> {
         Ok(serde_json::from_str(json)?)
     }
+    /// Checks that the values of the `Config` make sense together. Returns an
+    /// `Error::InvalidArgument` describing the first problem found.
+    pub fn validate(&self) -> Result<()> {
+        for addr in [Some(self.socket_addr()), self.advertised_addr.as_deref()].into_iter().flatten() {
+            if !addr.starts_with("unix:") && split_host_port(addr).is_none() {
+                return Err(Error::InvalidArgument(format!("Invalid socket address \"{}\".", addr)));
+            }
+        }
+        if let Some(addr) = self.health_addr() {
+            if split_host_port(addr).is_none() {
+                return Err(Error::InvalidArgument(format!("Invalid health probe address \"{}\".", addr)));
+            }
+        }
+        if let Some(url) = self.proxy().filter(|url| *url != "none") {
+            Proxy::parse(url)?;
+        }
+        // A server with users or paired devices doesn't need a shared password.
+        let has_devices = self.devices_location().is_some_and(|path| path.exists());
+        if self.encryption_password.is_empty() && self.password_command.is_none() && !self.password_in_keyring && self.users.is_empty() && !has_devices {
+            return Err(Error::InvalidArgument("The encryption password cannot be empty.".to_string()));
+        }
+        if self.username.is_some() && self.device.is_some() {
+            return Err(Error::InvalidArgument("A client can't log in both as a user and as a device.".to_string()));
+        }
+        for (i, user) in self.users.iter().enumerate() {
+            user.validate()?;
+            if self.users[..i].iter().any(|other| other.name == user.name) {
+                return Err(Error::InvalidArgument(format!("There is more than one user named {}.", user.name)));
+            }
+        }
+        if self.max_workers == 0 {
+            return Err(Error::InvalidArgument("A server must be able to serve at least one client at a time.".to_string()));
+        }
+        if self.timeout.is_zero() || self.maintenance_interval.is_zero() {
+            return Err(Error::InvalidArgument("Timeouts and intervals must be longer than zero.".to_string()));
+        }
+        if self.afternoon_start >= self.evening_start {
+            return Err(Error::InvalidArgument("The afternoon must start before the evening.".to_string()));
+        }
+        for rule in &self.rules {
+            rule.validate()?;
+        }
+        self.theme.validate()
+    }
     /// Creates a JSON string from the `Config`.
     pub fn to_json(&self) -> Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
@@ -67,10 +305,144 @@ impl Config {
     pub fn socket_addr(&self) -> &str {
         &self.socket_addr
     }
+    /// Returns the name of the DNS SRV record clients look up the server's address from, such as
+    /// `_mtd._tcp.example.com`. `None` if clients connect to the socket address.
+    pub fn srv_record(&self) -> Option<&str> {
+        self.srv_record.as_deref()
+    }
+    /// Sets the name of the DNS SRV record clients look up the server's address from. The record is
+    /// looked up again for every connection so the server can be moved by changing the record. `None`
+    /// makes clients connect to the socket address.
+    pub fn set_srv_record(&mut self, name: Option<String>) {
+        self.srv_record = name;
+    }
+    /// Returns the URL of the proxy clients connect to the server through, such as
+    /// `socks5://localhost:1080` or `http://proxy.example.com:3128`. `None` if the proxy is taken from
+    /// the `ALL_PROXY` or the `HTTPS_PROXY` environment variable. `"none"` means that no proxy is
+    /// used even if the environment sets one.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+    /// Sets the URL of the proxy clients connect to the server through. See `Config::proxy`.
+    pub fn set_proxy(&mut self, proxy: Option<String>) {
+        self.proxy = proxy;
+    }
+    /// Returns the address of the control port of a Tor process, such as `127.0.0.1:9051`. If set, a
+    /// server publishes itself as an onion service through it.
+    pub fn tor_control_addr(&self) -> Option<&str> {
+        self.tor_control_addr.as_deref()
+    }
+    /// Sets the address of the control port of a Tor process. See `Config::tor_control_addr`.
+    pub fn set_tor_control_addr(&mut self, addr: Option<String>) {
+        self.tor_control_addr = addr;
+    }
+    /// Returns the password for Tor's control port. If `None`, the server authenticates with Tor's
+    /// cookie file or without authentication if Tor allows it.
+    pub fn tor_control_password(&self) -> Option<&str> {
+        self.tor_control_password.as_deref()
+    }
+    /// Sets the password for Tor's control port.
+    pub fn set_tor_control_password(&mut self, password: Option<String>) {
+        self.tor_control_password = password;
+    }
+    /// Returns the address clients should use for connecting to a server. This is the advertised
+    /// address if one is set and the socket address otherwise.
+    pub fn advertised_addr(&self) -> &str {
+        self.advertised_addr.as_deref().unwrap_or(&self.socket_addr)
+    }
+    /// Sets the address clients should use for connecting to a server. `None` makes clients use the
+    /// socket address the server listens on.
+    pub fn set_advertised_addr(&mut self, addr: Option<String>) {
+        self.advertised_addr = addr;
+    }
+    /// Makes a client connect to the server address of a pairing payload made with
+    /// `pairing_payload` instead of its socket address or SRV record. A pairing code without an
+    /// address leaves the `Config` unchanged. Returns the pairing code of the payload.
+    pub fn use_pairing_server<'a>(&mut self, payload: &'a str) -> &'a str {
+        let (code, addr) = split_pairing_payload(payload);
+        if let Some(addr) = addr {
+            self.socket_addr = addr.to_string();
+            self.srv_record = None;
+        }
+        code
+    }
+    /// Returns the TCP socket address a server answers health probes on, such as `0.0.0.0:8080`.
+    /// `None` if the server doesn't answer health probes.
+    pub fn health_addr(&self) -> Option<&str> {
+        self.health_addr.as_deref()
+    }
+    /// Sets the TCP socket address a server answers health probes on. `/healthz` responds while the
+    /// server runs and `/readyz` while it can serve clients. Both respond with the state of the server
+    /// as JSON. `None` disables the probes.
+    pub fn set_health_addr(&mut self, addr: Option<String>) {
+        self.health_addr = addr;
+    }
     /// Returns the `Config`'s encryption password.
     pub fn encryption_password(&self) -> &Vec<u8> {
         &self.encryption_password
     }
+    /// Sets the `Config`'s encryption password.
+    pub fn set_encryption_password(&mut self, password: Vec<u8>) {
+        self.encryption_password = password;
+    }
+    /// Returns `true` if the `Config` contains an encryption password, a Tor control password or
+    /// the password of a user.
+    pub fn has_secrets(&self) -> bool {
+        !self.encryption_password.is_empty() || self.tor_control_password.is_some() || self.users.iter().any(|user| !user.password.is_empty())
+    }
+    /// Returns a copy of the `Config` without the encryption password, the Tor control password and
+    /// the passwords of the users.
+    pub fn without_secrets(&self) -> Config {
+        Config {
+            encryption_password: Vec::new(),
+            tor_control_password: None,
+            users: self.users.iter().map(|user| ServerUser { password: String::new(), ..user.clone() }).collect(),
+            ..self.clone()
+        }
+    }
+    /// Returns the users of a server. Each user has a list of their own.
+    pub fn users(&self) -> &[ServerUser] {
+        &self.users
+    }
+    /// Sets the users of a server. The shared list is still served to clients that don't log in as
+    /// a user unless the encryption password is empty.
+    pub fn set_users(&mut self, users: Vec<ServerUser>) {
+        self.users = users;
+    }
+    /// Returns the user a client logs in as. `None` if the client syncs with the shared list.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+    /// Sets the user a client logs in as. The encryption password is then the user's password.
+    pub fn set_username(&mut self, username: Option<String>) {
+        self.username = username;
+    }
+    /// Returns the paired device a client logs in as. `None` if the client uses the encryption
+    /// password shared with the server.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+    /// Sets the paired device a client logs in as. The encryption password is then the device's
+    /// token.
+    pub fn set_device(&mut self, device: Option<String>) {
+        self.device = device;
+    }
+    /// Returns where a server keeps its paired devices: `<save file>.devices.json` next to the save
+    /// file. `None` if the `Config` has no save location.
+    pub fn devices_location(&self) -> Option<PathBuf> {
+        let path = self.save_location()?;
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".devices.json");
+        Some(path.with_file_name(file_name))
+    }
+    /// Returns where a server saves the list of a user: `<save file>.users/<user>.json` next to the
+    /// save file. `None` if the `Config` has no save location.
+    pub fn user_save_location(&self, user: &str) -> Option<PathBuf> {
+        let path = self.save_location()?;
+        let mut dir_name = path.file_name().unwrap_or_default().to_os_string();
+        dir_name.push(".users");
+        Some(path.with_file_name(dir_name).join(format!("{}.json", user)))
+    }
     /// Returns the `Config`'s timeout duration.
     pub fn timeout(&self) -> Duration {
         self.timeout
@@ -79,13 +451,171 @@ impl Config {
     pub fn save_location(&self) -> Option<&PathBuf> {
         match &self.save_location {
             None => { None }
-            Some(p) => { Some(&p) }
+            Some(p) => { Some(p) }
         }
     }
+    /// Sets the `Config`'s save location. `None` means that the items aren't saved.
+    pub fn set_save_location(&mut self, save_location: Option<PathBuf>) {
+        self.save_location = save_location;
+    }
     /// Returns `true` if mtd should run only locally.
     pub fn local_only(&self) -> bool {
         self.local_only
     }
+    /// Returns how often a server runs its scheduled maintenance.
+    pub fn maintenance_interval(&self) -> Duration {
+        self.maintenance_interval
+    }
+    /// Sets how often a server runs its scheduled maintenance.
+    pub fn set_maintenance_interval(&mut self, interval: Duration) {
+        self.maintenance_interval = interval;
+    }
+    /// Returns how many backups of the save file a server keeps or `None` if backups are never
+    /// pruned.
+    pub fn max_backups(&self) -> Option<usize> {
+        self.max_backups
+    }
+    /// Sets how many backups of the save file a server keeps. `None` disables pruning.
+    pub fn set_max_backups(&mut self, max_backups: Option<usize>) {
+        self.max_backups = max_backups;
+    }
+    /// Returns how many `Todo`s and `Task`s a server accepts from a client or `None` if there is no
+    /// limit.
+    pub fn max_items(&self) -> Option<usize> {
+        self.max_items
+    }
+    /// Sets how many `Todo`s and `Task`s a server accepts from a client. `None` removes the limit.
+    pub fn set_max_items(&mut self, max_items: Option<usize>) {
+        self.max_items = max_items;
+    }
+    /// Returns how many clients a server serves at the same time. Other clients wait until one of
+    /// them is done.
+    pub fn max_workers(&self) -> usize {
+        self.max_workers
+    }
+    /// Sets how many clients a server serves at the same time.
+    pub fn set_max_workers(&mut self, max_workers: usize) {
+        self.max_workers = max_workers;
+    }
+    /// Returns how the data files are encrypted at rest.
+    pub fn data_encryption(&self) -> DataEncryption {
+        self.data_encryption
+    }
+    /// Sets how the data files are encrypted at rest.
+    pub fn set_data_encryption(&mut self, data_encryption: DataEncryption) {
+        self.data_encryption = data_encryption;
+    }
+    /// Returns the command that prints the encryption password if the password isn't stored in the
+    /// `Config`.
+    pub fn password_command(&self) -> Option<&str> {
+        self.password_command.as_deref()
+    }
+    /// Sets the command that prints the encryption password, such as a helper that unlocks it with a
+    /// hardware token. The command is run by the mtd binary when a sync needs the password.
+    pub fn set_password_command(&mut self, command: Option<String>) {
+        self.password_command = command;
+    }
+    /// Returns `true` if the encryption password is stored in the keyring of the operating system
+    /// instead of the `Config`.
+    pub fn password_in_keyring(&self) -> bool {
+        self.password_in_keyring
+    }
+    /// Sets whether the encryption password is stored in the keyring of the operating system. The
+    /// mtd binary reads it from there when a sync needs the password.
+    pub fn set_password_in_keyring(&mut self, password_in_keyring: bool) {
+        self.password_in_keyring = password_in_keyring;
+    }
+    /// Returns how long bodies in characters a server accepts from a client or `None` if there is
+    /// no limit.
+    pub fn max_body_len(&self) -> Option<usize> {
+        self.max_body_len
+    }
+    /// Sets how long bodies in characters a server accepts from a client. `None` removes the limit.
+    pub fn set_max_body_len(&mut self, max_body_len: Option<usize>) {
+        self.max_body_len = max_body_len;
+    }
+    /// Returns the rules applied to new `Todo`s, in the order they are applied.
+    pub fn rules(&self) -> &[AutoRule] {
+        &self.rules
+    }
+    /// Sets the rules applied to new `Todo`s. Later rules override what earlier ones set.
+    pub fn set_rules(&mut self, rules: Vec<AutoRule>) {
+        self.rules = rules;
+    }
+    /// Applies every rule that matches the body of a new `Todo` to it in order. A `Todo` that already
+    /// has a priority keeps it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mtd::{AutoRule, Config, Todo};
+    ///
+    /// let mut config = Config::new_default(b"pw".to_vec(), "localhost:55995".to_string(), None);
+    /// config.set_rules(vec![AutoRule { pattern: "rent".to_string(), priority: Some(3) }]);
+    ///
+    /// let mut todo = Todo::new_undated("Pay rent".to_string()).unwrap();
+    /// config.apply_rules(&mut todo);
+    ///
+    /// assert_eq!(todo.priority(), 3);
+    /// ```
+    pub fn apply_rules(&self, todo: &mut Todo) {
+        rules::apply_rules(&self.rules, todo);
+    }
+    /// Returns the time of day when the afternoon starts. Times before it are in the morning.
+    pub fn afternoon_start(&self) -> NaiveTime {
+        self.afternoon_start
+    }
+    /// Returns the time of day when the evening starts.
+    pub fn evening_start(&self) -> NaiveTime {
+        self.evening_start
+    }
+    /// Sets the times of day when the afternoon and the evening start.
+    pub fn set_day_sections(&mut self, afternoon_start: NaiveTime, evening_start: NaiveTime) {
+        self.afternoon_start = afternoon_start;
+        self.evening_start = evening_start;
+    }
+    /// Returns `true` if output should be plain ASCII without colors or other terminal styling.
+    pub fn ascii(&self) -> bool {
+        self.ascii
+    }
+    /// Sets whether output should be plain ASCII without colors or other terminal styling.
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.ascii = ascii;
+    }
+    /// Returns the colors and styles of the output.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+    /// Sets the colors and styles of the output.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+    /// Returns how the ids of items are shown and given in commands. The default is
+    /// `IdStyle::Number`.
+    pub fn id_style(&self) -> IdStyle {
+        self.id_style
+    }
+    /// Sets how the ids of items are shown and given in commands.
+    pub fn set_id_style(&mut self, id_style: IdStyle) {
+        self.id_style = id_style;
+    }
+    /// Returns how items changed both on this client and on the server are resolved when syncing.
+    /// The default is `ConflictPolicy::LastWriterWins`.
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        self.conflict_policy
+    }
+    /// Sets how items changed both on this client and on the server are resolved when syncing.
+    pub fn set_conflict_policy(&mut self, conflict_policy: ConflictPolicy) {
+        self.conflict_policy = conflict_policy;
+    }
+    /// Returns the weekday calendar weeks start on. The default is Monday.
+    pub fn week_starts_on(&self) -> Weekday {
+        self.week_starts_on
+    }
+    /// Sets the weekday calendar weeks start on.
+    pub fn set_week_starts_on(&mut self, weekday: Weekday) {
+        self.week_starts_on = weekday;
+    }
 }
 
 /// A struct used for synchronizing `TdList`s between a client and a server over the network. All
@@ -108,7 +638,7 @@ impl Config {
 /// // Create a new thread to act as a server.
 /// thread::spawn(move || {
 ///     let mut server_list = TdList::new_server();
-///     server_list.add_todo(Todo::new_undated("Todo 1".to_string()));
+///     server_list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 ///
 ///     let conf = Config::new_default(password.to_vec(), addr, None);
 ///     let mut server_mgr = MtdNetMgr::new(&mut server_list, &conf);
@@ -125,11 +655,105 @@ impl Config {
 /// let mut client_mgr = MtdNetMgr::new(&mut client_list, &conf);
 /// client_mgr.client_sync().unwrap();
 ///
-/// assert!(client_list.todos().contains(&&Todo::new_undated("Todo 1".to_string())));
+/// assert!(client_list.todos().contains(&&Todo::new_undated("Todo 1".to_string()).unwrap()));
 /// ```
 pub struct MtdNetMgr<'a> {
     td_list: &'a mut TdList,
+    user_lists: HashMap<String, TdList>,
     config: &'a Config,
+    clock_skew: Cell<Option<chrono::Duration>>,
+    conflicts: Vec<Conflict>,
+    transport: Box<dyn Transport>,
+    keep_alive: bool,
+    session: Option<Session>,
+    progress: Option<RefCell<ProgressCallback>>,
+    last_progress: Cell<SyncProgress>,
+    last_persist: Cell<Option<NaiveDateTime>>,
+    metrics: ServerMetrics,
+    log: Option<RefCell<LogCallback>>,
+    stop: Option<Arc<AtomicBool>>,
+    storage: Option<&'a dyn StorageEncryption>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<Mutex<FaultInjector>>>,
+}
+
+/// A fault that a `FaultInjector` can cause to a sent message.
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The message isn't sent at all.
+    Drop,
+    /// The message is sent twice.
+    Duplicate,
+    /// Only a part of the message is sent after which the connection is shut down.
+    Truncate,
+    /// The message is sent after a delay.
+    Delay,
+}
+
+/// Causes faults to the messages sent by a `MtdNetMgr` for testing how synchronization copes with
+/// an unreliable network. Faults are chosen randomly but the same seed always causes the same faults
+/// in the same order. Only available with the `fault-injection` feature.
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    rng: StdRng,
+    probability: f64,
+    faults: Vec<Fault>,
+    delay: Duration,
+}
+
+#[cfg(feature = "fault-injection")]
+impl FaultInjector {
+    /// Creates a new `FaultInjector` which causes any kind of `Fault` to a message with the given
+    /// probability between 0 and 1.
+    pub fn new(seed: u64, probability: f64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            probability: probability.clamp(0.0, 1.0),
+            faults: vec![Fault::Drop, Fault::Duplicate, Fault::Truncate, Fault::Delay],
+            delay: Duration::from_millis(50),
+        }
+    }
+    /// Sets the kinds of `Fault`s the `FaultInjector` causes.
+    pub fn with_faults(mut self, faults: Vec<Fault>) -> Self {
+        self.faults = faults;
+        self
+    }
+    /// Sets how long delayed messages are delayed. The default is 50 milliseconds.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Returns the `Fault` to cause to the next message or `None` if the message is sent normally.
+    fn next_fault(&mut self) -> Option<Fault> {
+        if self.faults.is_empty() || !self.rng.gen_bool(self.probability) {
+            return None;
+        }
+        Some(self.faults[self.rng.gen_range(0..self.faults.len())])
+    }
+
+    /// Writes a message to a `Connection` possibly causing a `Fault`.
+    fn write(&mut self, stream: &mut dyn Connection, msg: &[u8]) -> io::Result<()> {
+        match self.next_fault() {
+            None => stream.write_all(msg),
+            Some(Fault::Drop) => Ok(()),
+            Some(Fault::Duplicate) => {
+                stream.write_all(msg)?;
+                stream.write_all(msg)
+            }
+            Some(Fault::Truncate) => {
+                let len = self.rng.gen_range(0..msg.len());
+                stream.write_all(&msg[..len])?;
+                stream.shutdown_write()
+            }
+            Some(Fault::Delay) => {
+                thread::sleep(self.delay);
+                stream.write_all(msg)
+            }
+        }
+    }
 }
 
 impl<'a> MtdNetMgr<'a> {
@@ -137,184 +761,1033 @@ impl<'a> MtdNetMgr<'a> {
     // the value of td_list which isn't easy without ownership.
     /// Creates a new `MtdNetMgr`.
     pub fn new(td_list: &'a mut TdList, config: &'a Config) -> Self {
-        Self { td_list, config }
+        Self {
+            td_list,
+            user_lists: HashMap::new(),
+            config,
+            clock_skew: Cell::new(None),
+            conflicts: Vec::new(),
+            last_progress: Cell::new(SyncProgress::default()),
+            last_persist: Cell::new(None),
+            metrics: ServerMetrics::default(),
+            transport: transport::for_config(config),
+            keep_alive: false,
+            session: None,
+            progress: None,
+            log: None,
+            stop: None,
+            storage: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+
+    /// Makes the `MtdNetMgr` connect and listen with the given `Transport` instead of the one chosen
+    /// by the socket address of the `Config`.
+    pub fn set_transport(&mut self, transport: Box<dyn Transport>) {
+        self.transport = transport;
+        self.session = None;
+    }
+
+    /// Returns the local `TdList`.
+    pub fn td_list(&self) -> &TdList {
+        self.td_list
+    }
+
+    /// Returns the local `TdList` mutably, for example for replacing it with a newer copy between
+    /// syncs over a kept connection.
+    pub fn td_list_mut(&mut self) -> &mut TdList {
+        self.td_list
+    }
+
+    /// Returns the list a server hosts for a user. `None` if the user isn't in the `Config` or the
+    /// server hasn't been started.
+    pub fn user_list(&self, user: &str) -> Option<&TdList> {
+        self.user_lists.get(user)
+    }
+
+    /// Sets whether `client_sync` keeps the connection to the server open and reuses it for the next
+    /// sync instead of connecting and doing a handshake again. A kept connection is closed by the
+    /// server after it has been idle for `SESSION_IDLE_TIMEOUT`, which `send_keepalive` prevents.
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+        if !keep_alive {
+            self.session = None;
+        }
+    }
+
+    /// Sets a callback that is called with the progress of `client_sync`, `fetch_remote` and
+    /// `check_connection` whenever it changes, for example for showing a progress bar during a
+    /// large first sync. Large messages from the server are reported in parts as they arrive.
+    pub fn set_progress_callback(&mut self, callback: impl FnMut(&SyncProgress) + 'static) {
+        self.progress = Some(RefCell::new(Box::new(callback)));
+    }
+
+    /// Sets a callback that is called with the messages a server logs while running
+    /// `server_listening_loop`, such as errors with connections. By default informational messages
+    /// are printed to stdout and errors to stderr.
+    pub fn set_log_callback(&mut self, callback: impl FnMut(LogLevel, &str) + 'static) {
+        self.log = Some(RefCell::new(Box::new(callback)));
+    }
+
+    /// Sets a flag that makes `server_listening_loop` save the items and return once it is set, for
+    /// example by a `ControlSocket`.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    /// Makes a server encrypt its save file with the given encryption.
+    pub fn set_storage_encryption(&mut self, encryption: &'a dyn StorageEncryption) {
+        self.storage = Some(encryption);
+    }
+
+    /// Returns `true` if a connection to the server is kept open for the next sync.
+    pub fn is_connected(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Tells the server that a kept connection is still in use. Does nothing if no connection is
+    /// kept. If the server doesn't respond, the connection is closed and the next sync connects again.
+    pub fn send_keepalive(&mut self) -> Result<()> {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+
+        let mut engine = ClientEngine::resume(ClientRequest::Ping, TdList::new_client(), self.config.encryption_password(), session.sid);
+        self.run_client(&mut engine, session.stream.as_mut())?;
+
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// Makes the `MtdNetMgr` cause faults to the messages it sends. Only available with the
+    /// `fault-injection` feature.
+    #[cfg(feature = "fault-injection")]
+    pub fn set_fault_injector(&mut self, faults: FaultInjector) {
+        self.faults = Some(Arc::new(Mutex::new(faults)));
+    }
+
+    /// Connects to a server and synchronizes the local `TdList` with a server. Writes the local
+    /// `TdList` if the initialization `Config` defined a `save_location`.
+    ///
+    /// A sync has a commit and an ack phase. First the client sends the synchronized list, which the
+    /// server saves and confirms. Only then is the local `TdList` changed after which the client
+    /// acknowledges the sync. A failed sync therefore leaves the local `TdList` as it was. If the
+    /// server saved the list but the client never got the confirmation, the next sync reconciles the
+    /// changes without duplicating or losing items.
+    ///
+    /// If keep-alive is set, the connection is kept open for the next sync. A kept connection that
+    /// fails is replaced with a new one, which is safe because syncing again never duplicates items.
+    pub fn client_sync(&mut self) -> Result<()> {
+        if self.config.local_only {
+            return Err(Error::OnlineOnlyOperation);
+        }
+        if self.td_list.server {
+            return Err(Error::ClientOnlyOperation);
+        }
+
+        if let Some(session) = self.session.take() {
+            let mut engine = ClientEngine::resume(ClientRequest::Sync, self.td_list.clone(), self.config.encryption_password(), session.sid);
+            engine.set_server_version(session.version);
+            if self.sync_over(engine, session.stream).is_ok() {
+                return Ok(());
+            }
+        }
+
+        let engine = self.client_engine(ClientRequest::Sync, self.td_list.clone());
+        let stream = self.connect()?;
+        self.sync_over(engine, stream)
+    }
+
+    /// Synchronizes the local `TdList` over a connection with the given engine.
+    fn sync_over(&mut self, mut engine: ClientEngine, mut stream: Box<dyn Connection>) -> Result<()> {
+        engine.set_keep_alive(self.keep_alive);
+        engine.set_conflict_policy(self.config.conflict_policy());
+
+        let synced = match self.run_client(&mut engine, stream.as_mut())? {
+            (ClientOutcome::Synced(synced), reply) => {
+                // Ack phase: the server has already saved its list so a failed ack doesn't matter.
+                if let (Some(reply), Some(sid)) = (reply, engine.sid()) {
+                    if self.write_frame(stream.as_mut(), &engine.encode(&reply)?).is_ok() && self.keep_alive {
+                        self.session = Some(Session::new(stream, sid, engine.server_version()));
+                    }
+                }
+                synced
+            }
+            _ => return Err(Error::Unknown),
+        };
+
+        *self.td_list = synced;
+        self.conflicts = engine.conflicts().to_vec();
+        Ok(())
+    }
+
+    /// Fetches the server's `TdList` without synchronizing anything. The returned list is a server list
+    /// and the local `TdList` is left untouched.
+    pub fn fetch_remote(&self) -> Result<TdList> {
+        if self.config.local_only {
+            return Err(Error::OnlineOnlyOperation);
+        }
+        if self.td_list.server {
+            return Err(Error::ClientOnlyOperation);
+        }
+
+        let mut engine = self.client_engine(ClientRequest::Fetch, TdList::new_client());
+        match self.run_client(&mut engine, self.connect()?.as_mut())? {
+            (ClientOutcome::Fetched(server), _) => Ok(server),
+            _ => Err(Error::Unknown),
+        }
+    }
+
+    /// Connects to a server and checks that the server is reachable and uses the same encryption
+    /// password without synchronizing anything. Works regardless of whether the `TdList` is a client or
+    /// a server list.
+    pub fn check_connection(&self) -> Result<()> {
+        if self.config.local_only {
+            return Err(Error::OnlineOnlyOperation);
+        }
+
+        let mut engine = self.client_engine(ClientRequest::Ping, TdList::new_client());
+        self.run_client(&mut engine, self.connect()?.as_mut())?;
+        Ok(())
+    }
+
+    /// Returns how much the server's clock is ahead of the local clock, measured during the latest
+    /// handshake with the server. Returns `None` if the `MtdNetMgr` hasn't connected to a server or
+    /// the server is too old to tell its time. Done dates are based on the local date, so a large
+    /// difference means that the devices may disagree about which day it is.
+    pub fn clock_skew(&self) -> Option<chrono::Duration> {
+        self.clock_skew.get()
+    }
+
+    /// Returns the items that were changed both on this client and on the server since the last
+    /// sync, and how they were resolved. Empty before the first sync.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Returns the latest progress of a request to the server, such as the number of items and
+    /// bytes sent and received during the latest sync.
+    pub fn progress(&self) -> SyncProgress {
+        self.last_progress.get()
+    }
+
+    /// Connects to the server with the `Transport` of the `MtdNetMgr`.
+    fn connect(&self) -> Result<Box<dyn Connection>> {
+        self.report(SyncProgress::default());
+        let mut stream = self.transport.connect(self.config)?;
+        stream.set_timeout(self.config.timeout())?;
+        Ok(stream)
+    }
+
+    /// Creates an engine for a request that starts with a handshake, logging in as the user or the
+    /// device of the `Config` if it has one.
+    fn client_engine(&self, request: ClientRequest, list: TdList) -> ClientEngine {
+        let mut engine = ClientEngine::new(request, list, self.config.encryption_password());
+        engine.set_user(self.config.username().map(str::to_string));
+        if self.config.device().is_some() {
+            engine.set_device(self.config.device());
+        }
+        engine
+    }
+
+    /// Pairs this device with the server using a pairing code printed by the server. Returns the
+    /// name of the device and its token, which should be saved in the `Config` with
+    /// `Config::set_device` and `Config::set_encryption_password`. The encryption password of the
+    /// `Config` isn't needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the code isn't a pairing code, `Error::AuthFailed` or
+    /// `Error::DecryptingFailed` if the server doesn't know the code or it has expired, and
+    /// `Error::IncompatibleProtocol` if the server is too old to pair devices.
+    pub fn pair(&self, code: &str) -> Result<(String, String)> {
+        if self.config.local_only {
+            return Err(Error::OnlineOnlyOperation);
+        }
+
+        let (name, secret) = parse_pairing_code(code)?;
+        let mut engine = ClientEngine::pair(&name, secret.as_bytes());
+        match self.run_client(&mut engine, self.connect()?.as_mut())? {
+            (ClientOutcome::Paired(token), _) => Ok((name, token)),
+            _ => Err(Error::Unknown),
+        }
+    }
+
+    /// Drives a client engine over a connection until its request is done. Returns the outcome and
+    /// the last message the client should still send. Also records the clock skew measured during
+    /// the handshake.
+    fn run_client(&self, engine: &mut ClientEngine, stream: &mut dyn Connection) -> Result<(ClientOutcome, Option<ClientMessage>)> {
+        let mut msg = engine.start();
+        loop {
+            let frame = engine.encode(&msg)?;
+            self.report(engine.progress());
+            self.write_frame(stream, &frame)?;
+
+            let frame = self.read_frame(stream, engine.progress())?;
+            let reply = engine.decode(&frame)?;
+            self.report(engine.progress());
+
+            let action = engine.handle(reply)?;
+            self.report(engine.progress());
+            if let Some(skew) = engine.clock_skew() {
+                self.clock_skew.set(Some(skew));
+            }
+            match action {
+                ClientAction::Send(next) => msg = next,
+                ClientAction::Finish { reply, outcome } => return Ok((outcome, reply)),
+            }
+        }
+    }
+
+    /// Creates a loop which handles incoming sync connections. Each connection is served by a worker
+    /// thread that reads, decrypts, encrypts and writes its messages, so up to
+    /// `Config.max_workers()` clients are served at once. Further connections wait until a worker
+    /// is free. The items are only changed on the thread running the loop, one message at a time.
+    /// Writes the local `TdList` if the initialization `Config` defined a `save_location`. The
+    /// server runs `run_maintenance` once every `Config.maintenance_interval()`.
+    ///
+    /// While running, the server keeps a lock file named `<save file>.lock` next to the save file.
+    /// Returns `Error::ServerAlreadyRunning` if another running server holds the lock. A lock left
+    /// behind by a server that didn't exit cleanly is taken over where it can be checked that its
+    /// process isn't running anymore.
+    ///
+    /// If the `Config` has a Tor control address, the server is published as an onion service for as
+    /// long as it runs. The private key of the service is kept in `<save file>.onion_key` so that the
+    /// onion address stays the same across restarts.
+    ///
+    /// Clients that log in as one of the `Config`'s users sync with the user's own list, which is
+    /// saved in `<save file>.users/<user>.json`.
+    ///
+    /// The loop runs until the flag set with `set_stop_flag` is set. The items are then written to
+    /// the save location and the loop returns `Ok`.
+    ///
+    /// # Panics
+    ///
+    /// If the `TdList` is a client list.
+    pub fn server_listening_loop(&mut self) -> Result<()> {
+        if self.config.local_only {
+            return Err(Error::OnlineOnlyOperation);
+        }
+        if !self.td_list.server {
+            return Err(Error::ServerOnlyOperation);
+        }
+
+        // Two servers saving to the same file would overwrite each other's changes.
+        let lock = match self.config.save_location() {
+            Some(path) => Some(ServerLock::acquire(path)?),
+            None => None,
+        };
+
+        // Probes are answered while the lists are loaded, but the server isn't ready until then.
+        let health = match self.config.health_addr() {
+            Some(addr) => {
+                let lock_path = lock.as_ref().map(|lock| lock.path.clone());
+                Some(HealthServer::start(addr, move || lock_path.as_deref().is_none_or(ServerLock::is_held))?)
+            }
+            None => None,
+        };
+
+        self.read_user_lists()?;
+
+        let _onion_service = match self.config.tor_control_addr() {
+            Some(addr) => Some(self.publish_onion_service(addr)?),
+            None => None,
+        };
+
+        // The listener doesn't block so that maintenance can run even if no clients connect.
+        let mut listener = self.transport.listen(self.config)?;
+
+        let mut next_maintenance = Instant::now() + self.config.maintenance_interval();
+        // Each client is served by a worker thread that reads, decrypts, encrypts and writes its
+        // messages, so slow clients don't hold up the others. The items are only changed on this
+        // thread.
+        let (events_tx, events) = mpsc::channel();
+        let mut clients: HashMap<usize, Client> = HashMap::new();
+        let mut next_id = 0;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let mut stop_deadline = None;
+
+        loop {
+            let mut accepted = false;
+            if stop_deadline.is_none() && clients.len() < self.config.max_workers() {
+                match listener.accept() {
+                    Ok(Some(stream)) => {
+                        accepted = true;
+                        let (replies_tx, replies) = mpsc::channel();
+                        let mut engine = ServerEngine::new(self.config.encryption_password());
+                        engine.set_limits(self.config.max_items(), self.config.max_body_len());
+                        engine.set_users(self.config.users().iter().map(|user| (user.name.clone(), user.password.clone().into_bytes())).collect());
+                        // The devices are read for each connection so that changes made while the
+                        // server runs take effect right away.
+                        if let Some(path) = self.config.devices_location() {
+                            match DeviceRegistry::read(&path) {
+                                Ok(registry) => {
+                                    engine.set_devices(registry.tokens());
+                                    engine.set_pairings(registry.secrets(current_time()));
+                                }
+                                Err(e) => self.log(LogLevel::Error, &format!("Error occurred while reading the devices: {}", e)),
+                            }
+                        }
+                        let peer = stream.peer();
+                        self.metrics.connections += 1;
+                        server_log!(self, Debug, [peer = %peer], "Client from {} connected.", peer);
+                        let engine = Arc::new(Mutex::new(engine));
+                        clients.insert(next_id, Client { engine: engine.clone(), peer, started: Instant::now(), replies: replies_tx });
+
+                        let worker = Worker {
+                            id: next_id,
+                            engine,
+                            timeout: self.config.timeout(),
+                            events: events_tx.clone(),
+                            replies,
+                            shutdown: shutdown.clone(),
+                            #[cfg(feature = "fault-injection")]
+                            faults: self.faults.clone(),
+                        };
+                        thread::spawn(move || worker.serve(stream));
+                        next_id += 1;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.log(LogLevel::Error, &format!("Error occurred: {}", e))
+                    }
+                }
+            }
+
+            // Wait a moment for the workers if there's nothing else to do.
+            let first = if accepted { events.try_recv().ok() } else { events.recv_timeout(Duration::from_millis(50)).ok() };
+            for event in first.into_iter().chain(std::iter::from_fn(|| events.try_recv().ok())) {
+                self.handle_worker_event(event, &mut clients);
+            }
+
+            if let Some(health) = &health {
+                health.update(HealthStatus {
+                    list_loaded: true,
+                    lock_held: true,
+                    last_persist: self.last_persist.get(),
+                    metrics: ServerMetrics {
+                        clients: clients.len(),
+                        items: self.td_list.item_count() + self.user_lists.values().map(TdList::item_count).sum::<usize>(),
+                        ..self.metrics.clone()
+                    },
+                });
+            }
+
+            if Instant::now() >= next_maintenance {
+                if let Err(e) = self.run_maintenance() {
+                    self.log(LogLevel::Error, &format!("Error occurred during maintenance: {}", e))
+                }
+                next_maintenance = Instant::now() + self.config.maintenance_interval();
+            }
+
+            // Stopping lets the clients in the middle of a sync finish for as long as they may take.
+            if stop_deadline.is_none() && self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst)) {
+                shutdown.store(true, Ordering::SeqCst);
+                stop_deadline = Some(Instant::now() + self.config.timeout());
+            }
+            if stop_deadline.is_some_and(|deadline| clients.is_empty() || Instant::now() >= deadline) {
+                self.persist_all()?;
+                self.log(LogLevel::Info, "Stopped.");
+                return Ok(());
+            }
+        }
+    }
+
+    fn publish_onion_service(&self, control_addr: &str) -> Result<OnionService> {
+        let (_, port) = split_host_port(self.config.socket_addr())
+            .ok_or_else(|| Error::InvalidArgument(format!("Invalid socket address \"{}\".", self.config.socket_addr())))?;
+
+        let key_path = self.config.save_location().map(|path| {
+            let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+            file_name.push(".onion_key");
+            path.with_file_name(file_name)
+        });
+        let key = match &key_path {
+            Some(path) if path.exists() => Some(fs::read_to_string(path)?.trim().to_string()),
+            _ => None,
+        };
+
+        let target = tor::forward_target(self.config.socket_addr());
+        let service = OnionService::publish(control_addr, self.config.tor_control_password(), port, &target, key.as_deref())?;
+        if let (Some(path), None) = (&key_path, &key) {
+            write_private_file(path, service.private_key().as_bytes())?;
+        }
+
+        self.log(LogLevel::Info, &format!("Published as an onion service. Clients can connect through Tor to {}.", service.address()));
+        Ok(service)
+    }
+
+    /// Runs server maintenance: removes old `Todo`s and expired `Task`s, writes the `TdList` if the
+    /// `Config` defines a `save_location` and prunes the oldest backups of the save file if the
+    /// `Config` limits the number of backups. Backups are files next to the save file named
+    /// `<save file name>.<timestamp>.bak`. This is called automatically by `server_listening_loop`.
+    pub fn run_maintenance(&mut self) -> Result<()> {
+        if !self.td_list.server {
+            return Err(Error::ServerOnlyOperation);
+        }
+
+        self.td_list.self_sync();
+        for list in self.user_lists.values_mut() {
+            list.self_sync();
+        }
+        self.persist_all()?;
+
+        if let (Some(path), Some(max_backups)) = (self.config.save_location(), self.config.max_backups()) {
+            prune_backups(path, max_backups)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the lists of the users in the `Config` that aren't read yet. Users without a saved list
+    /// start with an empty one.
+    fn read_user_lists(&mut self) -> Result<()> {
+        for user in self.config.users() {
+            if self.user_lists.contains_key(&user.name) {
+                continue;
+            }
+            let list = match self.config.user_save_location(&user.name).filter(|path| path.exists()) {
+                Some(path) => TdList::new_from_json(&read_data_file(&path, self.storage)?)?,
+                None => TdList::new_server(),
+            };
+            if !list.server {
+                return Err(Error::InvalidArgument(format!("The data file of {} contains a client's items.", user.name)));
+            }
+            self.user_lists.insert(user.name.clone(), list);
+        }
+        Ok(())
+    }
+
+    /// Writes the `TdList` and the lists of the users if the `Config` defines a `save_location`.
+    fn persist_all(&self) -> Result<()> {
+        if let Some(path) = self.config.save_location() {
+            self.persist(path)?;
+        }
+        for user in self.user_lists.keys() {
+            self.persist_user(user)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the list of a user next to the save file if the `Config` defines a `save_location`.
+    fn persist_user(&self, user: &str) -> Result<()> {
+        if let (Some(path), Some(list)) = (self.config.user_save_location(user), self.user_lists.get(user)) {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_data_file(&path, list.to_json()?.as_bytes(), self.storage)?;
+            self.last_persist.set(Some(current_time()));
+        }
+        Ok(())
+    }
+
+    /// Writes the `TdList` to the given path and remembers when it was written.
+    fn persist(&self, path: &Path) -> Result<()> {
+        write_data_file(path, self.td_list.to_json()?.as_bytes(), self.storage)?;
+        self.last_persist.set(Some(current_time()));
+        Ok(())
+    }
+
+    /// Handles what a worker has read from its client and tells the worker what to do next. The
+    /// client is forgotten once its connection is done with.
+    fn handle_worker_event(&mut self, event: WorkerEvent, clients: &mut HashMap<usize, Client>) {
+        let (id, result) = match event {
+            WorkerEvent::Message(id, msg) => match clients.get_mut(&id) {
+                Some(client) => (id, self.handle_message(client, msg)),
+                None => return,
+            },
+            WorkerEvent::Failed(id, e) => (id, Err(e)),
+            WorkerEvent::Closed(id) => {
+                clients.remove(&id);
+                return;
+            }
+        };
+
+        let client = match clients.get_mut(&id) {
+            Some(client) => client,
+            None => {
+                // The connection failed after its last message.
+                if let Err(e) = result {
+                    self.log(LogLevel::Error, &format!("Error occurred: {}", e));
+                }
+                return;
+            }
+        };
+
+        let state = client.engine.lock().map(|engine| engine.state()).ok();
+        match result {
+            Ok(reply) => {
+                let finished = reply.finished;
+                // A client that keeps the connection open for its next sync continues with the same
+                // engine, which is waiting for a new request and still knows the client's user.
+                if client.replies.send(reply).is_err() || finished == Some(false) {
+                    clients.remove(&id);
+                } else if finished == Some(true) {
+                    client.started = Instant::now();
+                }
+            }
+            // The changes are already saved so a missing ack isn't an error. The client keeps its
+            // old list and reconciles the changes on its next sync.
+            Err(_) if state == Some(ServerState::AwaitAck) => {
+                self.log(LogLevel::Info, &format!("Client from {} didn't acknowledge the sync. It will reconcile the changes on its next sync.", client.peer));
+                clients.remove(&id);
+            }
+            Err(Error::Unknown) if state == Some(ServerState::AwaitCommand) => {
+                self.log(LogLevel::Info, &format!("Client from {} didn't try to read server items. Stopping connection. This is probably a bad sign.", client.peer));
+                clients.remove(&id);
+            }
+            Err(e @ (Error::AuthFailed | Error::DecryptingFailed)) => {
+                self.metrics.auth_failures += 1;
+                server_log!(self, Error, [peer = %client.peer], "Authenticating a client from {} failed: {}", client.peer, e);
+                clients.remove(&id);
+            }
+            Err(e) => {
+                self.metrics.errors += 1;
+                self.log(LogLevel::Error, &format!("Error occurred: {}", e));
+                clients.remove(&id);
+            }
+        }
+    }
+
+    /// Handles a message from a client. Saves the `TdList`, or the list of the client's user, when
+    /// the client commits a sync. The `Worker` of the client decrypts the message and encrypts the
+    /// reply.
+    fn handle_message(&mut self, client: &mut Client, msg: ClientMessage) -> Result<WorkerReply> {
+        let mut engine = client.engine.lock().map_err(|_| Error::Unknown)?;
+        let list = match engine.user() {
+            Some(user) => self.user_lists.get_mut(user).ok_or(Error::AuthFailed)?,
+            None => &mut *self.td_list,
+        };
+        let action = engine.handle(msg, list)?;
+        if let Some((device, token)) = &action.paired {
+            // The device only gets its token once it is saved.
+            let path = self.config.devices_location().ok_or(Error::Unknown)?;
+            let mut registry = DeviceRegistry::read(&path)?;
+            registry.complete_pairing(device, token.clone(), current_time());
+            registry.write(&path)?;
+            server_log!(self, Info, [peer = %client.peer, device = device.as_str()], "Paired the device {} from {}.", device, client.peer);
+        }
+        if action.save {
+            // Kept connections time each sync from the end of the previous one.
+            let duration = client.started.elapsed();
+            self.metrics.syncs += 1;
+            self.metrics.sync_seconds += duration.as_secs_f64();
+            match engine.user() {
+                Some(user) => {
+                    self.persist_user(user)?;
+                    server_log!(self, Info, [peer = %client.peer, user, duration_ms = duration.as_millis() as u64], "Synced with {} from {}.", user, client.peer);
+                }
+                None => {
+                    if let Some(path) = self.config.save_location() {
+                        self.persist(path)?;
+                    }
+                    match engine.device() {
+                        Some(device) => {
+                            server_log!(self, Info, [peer = %client.peer, device, duration_ms = duration.as_millis() as u64], "Synced with the device {} from {}.", device, client.peer);
+                        }
+                        None => {
+                            server_log!(self, Info, [peer = %client.peer, duration_ms = duration.as_millis() as u64], "Synced with a client from {}.", client.peer);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(ServerMessage::Rejected(problems)) = &action.reply {
+            self.metrics.rejected_syncs += 1;
+            self.log(LogLevel::Info, &format!("Rejected a sync from {}: {}", client.peer, problems.join(" ")));
+        }
+        Ok(WorkerReply { reply: action.reply, finished: action.finished })
+    }
+
+    /// Writes a frame to a `Connection` prefixed with its length.
+    fn write_frame(&self, stream: &mut dyn Connection, frame: &[u8]) -> Result<()> {
+        #[cfg(feature = "fault-injection")]
+        return write_frame(stream, frame, self.faults.as_deref());
+        #[cfg(not(feature = "fault-injection"))]
+        write_frame(stream, frame)
+    }
+
+    /// Calls the log callback if one is set. Otherwise the message is a `tracing` event with the
+    /// `tracing` feature and printed without it.
+    fn log(&self, level: LogLevel, msg: &str) {
+        match (&self.log, level) {
+            (Some(callback), _) => (callback.borrow_mut())(level, msg),
+            #[cfg(feature = "tracing")]
+            (None, LogLevel::Debug) => tracing::debug!("{}", msg),
+            #[cfg(feature = "tracing")]
+            (None, LogLevel::Info) => tracing::info!("{}", msg),
+            #[cfg(feature = "tracing")]
+            (None, LogLevel::Error) => tracing::error!("{}", msg),
+            #[cfg(not(feature = "tracing"))]
+            (None, LogLevel::Debug) => {}
+            #[cfg(not(feature = "tracing"))]
+            (None, LogLevel::Info) => println!("{}", msg),
+            #[cfg(not(feature = "tracing"))]
+            (None, LogLevel::Error) => eprintln!("{}", msg),
+        }
+    }
+
+    fn report(&self, progress: SyncProgress) {
+        self.last_progress.set(progress);
+        if let Some(callback) = &self.progress {
+            (callback.borrow_mut())(&progress);
+        }
+    }
+
+    /// Reads a length-prefixed frame from a `Connection` in parts. The progress before the frame
+    /// is reported after each part.
+    fn read_frame(&self, stream: &mut dyn Connection, progress: SyncProgress) -> Result<Vec<u8>> {
+        let mut frame = vec![0u8; read_frame_len(stream, MAX_FRAME_LENGTH)?];
+        let len = frame.len() as u64;
+
+        let mut read = 0;
+        for part in frame.chunks_mut(PROGRESS_CHUNK_LENGTH) {
+            stream.read_exact(part)?;
+            read += part.len() as u64;
+            self.report(SyncProgress {
+                bytes_received: progress.bytes_received + read,
+                bytes_expected: progress.bytes_received + len,
+                ..progress
+            });
+        }
+        Ok(frame)
+    }
+}
+
+/// Reads the length header of a frame from a `Connection`. Frames longer than `max_len` are refused.
+fn read_frame_len(stream: &mut dyn Connection, max_len: u32) -> Result<usize> {
+    let mut msg_len_header = [0u8; 4];
+    stream.read_exact(&mut msg_len_header)?;
+    let len = u32::from_le_bytes(msg_len_header);
+    // Refuse before allocating so that a misbehaving peer can't make us reserve gigabytes.
+    if len > max_len {
+        return Err(Error::LimitsExceeded(vec![format!("A message of {} bytes is longer than the limit of {} bytes.", len, max_len)]));
+    }
+    Ok(len as usize)
+}
+
+/// Writes a frame to a `Connection` prefixed with its length, possibly causing faults to it.
+fn write_frame(stream: &mut dyn Connection, frame: &[u8], #[cfg(feature = "fault-injection")] faults: Option<&Mutex<FaultInjector>>) -> Result<()> {
+    let len_header = (frame.len() as u32).to_le_bytes();
+
+    #[cfg(feature = "fault-injection")]
+    if let Some(faults) = faults {
+        faults.lock().map_err(|_| Error::Unknown)?.write(stream, &[len_header.as_slice(), frame].concat())?;
+        return Ok(());
     }
 
-    /// Connects to a server and synchronizes the local `TdList` with a server. Writes the local
-    /// `TdList` if the initialization `Config` defined a `save_location`.
-    pub fn client_sync(&mut self) -> Result<()> {
-        if self.config.local_only {
-            return Err(Error::OnlineOnlyOperation);
-        }
-        if self.td_list.server {
-            return Err(Error::ClientOnlyOperation);
-        }
+    stream.write_all(&len_header)?;
+    stream.write_all(frame)?;
+    Ok(())
+}
 
-        let mut stream = TcpStream::connect(self.config.socket_addr())?;
+/// A callback for the progress of a client's request.
+type ProgressCallback = Box<dyn FnMut(&SyncProgress)>;
+
+/// A callback for the messages logged by a server.
+type LogCallback = Box<dyn FnMut(LogLevel, &str)>;
+
+/// How serious a message logged by a server is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    /// Details only needed when looking into a problem, such as each connection.
+    Debug,
+    /// Something a server operator may want to know about, such as a rejected sync.
+    Info,
+    /// An error that stopped a connection or maintenance from completing.
+    Error,
+}
 
-        stream.set_read_timeout(Some(self.config.timeout()))?;
-        stream.set_write_timeout(Some(self.config.timeout()))?;
+/// The longest message in bytes an `MtdNetMgr` reads from a connection.
+const MAX_FRAME_LENGTH: u32 = 64 * 1024 * 1024;
 
-        // Send random data to the server to verify that the server is authentic.
-        let random_auth_data: [u8; 8] = random();
-        self.write_encrypted(&mut stream, &random_auth_data)?;
+/// The longest first message in bytes a server reads from a client. Anyone can connect, so longer
+/// messages are only read once the handshake has shown that the client knows a password.
+const MAX_HELLO_LENGTH: u32 = 4 * 1024;
 
-        // Server responds with a session id and the previous random data.
-        let msg = self.read_decrypted(&mut stream)?;
-        if msg.len() < 16 {
-            return Err(Error::AuthFailed);
-        }
+/// How many bytes of a message are read between progress reports.
+const PROGRESS_CHUNK_LENGTH: usize = 64 * 1024;
 
-        // set session id
-        let sid: [u8; 8] = (&msg[..8]).try_into().unwrap();
-        let auth_data: &[u8] = &msg[8..];
+/// How long a server keeps a connection open between a client's syncs without hearing from the client.
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
-        // Check random data
-        if auth_data != random_auth_data {
-            return Err(Error::AuthFailed);
-        }
+/// An authenticated connection between a client and a server, identified by its session id.
+struct Session {
+    stream: Box<dyn Connection>,
+    sid: [u8; 8],
+    // The sync protocol version agreed on during the handshake.
+    version: u32,
+}
 
-        // Send read command to server to verify our authenticity.
-        self.write_encrypted(&mut stream, &[&sid, b"read".as_slice()].concat())?;
+impl Session {
+    fn new(stream: Box<dyn Connection>, sid: [u8; 8], version: u32) -> Session {
+        Session { stream, sid, version }
+    }
+}
 
-        // Server sends its TdList, sync with that list
-        let msg = self.read_check_decrypted(&mut stream, &sid)?;
-        let mut server = TdList::new_from_json(&String::from_utf8_lossy(&msg))?;
+/// A client of a server. The messages of the client are read and written by its `Worker`.
+struct Client {
+    /// Shared with the `Worker`, which only uses it while the server waits for the client's next
+    /// message.
+    engine: Arc<Mutex<ServerEngine>>,
+    peer: String,
+    /// When the client connected or finished its previous sync over a kept connection.
+    started: Instant,
+    replies: Sender<WorkerReply>,
+}
 
-        self.td_list.sync(&mut server);
+/// What a `Worker` tells the server.
+enum WorkerEvent {
+    /// The client sent a message.
+    Message(usize, ClientMessage),
+    /// Reading from or writing to the client failed.
+    Failed(usize, Error),
+    /// The client closed its kept connection or it was idle for too long.
+    Closed(usize),
+}
 
-        // send the synced list back to the server
-        self.write_encrypted(&mut stream, &[&sid, server.to_json()?.as_bytes()].concat())?;
+/// What the server tells a `Worker` after handling a message.
+struct WorkerReply {
+    reply: Option<ServerMessage>,
+    /// `Some` once the client's request is done, `true` if the connection should be kept open.
+    finished: Option<bool>,
+}
 
-        // Verify that the server actually got its list.
-        let msg = self.read_check_decrypted(&mut stream, &sid)?;
+/// Serves a single client of a server on its own thread. The worker reads and decrypts messages
+/// from the client and passes them to the server, and encrypts and writes the replies of the
+/// server back to the client.
+struct Worker {
+    id: usize,
+    engine: Arc<Mutex<ServerEngine>>,
+    timeout: Duration,
+    events: Sender<WorkerEvent>,
+    replies: Receiver<WorkerReply>,
+    shutdown: Arc<AtomicBool>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<Mutex<FaultInjector>>>,
+}
 
-        if msg == b"ok" {
-            Ok(())
-        } else {
-            Err(Error::Unknown)
+impl Worker {
+    fn serve(self, mut stream: Box<dyn Connection>) {
+        if let Err(e) = self.exchange(stream.as_mut()) {
+            let _ = self.events.send(WorkerEvent::Failed(self.id, e));
         }
     }
 
-    /// Creates a loop which handles incoming sync connections. Note that each connection is handled in
-    /// the same thread sequentially so only one connection can be processed at a time. Writes the local
-    /// `TdList` if the initialization `Config` defined a `save_location`.
-    ///
-    /// # Panics
-    ///
-    /// If the `TdList` is a client list.
-    pub fn server_listening_loop(&mut self) -> Result<()> {
-        if self.config.local_only {
-            return Err(Error::OnlineOnlyOperation);
+    /// Passes messages between the client and the server until the connection is done with.
+    fn exchange(&self, stream: &mut dyn Connection) -> Result<()> {
+        stream.set_timeout(self.timeout)?;
+
+        let mut max_len = MAX_HELLO_LENGTH;
+        loop {
+            let mut frame = vec![0u8; read_frame_len(stream, max_len)?];
+            stream.read_exact(&mut frame)?;
+            let msg = self.engine.lock().map_err(|_| Error::Unknown)?.decode(&frame)?;
+            // The server answers every message, so it has stopped if it can't be reached.
+            if self.events.send(WorkerEvent::Message(self.id, msg)).is_err() {
+                return Ok(());
+            }
+            let reply = match self.replies.recv() {
+                Ok(reply) => reply,
+                Err(_) => return Ok(()),
+            };
+
+            if let Some(reply) = reply.reply {
+                // The server only answers clients that it could authenticate.
+                max_len = MAX_FRAME_LENGTH;
+                let frame = self.engine.lock().map_err(|_| Error::Unknown)?.encode(&reply)?;
+                #[cfg(feature = "fault-injection")]
+                write_frame(stream, &frame, self.faults.as_deref())?;
+                #[cfg(not(feature = "fault-injection"))]
+                write_frame(stream, &frame)?;
+            }
+            match reply.finished {
+                Some(true) if self.wait_for_command(stream) => {}
+                Some(true) => {
+                    let _ = self.events.send(WorkerEvent::Closed(self.id));
+                    return Ok(());
+                }
+                Some(false) => return Ok(()),
+                None => {}
+            }
         }
-        if !self.td_list.server {
-            return Err(Error::ServerOnlyOperation);
+    }
+
+    /// Waits for the next command on a kept connection. Returns `false` if the connection was
+    /// closed, idle for `SESSION_IDLE_TIMEOUT` or the server is shutting down.
+    fn wait_for_command(&self, stream: &mut dyn Connection) -> bool {
+        let idle_since = Instant::now();
+        while idle_since.elapsed() < SESSION_IDLE_TIMEOUT && !self.shutdown.load(Ordering::SeqCst) {
+            match stream.readiness() {
+                Readiness::Idle => thread::sleep(Duration::from_millis(50)),
+                Readiness::Ready => return true,
+                Readiness::Closed => return false,
+            }
         }
+        false
+    }
+}
 
-        let tcp = TcpListener::bind(self.config.socket_addr())?;
+/// A lock file next to a server's save file that keeps other servers from using the same file. The
+/// file contains the process id of the server and is removed when the lock is dropped. The server
+/// also holds an advisory lock on the file for as long as it runs.
+struct ServerLock {
+    path: PathBuf,
+    file: fs::File,
+}
 
-        for stream in tcp.incoming() {
-            match self.handle_stream(stream) {
-                Err(e) => {
-                    eprintln!("Error occurred: {}", e)
-                }
-                Ok(_) => {}
+impl ServerLock {
+    fn acquire(save_location: &Path) -> Result<ServerLock> {
+        let mut file_name = save_location.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".lock");
+        let path = save_location.with_file_name(file_name);
+
+        let (mut file, created) = match fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path) {
+            Ok(file) => (file, true),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (fs::OpenOptions::new().read(true).write(true).open(&path)?, false),
+            Err(e) => return Err(e.into()),
+        };
+        let pid = |file: &mut fs::File| {
+            let mut content = String::new();
+            file.read_to_string(&mut content).ok().and_then(|_| content.trim().parse::<u32>().ok())
+        };
+
+        if file.try_lock().is_err() {
+            return Err(Error::ServerAlreadyRunning(pid(&mut file).unwrap_or_default(), path));
+        }
+        if !created {
+            // Only a lock left behind by a server that didn't exit cleanly is taken over. A lock
+            // file without a process id may be one that another server is just creating.
+            match pid(&mut file) {
+                Some(pid) if !process_is_running(pid) => {}
+                pid => return Err(Error::ServerAlreadyRunning(pid.unwrap_or_default(), path)),
             }
+            // The file may have been removed by its server after it was opened.
+            if !same_file(&file, &path) {
+                return Err(Error::ServerAlreadyRunning(0, path));
+            }
+            file.set_len(0)?;
+            file.seek(io::SeekFrom::Start(0))?;
         }
+        write!(file, "{}", process::id())?;
+        file.flush()?;
 
-        Ok(())
+        Ok(ServerLock { path, file })
     }
+}
 
-    fn handle_stream(&mut self, stream: io::Result<TcpStream>) -> Result<()> {
-        let mut stream = stream?;
-
-        stream.set_read_timeout(Some(self.config.timeout()))?;
-        stream.set_write_timeout(Some(self.config.timeout()))?;
-
-        // Random session id for the sync exchange.
-        let sid: [u8; 8] = random();
+impl ServerLock {
+    /// Returns `true` if the lock file at the path still exists and names this process.
+    fn is_held(path: &Path) -> bool {
+        fs::read_to_string(path).is_ok_and(|pid| pid.trim() == process::id().to_string())
+    }
+}
 
-        // First the client sends some random data in an encrypted form to the server.
-        let random_auth_data = self.read_decrypted(&mut stream)?;
-        // The server sends the data back with a new session id attached.
-        self.write_encrypted(&mut stream, &[&sid, random_auth_data.as_slice()].concat())?;
+impl Drop for ServerLock {
+    fn drop(&mut self) {
+        // The file is removed before it's unlocked so that no other server can lock the removed file.
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
 
-        // Client sends a command to the server.
-        let msg = self.read_check_decrypted(&mut stream, &sid)?;
+/// Returns `true` if an open file is the file at a path.
+#[cfg(unix)]
+fn same_file(file: &fs::File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
 
-        // Verify that the request is a read request. This just verifies that the client has the right
-        // encryption password.
-        if msg == b"read" {
-            // Respond with the server TdList
-            self.write_encrypted(&mut stream, &[&sid, self.td_list.to_json()?.as_bytes()].concat())?;
-        } else {
-            println!("Client from {} didn't try to read server items. Stopping connection. This is probably a bad sign.", stream.peer_addr()?);
-            return Ok(());
-        }
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(named)) => open.dev() == named.dev() && open.ino() == named.ino(),
+        _ => false,
+    }
+}
 
-        // Client sends a response with a new synced TdList for the server.
-        let msg = self.read_check_decrypted(&mut stream, &sid)?;
-        let json_string = String::from_utf8_lossy(&msg).to_string();
-        let new_td_list = TdList::new_from_json(&json_string)?;
+/// Returns `true` if an open file is the file at a path. Only checks that the path exists where
+/// files can't be compared.
+#[cfg(not(unix))]
+fn same_file(_file: &fs::File, path: &Path) -> bool {
+    path.exists()
+}
 
-        self.td_list.todos = new_td_list.todos;
-        self.td_list.tasks = new_td_list.tasks;
+/// Returns `true` if a process with the given id is running. On platforms where this can't be
+/// checked every process is assumed to be running.
+fn process_is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    !proc.is_dir() || proc.join(pid.to_string()).exists()
+}
 
-        if let Some(path) = self.config.save_location() {
-            fs::write(path, &json_string)?;
+/// Returns the backups of a file sorted from oldest to newest. Backups are files next to the file
+/// named `<file name>.<timestamp>.bak`.
+pub fn backup_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, file_name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(file_name)) => (dir, file_name.to_string_lossy()),
+        _ => return Ok(Vec::new()),
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{}.", file_name);
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(&prefix) && name.ends_with(".bak") {
+            backups.push(entry.path());
         }
-
-        // Send ok to the client to verify that everything went right.
-        self.write_encrypted(&mut stream, &[&sid, b"ok".as_slice()].concat())?;
-
-        Ok(())
     }
 
-    /// Encrypts and writes a message to a `TcpStream`.
-    fn write_encrypted(&self, stream: &mut TcpStream, content: &[u8]) -> Result<()> {
-        let enc = encrypt(content, &self.config.encryption_password())?;
-        let len = enc.len() as u32;
-        let len_header = len.to_le_bytes();
-        stream.write(&len_header)?;
-        stream.write(&enc)?;
-        Ok(())
-    }
+    // Backup names end with a sortable timestamp so the oldest ones sort first.
+    backups.sort();
 
-    /// Reads a message from a `TcpStream` and decrypts it.
-    fn read_decrypted(&self, stream: &mut TcpStream) -> Result<Vec<u8>> {
-        let mut msg_len_header = [0u8; 4];
-        stream.read_exact(&mut msg_len_header)?;
-        let len = u32::from_le_bytes(msg_len_header);
-        let mut encrypted_msg = vec![0u8; len as usize];
-        stream.read_exact(&mut encrypted_msg)?;
-        decrypt(&encrypted_msg, &self.config.encryption_password())
-    }
+    Ok(backups)
+}
 
-    /// Reads a message from a `TcpStream` and decrypts it. Checks the message's session id and returns
-    /// the message without a session id.
-    fn read_check_decrypted(&self, stream: &mut TcpStream, correct_sid: &[u8; 8]) -> Result<Vec<u8>> {
-        MtdNetMgr::check_sid(correct_sid, &self.read_decrypted(stream)?).map(|l| l.to_vec())
-    }
+/// Removes the oldest backups of the given file so that at most `keep` backups remain. Returns the
+/// number of removed backups.
+fn prune_backups(path: &Path, keep: usize) -> Result<usize> {
+    let backups = backup_files(path)?;
 
-    /// Checks if a message contains a valid session id. Returns the message without the session id
-    /// if the session id is correct. Otherwise returns an Err.
-    fn check_sid<'b>(correct_sid: &[u8; 8], msg_with_sid: &'b [u8]) -> Result<&'b [u8]> {
-        if msg_with_sid.len() >= 8 && &msg_with_sid[..8] == correct_sid {
-            Ok(&msg_with_sid[8..])
-        } else {
-            Err(Error::AuthFailed)
-        }
+    let remove_count = backups.len().saturating_sub(keep);
+    for backup in &backups[..remove_count] {
+        fs::remove_file(backup)?;
     }
+
+    Ok(remove_count)
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod network_tests {
-    use std::{env, fs, thread};
+    use std::{env, fs, process, thread};
+    use std::cell::RefCell;
+    use std::io::{self, Read, Write};
+    use std::net::TcpStream;
+    use std::rc::Rc;
     use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::time::Duration;
 
-    use crate::{Config, Error, TdList, Todo};
-    use crate::network::MtdNetMgr;
+    use chrono::NaiveDate;
+
+    use crate::{Config, current_time, DeviceRegistry, Error, MemoryTransport, pairing_payload, ServerUser, TdList, Todo};
+    use crate::engine::SyncPhase;
+    use crate::network::{MtdNetMgr, ServerLock};
+
+    #[test]
+    fn config_advertised_addr_defaults_to_socket_addr() {
+        let mut conf = Config::new_default(Vec::new(), "0.0.0.0:55995".to_string(), None);
+        assert_eq!(conf.advertised_addr(), "0.0.0.0:55995");
+
+        conf.set_advertised_addr(Some("mtd.example.com:4000".to_string()));
+        assert_eq!(conf.advertised_addr(), "mtd.example.com:4000");
+        assert_eq!(conf.socket_addr(), "0.0.0.0:55995");
+    }
 
     #[test]
     fn mtd_net_mgr_returns_err_if_server_listener_ran_with_client_td_list() {
@@ -376,6 +1849,595 @@ mod network_tests {
         }
     }
 
+    #[test]
+    fn mtd_net_mgr_run_maintenance_removes_old_items_and_prunes_backups() {
+        let dir = env::temp_dir().join(Path::new("mtd-maintenance-test-dir"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let save_path = dir.join("data.json");
+        for timestamp in ["20220101-000000", "20220102-000000", "20220103-000000"] {
+            fs::write(dir.join(format!("data.json.{}.bak", timestamp)), "").unwrap();
+        }
+
+        let mut conf = Config::new_default(Vec::new(), "127.0.0.1:55999".to_string(), Some(save_path.clone()));
+        conf.set_max_backups(Some(1));
+
+        let mut server = TdList::new_server();
+        server.add_todo(Todo::new_undated("Old".to_string()).unwrap());
+        server.add_todo(Todo::new_undated("Current".to_string()).unwrap());
+        server.get_todo_mut(0).unwrap().set_done_wtd(true, NaiveDate::from_ymd(2022, 1, 1));
+
+        MtdNetMgr::new(&mut server, &conf).run_maintenance().unwrap();
+
+        assert_eq!(server.todos().len(), 1);
+        assert_eq!(server.todos()[0].body(), "Current");
+        assert_eq!(TdList::new_from_json(&fs::read_to_string(&save_path).unwrap()).unwrap().todos().len(), 1);
+
+        assert!(!dir.join("data.json.20220101-000000.bak").exists());
+        assert!(!dir.join("data.json.20220102-000000.bak").exists());
+        assert!(dir.join("data.json.20220103-000000.bak").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_refuses_to_start_if_save_file_is_locked() {
+        let save_path = env::temp_dir().join(Path::new("mtd-lock-test-file"));
+        let lock_path = env::temp_dir().join(Path::new("mtd-lock-test-file.lock"));
+        let conf = Config::new("127.0.0.1:55990".to_string(), Vec::new(), Duration::from_secs(30), Some(save_path.clone()), false);
+
+        // This process is running so the lock is held.
+        fs::write(&lock_path, process::id().to_string()).unwrap();
+        let err = MtdNetMgr::new(&mut TdList::new_server(), &conf).server_listening_loop().unwrap_err();
+        assert!(matches!(err, Error::ServerAlreadyRunning(pid, _) if pid == process::id()));
+        fs::remove_file(&lock_path).unwrap();
+
+        // A lock left by a server that isn't running anymore is taken over and removed when dropped.
+        // Stale locks can only be detected where processes can be checked.
+        if Path::new("/proc").is_dir() {
+            fs::write(&lock_path, u32::MAX.to_string()).unwrap();
+            let lock = ServerLock::acquire(&save_path).unwrap();
+            assert_eq!(fs::read_to_string(&lock_path).unwrap(), process::id().to_string());
+            drop(lock);
+            assert!(!lock_path.exists());
+        }
+    }
+
+    #[test]
+    fn server_lock_is_held_while_locked_or_without_a_process_id() {
+        let save_path = env::temp_dir().join(Path::new("mtd-lock-held-test-file"));
+        let lock_path = env::temp_dir().join(Path::new("mtd-lock-held-test-file.lock"));
+        let _ = fs::remove_file(&lock_path);
+
+        // A lock file that is being created may not have a process id yet.
+        fs::write(&lock_path, "").unwrap();
+        assert!(matches!(ServerLock::acquire(&save_path), Err(Error::ServerAlreadyRunning(_, _))));
+        fs::write(&lock_path, "not a pid").unwrap();
+        assert!(matches!(ServerLock::acquire(&save_path), Err(Error::ServerAlreadyRunning(_, _))));
+        fs::remove_file(&lock_path).unwrap();
+
+        // The advisory lock keeps the lock even if its process id is replaced with a stale one.
+        let lock = ServerLock::acquire(&save_path).unwrap();
+        fs::write(&lock_path, u32::MAX.to_string()).unwrap();
+        assert!(matches!(ServerLock::acquire(&save_path), Err(Error::ServerAlreadyRunning(_, _))));
+        drop(lock);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_answers_health_probes() {
+        let save_path = env::temp_dir().join("mtd-health-probe-test.json");
+        let lock_path = env::temp_dir().join("mtd-health-probe-test.json.lock");
+        let _ = fs::remove_file(&lock_path);
+        let mut conf = Config::new("127.0.0.1:55978".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), Some(save_path.clone()), false);
+        conf.set_health_addr(Some("127.0.0.1:55977".to_string()));
+        thread::spawn(move || {
+            MtdNetMgr::new(&mut TdList::new_server(), &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let probe = |path: &str| {
+            let mut stream = TcpStream::connect("127.0.0.1:55977").unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        assert!(probe("/healthz").starts_with("HTTP/1.1 200 OK"));
+        let ready = probe("/readyz");
+        assert!(ready.starts_with("HTTP/1.1 200 OK"));
+        assert!(ready.contains(r#""lock_held":true"#));
+
+        // Syncs and clients with a wrong password are counted in the metrics.
+        let mut client_list = TdList::new_client();
+        client_list.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        let client_conf = Config::new("127.0.0.1:55978".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        MtdNetMgr::new(&mut client_list, &client_conf).client_sync().unwrap();
+        let wrong_conf = Config::new("127.0.0.1:55978".to_string(), b"hunter43".to_vec(), Duration::from_secs(30), None, false);
+        assert!(MtdNetMgr::new(&mut TdList::new_client(), &wrong_conf).client_sync().is_err());
+        // Probes are answered with the status the server last shared, so it may take a moment.
+        thread::sleep(Duration::from_millis(200));
+        let metrics = probe("/metrics");
+        assert!(metrics.contains("Content-Type: text/plain"));
+        assert!(metrics.contains("\nmtd_syncs_total 1\n"), "{}", metrics);
+        assert!(metrics.contains("\nmtd_auth_failures_total 1\n"), "{}", metrics);
+        assert!(metrics.contains("\nmtd_items 1\n"), "{}", metrics);
+
+        // Someone else taking the lock makes the server unready.
+        fs::write(&lock_path, "1").unwrap();
+        let unready = probe("/readyz");
+        assert!(unready.starts_with("HTTP/1.1 503"), "{}", unready);
+        fs::remove_file(&lock_path).unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_saves_and_returns_when_stopped() {
+        let save_path = env::temp_dir().join("mtd-stop-flag-test.json");
+        let _ = fs::remove_file(&save_path);
+        let conf = Config::new("127.0.0.1:55976".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), Some(save_path.clone()), false);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let server = thread::spawn(move || {
+            let mut list = TdList::new_server();
+            let mut net_mgr = MtdNetMgr::new(&mut list, &conf);
+            net_mgr.set_stop_flag(server_stop);
+            net_mgr.server_listening_loop()
+        });
+
+        thread::sleep(Duration::from_millis(200));
+        stop.store(true, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+
+        assert!(TdList::new_from_json(&fs::read_to_string(&save_path).unwrap()).unwrap().is_server());
+        assert!(!env::temp_dir().join("mtd-stop-flag-test.json.lock").exists());
+        fs::remove_file(&save_path).unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_serves_clients_while_another_is_slow() {
+        let addr = "127.0.0.1:55975".to_string();
+        let server_conf = Config::new(addr.clone(), b"hunter42".to_vec(), Duration::from_secs(10), None, false);
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let server = thread::spawn(move || {
+            let mut list = TdList::new_server();
+            list.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            let mut net_mgr = MtdNetMgr::new(&mut list, &server_conf);
+            net_mgr.set_stop_flag(server_stop);
+            net_mgr.server_listening_loop()
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // A client that connects but never sends anything.
+        let mut slow = TcpStream::connect(&addr).unwrap();
+        slow.write_all(&[1, 0]).unwrap();
+
+        let conf = Config::new(addr, b"hunter42".to_vec(), Duration::from_secs(2), None, false);
+        let mut client = TdList::new_client();
+        MtdNetMgr::new(&mut client, &conf).client_sync().unwrap();
+        assert_eq!(client.todos().len(), 1);
+
+        // Stopping waits for the slow client until the server gives up on it.
+        stop.store(true, Ordering::SeqCst);
+        drop(slow);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_refuses_long_messages_before_the_handshake() {
+        let addr = "127.0.0.1:55973".to_string();
+        let server_conf = Config::new(addr.clone(), b"hunter42".to_vec(), Duration::from_secs(10), None, false);
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let server = thread::spawn(move || {
+            let mut list = TdList::new_server();
+            let mut net_mgr = MtdNetMgr::new(&mut list, &server_conf);
+            net_mgr.set_stop_flag(server_stop);
+            net_mgr.server_listening_loop()
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // The server closes the connection instead of waiting for a megabyte from an unknown client.
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(&(1024 * 1024u32).to_le_bytes()).unwrap();
+        match stream.read(&mut [0u8; 1]) {
+            Ok(read) => assert_eq!(read, 0),
+            Err(e) => assert!(!matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut), "{}", e),
+        }
+
+        // Authenticated clients can still send long messages.
+        let conf = Config::new(addr, b"hunter42".to_vec(), Duration::from_secs(10), None, false);
+        let mut client = TdList::new_client();
+        for i in 0..100 {
+            client.add_todo(Todo::new_undated(format!("{} {}", "x".repeat(100), i)).unwrap());
+        }
+        MtdNetMgr::new(&mut client, &conf).client_sync().unwrap();
+
+        stop.store(true, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_keeps_a_separate_list_for_each_user() {
+        let dir = std::env::temp_dir().join("mtd-server-users-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let addr = "127.0.0.1:55974".to_string();
+        let mut server_conf = Config::new(addr.clone(), Vec::new(), Duration::from_secs(10), Some(dir.join("server.json")), false);
+        server_conf.set_users(vec![
+            ServerUser { name: "alice".to_string(), password: "alice's".to_string() },
+            ServerUser { name: "bob".to_string(), password: "bob's".to_string() },
+        ]);
+        server_conf.validate().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let server = thread::spawn(move || {
+            let mut list = TdList::new_server();
+            let mut net_mgr = MtdNetMgr::new(&mut list, &server_conf);
+            net_mgr.set_stop_flag(server_stop);
+            net_mgr.server_listening_loop()?;
+            Ok::<_, Error>(net_mgr.user_list("alice").unwrap().todos().len())
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let client_conf = |user: Option<&str>, password: &str| {
+            let mut conf = Config::new(addr.clone(), password.as_bytes().to_vec(), Duration::from_secs(5), None, false);
+            conf.set_username(user.map(str::to_string));
+            conf
+        };
+
+        let mut alice = TdList::new_client();
+        alice.add_todo(Todo::new_undated("Alice's todo".to_string()).unwrap());
+        MtdNetMgr::new(&mut alice, &client_conf(Some("alice"), "alice's")).client_sync().unwrap();
+
+        let mut bob = TdList::new_client();
+        MtdNetMgr::new(&mut bob, &client_conf(Some("bob"), "bob's")).client_sync().unwrap();
+        assert!(bob.todos().is_empty());
+
+        assert!(MtdNetMgr::new(&mut TdList::new_client(), &client_conf(Some("bob"), "alice's")).check_connection().is_err());
+        assert!(MtdNetMgr::new(&mut TdList::new_client(), &client_conf(Some("carol"), "carol's")).check_connection().is_err());
+        // The server has no shared list without a shared password.
+        assert!(MtdNetMgr::new(&mut TdList::new_client(), &client_conf(None, "")).check_connection().is_err());
+
+        stop.store(true, Ordering::SeqCst);
+        assert_eq!(server.join().unwrap().unwrap(), 1);
+
+        let saved = TdList::new_from_json(&fs::read_to_string(dir.join("server.json.users").join("alice.json")).unwrap()).unwrap();
+        assert_eq!(saved.todos().len(), 1);
+        assert!(TdList::new_from_json(&fs::read_to_string(dir.join("server.json")).unwrap()).unwrap().todos().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_pairs_with_the_server_address_of_the_pairing_code() {
+        let dir = env::temp_dir().join(format!("mtd-pairing-address-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut server_conf = Config::new("0.0.0.0:55991".to_string(), b"SecurePw".to_vec(), Duration::from_secs(10), Some(dir.join("server.json")), false);
+        server_conf.set_advertised_addr(Some("127.0.0.1:55991".to_string()));
+        let mut registry = DeviceRegistry::default();
+        let code = registry.add_pairing("laptop", current_time()).unwrap();
+        registry.write(&server_conf.devices_location().unwrap()).unwrap();
+        let payload = pairing_payload(&code, server_conf.advertised_addr());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let server = thread::spawn(move || {
+            let mut list = TdList::new_server();
+            let mut net_mgr = MtdNetMgr::new(&mut list, &server_conf);
+            net_mgr.set_stop_flag(server_stop);
+            net_mgr.server_listening_loop()
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // The client was set up for another server.
+        let mut client_conf = Config::new("127.0.0.1:1".to_string(), Vec::new(), Duration::from_secs(5), None, false);
+        client_conf.set_srv_record(Some("_mtd._tcp.example.com".to_string()));
+        assert_eq!(client_conf.use_pairing_server(&payload), code);
+        assert_eq!((client_conf.socket_addr(), client_conf.srv_record()), ("127.0.0.1:55991", None));
+
+        let mut client = TdList::new_client();
+        let (name, token) = MtdNetMgr::new(&mut client, &client_conf).pair(&code).unwrap();
+        client_conf.set_device(Some(name));
+        client_conf.set_encryption_password(token.into_bytes());
+        client.add_todo(Todo::new_undated("Todo".to_string()).unwrap());
+        MtdNetMgr::new(&mut client, &client_conf).client_sync().unwrap();
+
+        stop.store(true, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mtd_net_mgr_run_maintenance_fails_with_client_td_list() {
+        let conf = Config::new_default(Vec::new(), "127.0.0.1:55999".to_string(), None);
+        assert!(matches!(MtdNetMgr::new(&mut TdList::new_client(), &conf).run_maintenance().unwrap_err(), Error::ServerOnlyOperation));
+    }
+
+    #[test]
+    fn mtd_net_mgr_check_connection_succeeds_only_with_correct_password() {
+        thread::spawn(|| {
+            let conf = Config::new("127.0.0.1:55998".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            MtdNetMgr::new(&mut TdList::new_server(), &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new("127.0.0.1:55998".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        MtdNetMgr::new(&mut TdList::new_client(), &conf).check_connection().unwrap();
+
+        let conf = Config::new("127.0.0.1:55998".to_string(), b"hunter2".to_vec(), Duration::from_secs(30), None, false);
+        assert!(MtdNetMgr::new(&mut TdList::new_client(), &conf).check_connection().is_err());
+    }
+
+    #[test]
+    fn mtd_net_mgr_check_connection_measures_clock_skew() {
+        thread::spawn(|| {
+            let conf = Config::new("127.0.0.1:55985".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            MtdNetMgr::new(&mut TdList::new_server(), &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new("127.0.0.1:55985".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        let client_mgr = MtdNetMgr::new(&mut client, &conf);
+        assert!(client_mgr.clock_skew().is_none());
+        client_mgr.check_connection().unwrap();
+        // Both ends use the same clock.
+        assert!(client_mgr.clock_skew().unwrap().num_seconds().abs() < 5);
+    }
+
+    #[test]
+    fn mtd_net_mgr_fetch_remote_returns_server_list_without_syncing() {
+        thread::spawn(|| {
+            let conf = Config::new("127.0.0.1:55994".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            let mut server = TdList::new_server();
+            server.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            MtdNetMgr::new(&mut server, &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new("127.0.0.1:55994".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        client.add_todo(Todo::new_undated("Client todo".to_string()).unwrap());
+
+        let remote = MtdNetMgr::new(&mut client, &conf).fetch_remote().unwrap();
+        assert_eq!(remote.todos().len(), 1);
+        assert_eq!(remote.todos()[0].body(), "Server todo");
+
+        // Neither list was synchronized.
+        assert_eq!(client.todos().len(), 1);
+        assert_eq!(MtdNetMgr::new(&mut client, &conf).fetch_remote().unwrap().todos().len(), 1);
+    }
+
+    #[test]
+    fn mtd_net_mgr_syncs_over_memory_transport() {
+        let transport = MemoryTransport::new();
+        let server_transport = transport.clone();
+        thread::spawn(move || {
+            let conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            let mut server = TdList::new_server();
+            server.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            let mut net_mgr = MtdNetMgr::new(&mut server, &conf);
+            net_mgr.set_transport(Box::new(server_transport));
+            net_mgr.server_listening_loop().unwrap();
+        });
+
+        let conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        client.add_todo(Todo::new_undated("Client todo".to_string()).unwrap());
+
+        let mut net_mgr = MtdNetMgr::new(&mut client, &conf);
+        net_mgr.set_transport(Box::new(transport));
+        net_mgr.client_sync().unwrap();
+
+        assert_eq!(client.todos().len(), 2);
+    }
+
+    #[test]
+    fn mtd_net_mgr_reports_the_phases_of_a_sync() {
+        let transport = MemoryTransport::new();
+        let server_transport = transport.clone();
+        thread::spawn(move || {
+            let conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            let mut server = TdList::new_server();
+            server.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            let mut net_mgr = MtdNetMgr::new(&mut server, &conf);
+            net_mgr.set_transport(Box::new(server_transport));
+            net_mgr.server_listening_loop().unwrap();
+        });
+
+        let conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+
+        let phases = Rc::new(RefCell::new(Vec::new()));
+        let reported = phases.clone();
+        let mut net_mgr = MtdNetMgr::new(&mut client, &conf);
+        net_mgr.set_transport(Box::new(transport));
+        net_mgr.set_progress_callback(move |progress| reported.borrow_mut().push(progress.phase));
+        net_mgr.client_sync().unwrap();
+
+        let mut phases = phases.take();
+        phases.dedup();
+        assert_eq!(phases, vec![SyncPhase::Connecting, SyncPhase::Handshake, SyncPhase::Receiving, SyncPhase::Merging, SyncPhase::Sending, SyncPhase::Done]);
+    }
+
+    #[test]
+    fn mtd_net_mgr_server_rejects_syncs_exceeding_limits() {
+        let transport = MemoryTransport::new();
+        let server_transport = transport.clone();
+        thread::spawn(move || {
+            let mut conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            conf.set_max_items(Some(2));
+            let mut server = TdList::new_server();
+            let mut net_mgr = MtdNetMgr::new(&mut server, &conf);
+            net_mgr.set_transport(Box::new(server_transport));
+            net_mgr.server_listening_loop().unwrap();
+        });
+
+        let conf = Config::new("memory".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        for i in 0..3 {
+            client.add_todo(Todo::new_undated(format!("Todo {}", i)).unwrap());
+        }
+
+        let mut net_mgr = MtdNetMgr::new(&mut client, &conf);
+        net_mgr.set_transport(Box::new(transport));
+        assert!(matches!(net_mgr.client_sync(), Err(Error::LimitsExceeded(_))));
+        assert_eq!(net_mgr.fetch_remote().unwrap().todos().len(), 0);
+
+        net_mgr.td_list_mut().remove_todo(0).unwrap();
+        net_mgr.client_sync().unwrap();
+        assert_eq!(net_mgr.fetch_remote().unwrap().todos().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mtd_net_mgr_syncs_over_unix_socket() {
+        let socket = env::temp_dir().join(format!("mtd-test-{}.sock", process::id()));
+        let addr = format!("unix:{}", socket.display());
+
+        let server_addr = addr.clone();
+        thread::spawn(move || {
+            let conf = Config::new(server_addr, b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            let mut server = TdList::new_server();
+            server.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            MtdNetMgr::new(&mut server, &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new(addr, b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        let mut net_mgr = MtdNetMgr::new(&mut client, &conf);
+        net_mgr.set_keep_alive(true);
+        net_mgr.client_sync().unwrap();
+        net_mgr.send_keepalive().unwrap();
+        net_mgr.client_sync().unwrap();
+        assert!(net_mgr.is_connected());
+        assert_eq!(net_mgr.td_list().todos().len(), 1);
+
+        let _ = fs::remove_file(socket);
+    }
+
+    #[test]
+    fn mtd_net_mgr_keeps_connection_open_between_syncs() {
+        thread::spawn(|| {
+            let conf = Config::new("127.0.0.1:55986".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+            let mut server = TdList::new_server();
+            MtdNetMgr::new(&mut server, &conf).server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new("127.0.0.1:55986".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
+        let mut client = TdList::new_client();
+        client.add_todo(Todo::new_undated("First todo".to_string()).unwrap());
+
+        let mut net_mgr = MtdNetMgr::new(&mut client, &conf);
+        net_mgr.set_keep_alive(true);
+        net_mgr.client_sync().unwrap();
+        assert!(net_mgr.is_connected());
+        net_mgr.send_keepalive().unwrap();
+        assert!(net_mgr.is_connected());
+
+        // A kept connection doesn't keep other clients from syncing.
+        let mut other = TdList::new_client();
+        other.add_todo(Todo::new_undated("Other todo".to_string()).unwrap());
+        MtdNetMgr::new(&mut other, &conf).client_sync().unwrap();
+
+        net_mgr.client_sync().unwrap();
+        assert!(net_mgr.is_connected());
+        assert_eq!(net_mgr.td_list().todos().len(), 2);
+
+        net_mgr.set_keep_alive(false);
+        assert!(!net_mgr.is_connected());
+    }
+
+    /// Syncs a client list with a server several times with faults in the messages sent by either
+    /// the client or the server until a sync succeeds. Returns the client list and the number of
+    /// failed syncs.
+    #[cfg(feature = "fault-injection")]
+    fn sync_with_faults(port: u16, client_faults: bool) -> (TdList, usize) {
+        use crate::FaultInjector;
+
+        let addr = format!("127.0.0.1:{}", port);
+        let timeout = Duration::from_secs(1);
+
+        let server_addr = addr.clone();
+        thread::spawn(move || {
+            let conf = Config::new(server_addr, b"hunter42".to_vec(), timeout, None, false);
+            let mut server = TdList::new_server();
+            server.add_todo(Todo::new_undated("Server todo".to_string()).unwrap());
+            let mut server_mgr = MtdNetMgr::new(&mut server, &conf);
+            if !client_faults {
+                server_mgr.set_fault_injector(FaultInjector::new(port as u64, 0.5));
+            }
+            server_mgr.server_listening_loop().unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(500));
+
+        let conf = Config::new(addr, b"hunter42".to_vec(), timeout, None, false);
+        let mut client = TdList::new_client();
+        client.add_todo(Todo::new_undated("Client todo".to_string()).unwrap());
+
+        let mut failures = 0;
+        for seed in 0..30 {
+            let before = client.to_json().unwrap();
+
+            let mut client_mgr = MtdNetMgr::new(&mut client, &conf);
+            if client_faults {
+                client_mgr.set_fault_injector(FaultInjector::new(seed, 0.3));
+            }
+
+            if client_mgr.client_sync().is_err() {
+                failures += 1;
+                assert_eq!(client.to_json().unwrap(), before);
+            } else {
+                assert!(!client.has_unsynced_changes());
+                if seed >= 4 {
+                    break;
+                }
+            }
+        }
+
+        (client, failures)
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn mtd_net_mgr_client_faults_dont_corrupt_client_list() {
+        let (client, failures) = sync_with_faults(55993, true);
+        assert!(failures > 0);
+
+        assert_eq!(client.todos().len(), 2);
+        assert!(client.todos().contains(&&Todo::new_undated("Server todo".to_string()).unwrap()));
+        assert!(client.todos().contains(&&Todo::new_undated("Client todo".to_string()).unwrap()));
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn mtd_net_mgr_server_faults_dont_corrupt_client_list() {
+        // The server may save changes the client never hears of. Those are reconciled on the next
+        // successful sync without duplicates.
+        let (client, failures) = sync_with_faults(55992, false);
+        assert!(failures > 0);
+
+        assert_eq!(client.todos().len(), 2);
+        assert!(client.todos().contains(&&Todo::new_undated("Server todo".to_string()).unwrap()));
+        assert!(client.todos().contains(&&Todo::new_undated("Client todo".to_string()).unwrap()));
+    }
+
     // This test tests more than one thing, but I believe it to be rather useful. Running more than
     // one test takes more time and this test (and its sub-parts) also depends on external state (IO).
     #[test]
@@ -383,15 +2445,15 @@ mod network_tests {
         let mut client = TdList::new_client();
         let mut server = TdList::new_server();
 
-        server.add_todo(Todo::new_undated("Todo 1".to_string()));
+        server.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
 
         // Sync once to set "Todo 1" for both client and server.
         server.sync(&mut client);
 
-        server.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string());
-        server.add_todo(Todo::new_undated("Todo 2".to_string()));
+        server.get_todo_mut(0).unwrap().set_body("New Todo 1".to_string()).unwrap();
+        server.add_todo(Todo::new_undated("Todo 2".to_string()).unwrap());
 
-        client.add_todo(Todo::new_undated("Todo 3".to_string()));
+        client.add_todo(Todo::new_undated("Todo 3".to_string()).unwrap());
 
         let client_conf = Config::new("127.0.0.1:55997".to_string(), b"hunter42".to_vec(), Duration::from_secs(30), None, false);
         let mut client_mgr = MtdNetMgr::new(&mut client, &client_conf);
@@ -408,39 +2470,50 @@ mod network_tests {
         client_mgr.client_sync().unwrap();
 
         assert_eq!(client.todos().len(), 3);
-        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
-        assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
-        assert!(client.todos().contains(&&Todo::new_undated("Todo 3".to_string())));
+        assert!(client.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
+        assert!(client.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
+        assert!(client.todos().contains(&&Todo::new_undated("Todo 3".to_string()).unwrap()));
 
         let server_path = env::temp_dir().join(Path::new("mtd-server-write-test-file"));
         let server = TdList::new_from_json(&fs::read_to_string(server_path).unwrap()).unwrap();
 
         assert_eq!(server.todos().len(), 3);
-        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string())));
-        assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string())));
-        assert!(server.todos().contains(&&Todo::new_undated("Todo 3".to_string())));
+        assert!(server.todos().contains(&&Todo::new_undated("New Todo 1".to_string()).unwrap()));
+        assert!(server.todos().contains(&&Todo::new_undated("Todo 2".to_string()).unwrap()));
+        assert!(server.todos().contains(&&Todo::new_undated("Todo 3".to_string()).unwrap()));
     }
 }
 
 /// Module containing functionality for encrypting/decrypting messages used for secure network
-/// communication. Data is encrypted with AES-GCM. The encryption key is generated from a password
-/// using Argon2. For network communications, session ids should be used in addition to encrypting
-/// data.
-mod crypt {
+/// communication. Data is encrypted with AES-GCM. The encryption key is derived from a password
+/// with Argon2id and a random salt stored in front of each ciphertext. For network communications,
+/// session ids should be used in addition to encrypting data.
+pub(crate) mod crypt {
     use aes_gcm::{Aes256Gcm, Nonce};
     use aes_gcm::aead::{Aead, KeyInit};
-    use argon2::Argon2;
+    use argon2::{Algorithm, Argon2, Params, Version};
     use rand::random;
 
     use crate::network::Error;
 
+    /// Memory cost of the key derivation in KiB, time cost and parallelism. These are fixed instead
+    /// of using the defaults of the argon2 crate, which change between its versions, because both
+    /// ends of a sync and old data files must derive the same key.
+    const KDF_COST: (u32, u32, u32) = (4096, 3, 1);
+
+    /// Derives a 256-bit key from a password and a salt with Argon2id.
+    fn derive_key(passwd: &[u8], salt: &[u8]) -> Option<[u8; 32]> {
+        let (m_cost, t_cost, p_cost) = KDF_COST;
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32)).ok()?;
+        let mut key = [0; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(passwd, salt, &mut key).ok()?;
+        Some(key)
+    }
+
     /// Encrypts a given byte array with the given password.
     pub fn encrypt(msg: &[u8], passwd: &[u8]) -> Result<Vec<u8>, Error> {
         let key_salt: [u8; 16] = random();
-        let argon2 = Argon2::default();
-
-        let mut secret_passwd_hash: [u8; 32] = [0; 32];
-        argon2.hash_password_into(passwd, &key_salt, &mut secret_passwd_hash).map_err(|_| Error::EncryptingFailed)?;
+        let secret_passwd_hash = derive_key(passwd, &key_salt).ok_or(Error::EncryptingFailed)?;
 
         let cipher = Aes256Gcm::new_from_slice(&secret_passwd_hash).map_err(|_| Error::EncryptingFailed)?;
 
@@ -461,23 +2534,45 @@ mod crypt {
 
     /// Decrypts a given ciphertext with the given password.
     pub fn decrypt(ciphertext: &[u8], passwd: &[u8]) -> Result<Vec<u8>, Error> {
+        // Salt and nonce.
+        if ciphertext.len() < 28 {
+            return Err(Error::DecryptingFailed);
+        }
         let key_salt = &ciphertext[0..16];
-        let argon2 = Argon2::default();
-
-        let mut secret_passwd_hash: [u8; 32] = [0; 32];
-        argon2.hash_password_into(passwd, key_salt, &mut secret_passwd_hash).map_err(|_| Error::DecryptingFailed)?;
+        let secret_passwd_hash = derive_key(passwd, key_salt).ok_or(Error::DecryptingFailed)?;
 
         let cipher = Aes256Gcm::new_from_slice(&secret_passwd_hash).map_err(|_| Error::DecryptingFailed)?;
 
         let nonce_bits = &ciphertext[16..28];
         let nonce = Nonce::from_slice(nonce_bits);
 
-        Ok(cipher.decrypt(nonce, &ciphertext[28..]).map_err(|_| Error::DecryptingFailed)?)
+        cipher.decrypt(nonce, &ciphertext[28..]).map_err(|_| Error::DecryptingFailed)
+    }
+
+    /// Encrypts a given byte array with a 256-bit key instead of a password.
+    pub fn encrypt_with_key(msg: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| Error::EncryptingFailed)?;
+
+        let nonce_bits: [u8; 12] = random();
+        let mut result = nonce_bits.to_vec();
+        result.append(&mut cipher.encrypt(Nonce::from_slice(&nonce_bits), msg).map_err(|_| Error::EncryptingFailed)?);
+
+        Ok(result)
+    }
+
+    /// Decrypts a ciphertext encrypted with `encrypt_with_key`.
+    pub fn decrypt_with_key(ciphertext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        if ciphertext.len() < 12 {
+            return Err(Error::DecryptingFailed);
+        }
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| Error::DecryptingFailed)?;
+
+        cipher.decrypt(Nonce::from_slice(&ciphertext[..12]), &ciphertext[12..]).map_err(|_| Error::DecryptingFailed)
     }
 
     #[cfg(test)]
     mod tests {
-        use crate::network::crypt::{decrypt, encrypt};
+        use crate::network::crypt::{decrypt, derive_key, encrypt};
 
         #[test]
         fn decrypting_encrypted_returns_original() {
@@ -525,5 +2620,15 @@ mod crypt {
 
             assert!(decrypt(&ct, ps).is_err());
         }
+
+        #[test]
+        fn derived_key_is_stable() {
+            // Frames and data files encrypted by earlier versions must still decrypt.
+            let key = [
+                160, 163, 202, 241, 24, 72, 34, 230, 62, 37, 200, 125, 208, 207, 200, 235,
+                135, 7, 53, 88, 254, 13, 95, 160, 100, 205, 245, 154, 50, 110, 234, 35,
+            ];
+            assert_eq!(derive_key(b"Very secure passwd", b"0123456789abcdef").unwrap(), key);
+        }
     }
 }
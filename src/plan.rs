@@ -0,0 +1,185 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module defining day plans, which are `Todo`s suggested for every occurrence of a weekday, such
+//! as meal prep on Sundays. Day plans are stored in the `TdList` and synchronized like other items,
+//! but they are only suggestions: a `Todo` is added from a plan only when asked to.
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::{NaiveDate, Weekday};
+//! use mtd::{DayPlan, TdList};
+//!
+//! let mut list = TdList::new_client();
+//! list.add_plan(DayPlan::new("Meal prep".to_string(), Weekday::Sun).unwrap());
+//!
+//! // 2024-8-18 is a Sunday.
+//! let suggestions = list.suggestions_for_date(NaiveDate::from_ymd(2024, 8, 18));
+//!
+//! assert_eq!(suggestions[0].body(), "Meal prep");
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+use chrono::Weekday;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{ItemState, Result, SyncItem, validate_body};
+
+/// A `Todo` suggested for every occurrence of a weekday.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayPlan {
+    body: String,
+    weekday: Weekday,
+    id: u64,
+    sync_id: u64,
+    state: ItemState,
+    uuid: Uuid,
+}
+
+impl DayPlan {
+    /// Creates a new plan suggesting a `Todo` with the given body on the weekday. Returns an
+    /// `Error::EmptyBody` or `Error::BodyTooLong` if the body isn't valid.
+    pub fn new(body: String, weekday: Weekday) -> Result<DayPlan> {
+        validate_body(&body)?;
+        Ok(DayPlan {
+            body,
+            weekday,
+            id: 0,
+            sync_id: random(),
+            state: ItemState::Unchanged,
+            uuid: Uuid::new_v4(),
+        })
+    }
+
+    /// Gets the body of the suggested `Todo`.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Gets the weekday the `Todo` is suggested for.
+    pub fn weekday(&self) -> Weekday {
+        self.weekday
+    }
+
+    /// Gets the `id` of the plan.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Gets the `uuid` of the plan.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+impl Display for DayPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}. {}", self.id, self.body)
+    }
+}
+
+impl PartialEq for DayPlan {
+    fn eq(&self, other: &Self) -> bool {
+        self.body == other.body && self.weekday == other.weekday
+    }
+}
+
+impl SyncItem for DayPlan {
+    fn set_state(&mut self, state: ItemState) {
+        self.state = state;
+    }
+
+    fn state(&self) -> ItemState {
+        self.state
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    fn sync_id(&self) -> u64 {
+        self.sync_id
+    }
+
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn body(&self) -> &str {
+        &self.body
+    }
+
+    fn update_old(&self, old: &mut Self) {
+        old.body = self.body.clone();
+        old.weekday = self.weekday;
+        old.uuid = self.uuid;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+
+    use crate::{DayPlan, TdList, Todo};
+
+    #[test]
+    fn suggestions_skip_plans_already_added_for_the_date() {
+        let mut list = TdList::new_client();
+        list.add_plan(DayPlan::new("Meal prep".to_string(), Weekday::Sun).unwrap());
+        list.add_plan(DayPlan::new("Call mom".to_string(), Weekday::Sun).unwrap());
+        list.add_plan(DayPlan::new("Gym".to_string(), Weekday::Mon).unwrap());
+
+        // 2024-8-18 is a Sunday.
+        let sunday = NaiveDate::from_ymd(2024, 8, 18);
+        list.add_todo(Todo::new_specific_date("meal prep".to_string(), sunday).unwrap());
+
+        let bodies: Vec<&str> = list.suggestions_for_date(sunday).iter().map(|plan| plan.body()).collect();
+        assert_eq!(bodies, vec!["Call mom"]);
+    }
+
+    #[test]
+    fn plans_are_synced() {
+        let mut client = TdList::new_client();
+        let mut server = TdList::new_server();
+        client.add_plan(DayPlan::new("Meal prep".to_string(), Weekday::Sun).unwrap());
+
+        client.sync(&mut server);
+        assert_eq!(server.plans().len(), 1);
+
+        client.remove_plan(0).unwrap();
+        client.sync(&mut server);
+        assert!(server.plans().is_empty());
+    }
+
+    #[test]
+    fn lists_without_plans_can_be_loaded_and_synced() {
+        let mut client = TdList::new_client();
+        // Lists received over the network aren't created with `TdList::new_from_json`.
+        let mut server: TdList = serde_json::from_str(r#"{"todos":{"items":[],"server":true},"tasks":{"items":[],"server":true},"server":true}"#).unwrap();
+        client.add_plan(DayPlan::new("Meal prep".to_string(), Weekday::Sun).unwrap());
+
+        client.sync(&mut server);
+        assert_eq!(server.plans().len(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module defining rules that change new `Todo`s based on their body. Rules are set in the
+//! `Config` and applied with `Config::apply_rules` when a `Todo` is added, imported, received,
+//! copied or converted from a `Task`, so every way of getting a new `Todo` behaves the same. A
+//! `Todo` that already has a priority keeps it.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{AutoRule, Todo};
+//!
+//! let rule = AutoRule { pattern: "buy".to_string(), priority: Some(2) };
+//! let mut todo = Todo::new_undated("Buy milk".to_string()).unwrap();
+//!
+//! assert!(rule.apply(&mut todo));
+//! assert_eq!(todo.priority(), 2);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Todo};
+
+/// A rule that changes the new `Todo`s whose body contains `pattern`. Case is ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoRule {
+    /// Text the body of a `Todo` must contain for the rule to apply.
+    pub pattern: String,
+    /// Priority given to matching `Todo`s. `None` leaves the priority unchanged.
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+impl AutoRule {
+    /// Returns `true` if the rule applies to a `Todo` with the given body.
+    pub fn matches(&self, body: &str) -> bool {
+        body.to_lowercase().contains(&self.pattern.to_lowercase())
+    }
+
+    /// Changes the `Todo` if the rule applies to it. Returns `true` if the rule applied.
+    pub fn apply(&self, todo: &mut Todo) -> bool {
+        if !self.matches(todo.body()) {
+            return false;
+        }
+        if let Some(priority) = self.priority {
+            todo.set_priority(priority);
+        }
+        true
+    }
+
+    /// Returns an `Error::InvalidArgument` if the rule would match every `Todo` or change nothing.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.pattern.trim().is_empty() {
+            return Err(Error::InvalidArgument("The pattern of a rule cannot be empty.".to_string()));
+        }
+        if self.priority.is_none() {
+            return Err(Error::InvalidArgument(format!("The rule for \"{}\" doesn't change anything.", self.pattern)));
+        }
+        Ok(())
+    }
+}
+
+/// Applies every rule that matches the body of the `Todo` to it in order, unless the `Todo` already
+/// has a priority.
+pub(crate) fn apply_rules(rules: &[AutoRule], todo: &mut Todo) {
+    if todo.priority() > 0 {
+        return;
+    }
+    for rule in rules {
+        rule.apply(todo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AutoRule, Todo};
+
+    #[test]
+    fn auto_rule_matches_ignoring_case() {
+        let rule = AutoRule { pattern: "Dentist".to_string(), priority: Some(1) };
+
+        let mut todo = Todo::new_undated("Call the dentist".to_string()).unwrap();
+        assert!(rule.apply(&mut todo));
+        assert_eq!(todo.priority(), 1);
+
+        let mut todo = Todo::new_undated("Call mom".to_string()).unwrap();
+        assert!(!rule.apply(&mut todo));
+        assert_eq!(todo.priority(), 0);
+
+        assert!(AutoRule { pattern: " ".to_string(), priority: Some(1) }.validate().is_err());
+        assert!(AutoRule { pattern: "a".to_string(), priority: None }.validate().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for sharing single items with other mtd users without sharing lists or servers. A
+//! `SharedItem` is sealed into an encrypted text blob with a passphrase. The blob can be sent over
+//! any channel and opened by anyone who knows the passphrase.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{SharedItem, TdList, Todo};
+//!
+//! let todo = Todo::new_undated("Review the report".to_string()).unwrap();
+//! let blob = SharedItem::from_todo(&todo).seal(b"correct horse").unwrap();
+//!
+//! let mut list = TdList::new_client();
+//! let received = SharedItem::open(&blob, b"correct horse").unwrap();
+//! let id = list.add_shared(&received).unwrap();
+//!
+//! assert_eq!(list.get_todo(id).unwrap().body(), "Review the report");
+//! ```
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Task, Todo};
+use crate::network::crypt;
+use crate::proxy::{base64, base64_decode};
+
+/// The prefix of sealed items. It tells blobs apart from other text and allows changing the format
+/// later.
+const BLOB_PREFIX: &str = "mtd1:";
+
+/// A single `Todo` or `Task` shared with someone else. Only the parts that make sense to the
+/// receiver are shared, so for example done state, ids and sync state are left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SharedItem {
+    /// A shared `Todo`.
+    Todo {
+        /// The body of the todo.
+        body: String,
+        /// The date of the todo.
+        date: NaiveDate,
+        /// The time of day of the todo.
+        time: Option<NaiveTime>,
+        /// The notes of the todo.
+        notes: Option<String>,
+    },
+    /// A shared `Task`.
+    Task {
+        /// The body of the task.
+        body: String,
+        /// The weekdays of the task.
+        weekdays: Vec<Weekday>,
+        /// The time of day of the task.
+        time: Option<NaiveTime>,
+        /// The times of day of the task on specific weekdays. Older blobs don't have them.
+        #[serde(default)]
+        weekday_times: HashMap<Weekday, NaiveTime>,
+        /// The notes of the task.
+        notes: Option<String>,
+    },
+}
+
+impl SharedItem {
+    /// Creates a `SharedItem` from a `Todo`.
+    pub fn from_todo(todo: &Todo) -> SharedItem {
+        SharedItem::Todo {
+            body: todo.body().to_string(),
+            date: todo.date(),
+            time: todo.time(),
+            notes: todo.notes().map(str::to_string),
+        }
+    }
+
+    /// Creates a `SharedItem` from a `Task`.
+    pub fn from_task(task: &Task) -> SharedItem {
+        SharedItem::Task {
+            body: task.body().to_string(),
+            weekdays: task.weekdays().clone(),
+            time: task.time(),
+            weekday_times: task.weekday_times().clone(),
+            notes: task.notes().map(str::to_string),
+        }
+    }
+
+    /// Returns the body of the shared item.
+    pub fn body(&self) -> &str {
+        match self {
+            SharedItem::Todo { body, .. } | SharedItem::Task { body, .. } => body,
+        }
+    }
+
+    /// Encrypts the `SharedItem` with the passphrase into a single line of text.
+    pub fn seal(&self, passphrase: &[u8]) -> Result<String> {
+        let encrypted = crypt::encrypt(serde_json::to_string(self)?.as_bytes(), passphrase)?;
+        Ok(format!("{}{}", BLOB_PREFIX, base64(&encrypted)))
+    }
+
+    /// Decrypts a `SharedItem` sealed with `SharedItem::seal`. Whitespace around the blob is
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the text isn't a sealed item and
+    /// `Error::DecryptingFailed` if the passphrase is incorrect.
+    pub fn open(blob: &str, passphrase: &[u8]) -> Result<SharedItem> {
+        let invalid = || Error::InvalidArgument("The text isn't a shared mtd item.".to_string());
+
+        let encoded = blob.trim().strip_prefix(BLOB_PREFIX).ok_or_else(invalid)?;
+        let encrypted = base64_decode(encoded).ok_or_else(invalid)?;
+        let json = crypt::decrypt(&encrypted, passphrase)?;
+
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime, Weekday};
+
+    use crate::{Error, SharedItem, Task, TdList, Todo};
+
+    #[test]
+    fn sealed_todo_opens_with_the_same_passphrase() {
+        let mut todo = Todo::new_specific_date("Todo".to_string(), NaiveDate::from_ymd(2024, 8, 15)).unwrap();
+        todo.set_time(Some(NaiveTime::from_hms(9, 30, 0)));
+        todo.set_notes(Some("Bring the slides".to_string()));
+        todo.set_done(true);
+        let shared = SharedItem::from_todo(&todo);
+
+        let blob = shared.seal(b"passphrase").unwrap();
+        assert!(blob.starts_with("mtd1:"));
+        assert!(!blob.contains("Todo"));
+        assert_eq!(SharedItem::open(&format!(" {}\n", blob), b"passphrase").unwrap(), shared);
+    }
+
+    #[test]
+    fn blobs_without_weekday_times_still_open() {
+        let json = r#"{"type":"task","body":"Task","weekdays":["Mon"],"time":null,"notes":null}"#;
+        let item: SharedItem = serde_json::from_str(json).unwrap();
+
+        assert!(matches!(item, SharedItem::Task { weekday_times, .. } if weekday_times.is_empty()));
+    }
+
+    #[test]
+    fn opening_fails_with_wrong_passphrase_or_invalid_text() {
+        let task = Task::new("Task".to_string(), vec![Weekday::Mon]).unwrap();
+        let blob = SharedItem::from_task(&task).seal(b"passphrase").unwrap();
+
+        assert!(matches!(SharedItem::open(&blob, b"wrong"), Err(Error::DecryptingFailed)));
+        assert!(matches!(SharedItem::open("mtd1:AAAA", b"passphrase"), Err(Error::DecryptingFailed)));
+        assert!(matches!(SharedItem::open("mtd1:not base64!", b"passphrase"), Err(Error::InvalidArgument(_))));
+        assert!(matches!(SharedItem::open("something else", b"passphrase"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn received_items_are_new_undone_items() {
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Fri]).unwrap();
+        task.set_notes(Some("Notes".to_string()));
+        task.set_time(Some(NaiveTime::from_hms(18, 0, 0)));
+        task.set_weekday_time(Weekday::Fri, Some(NaiveTime::from_hms(16, 0, 0)));
+        task.set_done(true, NaiveDate::from_ymd(2024, 8, 12));
+
+        let mut list = TdList::new_client();
+        list.add_task(Task::new("Existing".to_string(), vec![Weekday::Sun]).unwrap());
+        let id = list.add_shared(&SharedItem::from_task(&task)).unwrap();
+
+        let received = list.get_task(id).unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(received.body(), "Task");
+        assert_eq!(received.weekdays(), &vec![Weekday::Mon, Weekday::Fri]);
+        assert_eq!(received.notes(), Some("Notes"));
+        assert_eq!(received.time_for(Weekday::Mon), Some(NaiveTime::from_hms(18, 0, 0)));
+        assert_eq!(received.time_for(Weekday::Fri), Some(NaiveTime::from_hms(16, 0, 0)));
+        assert!(!received.done(NaiveDate::from_ymd(2024, 8, 12)));
+        assert_ne!(received.uuid(), task.uuid());
+    }
+}
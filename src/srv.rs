@@ -0,0 +1,321 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for looking up DNS SRV records, which tell clients the host and the port of a server.
+//! Queries are sent to the name servers of the system, which are read from `/etc/resolv.conf` on
+//! Unix and from the network adapters on Windows. Answers too long for UDP are asked again over TCP.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+use dns_parser::{Builder, Packet, QueryClass, QueryType, RData, ResponseCode};
+use rand::{random, Rng};
+
+use crate::{Error, Result};
+
+/// A target of a SRV record.
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Record {
+    priority: u16,
+    weight: u16,
+    target: String,
+    port: u16,
+}
+
+/// Looks up the SRV record with the given name, such as `_mtd._tcp.example.com`. Returns the hosts
+/// and ports of the targets in the order they should be tried.
+pub(crate) fn resolve(name: &str, timeout: Duration) -> Result<Vec<(String, u16)>> {
+    let id: u16 = random();
+    let query = query(name, id)?;
+
+    let mut last_err = Error::SrvLookupFailed("No name server configured.".to_string());
+    for name_server in name_servers()? {
+        let addr = SocketAddr::new(name_server, 53);
+        match ask(addr, &query, id, timeout) {
+            Ok(packet) => {
+                let mut rng = rand::thread_rng();
+                return order(parse_response(&packet, id)?, |total| rng.gen_range(0..=total));
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+/// Returns the name servers configured in `/etc/resolv.conf`.
+#[cfg(unix)]
+fn name_servers() -> Result<Vec<IpAddr>> {
+    let resolv_conf = std::fs::read_to_string("/etc/resolv.conf")
+        .map_err(|_| Error::SrvLookupFailed("No name server configured.".to_string()))?;
+    Ok(resolv_conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|addr| addr.trim().parse().ok())
+        .collect())
+}
+
+/// Returns the name servers of the network adapters that are up.
+#[cfg(windows)]
+fn name_servers() -> Result<Vec<IpAddr>> {
+    let adapters = ipconfig::get_adapters()
+        .map_err(|e| Error::SrvLookupFailed(format!("Couldn't read the network adapters: {}", e)))?;
+    let mut name_servers: Vec<IpAddr> = Vec::new();
+    for adapter in adapters.iter().filter(|adapter| adapter.oper_status() == ipconfig::OperStatus::IfOperStatusUp) {
+        for name_server in adapter.dns_servers() {
+            if !name_servers.contains(name_server) {
+                name_servers.push(*name_server);
+            }
+        }
+    }
+    Ok(name_servers)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn name_servers() -> Result<Vec<IpAddr>> {
+    Err(Error::SrvLookupFailed("No name server configured.".to_string()))
+}
+
+/// Sends the query to the name server over UDP and returns the response. If the response was
+/// truncated, the query is sent again over TCP.
+fn ask(addr: SocketAddr, query: &[u8], id: u16, timeout: Duration) -> Result<Vec<u8>> {
+    let socket = UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" })?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(addr)?;
+    socket.send(query)?;
+
+    let mut buf = [0u8; 4096];
+    let len = socket.recv(&mut buf)?;
+    let packet = Packet::parse(&buf[..len]).map_err(|e| Error::SrvLookupFailed(e.to_string()))?;
+    if packet.header.id == id && packet.header.truncated {
+        ask_tcp(addr, query, timeout)
+    } else {
+        Ok(buf[..len].to_vec())
+    }
+}
+
+/// Sends the query to the name server over TCP and returns the response.
+fn ask_tcp(addr: SocketAddr, query: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    // Messages over TCP are prefixed with their length.
+    let mut message = (query.len() as u16).to_be_bytes().to_vec();
+    message.extend_from_slice(query);
+    stream.write_all(&message)?;
+
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response)?;
+    Ok(response)
+}
+
+/// Builds a query for the SRV record with the given name.
+fn query(name: &str, id: u16) -> Result<Vec<u8>> {
+    let mut builder = Builder::new_query(id, true);
+    builder.add_question(name, false, QueryType::SRV, QueryClass::IN);
+    builder.build().map_err(|_| Error::SrvLookupFailed(format!("Invalid name \"{}\".", name)))
+}
+
+/// Parses the records of a response to a SRV query.
+fn parse_response(packet: &[u8], id: u16) -> Result<Vec<Record>> {
+    let packet = Packet::parse(packet).map_err(|e| Error::SrvLookupFailed(e.to_string()))?;
+    if packet.header.id != id {
+        return Err(Error::SrvLookupFailed("Response doesn't match the query.".to_string()));
+    }
+    if packet.header.response_code != ResponseCode::NoError {
+        return Err(Error::SrvLookupFailed(format!("Name server responded with {:?}.", packet.header.response_code)));
+    }
+
+    let records: Vec<Record> = packet.answers.iter()
+        .filter_map(|answer| match &answer.data {
+            RData::SRV(srv) => Some(Record {
+                priority: srv.priority,
+                weight: srv.weight,
+                target: srv.target.to_string(),
+                port: srv.port,
+            }),
+            _ => None,
+        })
+        .collect();
+
+    if records.is_empty() {
+        return Err(Error::SrvLookupFailed("No SRV records found.".to_string()));
+    }
+    Ok(records)
+}
+
+/// Orders the targets as described in RFC 2782. Targets are ordered by priority, and targets with
+/// the same priority are picked at random with a chance proportional to their weight. `pick` returns
+/// a random number from zero to the given total weight. A target of "." means the service isn't
+/// available at the domain.
+fn order(records: Vec<Record>, mut pick: impl FnMut(u32) -> u32) -> Result<Vec<(String, u16)>> {
+    let mut records: Vec<Record> = records.into_iter()
+        .filter(|record| !record.target.is_empty() && record.target != ".")
+        .collect();
+    if records.is_empty() {
+        return Err(Error::SrvLookupFailed("The service isn't available at this domain.".to_string()));
+    }
+
+    // Targets without weight go first so that they have a small chance to be picked early.
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let same_priority = records.iter().take_while(|record| record.priority == priority).count();
+
+        let total: u32 = records[..same_priority].iter().map(|record| record.weight as u32).sum();
+        let chosen = pick(total);
+        let mut running = 0;
+        let index = records[..same_priority].iter()
+            .position(|record| {
+                running += record.weight as u32;
+                running >= chosen
+            })
+            .unwrap_or(same_priority - 1);
+
+        let record = records.remove(index);
+        ordered.push((record.target, record.port));
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, UdpSocket};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::srv::{ask, order, parse_response, query, Record};
+
+    /// Builds a response to a query with SRV records given as (priority, weight, port, target).
+    fn response(query: &[u8], records: &[(u16, u16, u16, &str)]) -> Vec<u8> {
+        let mut packet = query.to_vec();
+        // Mark as a response with recursion available and set the answer count.
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+
+        for (priority, weight, port, target) in records {
+            let mut rdata = Vec::new();
+            rdata.extend_from_slice(&priority.to_be_bytes());
+            rdata.extend_from_slice(&weight.to_be_bytes());
+            rdata.extend_from_slice(&port.to_be_bytes());
+            for label in target.split('.').filter(|label| !label.is_empty()) {
+                rdata.push(label.len() as u8);
+                rdata.extend_from_slice(label.as_bytes());
+            }
+            rdata.push(0);
+
+            // Pointer to the name of the question.
+            packet.extend_from_slice(&[0xC0, 0x0C]);
+            // Type SRV, class IN and a TTL of 60 seconds.
+            packet.extend_from_slice(&[0, 33, 0, 1, 0, 0, 0, 60]);
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&rdata);
+        }
+
+        packet
+    }
+
+    fn record(priority: u16, weight: u16, target: &str) -> Record {
+        Record { priority, weight, target: target.to_string(), port: 55995 }
+    }
+
+    #[test]
+    fn parse_response_reads_the_records() {
+        let query = query("_mtd._tcp.example.com", 42).unwrap();
+        let packet = response(&query, &[
+            (20, 0, 55995, "backup.example.com"),
+            (10, 5, 4000, "heavy.example.com"),
+            (0, 0, 0, "."),
+        ]);
+
+        assert_eq!(parse_response(&packet, 42).unwrap(), vec![
+            record(20, 0, "backup.example.com"),
+            Record { priority: 10, weight: 5, target: "heavy.example.com".to_string(), port: 4000 },
+            Record { priority: 0, weight: 0, target: "".to_string(), port: 0 },
+        ]);
+        assert!(parse_response(&packet, 43).is_err());
+        assert!(parse_response(&response(&query, &[]), 42).is_err());
+    }
+
+    #[test]
+    fn order_orders_targets_by_priority_and_picks_them_by_weight() {
+        let records = vec![
+            record(20, 0, "backup.example.com"),
+            record(10, 1, "light.example.com"),
+            record(10, 0, "weightless.example.com"),
+            record(10, 5, "heavy.example.com"),
+        ];
+        let targets = |ordered: Vec<(String, u16)>| ordered.into_iter().map(|(target, _)| target).collect::<Vec<_>>();
+
+        // Picking the largest number chooses the last target of the running sum.
+        assert_eq!(targets(order(records.clone(), |total| total).unwrap()), vec![
+            "heavy.example.com", "light.example.com", "weightless.example.com", "backup.example.com",
+        ]);
+        // Picking zero chooses the first one, which is the target without weight.
+        assert_eq!(targets(order(records.clone(), |_| 0).unwrap()), vec![
+            "weightless.example.com", "light.example.com", "heavy.example.com", "backup.example.com",
+        ]);
+        // Picking 1 out of 6 chooses the light target, 1 out of 5 then the heavy one.
+        assert_eq!(targets(order(records, |_| 1).unwrap()), vec![
+            "light.example.com", "heavy.example.com", "weightless.example.com", "backup.example.com",
+        ]);
+    }
+
+    #[test]
+    fn order_treats_a_root_target_as_an_unavailable_service() {
+        assert!(order(vec![record(0, 0, "")], |total| total).is_err());
+        assert_eq!(order(vec![record(0, 0, ""), record(10, 0, "example.com")], |total| total).unwrap(), vec![
+            ("example.com".to_string(), 55995),
+        ]);
+    }
+
+    #[test]
+    fn ask_retries_truncated_responses_over_tcp() {
+        let query = query("_mtd._tcp.example.com", 42).unwrap();
+        let full = response(&query, &[(10, 0, 55995, "example.com")]);
+        let mut truncated = response(&query, &[]);
+        truncated[2] |= 0x02;
+
+        let udp = UdpSocket::bind("127.0.0.1:55972").unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:55972").unwrap();
+        let tcp_response = full.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (_, client) = udp.recv_from(&mut buf).unwrap();
+            udp.send_to(&truncated, client).unwrap();
+        });
+        thread::spawn(move || {
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut query).unwrap();
+
+            let mut message = (tcp_response.len() as u16).to_be_bytes().to_vec();
+            message.extend_from_slice(&tcp_response);
+            stream.write_all(&message).unwrap();
+        });
+
+        let packet = ask("127.0.0.1:55972".parse().unwrap(), &query, 42, Duration::from_secs(5)).unwrap();
+        assert_eq!(packet, full);
+    }
+}
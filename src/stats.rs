@@ -0,0 +1,384 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for computing completion statistics of `Task`s and `Todo`s. Statistics are computed from
+//! the completion history of `Task`s, so `Task`s saved before the history was recorded only count
+//! their later completions.
+
+use std::cmp::Reverse;
+use std::ops::RangeInclusive;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{Task, TdList};
+
+/// Completion statistics of a `TdList` for a range of dates. Created with `TdList::statistics`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statistics {
+    /// Statistics of every week that overlaps the range, oldest first. Weeks start on Monday.
+    pub weeks: Vec<WeekStatistics>,
+    /// Statistics of every `Task` in the list.
+    pub tasks: Vec<TaskStatistics>,
+    /// The number of undone `Todo`s that were scheduled for a date before today.
+    pub overdue_todos: usize,
+}
+
+/// Completion statistics of all `Task`s for a single week.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekStatistics {
+    /// The Monday the week starts on.
+    pub start: NaiveDate,
+    /// The number of `Task` occurrences within the week that should have been done.
+    pub scheduled: usize,
+    /// The number of those occurrences that were done.
+    pub completed: usize,
+}
+
+/// Completion statistics of a single `Task`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskStatistics {
+    /// The `id` of the `Task`.
+    pub id: u64,
+    /// The `uuid` of the `Task`.
+    pub uuid: Uuid,
+    /// The `body` of the `Task`.
+    pub body: String,
+    /// The number of occurrences within the range that should have been done.
+    pub scheduled: usize,
+    /// The number of those occurrences that were done.
+    pub completed: usize,
+    /// The number of latest occurrences done in a row. An occurrence today that isn't done yet
+    /// doesn't break the streak.
+    pub current_streak: usize,
+    /// The most occurrences ever done in a row.
+    pub longest_streak: usize,
+}
+
+/// Undone `Todo`s and `Task`s that keep being put off. Created with `TdList::stale_report`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StaleReport {
+    /// Every undone `Todo`, the most postponed first. `Todo`s postponed equally often are ordered
+    /// by the days they are overdue and then by their age.
+    pub todos: Vec<StaleTodo>,
+    /// The `Task`s whose latest occurrence was missed, the most missed in a row first.
+    pub tasks: Vec<StaleTask>,
+}
+
+/// An undone `Todo` in a `StaleReport`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StaleTodo {
+    /// The `id` of the `Todo`.
+    pub id: u64,
+    /// The `uuid` of the `Todo`.
+    pub uuid: Uuid,
+    /// The `body` of the `Todo`.
+    pub body: String,
+    /// The number of times the `Todo` has been postponed.
+    pub postponed: u32,
+    /// The number of days the `Todo` has rolled over to the next day since its date.
+    pub days_overdue: u64,
+    /// The number of days since the `Todo` was created or `None` if the creation time is unknown.
+    pub age_days: Option<i64>,
+}
+
+/// A `Task` in a `StaleReport`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StaleTask {
+    /// The `id` of the `Task`.
+    pub id: u64,
+    /// The `uuid` of the `Task`.
+    pub uuid: Uuid,
+    /// The `body` of the `Task`.
+    pub body: String,
+    /// The number of latest occurrences missed in a row. An occurrence today that isn't done yet
+    /// isn't counted.
+    pub missed_in_row: usize,
+    /// The latest date the `Task` was done for or `None` if it has never been done.
+    pub last_done: Option<NaiveDate>,
+}
+
+impl WeekStatistics {
+    /// Returns the share of scheduled occurrences that were done, between 0 and 1. Returns `None`
+    /// if nothing was scheduled.
+    pub fn completion_rate(&self) -> Option<f64> {
+        completion_rate(self.scheduled, self.completed)
+    }
+}
+
+impl TaskStatistics {
+    /// Returns the share of scheduled occurrences that were done, between 0 and 1. Returns `None`
+    /// if nothing was scheduled.
+    pub fn completion_rate(&self) -> Option<f64> {
+        completion_rate(self.scheduled, self.completed)
+    }
+}
+
+fn completion_rate(scheduled: usize, completed: usize) -> Option<f64> {
+    if scheduled == 0 {
+        None
+    } else {
+        Some(completed as f64 / scheduled as f64)
+    }
+}
+
+impl Statistics {
+    pub(crate) fn compute_wtd(list: &TdList, range: RangeInclusive<NaiveDate>, today: NaiveDate) -> Statistics {
+        let (from, to) = (*range.start(), *range.end());
+
+        let mut weeks = Vec::new();
+        let mut week_start = from - Duration::days(from.weekday().num_days_from_monday() as i64);
+        while week_start <= to {
+            let week_end = week_start + Duration::days(6);
+            let week = week_start.max(from)..=week_end.min(to);
+
+            let mut stats = WeekStatistics { start: week_start, scheduled: 0, completed: 0 };
+            for task in list.tasks() {
+                let (scheduled, completed) = count_occurrences(task, week.clone(), today);
+                stats.scheduled += scheduled;
+                stats.completed += completed;
+            }
+            weeks.push(stats);
+
+            week_start += Duration::weeks(1);
+        }
+
+        let mut tasks = Vec::new();
+        for task in list.tasks() {
+            let (scheduled, completed) = count_occurrences(task, range.clone(), today);
+            let (current_streak, longest_streak) = streaks(task, today);
+            tasks.push(TaskStatistics {
+                id: task.id(),
+                uuid: task.uuid(),
+                body: task.body().to_string(),
+                scheduled,
+                completed,
+                current_streak,
+                longest_streak,
+            });
+        }
+
+        let overdue_todos = list.todos().iter().filter(|todo| todo.days_overdue(today) > 0).count();
+
+        Statistics { weeks, tasks, overdue_todos }
+    }
+}
+
+impl StaleReport {
+    pub(crate) fn compute_wtd(list: &TdList, today: NaiveDate) -> StaleReport {
+        let mut todos: Vec<StaleTodo> = list.todos().into_iter()
+            .filter(|todo| !todo.done())
+            .map(|todo| StaleTodo {
+                id: todo.id(),
+                uuid: todo.uuid(),
+                body: todo.body().to_string(),
+                postponed: todo.postponed(),
+                days_overdue: todo.days_overdue(today),
+                age_days: todo.created().map(|created| (today - created.date()).num_days()),
+            })
+            .collect();
+        todos.sort_by_key(|todo| (Reverse(todo.postponed), Reverse(todo.days_overdue), Reverse(todo.age_days), todo.id));
+
+        let mut tasks: Vec<StaleTask> = list.tasks().into_iter()
+            .map(|task| StaleTask {
+                id: task.id(),
+                uuid: task.uuid(),
+                body: task.body().to_string(),
+                missed_in_row: missed_in_row(task, today),
+                last_done: task.completions().iter().next_back().copied(),
+            })
+            .filter(|task| task.missed_in_row > 0)
+            .collect();
+        tasks.sort_by_key(|task| (Reverse(task.missed_in_row), task.id));
+
+        StaleReport { todos, tasks }
+    }
+}
+
+/// Returns the date from which the occurrences of a `Task` are counted.
+fn task_start(task: &Task) -> Option<NaiveDate> {
+    task.created().or_else(|| task.completions().iter().next().copied())
+}
+
+/// Returns `true` if the `Task` should be done at the given date. Occurrences today that aren't
+/// done yet aren't counted because there is still time to do them.
+fn is_occurrence(task: &Task, date: NaiveDate, today: NaiveDate) -> bool {
+    task.for_date(date)
+        && !task.paused(date)
+        && date <= today
+        && (date < today || task.completions().contains(&date))
+}
+
+/// Counts the scheduled and completed occurrences of a `Task` within the range.
+fn count_occurrences(task: &Task, range: RangeInclusive<NaiveDate>, today: NaiveDate) -> (usize, usize) {
+    let from = match task_start(task) {
+        Some(start) => start.max(*range.start()),
+        None => *range.start(),
+    };
+
+    let mut scheduled = 0;
+    let mut completed = 0;
+    let mut date = from;
+    while date <= *range.end() && date <= today {
+        if is_occurrence(task, date, today) {
+            scheduled += 1;
+            if task.completions().contains(&date) {
+                completed += 1;
+            }
+        }
+        date = date.succ();
+    }
+
+    (scheduled, completed)
+}
+
+/// Returns the current and the longest streak of a `Task`.
+fn streaks(task: &Task, today: NaiveDate) -> (usize, usize) {
+    let mut date = match task_start(task) {
+        Some(start) => start,
+        None => return (0, 0),
+    };
+
+    let mut current = 0;
+    let mut longest = 0;
+    while date <= today {
+        if is_occurrence(task, date, today) {
+            if task.completions().contains(&date) {
+                current += 1;
+                longest = longest.max(current);
+            } else {
+                current = 0;
+            }
+        }
+        date = date.succ();
+    }
+
+    (current, longest)
+}
+
+/// Returns the number of latest occurrences of a `Task` that weren't done.
+fn missed_in_row(task: &Task, today: NaiveDate) -> usize {
+    let mut date = match task_start(task) {
+        Some(start) => start,
+        None => return 0,
+    };
+
+    let mut missed = 0;
+    while date <= today {
+        if is_occurrence(task, date, today) {
+            if task.completions().contains(&date) {
+                missed = 0;
+            } else {
+                missed += 1;
+            }
+        }
+        date = date.succ();
+    }
+
+    missed
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+
+    use crate::{StaleReport, Statistics, Task, TdList, Todo};
+
+    fn list_with_mon_wed_task() -> TdList {
+        let mut list = TdList::new_client();
+        let mut task = Task::new("Task".to_string(), vec![Weekday::Mon, Weekday::Wed]).unwrap();
+        // 2022-6-6 is a Monday.
+        task.created = Some(NaiveDate::from_ymd(2022, 6, 6));
+        list.add_task(task);
+        list
+    }
+
+    #[test]
+    fn statistics_counts_weeks_and_streaks() {
+        let mut list = list_with_mon_wed_task();
+        let task = list.get_task_mut(0).unwrap();
+        task.set_done(true, NaiveDate::from_ymd(2022, 6, 6));
+        task.set_done(true, NaiveDate::from_ymd(2022, 6, 8));
+        // 2022-6-13 is missed.
+        task.set_done(true, NaiveDate::from_ymd(2022, 6, 15));
+        task.set_done(true, NaiveDate::from_ymd(2022, 6, 20));
+
+        let today = NaiveDate::from_ymd(2022, 6, 22);
+        let stats = Statistics::compute_wtd(&list, NaiveDate::from_ymd(2022, 6, 6)..=today, today);
+
+        assert_eq!(stats.weeks.len(), 3);
+        assert_eq!((stats.weeks[0].scheduled, stats.weeks[0].completed), (2, 2));
+        assert_eq!((stats.weeks[1].scheduled, stats.weeks[1].completed), (2, 1));
+        // The occurrence today isn't done yet so it isn't counted.
+        assert_eq!((stats.weeks[2].scheduled, stats.weeks[2].completed), (1, 1));
+
+        assert_eq!(stats.tasks[0].scheduled, 5);
+        assert_eq!(stats.tasks[0].completed, 4);
+        assert_eq!(stats.tasks[0].current_streak, 2);
+        assert_eq!(stats.tasks[0].longest_streak, 2);
+    }
+
+    #[test]
+    fn statistics_doesnt_count_dates_before_creation() {
+        let list = list_with_mon_wed_task();
+
+        let today = NaiveDate::from_ymd(2022, 6, 9);
+        let stats = Statistics::compute_wtd(&list, NaiveDate::from_ymd(2022, 5, 1)..=today, today);
+
+        assert_eq!(stats.tasks[0].scheduled, 2);
+        assert_eq!(stats.tasks[0].completion_rate(), Some(0.0));
+    }
+
+    #[test]
+    fn statistics_counts_overdue_todos() {
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_specific_date("Overdue".to_string(), NaiveDate::from_ymd(2022, 6, 1)).unwrap());
+        list.add_todo(Todo::new_specific_date("Today".to_string(), NaiveDate::from_ymd(2022, 6, 9)).unwrap());
+
+        let today = NaiveDate::from_ymd(2022, 6, 9);
+        let stats = Statistics::compute_wtd(&list, today..=today, today);
+
+        assert_eq!(stats.overdue_todos, 1);
+    }
+
+    #[test]
+    fn stale_report_orders_todos_and_counts_missed_tasks() {
+        let today = NaiveDate::from_ymd(2022, 6, 22);
+        let mut list = list_with_mon_wed_task();
+        list.get_task_mut(0).unwrap().set_done(true, NaiveDate::from_ymd(2022, 6, 8));
+        // 2022-6-13, 2022-6-15 and 2022-6-20 are missed.
+
+        list.add_todo(Todo::new_specific_date("Overdue".to_string(), NaiveDate::from_ymd(2022, 6, 20)).unwrap());
+        let mut postponed = Todo::new_specific_date("Postponed".to_string(), today).unwrap();
+        postponed.postpone_wtd(1, today).unwrap();
+        postponed.postpone_wtd(1, today).unwrap();
+        list.add_todo(postponed);
+        list.add_todo(Todo::new_specific_date("Done".to_string(), today).unwrap());
+        list.get_todo_mut(2).unwrap().set_done(true);
+
+        let report = StaleReport::compute_wtd(&list, today);
+
+        let bodies: Vec<&str> = report.todos.iter().map(|todo| todo.body.as_str()).collect();
+        assert_eq!(bodies, vec!["Postponed", "Overdue"]);
+        assert_eq!(report.todos[0].postponed, 2);
+        assert_eq!(report.todos[1].days_overdue, 2);
+
+        assert_eq!(report.tasks.len(), 1);
+        assert_eq!(report.tasks[0].missed_in_row, 3);
+        assert_eq!(report.tasks[0].last_done, Some(NaiveDate::from_ymd(2022, 6, 8)));
+    }
+}
@@ -0,0 +1,243 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for encrypting data files at rest. How a data file is encrypted is chosen with
+//! `DataEncryption` in the `Config`, and the encryption itself is done by a `StorageEncryption`
+//! provider. `PasswordEncryption` derives the key from a password and `KeyEncryption` uses a random
+//! key, such as one kept in the keyring of the operating system.
+//!
+//! Data files that aren't encrypted are still read when encryption is used, so turning encryption
+//! on encrypts the file the next time it is written.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{KeyEncryption, read_data_file, TdList, write_data_file};
+//!
+//! let path = std::env::temp_dir().join("mtd-storage-doc-example.json");
+//! let key = KeyEncryption::generate();
+//!
+//! write_data_file(&path, TdList::new_client().to_json().unwrap().as_bytes(), Some(&key)).unwrap();
+//! assert!(read_data_file(&path, None).is_err());
+//!
+//! let list = TdList::new_from_json(&read_data_file(&path, Some(&key)).unwrap()).unwrap();
+//! assert!(!list.is_server());
+//! # std::fs::remove_file(path).unwrap();
+//! ```
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+use crate::network::crypt;
+use crate::proxy::{base64, base64_decode};
+
+/// The start of encrypted data files. It tells them apart from plain JSON and allows changing the
+/// format later.
+const MAGIC: &[u8] = b"mtdenc1\n";
+
+/// How the data files of a profile are encrypted at rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataEncryption {
+    /// Data files are plain JSON.
+    #[default]
+    None,
+    /// Data files are encrypted with a password asked for when mtd starts.
+    Password,
+    /// Data files are encrypted with a random key kept in the keyring of the operating system.
+    Keyring,
+}
+
+/// Encrypts and decrypts the contents of data files.
+pub trait StorageEncryption: Send + Sync {
+    /// Encrypts the contents of a data file.
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>>;
+    /// Decrypts the contents of a data file encrypted with `StorageEncryption::encrypt`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::DecryptingFailed` if the data was encrypted with another password or key.
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Encrypts data files with a key derived from a password.
+#[derive(Clone)]
+pub struct PasswordEncryption {
+    password: Vec<u8>,
+}
+
+impl PasswordEncryption {
+    /// Creates a new `PasswordEncryption` with the password.
+    pub fn new(password: Vec<u8>) -> Self {
+        PasswordEncryption { password }
+    }
+}
+
+impl StorageEncryption for PasswordEncryption {
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        crypt::encrypt(data, &self.password)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        crypt::decrypt(data, &self.password)
+    }
+}
+
+/// Encrypts data files with a random 256-bit key. The key can be stored as text, for example in the
+/// keyring of the operating system.
+#[derive(Clone)]
+pub struct KeyEncryption {
+    key: [u8; 32],
+}
+
+impl KeyEncryption {
+    /// Creates a new `KeyEncryption` with a random key.
+    pub fn generate() -> Self {
+        KeyEncryption { key: random() }
+    }
+
+    /// Creates a `KeyEncryption` from a key returned by `KeyEncryption::to_text`.
+    pub fn from_text(text: &str) -> Result<Self> {
+        base64_decode(text.trim())
+            .and_then(|key| key.try_into().ok())
+            .map(|key| KeyEncryption { key })
+            .ok_or_else(|| Error::InvalidArgument("The data file key is invalid.".to_string()))
+    }
+
+    /// Returns the key as text.
+    pub fn to_text(&self) -> String {
+        base64(&self.key)
+    }
+}
+
+impl StorageEncryption for KeyEncryption {
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        crypt::encrypt_with_key(data, &self.key)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        crypt::decrypt_with_key(data, &self.key)
+    }
+}
+
+/// Writes a data file, encrypting it if an encryption is given.
+pub fn write_data_file(path: &Path, contents: &[u8], encryption: Option<&dyn StorageEncryption>) -> Result<()> {
+    match encryption {
+        Some(encryption) => fs::write(path, [MAGIC, &encryption.encrypt(contents)?].concat())?,
+        None => fs::write(path, contents)?,
+    }
+    Ok(())
+}
+
+/// Writes a file that only its owner can read, such as a file with keys or tokens. The contents are
+/// written to a temporary file that then replaces the file, so the file is never left half written.
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    // Left behind by a write that was interrupted.
+    let _ = fs::remove_file(&temp_path);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+/// Reads a data file written with `write_data_file`. Files that aren't encrypted are read as they
+/// are even if an encryption is given.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidArgument` if the file is encrypted but no encryption is given and
+/// `Error::DecryptingFailed` if it was encrypted with another password or key.
+pub fn read_data_file(path: &Path, encryption: Option<&dyn StorageEncryption>) -> Result<String> {
+    let contents = fs::read(path)?;
+    let plain = match (contents.strip_prefix(MAGIC), encryption) {
+        (None, _) => contents,
+        (Some(encrypted), Some(encryption)) => encryption.decrypt(encrypted)?,
+        (Some(_), None) => {
+            return Err(Error::InvalidArgument(format!("'{}' is encrypted. Set data_encryption in the config to read it.", path.display())));
+        }
+    };
+    String::from_utf8(plain).map_err(|_| Error::InvalidArgument(format!("'{}' isn't a data file.", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::Error;
+    use crate::storage::{KeyEncryption, PasswordEncryption, read_data_file, write_data_file, write_private_file};
+
+    #[test]
+    fn data_files_are_encrypted_and_read_back() {
+        let path = env::temp_dir().join("mtd-storage-test.json");
+        let password = PasswordEncryption::new(b"hunter42".to_vec());
+
+        write_data_file(&path, b"{\"items\":[]}", Some(&password)).unwrap();
+        assert!(!fs::read(&path).unwrap().windows(5).any(|part| part == b"items"));
+        assert_eq!(read_data_file(&path, Some(&password)).unwrap(), "{\"items\":[]}");
+        assert!(matches!(read_data_file(&path, None), Err(Error::InvalidArgument(_))));
+        assert!(matches!(read_data_file(&path, Some(&PasswordEncryption::new(b"wrong".to_vec()))), Err(Error::DecryptingFailed)));
+
+        // Plain files are read as they are so that encryption can be turned on.
+        write_data_file(&path, b"{}", None).unwrap();
+        assert_eq!(read_data_file(&path, Some(&password)).unwrap(), "{}");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn keys_are_read_back_from_text() {
+        let key = KeyEncryption::generate();
+        let read = KeyEncryption::from_text(&key.to_text()).unwrap();
+        assert_eq!(read.key, key.key);
+
+        assert!(KeyEncryption::from_text("c2hvcnQ=").is_err());
+        assert!(KeyEncryption::from_text("not base64!").is_err());
+    }
+
+    #[test]
+    fn private_files_are_replaced_and_readable_only_by_the_owner() {
+        let path = env::temp_dir().join("mtd-private-file-test");
+        fs::write(&path, "old").unwrap();
+
+        write_private_file(&path, b"secret").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+}
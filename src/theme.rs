@@ -0,0 +1,404 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module defining the terminal colors and styles of mtd's output. A `Theme` maps each `Style` to a
+//! style specification, which is a space separated list of words. The words are colors (`black`,
+//! `red`, `green`, `yellow`, `blue`, `magenta`, `cyan`, `white` and `default`), bright colors such as
+//! `bright-red` and the attributes `bold`, `dim`, `italic`, `underline`, `reverse` and
+//! `strikethrough`. An empty specification leaves text unstyled.
+//!
+//! Every theme is based on a `ThemePreset`, and only the styles set in the theme itself differ from
+//! the preset. Changing the preset of a theme therefore keeps the styles that were set by hand.
+//!
+//! Todos and tasks can also be told apart by a marker shown before each item, such as `•` or `↻`.
+//!
+//! # Example
+//!
+//! ```
+//! use mtd::{Style, Theme, ThemePreset};
+//!
+//! let mut theme = Theme::default();
+//! theme.set_style(Style::Header, "bold blue".to_string()).unwrap();
+//!
+//! assert_eq!(theme.paint("Todos:", Style::Header), "\x1B[1;34mTodos:\x1B[0m");
+//! assert!(theme.set_style(Style::Header, "sparkly".to_string()).is_err());
+//!
+//! theme.set_preset(ThemePreset::Monochrome);
+//! assert_eq!(theme.style(Style::Header), "bold blue");
+//! assert_eq!(theme.style(Style::Overdue), "bold italic");
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// The roles of styled text in mtd's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Weekday and date headers.
+    Weekday,
+    /// Section headers such as "Todos:".
+    Header,
+    /// Warnings.
+    Warning,
+    /// Done items.
+    Done,
+    /// Items removed on the next sync.
+    Removed,
+    /// Todos whose date has passed.
+    Overdue,
+    /// Todos that aren't done or overdue.
+    Todo,
+    /// Tasks that aren't done.
+    Task,
+}
+
+impl Style {
+    const ALL: [Style; 8] = [
+        Style::Weekday,
+        Style::Header,
+        Style::Warning,
+        Style::Done,
+        Style::Removed,
+        Style::Overdue,
+        Style::Todo,
+        Style::Task,
+    ];
+}
+
+/// The built-in sets of styles a `Theme` is based on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    /// Colors that tell the roles apart at a glance.
+    #[default]
+    Default,
+    /// Blue, yellow and magenta, which can be told apart with the common kinds of color blindness.
+    /// Overdue todos are also underlined.
+    ColorblindSafe,
+    /// Bold and bright colors for low contrast screens and low vision.
+    HighContrast,
+    /// No colors, only attributes such as bold and underline.
+    Monochrome,
+}
+
+impl ThemePreset {
+    /// Returns the style specification the preset gives a `Style`.
+    pub fn style(self, style: Style) -> &'static str {
+        match (self, style) {
+            (ThemePreset::Default, Style::Weekday) => "yellow",
+            (ThemePreset::Default, Style::Header) => "green",
+            (ThemePreset::Default, Style::Warning) => "red",
+            (ThemePreset::Default, Style::Done) => "dim strikethrough",
+            (ThemePreset::Default, Style::Removed) => "dim",
+            (ThemePreset::Default, Style::Overdue) => "red",
+            (ThemePreset::Default, Style::Todo) => "",
+            (ThemePreset::Default, Style::Task) => "cyan",
+
+            (ThemePreset::ColorblindSafe, Style::Weekday) => "yellow",
+            (ThemePreset::ColorblindSafe, Style::Header) => "bold blue",
+            (ThemePreset::ColorblindSafe, Style::Warning) => "bold magenta",
+            (ThemePreset::ColorblindSafe, Style::Done) => "dim strikethrough",
+            (ThemePreset::ColorblindSafe, Style::Removed) => "dim",
+            (ThemePreset::ColorblindSafe, Style::Overdue) => "magenta underline",
+            (ThemePreset::ColorblindSafe, Style::Todo) => "",
+            (ThemePreset::ColorblindSafe, Style::Task) => "blue",
+
+            (ThemePreset::HighContrast, Style::Weekday) => "bold bright-yellow",
+            (ThemePreset::HighContrast, Style::Header) => "bold underline bright-white",
+            (ThemePreset::HighContrast, Style::Warning) => "bold reverse",
+            // Dim text is hard to read on many screens.
+            (ThemePreset::HighContrast, Style::Done) => "strikethrough",
+            (ThemePreset::HighContrast, Style::Removed) => "italic",
+            (ThemePreset::HighContrast, Style::Overdue) => "bold bright-red",
+            (ThemePreset::HighContrast, Style::Todo) => "bright-white",
+            (ThemePreset::HighContrast, Style::Task) => "bold bright-cyan",
+
+            (ThemePreset::Monochrome, Style::Weekday) => "bold underline",
+            (ThemePreset::Monochrome, Style::Header) => "bold",
+            (ThemePreset::Monochrome, Style::Warning) => "bold reverse",
+            (ThemePreset::Monochrome, Style::Done) => "dim strikethrough",
+            (ThemePreset::Monochrome, Style::Removed) => "dim",
+            (ThemePreset::Monochrome, Style::Overdue) => "bold italic",
+            (ThemePreset::Monochrome, Style::Todo) => "",
+            (ThemePreset::Monochrome, Style::Task) => "italic",
+        }
+    }
+}
+
+/// Style specifications for each `Style`. Styles that aren't set use the specifications of the
+/// preset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ThemeFile")]
+pub struct Theme {
+    preset: ThemePreset,
+    weekday: Option<String>,
+    header: Option<String>,
+    warning: Option<String>,
+    done: Option<String>,
+    removed: Option<String>,
+    overdue: Option<String>,
+    todo: Option<String>,
+    task: Option<String>,
+    todo_marker: String,
+    task_marker: String,
+}
+
+/// A `Theme` as read from JSON.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    preset: Option<ThemePreset>,
+    weekday: Option<String>,
+    header: Option<String>,
+    warning: Option<String>,
+    done: Option<String>,
+    removed: Option<String>,
+    overdue: Option<String>,
+    todo: Option<String>,
+    task: Option<String>,
+    todo_marker: String,
+    task_marker: String,
+}
+
+impl From<ThemeFile> for Theme {
+    fn from(file: ThemeFile) -> Self {
+        let mut theme = Theme {
+            preset: file.preset.unwrap_or_default(),
+            weekday: file.weekday,
+            header: file.header,
+            warning: file.warning,
+            done: file.done,
+            removed: file.removed,
+            overdue: file.overdue,
+            todo: file.todo,
+            task: file.task,
+            todo_marker: file.todo_marker,
+            task_marker: file.task_marker,
+        };
+        // Themes saved before presets existed contain every style. Styles that match the default
+        // preset weren't set by hand and shouldn't hide the styles of another preset.
+        if file.preset.is_none() {
+            for style in Style::ALL {
+                let field = theme.field(style);
+                if field.as_deref() == Some(ThemePreset::Default.style(style)) {
+                    *field = None;
+                }
+            }
+        }
+        theme
+    }
+}
+
+impl Theme {
+    /// Creates a `Theme` that doesn't style anything.
+    pub fn plain() -> Self {
+        let mut theme = Theme::default();
+        for style in Style::ALL {
+            *theme.field(style) = Some(String::new());
+        }
+        theme
+    }
+
+    /// Creates a `Theme` that uses the styles of a preset.
+    pub fn from_preset(preset: ThemePreset) -> Self {
+        Theme { preset, ..Theme::default() }
+    }
+
+    /// Returns the preset the theme is based on.
+    pub fn preset(&self) -> ThemePreset {
+        self.preset
+    }
+
+    /// Sets the preset the theme is based on. Styles set with `Theme::set_style` are kept.
+    pub fn set_preset(&mut self, preset: ThemePreset) {
+        self.preset = preset;
+    }
+
+    fn field(&mut self, style: Style) -> &mut Option<String> {
+        match style {
+            Style::Weekday => &mut self.weekday,
+            Style::Header => &mut self.header,
+            Style::Warning => &mut self.warning,
+            Style::Done => &mut self.done,
+            Style::Removed => &mut self.removed,
+            Style::Overdue => &mut self.overdue,
+            Style::Todo => &mut self.todo,
+            Style::Task => &mut self.task,
+        }
+    }
+
+    /// Returns the style specification of a `Style`.
+    pub fn style(&self, style: Style) -> &str {
+        let field = match style {
+            Style::Weekday => &self.weekday,
+            Style::Header => &self.header,
+            Style::Warning => &self.warning,
+            Style::Done => &self.done,
+            Style::Removed => &self.removed,
+            Style::Overdue => &self.overdue,
+            Style::Todo => &self.todo,
+            Style::Task => &self.task,
+        };
+        field.as_deref().unwrap_or_else(|| self.preset.style(style))
+    }
+
+    /// Sets the style specification of a `Style`. Returns an `Error::InvalidArgument` if the
+    /// specification contains unknown words.
+    pub fn set_style(&mut self, style: Style, spec: String) -> Result<()> {
+        ansi_codes(&spec)?;
+        *self.field(style) = Some(spec);
+        Ok(())
+    }
+
+    /// Returns the marker shown before items of a `Style`. Only `Style::Todo` and `Style::Task` have
+    /// markers, the marker of other styles is empty.
+    pub fn marker(&self, style: Style) -> &str {
+        match style {
+            Style::Todo => &self.todo_marker,
+            Style::Task => &self.task_marker,
+            _ => "",
+        }
+    }
+
+    /// Sets the marker shown before todos or tasks. Returns an `Error::InvalidArgument` for other
+    /// styles.
+    pub fn set_marker(&mut self, style: Style, marker: String) -> Result<()> {
+        match style {
+            Style::Todo => self.todo_marker = marker,
+            Style::Task => self.task_marker = marker,
+            _ => return Err(Error::InvalidArgument("Only todos and tasks have markers.".to_string())),
+        }
+        Ok(())
+    }
+
+    /// Returns an `Error::InvalidArgument` if any of the style specifications contains unknown words.
+    pub fn validate(&self) -> Result<()> {
+        for style in Style::ALL {
+            ansi_codes(self.style(style))?;
+        }
+        Ok(())
+    }
+
+    /// Styles text for the terminal with ANSI escape codes. Text with an empty or invalid
+    /// specification is returned as is.
+    pub fn paint(&self, text: &str, style: Style) -> String {
+        match ansi_codes(self.style(style)) {
+            Ok(codes) if !codes.is_empty() => {
+                let codes: Vec<String> = codes.iter().map(|code| code.to_string()).collect();
+                format!("\x1B[{}m{}\x1B[0m", codes.join(";"), text)
+            }
+            _ => text.to_string(),
+        }
+    }
+}
+
+/// Returns the ANSI SGR codes of a style specification.
+fn ansi_codes(spec: &str) -> Result<Vec<u8>> {
+    const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+    let mut codes = Vec::new();
+    for word in spec.split_whitespace() {
+        let word = word.to_lowercase();
+        let code = match word.as_str() {
+            "bold" => 1,
+            "dim" => 2,
+            "italic" => 3,
+            "underline" => 4,
+            "reverse" => 7,
+            "strikethrough" => 9,
+            "default" => 39,
+            _ => {
+                let (base, color) = match word.strip_prefix("bright-") {
+                    Some(color) => (90, color),
+                    None => (30, word.as_str()),
+                };
+                match COLORS.iter().position(|c| *c == color) {
+                    Some(i) => base + i as u8,
+                    None => return Err(Error::InvalidArgument(format!("Unknown style \"{}\".", word))),
+                }
+            }
+        };
+        codes.push(code);
+    }
+
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Style, Theme, ThemePreset};
+    use crate::theme::ansi_codes;
+
+    #[test]
+    fn ansi_codes_parses_colors_and_attributes() {
+        assert_eq!(ansi_codes("dim strikethrough").unwrap(), vec![2, 9]);
+        assert_eq!(ansi_codes("Bright-Cyan underline").unwrap(), vec![96, 4]);
+        assert!(ansi_codes("").unwrap().is_empty());
+        assert!(ansi_codes("bright-orange").is_err());
+    }
+
+    #[test]
+    fn theme_from_json_uses_defaults_for_missing_styles() {
+        let theme: Theme = serde_json::from_str(r#"{ "header": "bold blue" }"#).unwrap();
+        assert_eq!(theme.style(Style::Header), "bold blue");
+        assert_eq!(theme.style(Style::Weekday), "yellow");
+        assert_eq!(Theme::plain().paint("Text", Style::Done), "Text");
+    }
+
+    #[test]
+    fn theme_has_styles_and_markers_per_item_type() {
+        let mut theme: Theme = serde_json::from_str(r#"{ "task": "magenta", "task_marker": "↻ " }"#).unwrap();
+        assert_eq!(theme.paint("Task", Style::Task), "\x1B[35mTask\x1B[0m");
+        assert_eq!(theme.paint("Todo", Style::Todo), "Todo");
+        assert_eq!(theme.marker(Style::Task), "↻ ");
+
+        theme.set_marker(Style::Todo, "• ".to_string()).unwrap();
+        assert_eq!(theme.marker(Style::Todo), "• ");
+        assert!(theme.set_marker(Style::Header, "> ".to_string()).is_err());
+    }
+
+    #[test]
+    fn presets_are_valid_and_keep_styles_set_by_hand() {
+        for preset in [ThemePreset::Default, ThemePreset::ColorblindSafe, ThemePreset::HighContrast, ThemePreset::Monochrome] {
+            Theme::from_preset(preset).validate().unwrap();
+        }
+        let theme: Theme = serde_json::from_str(r#"{ "preset": "colorblind-safe", "task": "green" }"#).unwrap();
+        assert_eq!(theme.style(Style::Overdue), "magenta underline");
+        assert_eq!(theme.style(Style::Task), "green");
+
+        // Monochrome themes only use attributes.
+        let monochrome = Theme::from_preset(ThemePreset::Monochrome);
+        for style in [Style::Weekday, Style::Header, Style::Warning, Style::Overdue, Style::Task] {
+            assert!(ansi_codes(monochrome.style(style)).unwrap().iter().all(|code| *code < 30));
+        }
+    }
+
+    #[test]
+    fn themes_saved_before_presets_follow_a_new_preset() {
+        let mut theme: Theme = serde_json::from_str(
+            r#"{ "weekday": "yellow", "header": "bold blue", "warning": "red", "done": "dim strikethrough", "removed": "dim",
+                 "overdue": "red", "todo": "", "task": "cyan", "todo_marker": "", "task_marker": "" }"#,
+        ).unwrap();
+        theme.set_preset(ThemePreset::HighContrast);
+
+        assert_eq!(theme.style(Style::Header), "bold blue");
+        assert_eq!(theme.style(Style::Overdue), "bold bright-red");
+
+        let read: Theme = serde_json::from_str(&serde_json::to_string(&theme).unwrap()).unwrap();
+        assert_eq!(read, theme);
+    }
+}
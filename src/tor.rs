@@ -0,0 +1,202 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for publishing a server as a Tor onion service through the control port of a running
+//! Tor process. The onion service exists as long as the control connection stays open.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::{Error, Result};
+
+/// An onion service published through a Tor control connection. The service is removed when this is
+/// dropped.
+pub(crate) struct OnionService {
+    _control: TcpStream,
+    address: String,
+    private_key: String,
+}
+
+impl OnionService {
+    /// Publishes an onion service that forwards connections to its `port` to `target`. The control
+    /// connection is authenticated with the password if one is given and otherwise with no
+    /// authentication or with Tor's cookie file. If `private_key` is `None`, a new key and thus a new
+    /// onion address is created.
+    pub(crate) fn publish(control_addr: &str, password: Option<&str>, port: u16, target: &str, private_key: Option<&str>) -> Result<OnionService> {
+        let control = TcpStream::connect(control_addr)?;
+        let mut reader = BufReader::new(control.try_clone()?);
+        let mut writer = control.try_clone()?;
+        let mut command = |command: &str| -> Result<Vec<String>> {
+            writer.write_all(format!("{}\r\n", command).as_bytes())?;
+            read_reply(&mut reader)
+        };
+
+        let auth = match password {
+            Some(password) => format!("AUTHENTICATE {}", quote(password)),
+            None => {
+                let info = command("PROTOCOLINFO 1")?;
+                let (methods, cookie_file) = parse_auth_methods(&info);
+                match cookie_file {
+                    _ if methods.iter().any(|m| m == "NULL") => "AUTHENTICATE".to_string(),
+                    Some(path) if methods.iter().any(|m| m == "COOKIE") => {
+                        let cookie = fs::read(path)?;
+                        format!("AUTHENTICATE {}", cookie.iter().map(|b| format!("{:02X}", b)).collect::<String>())
+                    }
+                    _ => return Err(Error::TorFailed(format!("Unsupported authentication methods {}.", methods.join(", ")))),
+                }
+            }
+        };
+        command(&auth)?;
+
+        let key = private_key.unwrap_or("NEW:ED25519-V3");
+        let reply = command(&format!("ADD_ONION {} Port={},{}", key, port, target))?;
+
+        let value = |name: &str| reply.iter().find_map(|line| line.strip_prefix(name)).map(str::to_string);
+        let service_id = value("ServiceID=").ok_or_else(|| Error::TorFailed("Tor didn't return the onion address.".to_string()))?;
+        let private_key = match value("PrivateKey=") {
+            Some(key) => key,
+            None => key.to_string(),
+        };
+
+        Ok(OnionService { _control: control, address: format!("{}.onion:{}", service_id, port), private_key })
+    }
+
+    /// Returns the address clients should connect to through Tor.
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns the private key of the onion service. Publishing with the same key again keeps the
+    /// onion address.
+    pub(crate) fn private_key(&self) -> &str {
+        &self.private_key
+    }
+}
+
+/// Returns the address Tor should forward connections to for a server listening on `socket_addr`.
+/// A server listening on all interfaces is reached through the loopback interface.
+pub(crate) fn forward_target(socket_addr: &str) -> String {
+    match socket_addr.parse::<SocketAddr>() {
+        Ok(addr) if addr.ip().is_unspecified() => match addr {
+            SocketAddr::V4(_) => format!("127.0.0.1:{}", addr.port()),
+            SocketAddr::V6(_) => format!("[::1]:{}", addr.port()),
+        },
+        _ => socket_addr.to_string(),
+    }
+}
+
+/// Reads a reply to a control command. Returns the lines of the reply without their status codes or
+/// an `Error::TorFailed` if the command failed.
+fn read_reply(reader: &mut impl BufRead) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::TorFailed("The control connection was closed.".to_string()));
+        }
+        let line = line.trim_end();
+        if line.len() < 4 {
+            return Err(Error::TorFailed(format!("Invalid reply \"{}\".", line)));
+        }
+
+        let (status, rest) = line.split_at(3);
+        if status != "250" {
+            return Err(Error::TorFailed(rest[1..].to_string()));
+        }
+        lines.push(rest[1..].to_string());
+        // The last line of a reply separates the status code with a space.
+        if rest.starts_with(' ') {
+            return Ok(lines);
+        }
+    }
+}
+
+/// Parses the authentication methods and the cookie file from a reply to `PROTOCOLINFO`.
+fn parse_auth_methods(reply: &[String]) -> (Vec<String>, Option<String>) {
+    let line = match reply.iter().find_map(|line| line.strip_prefix("AUTH ")) {
+        Some(line) => line,
+        None => return (Vec::new(), None),
+    };
+
+    let methods = line.split_whitespace()
+        .find_map(|field| field.strip_prefix("METHODS="))
+        .map(|methods| methods.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    let cookie_file = line.split_once("COOKIEFILE=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .map(|(path, _)| path.replace("\\\"", "\"").replace("\\\\", "\\"));
+
+    (methods, cookie_file)
+}
+
+/// Quotes a string for a control command.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::tor::{forward_target, parse_auth_methods, OnionService};
+
+    #[test]
+    fn parse_auth_methods_reads_methods_and_cookie_file() {
+        let reply = vec![
+            "PROTOCOLINFO 1".to_string(),
+            "AUTH METHODS=COOKIE,SAFECOOKIE COOKIEFILE=\"/run/tor/control.authcookie\"".to_string(),
+            "OK".to_string(),
+        ];
+        assert_eq!(parse_auth_methods(&reply), (vec!["COOKIE".to_string(), "SAFECOOKIE".to_string()], Some("/run/tor/control.authcookie".to_string())));
+        assert_eq!(forward_target("0.0.0.0:55995"), "127.0.0.1:55995");
+        assert_eq!(forward_target("192.168.1.2:55995"), "192.168.1.2:55995");
+    }
+
+    #[test]
+    fn onion_service_publish_adds_onion_through_control_port() {
+        let control = TcpListener::bind("127.0.0.1:55987").unwrap();
+        let tor = thread::spawn(move || {
+            let (stream, _) = control.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut commands = Vec::new();
+            let replies = [
+                "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=NULL\r\n250 OK\r\n",
+                "250 OK\r\n",
+                "250-ServiceID=mtdexample\r\n250-PrivateKey=ED25519-V3:secretkey\r\n250 OK\r\n",
+            ];
+            for reply in replies {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                commands.push(line.trim_end().to_string());
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            commands
+        });
+
+        let service = OnionService::publish("127.0.0.1:55987", None, 55995, "127.0.0.1:55995", None).unwrap();
+        assert_eq!(service.address(), "mtdexample.onion:55995");
+        assert_eq!(service.private_key(), "ED25519-V3:secretkey");
+        assert_eq!(tor.join().unwrap(), vec![
+            "PROTOCOLINFO 1",
+            "AUTHENTICATE",
+            "ADD_ONION NEW:ED25519-V3 Port=55995,127.0.0.1:55995",
+        ]);
+    }
+}
@@ -0,0 +1,509 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module defining the transports that `MtdNetMgr` sends its messages over. A `Transport` connects
+//! clients to a server and lets a server listen for clients. The sync protocol only sees the
+//! resulting `Connection`s, so new transports can be added without touching the sync logic.
+//!
+//! `TcpTransport` is used by default. Socket addresses starting with `unix:` use `UnixTransport` on
+//! platforms that support Unix domain sockets. `MemoryTransport` connects a client and a server in the
+//! same process and is mostly useful for testing.
+//!
+//! # Example
+//!
+//! ```
+//! use std::thread;
+//! use std::time::Duration;
+//! use mtd::{Config, MemoryTransport, MtdNetMgr, TdList, Todo};
+//!
+//! let transport = MemoryTransport::new();
+//! let conf = Config::new_default(b"secret".to_vec(), "memory".to_string(), None);
+//!
+//! let server_transport = transport.clone();
+//! let server_conf = conf.clone();
+//! thread::spawn(move || {
+//!     let mut server_list = TdList::new_server();
+//!     let mut net_mgr = MtdNetMgr::new(&mut server_list, &server_conf);
+//!     net_mgr.set_transport(Box::new(server_transport));
+//!     net_mgr.server_listening_loop().unwrap();
+//! });
+//!
+//! let mut client_list = TdList::new_client();
+//! client_list.add_todo(Todo::new_undated("Todo 1".to_string()).unwrap());
+//!
+//! let mut net_mgr = MtdNetMgr::new(&mut client_list, &conf);
+//! net_mgr.set_transport(Box::new(transport));
+//! net_mgr.client_sync().unwrap();
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::Duration;
+
+use crate::{Config, Error, Result};
+use crate::proxy::Proxy;
+use crate::srv;
+
+/// A way of connecting clients to a server.
+pub trait Transport {
+    /// Connects to the server described by the `Config`. Used by clients.
+    fn connect(&self, config: &Config) -> Result<Box<dyn Connection>>;
+    /// Starts listening for clients on the address described by the `Config`. Used by servers.
+    fn listen(&self, config: &Config) -> Result<Box<dyn Listener>>;
+}
+
+/// Accepts connections from clients.
+pub trait Listener {
+    /// Returns the connection of a waiting client or `None` if no client is waiting. Doesn't block.
+    fn accept(&mut self) -> Result<Option<Box<dyn Connection>>>;
+}
+
+/// Whether a `Connection` that is kept open between messages has something to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// Nothing to read yet.
+    Idle,
+    /// A message can be read.
+    Ready,
+    /// The other end closed the connection.
+    Closed,
+}
+
+/// A connection between a client and a server. Servers serve each connection on its own thread.
+pub trait Connection: Read + Write + Send {
+    /// Sets how long reads and writes may block before failing.
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+    /// Returns a description of the other end for log messages.
+    fn peer(&self) -> String;
+    /// Checks without blocking whether there is something to read.
+    fn readiness(&mut self) -> Readiness;
+    /// Closes the writing half of the connection.
+    fn shutdown_write(&mut self) -> io::Result<()>;
+}
+
+/// Returns the transport for the socket address of the `Config`.
+pub(crate) fn for_config(config: &Config) -> Box<dyn Transport> {
+    #[cfg(unix)]
+    if config.socket_addr().starts_with("unix:") {
+        return Box::new(UnixTransport);
+    }
+    #[cfg(not(unix))]
+    let _ = config;
+    Box::new(TcpTransport)
+}
+
+/// Splits a socket address such as `example.com:55995` or `[::1]:55995` into its host and port.
+pub(crate) fn split_host_port(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    if host.is_empty() {
+        return None;
+    }
+    Some((host, port.parse().ok()?))
+}
+
+/// Plain TCP. Clients find the server through the DNS SRV record of the `Config` if it has one and
+/// connect through the proxy of the `Config` or of the environment if there is one. Onion addresses
+/// are connected to through a local Tor process if no proxy is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn connect(&self, config: &Config) -> Result<Box<dyn Connection>> {
+        let targets = match config.srv_record() {
+            Some(name) => srv::resolve(name, config.timeout())?,
+            None => {
+                let (host, port) = split_host_port(config.socket_addr())
+                    .ok_or_else(|| Error::InvalidArgument(format!("Invalid socket address \"{}\".", config.socket_addr())))?;
+                vec![(host.to_string(), port)]
+            }
+        };
+
+        let mut last_err = Error::SrvLookupFailed("No SRV records found.".to_string());
+        for (host, port) in targets {
+            let proxy = match config.proxy() {
+                Some("none") => None,
+                Some(url) => Some(Proxy::parse(url)?),
+                None => match Proxy::from_env(&host).transpose()? {
+                    None if host.ends_with(".onion") => Some(Proxy::tor()),
+                    proxy => proxy,
+                },
+            };
+            let connected = match proxy {
+                Some(proxy) => proxy.connect(&host, port, config.timeout()),
+                None => TcpStream::connect((host.trim_start_matches('[').trim_end_matches(']'), port)).map_err(Error::from),
+            };
+            match connected {
+                Ok(stream) => return Ok(Box::new(stream)),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn listen(&self, config: &Config) -> Result<Box<dyn Listener>> {
+        let tcp = TcpListener::bind(config.socket_addr())?;
+        // The listener doesn't block so that the server can do other things while no clients connect.
+        tcp.set_nonblocking(true)?;
+        Ok(Box::new(tcp))
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&mut self) -> Result<Option<Box<dyn Connection>>> {
+        match TcpListener::accept(self) {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Ok(Some(Box::new(stream)))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Connection for TcpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))
+    }
+
+    fn peer(&self) -> String {
+        self.peer_addr().map_or("an unknown address".to_string(), |addr| addr.to_string())
+    }
+
+    fn readiness(&mut self) -> Readiness {
+        let peeked = self.set_nonblocking(true).and_then(|_| self.peek(&mut [0u8; 1]));
+        let readiness = match peeked {
+            Ok(0) => Readiness::Closed,
+            Ok(_) => Readiness::Ready,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Readiness::Idle,
+            Err(_) => Readiness::Closed,
+        };
+        if self.set_nonblocking(false).is_err() {
+            return Readiness::Closed;
+        }
+        readiness
+    }
+
+    fn shutdown_write(&mut self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+/// Unix domain sockets for a client and a server on the same machine. The socket address is the path
+/// of the socket prefixed with `unix:`, such as `unix:/run/mtd.sock`.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnixTransport;
+
+#[cfg(unix)]
+impl UnixTransport {
+    fn path(config: &Config) -> Result<&str> {
+        config.socket_addr().strip_prefix("unix:")
+            .ok_or_else(|| Error::InvalidArgument(format!("Invalid socket address \"{}\".", config.socket_addr())))
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixTransport {
+    fn connect(&self, config: &Config) -> Result<Box<dyn Connection>> {
+        let stream = UnixStream::connect(UnixTransport::path(config)?)?;
+        Ok(Box::new(UnixConnection { stream, peeked: None }))
+    }
+
+    fn listen(&self, config: &Config) -> Result<Box<dyn Listener>> {
+        let path = UnixTransport::path(config)?;
+        // A socket file left behind by a server that didn't exit cleanly is replaced. A socket that
+        // accepts connections belongs to a running server and other files are never removed.
+        let is_socket = std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+        if is_socket && UnixStream::connect(path).is_err() {
+            let _ = std::fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Box::new(listener))
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    fn accept(&mut self) -> Result<Option<Box<dyn Connection>>> {
+        match UnixListener::accept(self) {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Ok(Some(Box::new(UnixConnection { stream, peeked: None })))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A Unix domain socket that remembers a byte read while checking its readiness.
+#[cfg(unix)]
+struct UnixConnection {
+    stream: UnixStream,
+    peeked: Option<u8>,
+}
+
+#[cfg(unix)]
+impl Read for UnixConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.peeked.take() {
+            Some(byte) if !buf.is_empty() => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            _ => self.stream.read(buf),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Write for UnixConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixConnection {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.set_write_timeout(Some(timeout))
+    }
+
+    fn peer(&self) -> String {
+        "a local client".to_string()
+    }
+
+    fn readiness(&mut self) -> Readiness {
+        if self.peeked.is_some() {
+            return Readiness::Ready;
+        }
+        let mut byte = [0u8; 1];
+        let read = self.stream.set_nonblocking(true).and_then(|_| self.stream.read(&mut byte));
+        let readiness = match read {
+            Ok(0) => Readiness::Closed,
+            Ok(_) => {
+                self.peeked = Some(byte[0]);
+                Readiness::Ready
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Readiness::Idle,
+            Err(_) => Readiness::Closed,
+        };
+        if self.stream.set_nonblocking(false).is_err() {
+            return Readiness::Closed;
+        }
+        readiness
+    }
+
+    fn shutdown_write(&mut self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Write)
+    }
+}
+
+/// Connects clients to a server in the same process without any sockets. Clones of a
+/// `MemoryTransport` share the same server, so a clone given to the server's `MtdNetMgr` accepts the
+/// connections of clients using the other clones. The socket address of the `Config` isn't used.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    pending: Arc<Mutex<VecDeque<MemoryConnection>>>,
+}
+
+impl MemoryTransport {
+    /// Creates a new `MemoryTransport` with no connections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn connect(&self, _config: &Config) -> Result<Box<dyn Connection>> {
+        let (client_tx, server_rx) = mpsc::channel();
+        let (server_tx, client_rx) = mpsc::channel();
+        let server = MemoryConnection::new(server_tx, server_rx, "an in-memory client");
+        self.pending.lock().map_err(|_| Error::Unknown)?.push_back(server);
+        Ok(Box::new(MemoryConnection::new(client_tx, client_rx, "the in-memory server")))
+    }
+
+    fn listen(&self, _config: &Config) -> Result<Box<dyn Listener>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl Listener for MemoryTransport {
+    fn accept(&mut self) -> Result<Option<Box<dyn Connection>>> {
+        let connection = self.pending.lock().map_err(|_| Error::Unknown)?.pop_front();
+        Ok(connection.map(|connection| Box::new(connection) as Box<dyn Connection>))
+    }
+}
+
+/// One end of an in-memory connection.
+#[derive(Debug)]
+struct MemoryConnection {
+    tx: Option<Sender<Vec<u8>>>,
+    rx: Receiver<Vec<u8>>,
+    buffer: VecDeque<u8>,
+    timeout: Option<Duration>,
+    peer: &'static str,
+}
+
+impl MemoryConnection {
+    fn new(tx: Sender<Vec<u8>>, rx: Receiver<Vec<u8>>, peer: &'static str) -> Self {
+        Self { tx: Some(tx), rx, buffer: VecDeque::new(), timeout: None, peer }
+    }
+}
+
+impl Read for MemoryConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffer.is_empty() {
+            let received = match self.timeout {
+                Some(timeout) => self.rx.recv_timeout(timeout),
+                None => self.rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(bytes) => self.buffer.extend(bytes),
+                Err(RecvTimeoutError::Timeout) => return Err(io::ErrorKind::WouldBlock.into()),
+                // The other end is gone, which reads as the end of the stream.
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.buffer.len());
+        for (byte, read) in buf.iter_mut().zip(self.buffer.drain(..len)) {
+            *byte = read;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for MemoryConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.tx {
+            Some(tx) if tx.send(buf.to_vec()).is_ok() => Ok(buf.len()),
+            _ => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for MemoryConnection {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.timeout = Some(timeout);
+        Ok(())
+    }
+
+    fn peer(&self) -> String {
+        self.peer.to_string()
+    }
+
+    fn readiness(&mut self) -> Readiness {
+        if !self.buffer.is_empty() {
+            return Readiness::Ready;
+        }
+        match self.rx.try_recv() {
+            Ok(bytes) => {
+                self.buffer.extend(bytes);
+                Readiness::Ready
+            }
+            Err(TryRecvError::Empty) => Readiness::Idle,
+            Err(TryRecvError::Disconnected) => Readiness::Closed,
+        }
+    }
+
+    fn shutdown_write(&mut self) -> io::Result<()> {
+        self.tx = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    use crate::Config;
+    use crate::transport::{MemoryTransport, Readiness, split_host_port, Transport};
+
+    #[test]
+    fn split_host_port_handles_names_and_ipv6() {
+        assert_eq!(split_host_port("example.com:55995"), Some(("example.com", 55995)));
+        assert_eq!(split_host_port("[::1]:55995"), Some(("[::1]", 55995)));
+        assert_eq!(split_host_port("example.com"), None);
+        assert_eq!(split_host_port(":55995"), None);
+    }
+
+    #[test]
+    fn memory_transport_connects_both_ends() {
+        let conf = Config::new_default(b"pw".to_vec(), "memory".to_string(), None);
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen(&conf).unwrap();
+        assert!(listener.accept().unwrap().is_none());
+
+        let mut client = transport.connect(&conf).unwrap();
+        let mut server = listener.accept().unwrap().unwrap();
+        server.set_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(server.readiness(), Readiness::Idle);
+
+        client.write_all(b"hello").unwrap();
+        assert_eq!(server.readiness(), Readiness::Ready);
+        let mut buf = [0u8; 5];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        client.shutdown_write().unwrap();
+        assert_eq!(server.readiness(), Readiness::Closed);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unix_transport_replaces_only_stale_sockets() {
+        use std::{env, fs, process};
+        use std::os::unix::net::UnixListener;
+
+        use crate::transport::UnixTransport;
+
+        let path = env::temp_dir().join(format!("mtd-stale-socket-test-{}.sock", process::id()));
+        let conf = Config::new_default(b"pw".to_vec(), format!("unix:{}", path.display()), None);
+
+        // A file that isn't a socket is kept.
+        fs::write(&path, "not a socket").unwrap();
+        assert!(UnixTransport.listen(&conf).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not a socket");
+        fs::remove_file(&path).unwrap();
+
+        // A socket nobody listens on anymore is replaced.
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(UnixTransport.listen(&conf).is_ok());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+/*
+This file is a part of mtd.
+
+Copyright (C) 2022 Windore
+
+Mtd is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+version.
+
+Mtd is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with this program. If not,
+see <https://www.gnu.org/licenses/>.
+ */
+
+
+//! A module for views that group the items of a `TdList` the way frontends show them.
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{Clock, SortOrder, Task, TdList, Todo};
+
+/// The items of a single date grouped into done and undone `Todo`s and `Task`s. Created with
+/// `TdList::today` or `TdList::day_view`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayView<'a> {
+    /// The date of the view.
+    pub date: NaiveDate,
+    /// The `Todo`s for the date that aren't done yet. Blocked `Todo`s aren't included. If the date
+    /// is today, `Todo`s from earlier dates that aren't done are included.
+    pub undone_todos: Vec<&'a Todo>,
+    /// The `Todo`s for the date that are done.
+    pub done_todos: Vec<&'a Todo>,
+    /// The `Task`s for the date that aren't done yet. Paused `Task`s aren't included.
+    pub undone_tasks: Vec<&'a Task>,
+    /// The `Task`s that are done for the date.
+    pub done_tasks: Vec<&'a Task>,
+    /// The `Task`s missed on an earlier date that are shown late because of their backfill days.
+    /// Only included if the date is today. See `Task::missed_date`.
+    pub missed_tasks: Vec<&'a Task>,
+    /// The undone `Todo`s whose date has passed, in the same order as in `undone_todos`.
+    pub overdue_todos: Vec<&'a Todo>,
+}
+
+impl<'a> DayView<'a> {
+    pub(crate) fn new(list: &'a TdList, date: NaiveDate, clock: &dyn Clock, order: SortOrder) -> DayView<'a> {
+        let today = clock.today();
+        let undone_todos = list.undone_todos_for_date_wtd_sorted(date, today, order);
+        let overdue_todos = undone_todos.iter().filter(|todo| todo.days_overdue(today) > 0).copied().collect();
+
+        DayView {
+            date,
+            undone_todos,
+            done_todos: list.done_todos_for_date_wtd_sorted(date, today, order),
+            undone_tasks: list.undone_tasks_for_date(date),
+            done_tasks: list.done_tasks_for_date(date),
+            missed_tasks: if date == today { list.missed_tasks(today) } else { Vec::new() },
+            overdue_todos,
+        }
+    }
+
+    /// Returns the number of items that aren't done yet, including the missed `Task`s.
+    pub fn undone_count(&self) -> usize {
+        self.undone_todos.len() + self.undone_tasks.len() + self.missed_tasks.len()
+    }
+
+    /// Returns the number of items that are done.
+    pub fn done_count(&self) -> usize {
+        self.done_todos.len() + self.done_tasks.len()
+    }
+
+    /// Returns `true` if there are no items for the date.
+    pub fn is_empty(&self) -> bool {
+        self.undone_count() + self.done_count() == 0
+    }
+}
+
+/// The items of 7 consecutive days as `DayView`s. Created with `TdList::week` or
+/// `TdList::week_view`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekView<'a> {
+    /// The first date of the week.
+    pub start: NaiveDate,
+    /// The days of the week in order starting from `start`.
+    pub days: Vec<DayView<'a>>,
+}
+
+impl<'a> WeekView<'a> {
+    pub(crate) fn new(list: &'a TdList, start: NaiveDate, clock: &dyn Clock, order: SortOrder) -> WeekView<'a> {
+        let days = start.iter_days()
+            .take(7)
+            .map(|date| DayView::new(list, date, clock, order))
+            .collect();
+
+        WeekView { start, days }
+    }
+
+    /// Returns the number of items of the week that aren't done yet.
+    pub fn undone_count(&self) -> usize {
+        self.days.iter().map(DayView::undone_count).sum()
+    }
+
+    /// Returns the number of items of the week that are done.
+    pub fn done_count(&self) -> usize {
+        self.days.iter().map(DayView::done_count).sum()
+    }
+
+    /// Returns the number of overdue `Todo`s. They are only shown on today, if today is in the week.
+    pub fn overdue_count(&self) -> usize {
+        self.days.iter().map(|day| day.overdue_todos.len()).sum()
+    }
+
+    /// Returns the share of the items of the week that are done or `None` if the week has no items.
+    pub fn completion_rate(&self) -> Option<f64> {
+        let total = self.undone_count() + self.done_count();
+        (total > 0).then(|| self.done_count() as f64 / total as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use crate::{FixedClock, SortOrder, Task, TdList, Todo};
+
+    #[test]
+    fn day_view_groups_items_and_lists_overdue_todos() {
+        let today = NaiveDate::from_ymd(2024, 8, 15);
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_specific_date("Late".to_string(), today - Duration::days(2)).unwrap());
+        list.add_todo(Todo::new_specific_date("Today".to_string(), today).unwrap());
+        list.add_todo(Todo::new_specific_date("Done".to_string(), today).unwrap());
+        list.add_todo(Todo::new_specific_date("Tomorrow".to_string(), today + Duration::days(1)).unwrap());
+        list.get_todo_mut(2).unwrap().set_done(true);
+        list.add_task(Task::new("Thursday task".to_string(), vec![chrono::Weekday::Thu]).unwrap());
+
+        let view = list.today(&FixedClock(today));
+        let bodies = |todos: &[&Todo]| todos.iter().map(|todo| todo.body().to_string()).collect::<Vec<_>>();
+        assert_eq!(bodies(&view.undone_todos), vec!["Late", "Today"]);
+        assert_eq!(bodies(&view.overdue_todos), vec!["Late"]);
+        assert_eq!(bodies(&view.done_todos), vec!["Done"]);
+        assert_eq!(view.undone_tasks.len(), 1);
+        assert_eq!((view.undone_count(), view.done_count()), (3, 1));
+
+        let tomorrow = list.day_view(today + Duration::days(1), &FixedClock(today), SortOrder::Manual);
+        assert_eq!(bodies(&tomorrow.undone_todos), vec!["Tomorrow"]);
+        assert!(tomorrow.overdue_todos.is_empty());
+    }
+
+    #[test]
+    fn day_view_lists_missed_tasks_only_for_today() {
+        // 2024-8-16 is a Friday.
+        let today = NaiveDate::from_ymd(2024, 8, 16);
+        let mut list = TdList::new_client();
+        for (body, backfill_days) in [("Pay rent", 1), ("Water plants", 0)] {
+            let mut task = Task::new(body.to_string(), vec![chrono::Weekday::Thu]).unwrap();
+            task.set_backfill_days(backfill_days);
+            task.created = None;
+            list.add_task(task);
+        }
+
+        let view = list.today(&FixedClock(today));
+        assert_eq!(view.missed_tasks.len(), 1);
+        assert_eq!(view.missed_tasks[0].missed_date(today), Some(today - Duration::days(1)));
+        assert_eq!(view.undone_count(), 1);
+        assert!(list.day_view(today + Duration::days(1), &FixedClock(today), SortOrder::Manual).missed_tasks.is_empty());
+        assert!(list.today(&FixedClock(today + Duration::days(1))).missed_tasks.is_empty());
+
+        list.get_task_mut(0).unwrap().set_done(true, today - Duration::days(1));
+        assert!(list.today(&FixedClock(today)).missed_tasks.is_empty());
+    }
+
+    #[test]
+    fn week_view_has_seven_days_and_sums_them() {
+        let today = NaiveDate::from_ymd(2024, 8, 15);
+        let mut list = TdList::new_client();
+        list.add_todo(Todo::new_specific_date("Late".to_string(), today - Duration::days(2)).unwrap());
+        list.add_todo(Todo::new_specific_date("Later".to_string(), today + Duration::days(3)).unwrap());
+        list.add_task(Task::new("Thursday task".to_string(), vec![chrono::Weekday::Thu]).unwrap());
+        list.get_task_mut(0).unwrap().set_done(true, today);
+
+        let week = list.week(&FixedClock(today), today);
+        assert_eq!(week.days.len(), 7);
+        assert_eq!(week.days[6].date, today + Duration::days(6));
+        assert_eq!(week.days[3].undone_todos[0].body(), "Later");
+        assert_eq!((week.undone_count(), week.done_count(), week.overdue_count()), (2, 1, 1));
+        assert_eq!(week.completion_rate(), Some(1.0 / 3.0));
+
+        let empty = TdList::new_client();
+        assert_eq!(empty.week(&FixedClock(today), today).completion_rate(), None);
+    }
+}